@@ -1,21 +1,34 @@
-use std::{
-    cmp::{max, min},
-    collections::HashMap,
-};
+use std::{cmp::max, collections::HashMap};
 
-use levenshtein::levenshtein;
 use rustdoc_types as types;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tracing::{instrument, trace};
 
-use crate::query::*;
+use crate::{
+    function::{FnItem, Owner},
+    metadata::{last_segment, TraitImpls},
+    query::*,
+    search::{Result, SearchError},
+    uses,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
 pub enum Similarity {
     /// Represents how digitally similar two objects are.
     Discrete(DiscreteSimilarity),
 
     /// Represents how analogly similar two objects are.
     Continuous(f32),
+
+    /// Represents how similar the name of an item or an argument is to the one in the query,
+    /// which only tells apart items whose signatures are as similar unless names are weighted.
+    Name(f32),
+
+    /// Represents how similar a part of the output of an item is to the one in the query.
+    Output(f32),
 }
 
 impl Similarity {
@@ -24,31 +37,188 @@ impl Similarity {
             Discrete(Equivalent) => 0.0,
             Discrete(Subequal) => 0.25,
             Discrete(Different) => 1.0,
-            Continuous(s) => *s,
+            Continuous(s) | Name(s) | Output(s) => *s,
         }
     }
 }
 
 use Similarity::*;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Empty for hits deserialized from a server unless it's asked to respond with them by `debug`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Similarities(pub Vec<Similarity>);
 
 impl Similarities {
-    /// Calculate objective similarity for sorting.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether every part of the query matched exactly, i.e. without generic types binding to
+    /// anything, names being just alike or whatever else makes scores more than zero.
+    pub fn is_exact(&self) -> bool {
+        !self.is_empty() && self.0.iter().all(|sim| sim.score() == 0.0)
+    }
+
+    /// Calculate objective similarity for sorting with the default [`Weights`].
+    ///
+    /// Names count only if the query has nothing else to compare, so that naming an item or its
+    /// arguments in a query never ranks an item of the exact signature below worse ones.
     pub fn score(&self) -> f32 {
-        let sum: f32 = self.0.iter().map(|sim| sim.score()).sum();
-        sum / self.0.len() as f32
+        self.score_with(&Weights::default())
+    }
+
+    /// Calculate objective similarity for sorting, weighting the mean similarities of names,
+    /// inputs and outputs by `weights`.
+    ///
+    /// If none of them which the query compares weighs anything, e.g. in queries with names
+    /// alone, every similarity counts the same instead.
+    pub fn score_with(&self, weights: &Weights) -> f32 {
+        // Sums and numbers of similarities of names, inputs and outputs.
+        let mut fields = [(0.0, 0); 3];
+        for sim in &self.0 {
+            let field = match sim {
+                Name(_) => 0,
+                Output(_) => 2,
+                _ => 1,
+            };
+            fields[field].0 += sim.score();
+            fields[field].1 += 1;
+        }
+
+        let weighted = fields
+            .iter()
+            .zip([weights.name, weights.inputs, weights.output])
+            .filter(|((_, len), weight)| *len > 0 && *weight > 0.0);
+        let (sum, total) = weighted.fold((0.0, 0.0), |(sum, total), ((s, len), weight)| {
+            (sum + weight * s / *len as f32, total + weight)
+        });
+        if total > 0.0 {
+            return sum / total;
+        }
+        let (sum, len) = fields
+            .iter()
+            .fold((0.0, 0), |(sum, len), (s, n)| (sum + s, len + n));
+        sum / len as f32
+    }
+
+    /// Similarity of names alone, which tells apart items of the same score, or 0 if there are
+    /// no names.
+    pub fn name_score(&self) -> f32 {
+        let names = self.0.iter().filter_map(|sim| match sim {
+            Name(s) => Some(*s),
+            _ => None,
+        });
+        let (sum, len) = names.fold((0.0, 0), |(sum, len), s| (sum + s, len + 1));
+        if len == 0 {
+            0.0
+        } else {
+            sum / len as f32
+        }
+    }
+
+    /// Order by score with `weights`, and then by similarity of names.
+    pub fn total_cmp(&self, other: &Self, weights: &Weights) -> std::cmp::Ordering {
+        self.score_with(weights)
+            .total_cmp(&other.score_with(weights))
+            .then_with(|| self.name_score().total_cmp(&other.name_score()))
     }
 }
 
 impl PartialOrd for Similarities {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        (self.score()).partial_cmp(&other.score())
+        (self.score(), self.name_score()).partial_cmp(&(other.score(), other.name_score()))
+    }
+}
+
+/// How much names, inputs and outputs count in scores relative to each other, e.g. `output:3` to
+/// rank items by their outputs first.
+///
+/// Each of them counts by the mean of its similarities, so that many inputs matching trivially
+/// don't drown the output. Names weigh nothing by default, only telling apart items of the same
+/// score.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Weights {
+    pub name: f32,
+    /// Weight of inputs, as well as of whatever else than names and outputs queries compare, e.g.
+    /// types of `uses` queries.
+    pub inputs: f32,
+    pub output: f32,
+}
+
+impl Weights {
+    fn to_bits(self) -> [u32; 3] {
+        [self.name, self.inputs, self.output].map(f32::to_bits)
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            name: 0.0,
+            inputs: 1.0,
+            output: 1.0,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+// Weights key caches along with queries, so they are compared bit by bit.
+impl PartialEq for Weights {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl Eq for Weights {}
+
+impl std::hash::Hash for Weights {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state)
+    }
+}
+
+/// Represents a failure of parsing [`Weights`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "invalid weight `{0}`: expected `name`, `inputs` or `output`, `:` and a non-negative number"
+)]
+pub struct ParseWeightsError(pub String);
+
+impl std::str::FromStr for Weights {
+    type Err = ParseWeightsError;
+
+    /// Parse comma-separated weights like `name:1,output:3`, where the others are the default.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut weights = Weights::default();
+        for weight in s
+            .split(',')
+            .map(str::trim)
+            .filter(|weight| !weight.is_empty())
+        {
+            let invalid = || ParseWeightsError(weight.to_owned());
+            let (field, value) = weight.split_once(':').ok_or_else(invalid)?;
+            let value = value
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(invalid)?;
+            match field.trim() {
+                "name" => weights.name = value,
+                "inputs" => weights.inputs = value,
+                "output" => weights.output = value,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
 pub enum DiscreteSimilarity {
     /// Indicates that two types are the same.
     ///
@@ -73,139 +243,511 @@ pub enum DiscreteSimilarity {
 
 use DiscreteSimilarity::*;
 
-pub trait Compare<Rhs> {
+/// Generics in scope of an item being compared, layered from its impl block if any to the item
+/// itself.
+///
+/// Each layer borrows the generics of an item and links to the outer layer on the stack, so that
+/// comparing an item doesn't clone them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenericsScope<'a> {
+    generics: Option<&'a types::Generics>,
+
+    /// Type which `Self` is bound to in this layer, i.e. the one of an impl block.
+    self_ty: Option<&'a types::Type>,
+
+    /// Whether `Self` is a generic type in this layer, i.e. it is the one of a trait definition.
+    generic_self: bool,
+
+    /// Whether newtypes of items may be compared as the types they wrap, which nested layers
+    /// inherit.
+    unwrap_newtypes: bool,
+
+    /// Impl blocks of traits which types match `impl Trait` by, which nested layers inherit.
+    trait_impls: Option<&'a TraitImpls>,
+
+    parent: Option<&'a GenericsScope<'a>>,
+}
+
+impl<'a> GenericsScope<'a> {
+    /// Scope of items in `impl_`, where `Self` is bound to the type it is for.
+    pub fn of_impl(impl_: &'a types::Impl) -> Self {
+        GenericsScope {
+            generics: Some(&impl_.generics),
+            self_ty: Some(&impl_.for_),
+            generic_self: false,
+            unwrap_newtypes: false,
+            trait_impls: None,
+            parent: None,
+        }
+    }
+
+    /// Scope of items declared in `trait_`, where `Self` is any type implementing it.
+    pub fn of_trait(trait_: &'a types::Trait) -> Self {
+        GenericsScope {
+            generics: Some(&trait_.generics),
+            self_ty: None,
+            generic_self: true,
+            unwrap_newtypes: false,
+            trait_impls: None,
+            parent: None,
+        }
+    }
+
+    /// Scope of items associated with `owner`.
+    pub fn of_owner(owner: Owner<'a>) -> Self {
+        match owner {
+            Owner::Impl(impl_) => Self::of_impl(impl_),
+            Owner::Trait(_, trait_) => Self::of_trait(trait_),
+        }
+    }
+
+    /// Scope of an item declaring `generics`, nested in `self`.
+    pub fn with<'b>(&'b self, generics: &'b types::Generics) -> GenericsScope<'b> {
+        GenericsScope {
+            generics: Some(generics),
+            self_ty: None,
+            generic_self: false,
+            unwrap_newtypes: self.unwrap_newtypes,
+            trait_impls: self.trait_impls,
+            parent: Some(self),
+        }
+    }
+
+    /// `self` comparing newtypes of items, i.e. tuple structs with a single field like
+    /// `struct Meters(f64)`, as the types they wrap as well if `unwrap` is true.
+    pub fn unwrap_newtypes(self, unwrap: bool) -> Self {
+        GenericsScope {
+            unwrap_newtypes: unwrap,
+            ..self
+        }
+    }
+
+    /// `self` matching types with `impl Trait` by the impl blocks of traits in `impls` as well as
+    /// by their bounds.
+    pub fn trait_impls(self, impls: &'a TraitImpls) -> Self {
+        GenericsScope {
+            trait_impls: Some(impls),
+            ..self
+        }
+    }
+
+    /// Whether `Self` is a generic type rather than bound to one, looking from the outermost
+    /// layer.
+    fn generic_self(&self) -> bool {
+        self.generic_self || self.parent.is_some_and(GenericsScope::generic_self)
+    }
+
+    /// Bounds of the generic type `name` in its declaration and where clauses, in every layer.
+    fn bounds<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'a types::GenericBound> + 'b {
+        let generics = self.generics.into_iter();
+        let params = generics
+            .clone()
+            .flat_map(|generics| &generics.params)
+            .filter_map(move |param| match param.kind {
+                types::GenericParamDefKind::Type { ref bounds, .. } if param.name == name => {
+                    Some(bounds)
+                }
+                _ => None,
+            });
+        let predicates = generics
+            .flat_map(|generics| &generics.where_predicates)
+            .filter_map(move |predicate| match predicate {
+                types::WherePredicate::BoundPredicate {
+                    ty: types::Type::Generic(ty),
+                    bounds,
+                } if ty == name => Some(bounds),
+                _ => None,
+            });
+        let parent: Box<dyn Iterator<Item = _>> = match self.parent {
+            Some(parent) => Box::new(parent.bounds(name)),
+            None => Box::new(std::iter::empty()),
+        };
+        params.chain(predicates).flatten().chain(parent)
+    }
+
+    /// Type which `Self` is bound to, looking from the outermost layer.
+    fn self_ty(&self) -> Option<&'a types::Type> {
+        if let Some(ty) = self.parent.and_then(|parent| parent.self_ty()) {
+            return Some(ty);
+        }
+        let predicates = self
+            .generics
+            .into_iter()
+            .flat_map(|generics| &generics.where_predicates);
+        for predicate in predicates {
+            if let types::WherePredicate::EqPredicate {
+                lhs: types::Type::Generic(lhs),
+                rhs,
+            } = predicate
+            {
+                if lhs == "Self" {
+                    return Some(rhs);
+                }
+            }
+        }
+        self.self_ty
+    }
+}
+
+pub trait Compare<'q, Rhs> {
     fn compare(
         &self,
         rhs: &Rhs,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity>;
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>>;
 }
 
-impl Compare<types::Item> for Query {
+impl<'q> Compare<'q, types::Item> for Query<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         item: &types::Item,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         let mut sims = vec![];
 
         match (&self.name, &item.name) {
-            (Some(q), Some(i)) => sims.append(&mut q.compare(i, krate, generics, substs)),
-            (Some(_), None) => sims.push(Discrete(Different)),
+            (Some(q), Some(i)) => sims.extend(names(q.compare(i, krate, generics, substs)?)),
+            (Some(_), None) => sims.push(Name(Discrete(Different).score())),
             _ => {}
         }
         trace!(?sims);
 
         if let Some(ref kind) = self.kind {
-            sims.append(&mut kind.compare(&item.inner, krate, generics, substs))
+            sims.append(&mut kind.compare(&item.inner, krate, generics, substs)?)
+        }
+        // The type in `Type::...` is compared with the one of the impl of the method, so that
+        // impls for `Vec<u8>` come before the ones for any `Vec<T>`.
+        match (&self.self_ty, generics.self_ty) {
+            (Some(q), Some(i)) => sims.append(&mut q.compare(i, krate, generics, substs)?),
+            (Some(_), None) => sims.push(Discrete(Different)),
+            _ => {}
+        }
+        if let Some(QueryKind::ConstructorQuery(_)) = self.kind {
+            sims.push(constructor_name(item.name.as_deref().unwrap_or_default()));
         }
         trace!(?sims);
 
-        sims
+        Ok(sims)
     }
 }
 
-impl Compare<String> for Symbol {
+/// `sims` of names, which only tell apart items whose signatures are as similar.
+fn names(sims: Vec<Similarity>) -> impl Iterator<Item = Similarity> {
+    sims.into_iter().map(|sim| Name(sim.score()))
+}
+
+impl<'q> Compare<'q, String> for Symbol<'q> {
     #[instrument]
     fn compare(
         &self,
         symbol: &String,
         _: &types::Crate,
-        _: &mut types::Generics,
-        _: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
-        use std::cmp::max;
+        _: &GenericsScope<'_>,
+        _: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
+        Ok(vec![compare_symbol(self, symbol, 1.0)])
+    }
+}
+
+/// Compare names `q` and `i`, ignoring the path of `i`.
+///
+/// Measuring stops early once their similarity can't be less than `bound`, which gives
+/// `Discrete(Different)` instead.
+pub(crate) fn compare_symbol(q: &str, i: &str, bound: f32) -> Similarity {
+    let i = i.rsplit("::").next().unwrap_or(i);
+    let len = max(q.len(), i.len());
+    if len == 0 {
+        // Two empty names, which would be NaN below.
+        return Discrete(Equivalent);
+    }
+    let max_distance = (bound * len as f32) as usize;
+    match levenshtein_within(q, i, max_distance) {
+        Some(distance) => Continuous(distance as f32 / len as f32),
+        None => Discrete(Different),
+    }
+}
+
+/// Compare argument names `q` and `i` word by word, split at `_` and case changes, so that e.g.
+/// `path` is like `file_path`, `contents` like `content` and `buf` like `buffer`.
+///
+/// Each word of `q` scores as its closest word of `i`, and words of `i` no word of `q` is closest
+/// to count as half different.
+pub(crate) fn compare_arg_names(q: &str, i: &str) -> Similarity {
+    let (q_words, i_words) = (words(q), words(i));
+    if q_words.is_empty() || i_words.is_empty() {
+        return compare_symbol(q, i, 1.0);
+    }
+
+    let mut closest = vec![false; i_words.len()];
+    let mut sum = 0.0;
+    for q_word in &q_words {
+        let (j, score) = i_words
+            .iter()
+            .map(|i_word| compare_word(q_word, i_word).score())
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, 1.0));
+        closest[j] = true;
+        sum += score;
+    }
+    let unmatched = closest.iter().filter(|closest| !**closest).count();
+    let score = (sum + 0.5 * unmatched as f32) / (q_words.len() + unmatched) as f32;
+    if score == 0.0 {
+        Discrete(Equivalent)
+    } else {
+        Continuous(score)
+    }
+}
+
+/// Lowercase words of `name` in snake case or camel case.
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '_' {
+            prev_lower = false;
+            words.push(String::new());
+            continue;
+        }
+        if words.is_empty() || (prev_lower && c.is_uppercase()) {
+            words.push(String::new());
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        if let Some(word) = words.last_mut() {
+            word.extend(c.to_lowercase());
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// Compare words of argument names, taking abbreviations like `buf` for `buffer` or `dst` for
+/// `destination` as close.
+fn compare_word(q: &str, i: &str) -> Similarity {
+    let (short, long) = if q.len() <= i.len() { (q, i) } else { (i, q) };
+    let sim = compare_symbol(q, i, 1.0);
+    if short != long && short.len() > 1 && abbreviates(short, long) {
+        return Continuous(sim.score().min(0.25));
+    }
+    sim
+}
 
-        let symbol = symbol.split("::").last().unwrap(); // SAFETY: `symbol` is not empty.
-        vec![Continuous(
-            levenshtein(self, symbol) as f32 / max(self.len(), symbol.len()) as f32,
-        )]
+/// Whether `short` is made of chars of `long` in order, starting with the same one.
+fn abbreviates(short: &str, long: &str) -> bool {
+    let mut long = long.chars();
+    let mut short = short.chars();
+    if short.next() != long.next() {
+        return false;
+    }
+    short.all(|c| long.any(|l| l == c))
+}
+
+/// Levenshtein distance between `a` and `b` in chars, or `None` once it turns out to exceed `max`.
+pub(crate) fn levenshtein_within(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // Distances between prefixes of `a` and ones of `b`, one row per char of `a`.
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        let mut min = row[0];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+            min = min.min(row[j + 1]);
+        }
+        // Distances only grow in later rows.
+        if min > max {
+            return None;
+        }
     }
+
+    Some(row[b.len()]).filter(|&distance| distance <= max)
 }
 
-impl Compare<types::ItemEnum> for QueryKind {
+impl<'q> Compare<'q, types::ItemEnum> for QueryKind<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         kind: &types::ItemEnum,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
-        use types::ItemEnum::*;
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         use QueryKind::*;
 
-        match (self, kind) {
-            (FunctionQuery(q), Function(i)) => q.compare(i, krate, generics, substs),
-            (FunctionQuery(q), Method(i)) => q.compare(i, krate, generics, substs),
-            (FunctionQuery(_), _) => vec![Discrete(Different)],
+        match (self, FnItem::of(kind)) {
+            (FunctionQuery(q), Some(i)) => q.compare(&i, krate, generics, substs),
+            (UsesQuery(q), Some(i)) => {
+                let generics = generics.with(i.generics);
+                if uses::roles(q, i, krate, &generics)?.is_empty() {
+                    Ok(vec![Discrete(Different)])
+                } else {
+                    Ok(vec![Discrete(Equivalent)])
+                }
+            }
+            (ConstructorQuery(q), Some(i)) => {
+                let generics = generics.with(i.generics);
+                compare_constructor(q, i, krate, &generics)
+            }
+            (FailsWithQuery(q), Some(i)) => {
+                let generics = generics.with(i.generics);
+                match i.decl.output.as_ref().and_then(|i| error_type(i, krate)) {
+                    Some(i) => q.compare(i, krate, &generics, &mut HashMap::new()),
+                    None => Ok(vec![Discrete(Different)]),
+                }
+            }
+            (_, None) => Ok(vec![Discrete(Different)]),
         }
     }
 }
 
-impl Compare<types::Function> for Function {
-    #[instrument(skip(krate))]
-    fn compare(
-        &self,
-        function: &types::Function,
-        krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
-        generics
-            .params
-            .append(&mut function.generics.params.clone());
-        generics
-            .where_predicates
-            .append(&mut function.generics.where_predicates.clone());
-        self.decl.compare(&function.decl, krate, generics, substs)
+/// Compare `q` with what `f` constructs, i.e. its output either as is or wrapped in `Result` or
+/// `Option`, unless `f` takes `self`.
+fn compare_constructor<'q>(
+    q: &Type<'q>,
+    f: FnItem<'_>,
+    krate: &types::Crate,
+    generics: &GenericsScope<'_>,
+) -> Result<Vec<Similarity>> {
+    let output = match (f.decl.inputs.first(), &f.decl.output) {
+        (Some((name, _)), _) if name == "self" => return Ok(vec![Discrete(Different)]),
+        (_, Some(output)) => output,
+        (_, None) => return Ok(vec![Discrete(Different)]),
+    };
+
+    // Outputs are compared apart, so they don't share bindings of generic types. Generic types
+    // other than `Self` are whatever callers pass in rather than constructed.
+    let constructs = |i: &types::Type| match i {
+        types::Type::Generic(name) if name != "Self" => Ok(vec![Discrete(Different)]),
+        _ => q.compare(i, krate, generics, &mut HashMap::new()),
+    };
+    let direct = constructs(output)?;
+    let wrapped = match output {
+        types::Type::ResolvedPath {
+            name,
+            args: Some(args),
+            ..
+        } if matches!(name.rsplit("::").next(), Some("Result" | "Option")) => match **args {
+            types::GenericArgs::AngleBracketed { ref args, .. } => match args.first() {
+                Some(types::GenericArg::Type(inner)) => {
+                    let mut sims = constructs(inner)?;
+                    // Fallible constructors are less direct answers.
+                    sims.push(Discrete(Subequal));
+                    Some(sims)
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let score = |sims: &[Similarity]| Similarities(sims.to_vec()).score();
+    match wrapped {
+        Some(wrapped) if score(&wrapped) < score(&direct) => Ok(wrapped),
+        _ => Ok(direct),
     }
 }
 
-impl Compare<types::Method> for Function {
+/// `E` of `ty` if it is `Result<T, E>`, looking through type aliases like `io::Result<T>` in
+/// `krate`.
+fn error_type<'k>(ty: &'k types::Type, krate: &'k types::Crate) -> Option<&'k types::Type> {
+    let (name, id, args) = match ty {
+        types::Type::ResolvedPath { name, id, args, .. } => (name, id, args),
+        _ => return None,
+    };
+    let args = args.as_deref().map(ItemArgs::of).unwrap_or_default();
+
+    match krate.index.get(id) {
+        Some(types::Item {
+            inner: types::ItemEnum::Typedef(typedef),
+            ..
+        }) => {
+            let e = error_type(&typedef.type_, krate)?;
+            // The error type may be a parameter of the alias, e.g. `E` of
+            // `type Result<T, E = Error> = result::Result<T, E>`.
+            let param = typedef
+                .generics
+                .params
+                .iter()
+                .enumerate()
+                .find_map(|(n, param)| match (&param.kind, e) {
+                    (types::GenericParamDefKind::Type { default, .. }, types::Type::Generic(e))
+                        if param.name == *e =>
+                    {
+                        Some((n, default))
+                    }
+                    _ => None,
+                });
+            match param {
+                Some((n, default)) => args.types.get(n).copied().flatten().or(default.as_ref()),
+                None => Some(e),
+            }
+        }
+        _ if last_segment(name) == "Result" => args.types.get(1).copied().flatten(),
+        _ => None,
+    }
+}
+
+/// How much `name` looks like the one of a constructor, preferring `new` over `new_*`, `with_*`
+/// and `from_*`, and them over any other name.
+fn constructor_name(name: &str) -> Similarity {
+    if name == "new" {
+        Discrete(Equivalent)
+    } else if ["new_", "with_", "from_"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+    {
+        Continuous(0.1)
+    } else {
+        Discrete(Subequal)
+    }
+}
+
+impl<'q> Compare<'q, FnItem<'_>> for Function<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
-        method: &types::Method,
+        function: &FnItem<'_>,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
-        generics.params.append(&mut method.generics.params.clone());
-        generics
-            .where_predicates
-            .append(&mut method.generics.where_predicates.clone());
-        self.decl.compare(&method.decl, krate, generics, substs)
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
+        let generics = generics.with(function.generics);
+        self.decl.compare(function.decl, krate, &generics, substs)
     }
 }
 
-impl Compare<types::FnDecl> for FnDecl {
+impl<'q> Compare<'q, types::FnDecl> for FnDecl<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         decl: &types::FnDecl,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         let mut sims = vec![];
 
-        if let Some(ref inputs) = self.inputs {
-            inputs.iter().enumerate().for_each(|(idx, q)| {
-                if let Some(i) = decl.inputs.get(idx) {
-                    sims.append(&mut q.compare(i, krate, generics, substs))
-                }
-            });
+        if let Some(inputs) = self.inputs {
+            for (q, i) in inputs.iter().zip(&decl.inputs) {
+                sims.append(&mut q.compare(i, krate, generics, substs)?);
+            }
 
             if inputs.len() != decl.inputs.len() {
-                // FIXME: Replace this line below with `usize::abs_diff` once it got stablized.
-                let abs_diff =
-                    max(inputs.len(), decl.inputs.len()) - min(inputs.len(), decl.inputs.len());
+                let abs_diff = inputs.len().abs_diff(decl.inputs.len());
                 sims.append(&mut vec![Discrete(Different); abs_diff])
             } else if inputs.is_empty() && decl.inputs.is_empty() {
                 sims.push(Discrete(Equivalent));
@@ -214,95 +756,95 @@ impl Compare<types::FnDecl> for FnDecl {
         trace!(?sims);
 
         if let Some(ref output) = self.output {
-            sims.append(&mut output.compare(&decl.output, krate, generics, substs));
+            let output = output.compare(&decl.output, krate, generics, substs)?;
+            sims.extend(output.into_iter().map(|sim| Output(sim.score())));
         }
         trace!(?sims);
 
-        sims
+        Ok(sims)
     }
 }
 
-impl Compare<(String, types::Type)> for Argument {
+impl<'q> Compare<'q, (String, types::Type)> for Argument<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         arg: &(String, types::Type),
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         let mut sims = vec![];
 
-        if let Some(ref name) = self.name {
-            sims.append(&mut name.compare(&arg.0, krate, generics, substs));
+        // Names only tell apart arguments of types as similar, unless they are all the query says
+        // about the arguments, as in `fn (path: _, contents: _)`.
+        if let Some(name) = self.name {
+            let sim = compare_arg_names(name, &arg.0);
+            sims.push(match self.ty {
+                Some(_) => Name(sim.score()),
+                None => sim,
+            });
         }
         trace!(?sims);
 
         if let Some(ref type_) = self.ty {
-            sims.append(&mut type_.compare(&arg.1, krate, generics, substs));
+            sims.append(&mut type_.compare(&arg.1, krate, generics, substs)?);
         }
         trace!(?sims);
 
-        sims
+        Ok(sims)
     }
 }
 
-impl Compare<Option<types::Type>> for FnRetTy {
+impl<'q> Compare<'q, Option<types::Type>> for FnRetTy<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         ret_ty: &Option<types::Type>,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         match (self, ret_ty) {
             (FnRetTy::Return(q), Some(i)) => q.compare(i, krate, generics, substs),
-            (FnRetTy::DefaultReturn, None) => vec![Discrete(Equivalent)],
-            _ => vec![Discrete(Different)],
+            (FnRetTy::DefaultReturn, None) => Ok(vec![Discrete(Equivalent)]),
+            _ => Ok(vec![Discrete(Different)]),
         }
     }
 }
 
-fn compare_type(
-    lhs: &Type,
+fn compare_type<'q>(
+    lhs: &Type<'q>,
     rhs: &types::Type,
     krate: &types::Crate,
-    generics: &mut types::Generics,
-    substs: &mut HashMap<String, Type>,
+    generics: &GenericsScope<'_>,
+    substs: &mut HashMap<String, Type<'q>>,
     allow_recursion: bool,
-) -> Vec<Similarity> {
+) -> Result<Vec<Similarity>> {
     use {crate::query::Type::*, types::Type};
 
     match (lhs, rhs) {
-        (q, Type::Generic(i)) if i == "Self" => {
-            let mut i = None;
-            for where_predicate in &generics.where_predicates {
-                if let types::WherePredicate::EqPredicate {
-                    lhs: Type::Generic(lhs),
-                    rhs,
-                } = where_predicate
-                {
-                    if lhs == "Self" {
-                        i = Some(rhs).cloned();
-                        break;
-                    }
-                }
-            }
-            let i = &i.unwrap(); // SAFETY: `Self` only appears in definitions of associated items.
+        (SelfType, Type::Generic(i)) if i == "Self" => Ok(vec![Discrete(Equivalent)]),
+        // `Self` of trait definitions is compared as a generic type below.
+        (q, Type::Generic(i)) if i == "Self" && !generics.generic_self() => {
+            // `Self` should only appear in definitions of associated items, where it is bound.
+            let i = generics.self_ty().ok_or(SearchError::UnboundSelf)?;
             q.compare(i, krate, generics, substs)
         }
+        (ImplTrait { trait_, args }, i) => {
+            compare_impl_trait(trait_, *args, i, krate, generics, substs)
+        }
         (q, Type::Generic(i)) => match substs.get(i) {
             Some(i) => {
                 if q == i {
-                    vec![Discrete(Equivalent)]
+                    Ok(vec![Discrete(Equivalent)])
                 } else {
-                    vec![Discrete(Different)]
+                    Ok(vec![Discrete(Different)])
                 }
             }
             None => {
                 substs.insert(i.clone(), q.clone());
-                vec![Discrete(Subequal)]
+                Ok(vec![Discrete(Subequal)])
             }
         },
         (q, Type::ResolvedPath { id, .. })
@@ -313,48 +855,112 @@ fn compare_type(
                 .unwrap_or(false)
                 && allow_recursion =>
         {
-            let sims_typedef = compare_type(lhs, rhs, krate, generics, substs, false);
+            // Each side binds generics of its own, so that only the closer one's bindings are kept.
+            let mut substs_typedef = substs.clone();
+            let sims_typedef = compare_type(lhs, rhs, krate, generics, &mut substs_typedef, false)?;
             if let Some(types::Item {
-                inner: types::ItemEnum::Typedef(types::Typedef { type_: ref i, .. }),
+                inner:
+                    types::ItemEnum::Typedef(types::Typedef {
+                        type_: ref i,
+                        generics: ref typedef_generics,
+                    }),
                 ..
             }) = krate.index.get(id)
             {
                 // TODO: Acknowledge `generics` of `types::Typedef` to get more accurate search results.
-                let sims_adt = q.compare(i, krate, generics, substs);
+                let mut substs_adt = substs.clone();
+                let sims_adt = q.compare(i, krate, generics, &mut substs_adt)?;
+                // Generics of the typedef aren't the item's, so their bindings aren't either.
+                substs_adt.retain(|name, _| {
+                    substs.contains_key(name)
+                        || !typedef_generics
+                            .params
+                            .iter()
+                            .any(|param| &param.name == name)
+                });
                 let sum =
                     |sims: &Vec<Similarity>| -> f32 { sims.iter().map(Similarity::score).sum() };
                 if sum(&sims_adt) < sum(&sims_typedef) {
-                    return sims_adt;
+                    *substs = substs_adt;
+                    return Ok(sims_adt);
                 }
             }
-            sims_typedef
+            *substs = substs_typedef;
+            Ok(sims_typedef)
+        }
+        (q, Type::ResolvedPath { id, args, .. })
+            if generics.unwrap_newtypes && newtype_field(krate, id, args.as_deref()).is_some() =>
+        {
+            // Newtypes are only unwrapped once, so that ones wrapping references to themselves
+            // don't recurse forever.
+            let generics = &generics.unwrap_newtypes(false);
+            // Both sides bind generics against the substitutions so far, and only the closer one
+            // keeps its bindings.
+            let mut substs_newtype = substs.clone();
+            let sims_newtype = compare_type(
+                lhs,
+                rhs,
+                krate,
+                generics,
+                &mut substs_newtype,
+                allow_recursion,
+            )?;
+            if let Some(i) = newtype_field(krate, id, args.as_deref()) {
+                let mut substs_inner = substs.clone();
+                let mut sims_inner = q.compare(i, krate, generics, &mut substs_inner)?;
+                // The wrapped type is only partially the newtype.
+                sims_inner.push(Discrete(Subequal));
+                let sum =
+                    |sims: &Vec<Similarity>| -> f32 { sims.iter().map(Similarity::score).sum() };
+                if sum(&sims_inner) < sum(&sims_newtype) {
+                    *substs = substs_inner;
+                    return Ok(sims_inner);
+                }
+            }
+            *substs = substs_newtype;
+            Ok(sims_newtype)
         }
         (Tuple(q), Type::Tuple(i)) => {
-            let mut sims = q
-                .iter()
-                .zip(i.iter())
-                .filter_map(|(q, i)| q.as_ref().map(|q| q.compare(i, krate, generics, substs)))
-                .flatten()
-                .collect::<Vec<_>>();
+            let mut sims = vec![];
+            for (q, i) in q.iter().zip(i.iter()) {
+                if let Some(q) = q {
+                    sims.append(&mut q.compare(i, krate, generics, substs)?);
+                }
+            }
 
             // They are both tuples.
             sims.push(Discrete(Equivalent));
 
-            // FIXME: Replace this line below with `usize::abs_diff` once it got stablized.
-            let abs_diff = max(q.len(), i.len()) - min(q.len(), i.len());
+            let abs_diff = q.len().abs_diff(i.len());
             sims.append(&mut vec![Discrete(Different); abs_diff]);
 
-            sims
+            Ok(sims)
+        }
+        (BareFunction(q), Type::FunctionPointer(i)) => {
+            // Outputs of function pointers are parts of the types rather than outputs of items.
+            let mut sims = q
+                .compare(&i.decl, krate, generics, substs)?
+                .into_iter()
+                .map(|sim| match sim {
+                    Output(s) => Continuous(s),
+                    sim => sim,
+                })
+                .collect::<Vec<_>>();
+
+            // They are both function pointers.
+            sims.push(Discrete(Equivalent));
+
+            Ok(sims)
         }
         (Slice(q), Type::Slice(i)) => {
             // They are both slices.
             let mut sims = vec![Discrete(Equivalent)];
 
             if let Some(q) = q {
-                sims.append(&mut q.compare(i, krate, generics, substs));
+                sims.append(&mut q.compare(i, krate, generics, substs)?);
             }
 
-            sims
+            Ok(sims)
         }
         (
             RawPointer {
@@ -377,103 +983,412 @@ fn compare_type(
                 ..
             },
         ) => {
-            if q_mut == i_mut {
-                q.compare(i, krate, generics, substs)
-            } else {
-                let mut sims = q.compare(i, krate, generics, substs);
+            let mut sims = q.compare(i, krate, generics, substs)?;
+            if q_mut != i_mut {
                 sims.push(Discrete(Subequal));
-                sims
             }
+            Ok(sims)
         }
         (q, Type::RawPointer { type_: i, .. } | Type::BorrowedRef { type_: i, .. }) => {
-            let mut sims = q.compare(i, krate, generics, substs);
+            let mut sims = q.compare(i, krate, generics, substs)?;
             sims.push(Discrete(Subequal));
-            sims
+            Ok(sims)
         }
         (RawPointer { type_: q, .. } | BorrowedRef { type_: q, .. }, i) => {
-            let mut sims = q.compare(i, krate, generics, substs);
+            let mut sims = q.compare(i, krate, generics, substs)?;
             sims.push(Discrete(Subequal));
-            sims
+            Ok(sims)
         }
         (
             UnresolvedPath {
-                name: q,
+                name: q_name,
                 args: q_args,
             },
             Type::ResolvedPath {
-                name: i,
+                name: i_name,
                 args: i_args,
                 ..
             },
         ) => {
-            let mut sims = q.compare(i, krate, generics, substs);
-
-            match (q_args, i_args) {
-                (Some(q), Some(i)) => match (&**q, &**i) {
-                    (
-                        GenericArgs::AngleBracketed { args: ref q },
-                        types::GenericArgs::AngleBracketed { args: ref i, .. },
-                    ) => {
-                        let q = q.iter().map(|q| {
-                            q.as_ref().map(|q| match q {
-                                GenericArg::Type(q) => q,
-                            })
-                        });
-                        let i = i.iter().map(|i| match i {
-                            types::GenericArg::Type(t) => Some(t),
-                            _ => None,
-                        });
-                        q.zip(i).for_each(|(q, i)| match (q, i) {
-                            (Some(q), Some(i)) => {
-                                sims.append(&mut q.compare(i, krate, generics, substs))
-                            }
-                            (Some(_), None) => sims.push(Discrete(Different)),
-                            (None, _) => {}
-                        });
+            let mut sims = last_segment(q_name).compare(
+                &last_segment(i_name).to_owned(),
+                krate,
+                generics,
+                substs,
+            )?;
+            match (q_args, i_args.as_deref()) {
+                // TODO: Support `GenericArgs::Parenthesized`.
+                (Some(_), Some(types::GenericArgs::Parenthesized { .. })) | (None, _) => {}
+                (Some(q), i) => {
+                    let i = i.map(ItemArgs::of);
+                    sims.append(&mut compare_generic_args(
+                        q,
+                        i.as_ref(),
+                        krate,
+                        generics,
+                        substs,
+                    )?);
+                    // Trailing type arguments of the same type the query leaves out are
+                    // subequal, e.g. `S` of `HashMap<K, V, S>` for `HashMap<K, V>`, as they often
+                    // have defaults.
+                    if last_segment(q_name) == last_segment(i_name) {
+                        let GenericArgs::AngleBracketed { args: q } = q;
+                        let written = q
+                            .iter()
+                            .filter(|arg| !matches!(arg, Some(GenericArg::Binding { .. })))
+                            .count();
+                        let missing = i.map_or(0, |i| i.types.len().saturating_sub(written));
+                        sims.extend(std::iter::repeat_n(Discrete(Subequal), missing));
                     }
-                    // TODO: Support `GenericArgs::Parenthesized`.
-                    (_, _) => {}
-                },
-                (Some(q), None) => {
-                    let GenericArgs::AngleBracketed { args: ref q } = **q;
-                    sims.append(&mut vec![Discrete(Different); q.len()])
                 }
-                (None, _) => {}
             }
-
-            sims
+            Ok(sims)
+        }
+        (
+            QualifiedPath {
+                self_type: q_self,
+                name: q,
+            },
+            Type::QualifiedPath {
+                self_type: i_self,
+                name: i,
+                ..
+            },
+        ) => {
+            let mut sims = q.compare(i, krate, generics, substs)?;
+            sims.append(&mut q_self.compare(i_self, krate, generics, substs)?);
+            Ok(sims)
         }
+        // `Self` of queries is bound to the type of the impl block the same way as the one of items.
+        (SelfType, i) => match generics.self_ty() {
+            Some(ty) if ty == i => Ok(vec![Discrete(Equivalent)]),
+            _ => Ok(vec![Discrete(Different)]),
+        },
         (Primitive(q), Type::Primitive(i)) => q.compare(i, krate, generics, substs),
-        _ => vec![Discrete(Different)],
+        // rustdoc writes `!` as a primitive type, e.g. in outputs of functions which panic.
+        (Never, Type::Primitive(i)) => PrimitiveType::Never.compare(i, krate, generics, substs),
+        _ => Ok(vec![Discrete(Different)]),
+    }
+}
+
+/// Type of the only field of the newtype `id`, i.e. a tuple struct like `struct Meters(f64)`, with
+/// the generic type of ones like `struct Wrapper<T>(T)` substituted by its argument in `args`.
+///
+/// Newtypes whose fields are private aren't unwrapped, as rustdoc leaves their fields out.
+fn newtype_field<'i>(
+    krate: &'i types::Crate,
+    id: &types::Id,
+    args: Option<&'i types::GenericArgs>,
+) -> Option<&'i types::Type> {
+    let struct_ = match krate.index.get(id)?.inner {
+        types::ItemEnum::Struct(ref struct_) => struct_,
+        _ => return None,
+    };
+    if !matches!(struct_.struct_type, types::StructType::Tuple) || struct_.fields_stripped {
+        return None;
+    }
+    let field = match struct_.fields.as_slice() {
+        [field] => krate.index.get(field)?,
+        _ => return None,
+    };
+    let ty = match field.inner {
+        types::ItemEnum::StructField(ref ty) => ty,
+        _ => return None,
+    };
+
+    // TODO: Substitute generic types nested in the field as well, e.g. `T` of `Vec<T>`.
+    match ty {
+        types::Type::Generic(name) => {
+            // Positions are counted the way `ItemArgs` counts them, i.e. leaving out lifetimes.
+            let position = struct_
+                .generics
+                .params
+                .iter()
+                .filter(|param| !matches!(param.kind, types::GenericParamDefKind::Lifetime { .. }))
+                .position(|param| &param.name == name)?;
+            ItemArgs::of(args?).types.get(position).copied().flatten()
+        }
+        ty => Some(ty),
+    }
+}
+
+/// Generic arguments of an item, with type arguments by position and bindings of associated
+/// types by name.
+#[derive(Debug, Default)]
+struct ItemArgs<'i> {
+    /// Type arguments, where arguments of other kinds like consts are `None`, leaving out
+    /// lifetimes which queries don't write, e.g. `str` alone of `Cow<'a, str>`.
+    types: Vec<Option<&'i types::Type>>,
+    bindings: Vec<(&'i str, &'i types::Type)>,
+}
+
+impl<'i> ItemArgs<'i> {
+    fn of(args: &'i types::GenericArgs) -> Self {
+        match args {
+            types::GenericArgs::AngleBracketed { args, bindings } => ItemArgs {
+                types: args
+                    .iter()
+                    .filter(|arg| !matches!(arg, types::GenericArg::Lifetime(_)))
+                    .map(|arg| match arg {
+                        types::GenericArg::Type(t) => Some(t),
+                        _ => None,
+                    })
+                    .collect(),
+                bindings: bindings
+                    .iter()
+                    .filter_map(|binding| match binding.binding {
+                        types::TypeBindingKind::Equality(ref t) => Some((binding.name.as_str(), t)),
+                        types::TypeBindingKind::Constraint(_) => None,
+                    })
+                    .collect(),
+            },
+            types::GenericArgs::Parenthesized { .. } => ItemArgs::default(),
+        }
+    }
+}
+
+/// Compare generic arguments of a query with the ones of an item, or with none if `i` is `None`.
+///
+/// Type arguments are compared by position, and bindings like `Item = T` by name, where a binding
+/// the item doesn't have is subequal as it may still be implied, e.g. by a bound elsewhere.
+fn compare_generic_args<'q>(
+    q: &GenericArgs<'q>,
+    i: Option<&ItemArgs<'_>>,
+    krate: &types::Crate,
+    generics: &GenericsScope<'_>,
+    substs: &mut HashMap<String, Type<'q>>,
+) -> Result<Vec<Similarity>> {
+    let GenericArgs::AngleBracketed { args: q } = q;
+    let i = match i {
+        Some(i) => i,
+        None => return Ok(vec![Discrete(Different); q.len()]),
+    };
+
+    let mut sims = vec![];
+    let mut types = i.types.iter();
+    for q in q.iter() {
+        match q {
+            Some(GenericArg::Binding { name, ty }) => {
+                match i.bindings.iter().find(|(i, _)| i == name) {
+                    Some((_, i)) => sims.append(&mut ty.compare(i, krate, generics, substs)?),
+                    None => sims.push(Discrete(Subequal)),
+                }
+            }
+            Some(GenericArg::Type(q)) => match types.next() {
+                Some(Some(i)) => sims.append(&mut q.compare(i, krate, generics, substs)?),
+                Some(None) => sims.push(Discrete(Different)),
+                None => {}
+            },
+            None => {
+                types.next();
+            }
+        }
     }
+    Ok(sims)
 }
 
-impl Compare<types::Type> for Type {
+/// Compare `impl Trait` of a query with a type of an item, which matches if the type implements
+/// the trait: by its bounds if it's generic or `impl Trait` itself, or else by trait impls for it
+/// in any crate of the index.
+fn compare_impl_trait<'q>(
+    trait_: &Symbol<'q>,
+    args: Option<&GenericArgs<'q>>,
+    i: &types::Type,
+    krate: &types::Crate,
+    generics: &GenericsScope<'_>,
+    substs: &mut HashMap<String, Type<'q>>,
+) -> Result<Vec<Similarity>> {
+    use types::Type;
+
+    // Traits which `i` implements, with the arguments of their impls and the crates they are in.
+    let traits: Vec<(&str, ItemArgs<'_>, &types::Crate)> = match i {
+        Type::ImplTrait(bounds) => traits_of_bounds(bounds.iter(), krate),
+        Type::Generic(name) => traits_of_bounds(generics.bounds(name), krate),
+        Type::ResolvedPath { id, .. } => generics
+            .trait_impls
+            .map_or(&[][..], |impls| impls.of_type(krate, id))
+            .iter()
+            .filter_map(|item| {
+                let (impl_, krate) = match (&item.inner, item.krate()) {
+                    (types::ItemEnum::Impl(impl_), Some(krate)) => (impl_, krate),
+                    _ => return None,
+                };
+                let (name, args) = match impl_.trait_ {
+                    Some(Type::ResolvedPath {
+                        ref name, ref args, ..
+                    }) => (name, args),
+                    _ => return None,
+                };
+                let mut args = args.as_deref().map(ItemArgs::of).unwrap_or_default();
+                // Associated types are bound in the impl rather than in the path of the trait.
+                args.bindings.extend(impl_.items.iter().filter_map(
+                    |id| match krate.index.get(id)? {
+                        types::Item {
+                            name: Some(name),
+                            inner:
+                                types::ItemEnum::AssocType {
+                                    default: Some(ty), ..
+                                },
+                            ..
+                        } => Some((name.as_str(), ty)),
+                        _ => None,
+                    },
+                ));
+                Some((last_segment(name), args, krate))
+            })
+            .collect(),
+        Type::RawPointer { type_: i, .. } | Type::BorrowedRef { type_: i, .. } => {
+            let mut sims = compare_impl_trait(trait_, args, i, krate, generics, substs)?;
+            sims.push(Discrete(Subequal));
+            return Ok(sims);
+        }
+        _ => vec![],
+    };
+
+    // Keep the substitutions of the closest trait only, as the others don't apply.
+    let mut closest: Option<(Similarities, HashMap<String, crate::query::Type<'q>>)> = None;
+    for (name, i, krate) in traits {
+        let mut substs = substs.clone();
+        let mut sims = trait_.compare(&name.to_owned(), krate, generics, &mut substs)?;
+        if let Some(q) = args {
+            sims.append(&mut compare_generic_args(
+                q,
+                Some(&i),
+                krate,
+                generics,
+                &mut substs,
+            )?);
+        }
+        let sims = Similarities(sims);
+        if closest
+            .as_ref()
+            .is_some_and(|(closest, _)| *closest <= sims)
+        {
+            continue;
+        }
+        closest = Some((sims, substs));
+    }
+
+    match closest {
+        Some((sims, closest)) => {
+            *substs = closest;
+            Ok(sims.0)
+        }
+        None => Ok(vec![Discrete(Different)]),
+    }
+}
+
+/// Traits in `bounds` of an item in `krate` with their arguments.
+fn traits_of_bounds<'i>(
+    bounds: impl Iterator<Item = &'i types::GenericBound>,
+    krate: &'i types::Crate,
+) -> Vec<(&'i str, ItemArgs<'i>, &'i types::Crate)> {
+    bounds
+        .filter_map(|bound| match bound {
+            types::GenericBound::TraitBound {
+                trait_: types::Type::ResolvedPath { name, args, .. },
+                ..
+            } => Some((
+                last_segment(name),
+                args.as_deref().map(ItemArgs::of).unwrap_or_default(),
+                krate,
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+impl<'q> Compare<'q, types::Type> for Type<'q> {
     #[instrument(skip(krate))]
     fn compare(
         &self,
         type_: &types::Type,
         krate: &types::Crate,
-        generics: &mut types::Generics,
-        substs: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        generics: &GenericsScope<'_>,
+        substs: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         compare_type(self, type_, krate, generics, substs, true)
     }
 }
 
-impl Compare<String> for PrimitiveType {
+impl<'q> Compare<'q, String> for PrimitiveType {
     #[instrument]
     fn compare(
         &self,
         prim_ty: &String,
         _: &types::Crate,
-        _: &mut types::Generics,
-        _: &mut HashMap<String, Type>,
-    ) -> Vec<Similarity> {
+        _: &GenericsScope<'_>,
+        _: &mut HashMap<String, Type<'q>>,
+    ) -> Result<Vec<Similarity>> {
         if self.as_str() == prim_ty {
-            vec![Discrete(Equivalent)]
+            Ok(vec![Discrete(Equivalent)])
         } else {
-            vec![Discrete(Different)]
+            Ok(vec![Discrete(Different)])
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_weights() {
+        let weights = "output:3, name:0.5".parse::<Weights>().unwrap();
+        assert_eq!(
+            weights,
+            Weights {
+                name: 0.5,
+                inputs: 1.0,
+                output: 3.0,
+            }
+        );
+        assert_eq!("".parse::<Weights>().unwrap(), Weights::default());
+        assert!("output".parse::<Weights>().is_err());
+        assert!("output:-1".parse::<Weights>().is_err());
+        assert!("return:1".parse::<Weights>().is_err());
+    }
+
+    #[test]
+    fn score_with_weights() {
+        // Many inputs matching trivially don't drown the output.
+        let sims = Similarities(vec![
+            Discrete(Equivalent),
+            Discrete(Equivalent),
+            Discrete(Equivalent),
+            Output(1.0),
+        ]);
+        assert_eq!(sims.score(), 0.5);
+
+        let weights = "output:3".parse().unwrap();
+        assert_eq!(sims.score_with(&weights), 0.75);
+        let weights = "inputs:0,output:0".parse().unwrap();
+        assert_eq!(sims.score_with(&weights), 0.25);
+    }
+
+    #[test]
+    fn compare_argument_names() {
+        let score = |q, i| compare_arg_names(q, i).score();
+        assert_eq!(score("path", "path"), 0.0);
+        assert_eq!(score("filePath", "file_path"), 0.0);
+        assert_eq!(score("path", "file_path"), 0.25);
+        assert_eq!(score("contents", "content"), 0.125);
+        assert_eq!(score("buf", "buffer"), 0.25);
+        assert_eq!(score("dst", "destination"), 0.25);
+        assert!(score("path", "file_path") < score("path", "pattern"));
+        assert!(score("path", "contents") > 0.5);
+    }
+
+    #[test]
+    fn levenshtein_within_bound() {
+        assert_eq!(levenshtein_within("kitten", "sitting", 3), Some(3));
+        assert_eq!(levenshtein_within("kitten", "sitting", 2), None);
+        assert_eq!(levenshtein_within("", "len", 3), Some(3));
+        assert_eq!(levenshtein_within("len", "length", 2), None);
+        assert_eq!(levenshtein_within("naïve", "naive", 1), Some(1));
+        assert_eq!(levenshtein_within("same", "same", 0), Some(0));
+
+        assert_eq!(compare_symbol("len", "Vec::len", 0.4), Continuous(0.0));
+        assert_eq!(compare_symbol("len", "push", 0.4), Discrete(Different));
+    }
+}
@@ -0,0 +1,45 @@
+//! Functions and methods in one shape, whichever items rustdoc JSON puts them in.
+//!
+//! Older formats have separate `Function` and `Method` items, while newer ones fold methods into
+//! `Function` and tell them apart only by the impl or trait owning them. Search goes through
+//! [`FnItem`], and decides whether an item is a method by its owner rather than its variant.
+
+use rustdoc_types as types;
+
+/// Impl block or trait a method is associated with.
+#[derive(Debug, Clone, Copy)]
+pub enum Owner<'a> {
+    Impl(&'a types::Impl),
+    /// Definition of a trait with its id, for methods declared in it.
+    Trait(&'a types::Id, &'a types::Trait),
+}
+
+/// Declaration and generics of a function or a method.
+#[derive(Debug, Clone, Copy)]
+pub struct FnItem<'a> {
+    pub decl: &'a types::FnDecl,
+    pub generics: &'a types::Generics,
+}
+
+impl<'a> FnItem<'a> {
+    /// `inner` as a function, or `None` if it is neither a function nor a method.
+    pub fn of(inner: &'a types::ItemEnum) -> Option<Self> {
+        match inner {
+            types::ItemEnum::Function(f) => Some(f.into()),
+            types::ItemEnum::Method(m) => Some(FnItem {
+                decl: &m.decl,
+                generics: &m.generics,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a types::Function> for FnItem<'a> {
+    fn from(f: &'a types::Function) -> Self {
+        FnItem {
+            decl: &f.decl,
+            generics: &f.generics,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Deserializing rustdoc JSON of any depth, shared by everything reading crates.
+
+use serde::Deserialize;
+
+/// Deepest nesting of arrays and objects [`from_json`] reads, far deeper than rustdoc JSON of
+/// crates nests, so that deserializing any input fits in [`STACK_SIZE`].
+pub const MAX_DEPTH: usize = 4096;
+
+/// Stack size of the thread [`from_json`] deserializes on, of which only the part in use is
+/// allocated. `MAX_DEPTH` levels take most of it in debug builds, as rustdoc buffers the inner
+/// parts of items and deserializes them again recursively.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// Deserialize rustdoc JSON, e.g. a [`rustdoc_types::Crate`] or a snapshot, from `json`.
+///
+/// Unlike `serde_json::from_str`, this reads up to [`MAX_DEPTH`] levels rather than 128, which
+/// deeply nested types in crates like `syn` exceed. It deserializes on a thread of its own with a
+/// stack large enough for them, so that neither the main thread nor workers or blocking tasks with
+/// smaller stacks overflow. On `wasm32`, which has no threads, it deserializes on the caller's
+/// stack.
+pub fn from_json<'de, T: Deserialize<'de> + Send>(json: &'de str) -> serde_json::Result<T> {
+    let depth = depth(json);
+    if depth > MAX_DEPTH {
+        return Err(serde::de::Error::custom(format!(
+            "nesting of {} levels exceeds the limit of {}",
+            depth, MAX_DEPTH
+        )));
+    }
+
+    on_large_stack(|| {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        deserializer.disable_recursion_limit();
+        let value = T::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(value)
+    })
+    .map_err(|e| serde::de::Error::custom(format!("failed to spawn a thread: {}", e)))?
+}
+
+/// Run `f` on a thread with a stack of [`STACK_SIZE`], e.g. to recurse into deeply nested types,
+/// or on the caller's stack on `wasm32`, which has no threads.
+pub(crate) fn on_large_stack<R: Send>(f: impl FnOnce() -> R + Send) -> std::io::Result<R> {
+    #[cfg(target_arch = "wasm32")]
+    return Ok(f());
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::scope(|scope| {
+        let value = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)?
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e));
+        Ok(value)
+    })
+}
+
+/// Deepest nesting of arrays and objects in `json`, counted without recursion so that it's safe
+/// for any input, which may be malformed.
+fn depth(json: &str) -> usize {
+    let (mut depth, mut max) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for b in json.bytes() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            _ if in_string => {}
+            b'{' | b'[' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use rustdoc_types::Type;
+
+    use super::*;
+
+    #[test]
+    fn deserialize_deeply_nested_type() {
+        // Deeper than the stack of the test thread holds.
+        let depth = 3000;
+        let json = format!(
+            r#"{}{{"kind":"primitive","inner":"u8"}}{}"#,
+            r#"{"kind":"slice","inner":"#.repeat(depth),
+            "}".repeat(depth)
+        );
+        assert!(serde_json::from_str::<Type>(&json).is_err());
+        assert_eq!(super::depth(&json), depth + 1);
+
+        let mut ty = from_json::<Type>(&json).unwrap();
+        for _ in 0..depth {
+            ty = match ty {
+                Type::Slice(ty) => *ty,
+                ty => panic!("expected a slice, found {:?}", ty),
+            };
+        }
+        assert_eq!(ty, Type::Primitive("u8".to_owned()));
+
+        let too_deep = format!("{}{}", "[".repeat(MAX_DEPTH + 1), "]".repeat(MAX_DEPTH + 1));
+        let e = from_json::<serde_json::Value>(&too_deep).unwrap_err();
+        assert!(e.to_string().contains("exceeds the limit"), "{}", e);
+        // Brackets in strings don't nest.
+        assert_eq!(super::depth(r#"{"a":"[{\"]","b":[]}"#), 2);
+    }
+}
@@ -1,12 +1,171 @@
 pub mod compare;
+pub mod function;
+pub mod json;
+pub mod matcher;
+pub mod metadata;
+mod names;
 pub mod query;
 pub mod search;
+pub mod signature;
+mod similar;
+pub mod snapshot;
+mod summary;
+pub mod templates;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod uses;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 use rustdoc_types::Crate;
 
+use crate::{
+    metadata::{ItemMetadata, Receivers, TraitImpls},
+    names::Names,
+    snapshot::{Manifest, SnapshotError},
+};
+
+/// Crates to search in, keyed by their names.
+///
+/// `Index` is `Send + Sync`, so it can be shared across threads such as the workers of a server,
+/// and searched from async tasks.
 #[derive(Debug, Default)]
 pub struct Index {
-    pub crates: HashMap<String, Crate>,
+    /// Crates shared with the metadata of their items, which refers to the items in them.
+    crates: HashMap<String, Arc<Crate>>,
+    /// Metadata of the items search compares queries with, keyed by the names of their crates.
+    metadata: HashMap<String, Vec<ItemMetadata>>,
+    /// Methods in the metadata keyed by the types they take as `self`, keyed by the names of their
+    /// crates.
+    receivers: HashMap<String, Receivers>,
+    /// Names of the items in the metadata, keyed by the names of their crates.
+    names: HashMap<String, Names>,
+    /// Impl blocks of traits in all the crates keyed by the types they are for.
+    trait_impls: TraitImpls,
+    /// Checksums of the crates, computed on first use, keyed by their names.
+    checksums: HashMap<String, OnceLock<String>>,
+}
+
+impl Index {
+    pub fn new(crates: HashMap<String, Crate>) -> Self {
+        let crates: HashMap<_, _> = crates
+            .into_iter()
+            .map(|(name, krate)| (name, Arc::new(krate)))
+            .collect();
+        let metadata = crates
+            .iter()
+            .map(|(name, krate)| (name.clone(), metadata::of_crate(krate)))
+            .collect();
+        let checksums = crates
+            .keys()
+            .map(|name| (name.clone(), OnceLock::new()))
+            .collect();
+        let mut index = Index {
+            crates,
+            metadata,
+            receivers: HashMap::new(),
+            names: HashMap::new(),
+            trait_impls: TraitImpls::default(),
+            checksums,
+        };
+        index.resolve_provided_methods();
+        index
+    }
+
+    pub fn crates(&self) -> &HashMap<String, Arc<Crate>> {
+        &self.crates
+    }
+
+    /// Metadata of the items in crate `name` which search compares queries with.
+    pub fn metadata(&self, name: &str) -> Option<&[ItemMetadata]> {
+        self.metadata.get(name).map(Vec::as_slice)
+    }
+
+    /// Checksum of the contents of crate `name`, which changes whenever the crate does, or `None`
+    /// if the index has no such crate.
+    ///
+    /// It's computed on first use, which takes about as long as serializing the crate; see
+    /// [`snapshot::checksum_of`] for how that fails.
+    pub fn checksum(&self, name: &str) -> std::io::Result<Option<&str>> {
+        let (krate, checksum) = match self.crates.get(name).zip(self.checksums.get(name)) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if let Some(checksum) = checksum.get() {
+            return Ok(Some(checksum));
+        }
+        let computed = snapshot::checksum_of(krate)?;
+        Ok(Some(checksum.get_or_init(|| computed)))
+    }
+
+    /// Add `krate` as `name`, returning the crate it replaced if any.
+    pub fn insert(&mut self, name: String, krate: Crate) -> Option<Arc<Crate>> {
+        let krate = Arc::new(krate);
+        self.metadata
+            .insert(name.clone(), metadata::of_crate(&krate));
+        self.checksums.insert(name.clone(), OnceLock::new());
+        let replaced = self.crates.insert(name, krate);
+        self.resolve_provided_methods();
+        replaced
+    }
+
+    /// Write a snapshot of the crates in the index into `path`, returning its manifest.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<Manifest, SnapshotError> {
+        let mut w = BufWriter::new(std::fs::File::create(path)?);
+        let manifest = snapshot::write(&mut w, &self.crates)?;
+        w.flush()?;
+        Ok(manifest)
+    }
+
+    /// Load the index from the snapshot at `path`, failing unless its crates are exactly the ones
+    /// its manifest lists.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let (_, crates) = snapshot::read(&std::fs::read_to_string(path)?)?;
+        Ok(Index::new(crates))
+    }
+
+    /// Add the metadata of methods impls get from traits of other crates to the metadata of their
+    /// crates anew, as the crates declaring the traits may have come or changed, and index the
+    /// methods by their receivers, the items by their names and the impls of traits by their types.
+    fn resolve_provided_methods(&mut self) {
+        let traits = metadata::traits(&self.crates);
+        for (name, krate) in &self.crates {
+            let metadata = self.metadata.entry(name.clone()).or_default();
+            metadata.retain(|metadata| !metadata.provided);
+            metadata.extend(metadata::of_provided_methods(krate, &traits));
+        }
+        self.receivers = self
+            .metadata
+            .iter()
+            .map(|(name, metadata)| (name.clone(), Receivers::of(metadata)))
+            .collect();
+        self.names = self
+            .metadata
+            .iter()
+            .map(|(name, metadata)| (name.clone(), Names::of(metadata)))
+            .collect();
+        self.trait_impls = TraitImpls::of(&self.crates);
+    }
 }
+
+// Keep types used across threads `Send + Sync`; this fails to compile once one of them isn't,
+// e.g. by holding an `Rc`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Index>();
+    assert_send_sync::<matcher::DefaultMatcher>();
+    assert_send_sync::<query::Query<'static>>();
+    assert_send_sync::<query::parse::QueryParseError>();
+    assert_send_sync::<search::Scope>();
+    assert_send_sync::<search::Hit>();
+    assert_send_sync::<search::SearchError>();
+    assert_send_sync::<snapshot::SnapshotError>();
+};
@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rustdoc_types as types;
+
+use crate::{
+    compare::{
+        compare_symbol, Compare, DiscreteSimilarity, GenericsScope, Similarities, Similarity,
+    },
+    function::Owner,
+    metadata::TraitImpls,
+    query::{Query, Type},
+    search::Result,
+};
+
+/// Strategy of scoring how similar an item is to a query.
+///
+/// Pass one to [`Index::search_with`](crate::Index::search_with) to search with other strategies
+/// than [`DefaultMatcher`], e.g. stricter unification or coercion-aware comparison.
+pub trait Matcher: Send + Sync {
+    /// Compare `query` with `item` in `krate`, which is indexed as `krate_name`, where `owner` is
+    /// the impl block or trait of `item` if it is a method and `impls` are the impl blocks of
+    /// traits in every crate of the index.
+    fn compare(
+        &self,
+        query: &Query,
+        item: &types::Item,
+        krate: &types::Crate,
+        krate_name: &str,
+        owner: Option<Owner<'_>>,
+        impls: &TraitImpls,
+    ) -> Result<Similarities>;
+
+    /// Whether items score by their names alone for queries with names alone, as
+    /// [`DefaultMatcher`] scores them, so that search may only compare items named close enough
+    /// to score less than the threshold, looking them up in an index of names.
+    fn scores_names_by_distance(&self) -> bool {
+        false
+    }
+}
+
+/// Compare queries with items structurally through [`Compare`], binding `Self` of methods to the
+/// type of their impl blocks.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultMatcher {
+    threshold: f32,
+    unwrap_newtypes: bool,
+}
+
+impl DefaultMatcher {
+    /// Matcher for search with `threshold`, which may stop comparing an item early once its
+    /// score can't be less than it.
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            unwrap_newtypes: false,
+        }
+    }
+
+    /// Matcher comparing newtypes of items, e.g. `Meters` of `struct Meters(f64)`, as the types
+    /// they wrap as well if `unwrap` is true, at a penalty, so that `f64` also finds `Meters`.
+    pub fn unwrap_newtypes(self, unwrap: bool) -> Self {
+        Self {
+            unwrap_newtypes: unwrap,
+            ..self
+        }
+    }
+}
+
+impl Default for DefaultMatcher {
+    /// Matcher computing exact scores of all items.
+    fn default() -> Self {
+        Self::new(f32::INFINITY)
+    }
+}
+
+impl Matcher for DefaultMatcher {
+    #[tracing::instrument(skip_all, fields(item = ?item.name))]
+    fn compare(
+        &self,
+        query: &Query,
+        item: &types::Item,
+        krate: &types::Crate,
+        _: &str,
+        owner: Option<Owner<'_>>,
+        impls: &TraitImpls,
+    ) -> Result<Similarities> {
+        if let (Some(name), None) = (&query.name, &query.kind) {
+            // The score is the similarity of the names alone, so it can stop being measured early.
+            let sim = match item.name {
+                Some(ref i) => compare_symbol(name, i, self.threshold),
+                None => Similarity::Discrete(DiscreteSimilarity::Different),
+            };
+            return Ok(Similarities(vec![Similarity::Name(sim.score())]));
+        }
+
+        let generics = owner
+            .map(GenericsScope::of_owner)
+            .unwrap_or_default()
+            .unwrap_newtypes(self.unwrap_newtypes)
+            .trait_impls(impls);
+        let mut substs = HashMap::default();
+
+        let sims = query.compare(item, krate, &generics, &mut substs)?;
+        Ok(Similarities(sims))
+    }
+
+    fn scores_names_by_distance(&self) -> bool {
+        true
+    }
+}
+
+/// Accept only items which another matcher finds [exact](Similarities::is_exact), e.g. to audit
+/// whether a function with exactly a signature exists anywhere.
+///
+/// Other items are different, which any threshold up to 1 leaves out.
+#[derive(Clone, Copy)]
+pub struct ExactMatcher<'m>(pub &'m dyn Matcher);
+
+impl Matcher for ExactMatcher<'_> {
+    fn compare(
+        &self,
+        query: &Query,
+        item: &types::Item,
+        krate: &types::Crate,
+        krate_name: &str,
+        owner: Option<Owner<'_>>,
+        impls: &TraitImpls,
+    ) -> Result<Similarities> {
+        let sims = self
+            .0
+            .compare(query, item, krate, krate_name, owner, impls)?;
+        if sims.is_exact() {
+            Ok(sims)
+        } else {
+            Ok(Similarities(vec![Similarity::Discrete(
+                DiscreteSimilarity::Different,
+            )]))
+        }
+    }
+
+    fn scores_names_by_distance(&self) -> bool {
+        self.0.scores_names_by_distance()
+    }
+}
+
+/// Generic types of `item` bound to concrete types of `query` by comparing them structurally,
+/// e.g. `T` to `u8` for query `Vec<u8>` and item `Vec<T>`, whichever matcher scored `item`.
+///
+/// Generic types bound to generic types of the query are left out, as they tell nothing.
+pub(crate) fn substitutions(
+    query: &Query,
+    item: &types::Item,
+    krate: &types::Crate,
+    owner: Option<Owner<'_>>,
+    impls: &TraitImpls,
+) -> Result<BTreeMap<String, String>> {
+    let generics = owner
+        .map(GenericsScope::of_owner)
+        .unwrap_or_default()
+        .trait_impls(impls);
+    let mut substs = HashMap::default();
+    query.compare(item, krate, &generics, &mut substs)?;
+
+    Ok(substs
+        .into_iter()
+        .filter(|(_, ty)| !matches!(ty, Type::Generic(_)))
+        .map(|(generic, ty)| (generic, ty.to_string()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        query::{parse::parse_query_in, Arena},
+        search::{ItemKind, Scope},
+        testing::load_fixture,
+    };
+
+    /// Matches any item perfectly.
+    struct Anything;
+
+    impl Matcher for Anything {
+        fn compare(
+            &self,
+            _: &Query,
+            _: &types::Item,
+            _: &types::Crate,
+            _: &str,
+            _: Option<Owner<'_>>,
+            _: &TraitImpls,
+        ) -> Result<Similarities> {
+            Ok(Similarities(vec![Similarity::Continuous(0.0)]))
+        }
+    }
+
+    #[test]
+    fn search_with_custom_matcher() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (&[u8]) -> Result<&str, Utf8Error>").unwrap();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        assert_eq!(index.search(&query, scope(), 0.1).unwrap().len(), 1);
+        let hits = index.search_with(&Anything, &query, scope(), 0.1).unwrap();
+        assert_eq!(hits.len(), 14);
+    }
+
+    #[test]
+    fn bind_self_of_query() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (usize) -> Self").unwrap();
+        let scope = Scope::Crate("mini_std".to_owned());
+
+        let hits = index.search(&query, scope, 0.4).unwrap();
+        assert_eq!(
+            hits[0].path,
+            vec!["mini_std", "vec", "Vec", "with_capacity"]
+        );
+    }
+
+    #[test]
+    fn search_in_trait() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        let query = parse_query_in(&arena, "fn (&self) -> Self").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert!(hits.iter().all(|hit| hit.name != "clone"));
+
+        let query = parse_query_in(&arena, "in Clone fn (&self) -> Self").unwrap();
+        let mut hits = index.search(&query, scope(), 0.4).unwrap();
+        hits.sort_by(|a, b| a.link.cmp(&b.link));
+        let links = hits
+            .iter()
+            .map(|hit| hit.link.join("/"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                "mini_std/clone/trait.Clone.html#method.clone",
+                "mini_std/clone/trait.Clone.html#tymethod.clone",
+            ]
+        );
+        assert!(hits.iter().all(|hit| hit.similarities().score() == 0.0));
+    }
+
+    #[test]
+    fn search_constructors() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        let query = parse_query_in(&arena, "new Vec").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        let names = hits.iter().map(|hit| hit.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["new", "with_capacity"]);
+
+        // Constructors may fail, returning what they construct in `Result`.
+        let query = parse_query_in(&arena, "make &str").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "from_utf8");
+    }
+
+    #[test]
+    fn search_impl_trait() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        // `IntoIter` implements `Iterator`, binding `Item` in the impl.
+        let query = parse_query_in(&arena, "fn (impl Iterator<Item = T>) -> Vec<T>").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "collect_vec");
+
+        // `Vec` implements `Clone` but not `Iterator`.
+        let query = parse_query_in(&arena, "fn (&impl Clone) -> usize").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "len");
+        assert_eq!(hits[0].similarities().score(), 0.0);
+        let query = parse_query_in(&arena, "fn (impl Iterator) -> usize").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert!(hits
+            .iter()
+            .filter(|hit| hit.name == "len")
+            .all(|hit| hit.similarities().score() > 0.25));
+    }
+
+    #[test]
+    fn search_fails_with() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let names = |query| {
+            let query = parse_query_in(&arena, query).unwrap();
+            let hits = index.search(&query, scope(), 0.1).unwrap();
+            hits.into_iter().map(|hit| hit.name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("fails_with Utf8Error"), vec!["from_utf8"]);
+        // `io::read` returns `io::Result<usize>`, which is an alias of `Result<usize, io::Error>`.
+        assert_eq!(names("fails_with io::Error"), vec!["read"]);
+    }
+
+    #[test]
+    fn substitutions_of_hits() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        let query = parse_query_in(&arena, "fn (&mut u8, &mut u8)").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "swap");
+        assert_eq!(hits[0].with_clause().as_deref(), Some("with T = u8"));
+
+        // Generic types of queries tell nothing about why items matched.
+        let query = parse_query_in(&arena, "fn (&mut T, &mut T)").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "swap");
+        assert!(hits[0].substitutions.is_empty());
+    }
+
+    #[test]
+    fn search_exactly() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let exact = ExactMatcher(&DefaultMatcher::new(0.4));
+
+        let query = parse_query_in(&arena, "fn (&[u8]) -> Result<&str, Utf8Error>").unwrap();
+        let hits = index.search_with(&exact, &query, scope(), 0.4).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].name, "from_utf8");
+
+        // `swap` is generic over `T`, which binds to `u8` but isn't it.
+        let query = parse_query_in(&arena, "fn (&mut u8, &mut u8) -> ()").unwrap();
+        assert_eq!(index.search(&query, scope(), 0.4).unwrap()[0].name, "swap");
+        assert!(index
+            .search_with(&exact, &query, scope(), 0.4)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn search_with_weights() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let mut query = parse_query_in(&arena, "fn (&mut [u8]) -> Vec<u8>").unwrap();
+
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "read");
+
+        // `read` takes the very input but returns no `Vec`.
+        query.weights = "output:3".parse().unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert!(matches!(
+            hits[0].name.as_str(),
+            "collect_vec" | "with_capacity"
+        ));
+        assert!(hits.iter().take(3).all(|hit| hit.name != "read"));
+    }
+
+    #[test]
+    fn search_argument_names() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        // `buf` abbreviates `buffer`, and `cap` does `capacity`.
+        let query = parse_query_in(&arena, "fn (buffer: _) -> Result<usize>").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "read");
+        let query = parse_query_in(&arena, "fn (cap: _) -> Vec<T>").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "with_capacity");
+        assert!(hits.iter().all(|hit| hit.name != "collect_vec"));
+    }
+
+    #[test]
+    fn search_methods_of_receivers() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let names = |query| {
+            let query = parse_query_in(&arena, query).unwrap();
+            let hits = index.search(&query, scope(), 0.6).unwrap();
+            hits.into_iter()
+                .map(|hit| hit.path.join("::"))
+                .collect::<Vec<_>>()
+        };
+
+        // Methods of `str` aren't walked, unlike functions taking anything.
+        assert_eq!(
+            names("fn (&Vec<T>) -> usize"),
+            vec!["mini_std::vec::Vec::len"]
+        );
+        assert!(
+            names("fn (Vec<u8>) -> Vec<u8>").contains(&"mini_std::convert::identity".to_owned())
+        );
+        // Misspelled types walk every method.
+        assert!(names("fn (&Vex<T>) -> usize").contains(&"str::len".to_owned()));
+    }
+
+    #[test]
+    fn search_with_attributes() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let names = |query| {
+            let query = parse_query_in(&arena, query).unwrap();
+            let hits = index.search(&query, scope(), 0.4).unwrap();
+            hits.into_iter()
+                .map(|hit| hit.path.join("::"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("fn (&self) -> usize").len(), 4);
+        let mut inline = names("inline fn (&self) -> usize");
+        inline.sort();
+        assert_eq!(inline, vec!["mini_std::vec::Vec::len", "str::len"]);
+        assert_eq!(
+            names("must_use inline fn (&self) -> usize"),
+            vec!["str::len"]
+        );
+        assert!(names("must_use fn (usize) -> Self")
+            .iter()
+            .all(|path| path.starts_with("mini_std::vec::Vec::")));
+
+        let query = parse_query_in(&arena, "fn (&mut [u8]) -> Result<usize>").unwrap();
+        let hits = index.search(&query, scope(), 0.4).unwrap();
+        assert_eq!(hits[0].name, "read");
+        assert_eq!(hits[0].feature.as_deref(), Some("io"));
+    }
+
+    #[test]
+    fn search_with_kinds() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let search = |kinds: &str| {
+            let query = Query {
+                kinds: kinds.parse().unwrap(),
+                ..parse_query_in(&arena, "fn (&[u8]) -> usize").unwrap()
+            };
+            index
+                .search(&query, Scope::Crate("mini_std".to_owned()), 0.6)
+                .unwrap()
+        };
+
+        let all = search("");
+        assert!(all.iter().any(|hit| hit.kind == ItemKind::Function));
+        assert!(all.iter().any(|hit| hit.kind == ItemKind::Method));
+        let methods = search("method");
+        assert!(!methods.is_empty());
+        assert!(methods.iter().all(|hit| hit.kind == ItemKind::Method));
+        assert_eq!(search("method,function").len(), all.len());
+    }
+}
@@ -0,0 +1,780 @@
+//! Metadata of items computed once when crates are added to an [`Index`](crate::Index), so that
+//! search walks flat lists of functions and methods instead of every item and impl each time.
+//!
+//! Impls are flattened into their methods then: each method carries itself and its impl block or
+//! trait, so search never resolves ids of items through the crates.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    sync::Arc,
+};
+
+use rustdoc_types as types;
+
+use crate::{
+    function::{FnItem, Owner},
+    query::Attribute,
+    search::ItemKind,
+};
+
+/// A function or method search compares queries with, along with the shape of its signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemMetadata {
+    pub id: types::Id,
+    pub kind: ItemKind,
+    /// The item itself.
+    pub item: SharedItem,
+    /// Impl block or trait the item is associated with if it is a method.
+    pub owner: Option<MethodOwner>,
+    /// Name of the trait the item is declared in or implements, if any.
+    ///
+    /// Methods of traits are only searched for when queries name their traits.
+    pub trait_: Option<String>,
+    /// Number of arguments, including `self`.
+    pub arity: usize,
+    pub input_heads: Vec<TypeHead>,
+    /// Head of the returned type, or `None` if it returns `()`.
+    pub output_head: Option<TypeHead>,
+    /// Whether the item or its impl block has generic type or const parameters.
+    pub has_generics: bool,
+    /// Name of the type the impl block of the item is for, if it is a method taking `self`, e.g.
+    /// `Vec` for `Vec::push` or `str` for `str::len`.
+    pub receiver: Option<String>,
+    /// Attributes of the item queries can require.
+    pub attrs: Vec<Attribute>,
+    /// Cargo feature the item or its impl block is only available with.
+    pub feature: Option<String>,
+    /// Whether the item is a method an impl block gets from a trait of another crate without
+    /// defining it, cloned from that crate with the ids in its signature qualified by the name of
+    /// the crate so they aren't taken for items of this one.
+    ///
+    /// Its id is made up of the ids of the method and the impl block, so it's unique in the crate.
+    pub provided: bool,
+}
+
+/// An item of a crate in an [`Index`](crate::Index), which shares the crate rather than being
+/// copied out of it.
+#[derive(Debug, Clone)]
+pub enum SharedItem {
+    /// Item `id` in the index of the crate.
+    InCrate(Arc<types::Crate>, types::Id),
+    /// Item the crate doesn't have as is, like a provided method cloned from the crate of its
+    /// trait.
+    Owned(Arc<types::Item>),
+}
+
+impl SharedItem {
+    /// Item `id` of `krate`, or `None` if `krate` has no such item.
+    pub fn of(krate: &Arc<types::Crate>, id: &types::Id) -> Option<Self> {
+        krate
+            .index
+            .contains_key(id)
+            .then(|| SharedItem::InCrate(krate.clone(), id.clone()))
+    }
+
+    /// Crate the item is in, or `None` if it is owned.
+    pub fn krate(&self) -> Option<&types::Crate> {
+        match self {
+            SharedItem::InCrate(krate, _) => Some(krate),
+            SharedItem::Owned(_) => None,
+        }
+    }
+}
+
+impl Deref for SharedItem {
+    type Target = types::Item;
+
+    fn deref(&self) -> &types::Item {
+        match self {
+            // Items are only shared once they are known to be in the crate, which never changes.
+            SharedItem::InCrate(krate, id) => &krate.index[id],
+            SharedItem::Owned(item) => item,
+        }
+    }
+}
+
+impl PartialEq for SharedItem {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+/// Impl block or trait methods are associated with, shared by the metadata of all of them.
+///
+/// Owners are only made of items of their kinds, once the items are matched as such.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MethodOwner {
+    Impl(SharedItem),
+    Trait(SharedItem),
+}
+
+impl MethodOwner {
+    pub fn id(&self) -> &types::Id {
+        &self.item().id
+    }
+
+    fn item(&self) -> &SharedItem {
+        match self {
+            MethodOwner::Impl(item) | MethodOwner::Trait(item) => item,
+        }
+    }
+
+    /// The impl block, or `None` if it is a trait.
+    pub fn impl_(&self) -> Option<&types::Impl> {
+        match self.as_owner()? {
+            Owner::Impl(impl_) => Some(impl_),
+            Owner::Trait(..) => None,
+        }
+    }
+
+    /// The impl block or trait as the owner of its methods, or `None` if the item isn't of the
+    /// kind of the owner, which no owner is made of.
+    pub fn as_owner(&self) -> Option<Owner<'_>> {
+        match (self, &self.item().inner) {
+            (MethodOwner::Impl(_), types::ItemEnum::Impl(impl_)) => Some(Owner::Impl(impl_)),
+            (MethodOwner::Trait(item), types::ItemEnum::Trait(trait_)) => {
+                Some(Owner::Trait(&item.id, trait_))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Outermost constructor of a type, looking through references and raw pointers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeHead {
+    /// Path like `Option`, without its generic arguments.
+    Path(String),
+    Generic(String),
+    Primitive(String),
+    Tuple,
+    Slice,
+    Array,
+    FunctionPointer,
+    ImplTrait,
+    /// `_`, or an associated type like `<T as Iterator>::Item`.
+    Other,
+}
+
+impl TypeHead {
+    pub fn of(ty: &types::Type) -> Self {
+        use types::Type;
+
+        match ty {
+            Type::ResolvedPath { name, .. } => TypeHead::Path(name.clone()),
+            Type::Generic(name) => TypeHead::Generic(name.clone()),
+            Type::Primitive(name) => TypeHead::Primitive(name.clone()),
+            Type::Tuple(_) => TypeHead::Tuple,
+            Type::Slice(_) => TypeHead::Slice,
+            Type::Array { .. } => TypeHead::Array,
+            Type::FunctionPointer(_) => TypeHead::FunctionPointer,
+            Type::ImplTrait(_) => TypeHead::ImplTrait,
+            Type::RawPointer { type_, .. } | Type::BorrowedRef { type_, .. } => TypeHead::of(type_),
+            Type::Infer | Type::QualifiedPath { .. } => TypeHead::Other,
+        }
+    }
+}
+
+/// Metadata of the functions and methods in `krate`, i.e. the items search compares queries with.
+///
+/// Functions owned by impls or traits are methods, whether they are `Method` items or `Function`
+/// ones as in newer formats of rustdoc JSON, so they are not taken as free functions. Methods
+/// missing from the index of `krate` are left out.
+pub fn of_crate(krate: &Arc<types::Crate>) -> Vec<ItemMetadata> {
+    let owned: HashSet<&types::Id> = krate
+        .index
+        .values()
+        .flat_map(|item| match item.inner {
+            types::ItemEnum::Impl(ref impl_) => impl_.items.as_slice(),
+            types::ItemEnum::Trait(ref trait_) => trait_.items.as_slice(),
+            _ => &[],
+        })
+        .collect();
+
+    let mut metadata = vec![];
+    for item in krate.index.values() {
+        let shared = || SharedItem::InCrate(krate.clone(), item.id.clone());
+        match item.inner {
+            types::ItemEnum::Impl(ref impl_) => {
+                let trait_ = match impl_.trait_ {
+                    Some(types::Type::ResolvedPath { ref name, .. }) => Some(last_segment(name)),
+                    Some(_) => continue,
+                    None => None,
+                };
+                let owner = MethodOwner::Impl(shared());
+                let methods = impl_
+                    .items
+                    .iter()
+                    .filter_map(|id| SharedItem::of(krate, id));
+                metadata.extend(of_methods(krate, methods, owner, trait_));
+            }
+            types::ItemEnum::Trait(ref trait_) => {
+                let name = item.name.as_deref().map(last_segment);
+                let owner = MethodOwner::Trait(shared());
+                let methods = trait_
+                    .items
+                    .iter()
+                    .filter_map(|id| SharedItem::of(krate, id));
+                metadata.extend(of_methods(krate, methods, owner, name));
+            }
+            _ if !owned.contains(&item.id) => {
+                if let types::ItemEnum::Function(ref f) = item.inner {
+                    metadata.push(of_fn(shared(), f.into(), None));
+                }
+            }
+            _ => {}
+        }
+    }
+    metadata
+}
+
+/// Traits declared in the crates of an index, keyed by their paths, along with the names of their
+/// crates.
+pub(crate) type Traits<'c> = HashMap<&'c [String], (&'c str, &'c types::Crate, &'c types::Trait)>;
+
+pub(crate) fn traits(crates: &HashMap<String, Arc<types::Crate>>) -> Traits<'_> {
+    crates
+        .iter()
+        .flat_map(|(name, krate)| {
+            krate
+                .index
+                .values()
+                .filter_map(move |item| match item.inner {
+                    types::ItemEnum::Trait(ref trait_) => {
+                        let summary = krate.paths.get(&item.id).filter(|s| s.crate_id == 0)?;
+                        Some((summary.path.as_slice(), (name.as_str(), &**krate, trait_)))
+                    }
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+/// Metadata of the methods impl blocks in `krate` get from traits among `traits` declared in other
+/// crates without defining them, e.g. `Clone::clone_from` of a type deriving `Clone`.
+///
+/// Their signatures are only in the crates of the traits, so they can't be found without them.
+pub(crate) fn of_provided_methods(
+    krate: &Arc<types::Crate>,
+    traits: &Traits<'_>,
+) -> Vec<ItemMetadata> {
+    let mut metadata = vec![];
+    for item in krate.index.values() {
+        let impl_ = match item.inner {
+            types::ItemEnum::Impl(ref impl_) if !impl_.provided_trait_methods.is_empty() => impl_,
+            _ => continue,
+        };
+        let (name, id) = match impl_.trait_ {
+            Some(types::Type::ResolvedPath {
+                ref name, ref id, ..
+            }) => (name, id),
+            _ => continue,
+        };
+        let (source_name, source, trait_) = match krate
+            .paths
+            .get(id)
+            .filter(|summary| summary.crate_id != 0)
+            .and_then(|summary| traits.get(summary.path.as_slice()))
+        {
+            Some(&trait_) => trait_,
+            None => continue,
+        };
+
+        let provided = trait_
+            .items
+            .iter()
+            .filter_map(|id| source.index.get(id))
+            .filter(|method| {
+                method
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| impl_.provided_trait_methods.contains(name))
+            });
+        let owner = MethodOwner::Impl(SharedItem::InCrate(krate.clone(), item.id.clone()));
+        for method in provided {
+            // Provided methods are only in the crates of their traits, and have their ids
+            // qualified, so they are the only items which are copied.
+            let mut provided = method.clone();
+            provided.id = types::Id(format!("{}@{}", method.id.0, item.id.0));
+            qualify::item(&mut provided.inner, source_name);
+            let of_method = of_methods(
+                krate,
+                [SharedItem::Owned(Arc::new(provided))].into_iter(),
+                owner.clone(),
+                Some(last_segment(name)),
+            )
+            .next();
+            if let Some(of_method) = of_method {
+                metadata.push(ItemMetadata {
+                    provided: true,
+                    ..of_method
+                });
+            }
+        }
+    }
+    metadata
+}
+
+/// Metadata of `methods` in `owner`, which are of `trait_` if it is given.
+fn of_methods<'a>(
+    krate: &'a types::Crate,
+    methods: impl Iterator<Item = SharedItem> + 'a,
+    owner: MethodOwner,
+    trait_: Option<&'a str>,
+) -> impl Iterator<Item = ItemMetadata> + 'a {
+    methods.filter_map(move |method| {
+        let f = FnItem::of(&method.inner)?;
+        let receiver = match owner.impl_() {
+            Some(impl_)
+                if f.decl
+                    .inputs
+                    .first()
+                    .is_some_and(|(name, _)| name == "self") =>
+            {
+                self_ty_name(&impl_.for_)
+            }
+            _ => None,
+        };
+        let metadata = of_fn(method.clone(), f, Some(&owner));
+        Some(ItemMetadata {
+            trait_: trait_.map(str::to_owned),
+            receiver,
+            feature: metadata
+                .feature
+                .or_else(|| feature(&krate.index.get(owner.id())?.attrs)),
+            ..metadata
+        })
+    })
+}
+
+/// Name of the type `ty` without its path or generic arguments, e.g. `Vec` of `Vec<T>` or `str`,
+/// if it is a path or a primitive type, looking through references.
+pub(crate) fn self_ty_name(ty: &types::Type) -> Option<String> {
+    match TypeHead::of(ty) {
+        TypeHead::Path(name) => Some(last_segment(&name).to_owned()),
+        TypeHead::Primitive(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// `Iterator` of `std::iter::Iterator`, as paths of traits are written as they are in the source.
+pub(crate) fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+fn of_fn(
+    item: SharedItem,
+    FnItem { decl, generics }: FnItem<'_>,
+    owner: Option<&MethodOwner>,
+) -> ItemMetadata {
+    let is_generic = |generics: &types::Generics| {
+        generics
+            .params
+            .iter()
+            .any(|param| !matches!(param.kind, types::GenericParamDefKind::Lifetime { .. }))
+    };
+
+    ItemMetadata {
+        id: item.id.clone(),
+        kind: match owner {
+            Some(_) => ItemKind::Method,
+            None => ItemKind::Function,
+        },
+        owner: owner.cloned(),
+        trait_: None,
+        arity: decl.inputs.len(),
+        input_heads: decl.inputs.iter().map(|(_, ty)| TypeHead::of(ty)).collect(),
+        output_head: decl.output.as_ref().map(TypeHead::of),
+        has_generics: is_generic(generics)
+            || owner
+                .and_then(MethodOwner::as_owner)
+                .is_some_and(|owner| match owner {
+                    Owner::Impl(impl_) => is_generic(&impl_.generics),
+                    Owner::Trait(_, trait_) => is_generic(&trait_.generics),
+                }),
+        receiver: None,
+        attrs: item
+            .attrs
+            .iter()
+            .filter_map(|attr| Attribute::of(attr))
+            .collect(),
+        feature: feature(&item.attrs),
+        provided: false,
+        item,
+    }
+}
+
+/// Feature in the first `#[cfg(feature = "...")]` among `attrs`, or in `#[doc(cfg(...))]`.
+fn feature(attrs: &[String]) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.starts_with("#[cfg(") || attr.starts_with("#[doc(cfg("))
+        // Items under `not(feature = "...")` are available without the feature.
+        .filter(|attr| !attr.contains("not("))
+        .find_map(|attr| {
+            let (_, rest) = attr.split_once("feature")?;
+            let rest = rest
+                .trim_start()
+                .strip_prefix('=')?
+                .trim_start()
+                .strip_prefix('"')?;
+            let (feature, _) = rest.split_once('"')?;
+            Some(feature.to_owned())
+        })
+}
+
+/// Positions of methods taking `self` in the metadata of a crate keyed by their
+/// [`ItemMetadata::receiver`], and of the other items, so that queries taking a type first only
+/// walk the methods of its impls rather than of every impl.
+#[derive(Debug, Default)]
+pub(crate) struct Receivers {
+    methods: HashMap<String, Vec<usize>>,
+    others: Vec<usize>,
+}
+
+impl Receivers {
+    pub(crate) fn of(metadata: &[ItemMetadata]) -> Self {
+        let mut receivers = Receivers::default();
+        for (i, metadata) in metadata.iter().enumerate() {
+            match metadata.receiver {
+                Some(ref receiver) => receivers
+                    .methods
+                    .entry(receiver.clone())
+                    .or_default()
+                    .push(i),
+                None => receivers.others.push(i),
+            }
+        }
+        receivers
+    }
+
+    /// Positions of the items whose first argument may be of type `receiver` in order, or `None`
+    /// if no methods take `self` of the type, e.g. as it is misspelled.
+    pub(crate) fn positions(&self, receiver: &str) -> Option<Vec<usize>> {
+        let methods = self.methods.get(receiver)?;
+        let mut positions = [methods.as_slice(), &self.others].concat();
+        positions.sort_unstable();
+        Some(positions)
+    }
+}
+
+/// Impl blocks of traits in the crates of an index keyed by the paths of the types they are for,
+/// so that a type matches `impl Trait` of queries by an impl in any crate, not only its own.
+#[derive(Debug, Default)]
+pub struct TraitImpls {
+    /// Crates of the index keyed by their names, which qualify ids in provided methods.
+    crates: HashMap<String, Arc<types::Crate>>,
+    impls: HashMap<Vec<String>, Vec<SharedItem>>,
+}
+
+impl TraitImpls {
+    pub(crate) fn of(crates: &HashMap<String, Arc<types::Crate>>) -> Self {
+        let mut impls: HashMap<_, Vec<_>> = HashMap::new();
+        for krate in crates.values() {
+            for item in krate.index.values() {
+                let for_ = match item.inner {
+                    types::ItemEnum::Impl(types::Impl {
+                        trait_: Some(_),
+                        for_: types::Type::ResolvedPath { ref id, .. },
+                        ..
+                    }) => id,
+                    _ => continue,
+                };
+                if let Some(summary) = krate.paths.get(for_) {
+                    impls
+                        .entry(summary.path.clone())
+                        .or_default()
+                        .extend(SharedItem::of(krate, &item.id));
+                }
+            }
+        }
+        TraitImpls {
+            crates: crates.clone(),
+            impls,
+        }
+    }
+
+    /// Impl blocks of traits in any crate for the type `id` refers to in `krate`, where `id` may
+    /// be [qualified](qualify) by the name of another crate.
+    pub fn of_type(&self, krate: &types::Crate, id: &types::Id) -> &[SharedItem] {
+        let qualified =
+            id.0.split_once(':')
+                .and_then(|(name, id)| Some((self.crates.get(name)?, types::Id(id.to_owned()))));
+        let summary = match qualified {
+            Some((krate, ref id)) => krate.paths.get(id),
+            None => krate.paths.get(id),
+        };
+        summary
+            .and_then(|summary| self.impls.get(&summary.path))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Qualification of the ids of paths in items cloned from other crates by the names of those
+/// crates, e.g. `0:42` of `core` as `core:0:42`, so lookups in the crate they are cloned into miss
+/// instead of finding unrelated items.
+mod qualify {
+    use rustdoc_types as types;
+
+    pub(super) fn item(inner: &mut types::ItemEnum, krate: &str) {
+        let (decl, generics) = match inner {
+            types::ItemEnum::Function(f) => (&mut f.decl, &mut f.generics),
+            types::ItemEnum::Method(m) => (&mut m.decl, &mut m.generics),
+            _ => return,
+        };
+        fn_decl(decl, krate);
+        self::generics(generics, krate);
+    }
+
+    fn fn_decl(decl: &mut types::FnDecl, krate: &str) {
+        for (_, type_) in &mut decl.inputs {
+            self::type_(type_, krate);
+        }
+        if let Some(ref mut output) = decl.output {
+            type_(output, krate);
+        }
+    }
+
+    fn generics(generics: &mut types::Generics, krate: &str) {
+        params(&mut generics.params, krate);
+        for predicate in &mut generics.where_predicates {
+            match predicate {
+                types::WherePredicate::BoundPredicate { ty, bounds } => {
+                    type_(ty, krate);
+                    self::bounds(bounds, krate);
+                }
+                types::WherePredicate::RegionPredicate { bounds, .. } => {
+                    self::bounds(bounds, krate)
+                }
+                types::WherePredicate::EqPredicate { lhs, rhs } => {
+                    type_(lhs, krate);
+                    type_(rhs, krate);
+                }
+            }
+        }
+    }
+
+    fn params(params: &mut [types::GenericParamDef], krate: &str) {
+        for param in params {
+            match param.kind {
+                types::GenericParamDefKind::Lifetime { .. } => {}
+                types::GenericParamDefKind::Type {
+                    ref mut bounds,
+                    ref mut default,
+                } => {
+                    self::bounds(bounds, krate);
+                    if let Some(default) = default {
+                        type_(default, krate);
+                    }
+                }
+                types::GenericParamDefKind::Const { ref mut ty, .. } => type_(ty, krate),
+            }
+        }
+    }
+
+    fn bounds(bounds: &mut [types::GenericBound], krate: &str) {
+        for bound in bounds {
+            if let types::GenericBound::TraitBound {
+                trait_,
+                generic_params,
+                ..
+            } = bound
+            {
+                type_(trait_, krate);
+                params(generic_params, krate);
+            }
+        }
+    }
+
+    fn type_(type_: &mut types::Type, krate: &str) {
+        match type_ {
+            types::Type::ResolvedPath {
+                id,
+                args,
+                param_names,
+                ..
+            } => {
+                id.0 = format!("{}:{}", krate, id.0);
+                if let Some(args) = args {
+                    generic_args(args, krate);
+                }
+                bounds(param_names, krate);
+            }
+            types::Type::FunctionPointer(f) => {
+                fn_decl(&mut f.decl, krate);
+                params(&mut f.generic_params, krate);
+            }
+            types::Type::Tuple(types) => {
+                for type_ in types {
+                    self::type_(type_, krate);
+                }
+            }
+            types::Type::Slice(type_)
+            | types::Type::Array { type_, .. }
+            | types::Type::RawPointer { type_, .. }
+            | types::Type::BorrowedRef { type_, .. } => self::type_(type_, krate),
+            types::Type::ImplTrait(bounds) => self::bounds(bounds, krate),
+            types::Type::QualifiedPath {
+                self_type, trait_, ..
+            } => {
+                self::type_(self_type, krate);
+                self::type_(trait_, krate);
+            }
+            types::Type::Generic(_) | types::Type::Primitive(_) | types::Type::Infer => {}
+        }
+    }
+
+    fn generic_args(args: &mut types::GenericArgs, krate: &str) {
+        match args {
+            types::GenericArgs::AngleBracketed { args, bindings } => {
+                for arg in args {
+                    match arg {
+                        types::GenericArg::Type(type_) => self::type_(type_, krate),
+                        types::GenericArg::Const(c) => type_(&mut c.type_, krate),
+                        types::GenericArg::Lifetime(_) | types::GenericArg::Infer => {}
+                    }
+                }
+                for binding in bindings {
+                    match binding.binding {
+                        types::TypeBindingKind::Equality(ref mut type_) => {
+                            self::type_(type_, krate)
+                        }
+                        types::TypeBindingKind::Constraint(ref mut bounds) => {
+                            self::bounds(bounds, krate)
+                        }
+                    }
+                }
+            }
+            types::GenericArgs::Parenthesized { inputs, output } => {
+                for input in inputs {
+                    type_(input, krate);
+                }
+                if let Some(output) = output {
+                    type_(output, krate);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{load_fixture, load_fixtures};
+
+    #[test]
+    fn metadata_of_functions_and_methods() {
+        let index = load_fixture("mini_std");
+        let metadata = index.metadata("mini_std").unwrap();
+        let of = |id: &str| {
+            metadata
+                .iter()
+                .find(|m| m.id == types::Id(id.to_owned()))
+                .unwrap()
+        };
+
+        let from_utf8 = of("0:1");
+        assert_eq!(from_utf8.owner, None);
+        assert_eq!(from_utf8.input_heads, vec![TypeHead::Slice]);
+        assert_eq!(
+            from_utf8.output_head,
+            Some(TypeHead::Path("Result".to_owned()))
+        );
+
+        let push = of("0:9");
+        assert_eq!(push.item.name.as_deref(), Some("push"));
+        // Items are shared with the crate in the index rather than copied out of it.
+        assert!(matches!(
+            push.item,
+            SharedItem::InCrate(ref krate, _) if Arc::ptr_eq(krate, &index.crates()["mini_std"])
+        ));
+        assert_eq!(
+            push.owner.as_ref().map(MethodOwner::id),
+            Some(&types::Id("0:5".to_owned()))
+        );
+        assert!(matches!(
+            push.owner.as_ref().and_then(MethodOwner::as_owner),
+            Some(Owner::Impl(types::Impl { trait_: None, .. }))
+        ));
+        assert_eq!(push.arity, 2);
+        assert!(push.has_generics);
+        assert_eq!(push.output_head, None);
+        assert_eq!(push.receiver.as_deref(), Some("Vec"));
+        assert_eq!(of("0:7").receiver, None);
+    }
+
+    #[test]
+    fn metadata_of_attributes() {
+        let index = load_fixture("mini_std");
+        let metadata = index.metadata("mini_std").unwrap();
+        let of = |id: &str| {
+            metadata
+                .iter()
+                .find(|m| m.id == types::Id(id.to_owned()))
+                .unwrap()
+        };
+
+        assert_eq!(of("0:7").attrs, vec![Attribute::MustUse]);
+        assert_eq!(
+            of("0:14").attrs,
+            vec![Attribute::MustUse, Attribute::Inline]
+        );
+        assert_eq!(of("0:34").feature.as_deref(), Some("io"));
+        // `collect_vec` is in an impl block under `#[doc(cfg(feature = "alloc"))]`.
+        assert_eq!(of("0:29").feature.as_deref(), Some("alloc"));
+        assert_eq!(of("0:9").feature, None);
+        assert_eq!(
+            feature(&[r#"#[cfg(not(feature = "std"))]"#.to_owned()]),
+            None
+        );
+    }
+
+    #[test]
+    fn methods_folded_into_functions() {
+        let mut index = load_fixture("mini_std");
+        let mut krate = (*index.crates()["mini_std"]).clone();
+        // Newer formats of rustdoc JSON have `Function` items for methods.
+        let push = krate.index.get_mut(&types::Id("0:9".to_owned())).unwrap();
+        if let types::ItemEnum::Method(ref m) = push.inner {
+            push.inner = types::ItemEnum::Function(types::Function {
+                decl: m.decl.clone(),
+                generics: m.generics.clone(),
+                header: m.header.clone(),
+                abi: m.abi.clone(),
+            });
+        }
+        index.insert("mini_std".to_owned(), krate);
+
+        let metadata = index.metadata("mini_std").unwrap();
+        let push = metadata
+            .iter()
+            .filter(|m| m.id == types::Id("0:9".to_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(push.len(), 1);
+        assert_eq!(
+            push[0].owner.as_ref().map(MethodOwner::id),
+            Some(&types::Id("0:5".to_owned()))
+        );
+    }
+
+    #[test]
+    fn trait_impls_of_types() {
+        let index = load_fixtures(&["mini_std", "mini_ext"]);
+        let impls = TraitImpls::of(index.crates());
+        let ids = |krate: &str, id: &str| {
+            impls
+                .of_type(&index.crates()[krate], &types::Id(id.to_owned()))
+                .iter()
+                .map(|impl_| impl_.id.0.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // `IntoIter` implements `Iterator`, and `Vec` implements `Clone` alone.
+        assert_eq!(ids("mini_std", "0:30"), vec!["0:26"]);
+        assert_eq!(ids("mini_std", "0:20"), vec!["0:16"]);
+        // Ids in methods provided by other crates are qualified by the names of those crates.
+        assert_eq!(ids("mini_ext", "mini_std:0:30"), vec!["0:26"]);
+        assert!(ids("mini_ext", "0:30").is_empty());
+    }
+}
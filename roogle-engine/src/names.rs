@@ -0,0 +1,178 @@
+//! Index of the names of items in a crate, finding the ones close to a name without comparing it
+//! with every item, so that queries with names alone like `fn from_utf8` stay fast over many
+//! crates.
+
+use std::collections::BTreeMap;
+
+use crate::{compare::levenshtein_within, metadata::ItemMetadata};
+
+/// Distinct names of the items in the metadata of a crate, grouped by their lengths in bytes into
+/// BK-trees, along with the positions of the items named so.
+///
+/// Scores of names are their distances in chars over the longer of the lengths in bytes, so each
+/// length allows its own distance; grouping by lengths lets each tree be searched within it.
+#[derive(Debug, Default)]
+pub(crate) struct Names {
+    trees: BTreeMap<usize, Tree>,
+}
+
+/// BK-tree of names, each of whose nodes has the nodes of the names at each distance from it as
+/// its children.
+#[derive(Debug, Default)]
+struct Tree {
+    nodes: Vec<Node>,
+    /// Least and most chars the names in the tree have, which bound their distances from others.
+    chars: (usize, usize),
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    positions: Vec<usize>,
+    /// Distances of the children from the name and their positions in the nodes of the tree.
+    children: Vec<(usize, usize)>,
+}
+
+impl Names {
+    pub(crate) fn of(metadata: &[ItemMetadata]) -> Self {
+        let mut names = Names::default();
+        for (i, metadata) in metadata.iter().enumerate() {
+            if let Some(ref name) = metadata.item.name {
+                names.trees.entry(name.len()).or_default().insert(name, i);
+            }
+        }
+        names
+    }
+
+    /// Positions of the items in order which may score less than `threshold` by their names alone
+    /// as [`compare_symbol`](crate::compare::compare_symbol) compares them with `name`, or `None`
+    /// if any item may, as with thresholds of 1 or more.
+    pub(crate) fn positions(&self, name: &str, threshold: f32) -> Option<Vec<usize>> {
+        if threshold >= 1.0 || threshold.is_nan() {
+            return None;
+        }
+        let chars = name.chars().count();
+        let mut positions = vec![];
+        for (&len, tree) in &self.trees {
+            // Rounded up so that no name is missed by rounding errors, as scores are checked anew.
+            let max = (threshold * name.len().max(len) as f32).ceil() as usize;
+            let (min_chars, max_chars) = tree.chars;
+            if chars
+                .saturating_sub(max_chars)
+                .max(min_chars.saturating_sub(chars))
+                > max
+            {
+                continue;
+            }
+            tree.within(name, max, &mut positions);
+        }
+        positions.sort_unstable();
+        Some(positions)
+    }
+}
+
+impl Tree {
+    fn insert(&mut self, name: &str, position: usize) {
+        let chars = name.chars().count();
+        self.chars = if self.nodes.is_empty() {
+            (chars, chars)
+        } else {
+            (self.chars.0.min(chars), self.chars.1.max(chars))
+        };
+
+        let mut node = 0;
+        while node < self.nodes.len() {
+            let d = distance(name, &self.nodes[node].name);
+            if d == 0 {
+                self.nodes[node].positions.push(position);
+                return;
+            }
+            match self.nodes[node].children.iter().find(|(c, _)| *c == d) {
+                Some(&(_, child)) => node = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes[node].children.push((d, child));
+                    break;
+                }
+            }
+        }
+        self.nodes.push(Node {
+            name: name.to_owned(),
+            positions: vec![position],
+            children: vec![],
+        });
+    }
+
+    /// Add the positions of the items named within `max` of `name` to `positions`.
+    fn within(&self, name: &str, max: usize, positions: &mut Vec<usize>) {
+        let mut nodes = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(node) = nodes.pop() {
+            let node = &self.nodes[node];
+            let d = distance(name, &node.name);
+            if d <= max {
+                positions.extend(&node.positions);
+            }
+            // Names `c` away from the node are at least `|d - c|` away from `name`.
+            nodes.extend(
+                node.children
+                    .iter()
+                    .filter(|(c, _)| c.abs_diff(d) <= max)
+                    .map(|(_, child)| *child),
+            );
+        }
+    }
+}
+
+/// Levenshtein distance between `a` and `b` in chars.
+fn distance(a: &str, b: &str) -> usize {
+    levenshtein_within(a, b, usize::MAX).unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compare::compare_symbol, testing::load_fixtures};
+
+    #[test]
+    fn find_every_name_scoring_below_thresholds() {
+        let index = load_fixtures(&["mini_std", "mini_ext"]);
+        let metadata = index.metadata("mini_std").unwrap();
+        let names = Names::of(metadata);
+        for query in [
+            "len",
+            "push",
+            "from_utf8",
+            "new",
+            "x",
+            "naïve",
+            "with_capacity",
+        ] {
+            for threshold in [0.0, 0.2, 0.4, 0.7, 0.99] {
+                let expected = metadata
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, metadata)| {
+                        let name = metadata.item.name.as_deref().unwrap_or_default();
+                        compare_symbol(query, name, 1.0).score() < threshold
+                    })
+                    .map(|(i, _)| i);
+                let positions = names.positions(query, threshold).unwrap();
+                for i in expected {
+                    assert!(
+                        positions.contains(&i),
+                        "`{}` below {} is missed for `{}`",
+                        query,
+                        threshold,
+                        metadata[i].item.name.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+        }
+        assert!(names.positions("len", 0.4).unwrap().len() < metadata.len());
+        assert_eq!(names.positions("len", 1.0), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Deserializing queries serialized with serde back into an [`Arena`], e.g. ones persisted as
+//! parsed so that they mean the same even after the grammar changes.
+//!
+//! Queries borrow everything from the arena, so they are deserialized into owned mirrors of their
+//! types first and then moved into it.
+
+use serde::{Deserialize, Deserializer};
+
+use crate::query::*;
+
+/// Deserialize a query serialized with serde from `deserializer`, allocating it in `arena`.
+///
+/// Weights and kinds aren't serialized along with queries, so they are the default ones.
+pub fn deserialize_in<'de, 'a, D>(arena: &'a Arena, deserializer: D) -> Result<Query<'a>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    OwnedQuery::deserialize(deserializer).map(|query| query.into_arena(arena))
+}
+
+// Fields serialized even if they are `None` are required, as `deserialize_with` keeps serde from
+// taking them for `None` when missing, so that e.g. `{}` isn't a query matching everything.
+#[derive(Deserialize)]
+struct OwnedQuery {
+    #[serde(deserialize_with = "Option::deserialize")]
+    name: Option<String>,
+    #[serde(deserialize_with = "Option::deserialize")]
+    kind: Option<OwnedQueryKind>,
+    #[serde(deserialize_with = "Option::deserialize")]
+    trait_: Option<String>,
+    #[serde(default)]
+    self_ty: Option<OwnedType>,
+    #[serde(default)]
+    attrs: Vec<Attribute>,
+}
+
+// Variants are named after the ones of `QueryKind`.
+#[allow(clippy::enum_variant_names)]
+#[derive(Deserialize)]
+enum OwnedQueryKind {
+    FunctionQuery(OwnedFunction),
+    UsesQuery(OwnedType),
+    ConstructorQuery(OwnedType),
+    FailsWithQuery(OwnedType),
+}
+
+#[derive(Deserialize)]
+struct OwnedFunction {
+    decl: OwnedFnDecl,
+}
+
+#[derive(Deserialize)]
+struct OwnedFnDecl {
+    inputs: Option<Vec<OwnedArgument>>,
+    output: Option<OwnedFnRetTy>,
+}
+
+#[derive(Deserialize)]
+struct OwnedArgument {
+    ty: Option<OwnedType>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+enum OwnedFnRetTy {
+    Return(OwnedType),
+    DefaultReturn,
+}
+
+#[derive(Deserialize)]
+enum OwnedGenericArgs {
+    AngleBracketed { args: Vec<Option<OwnedGenericArg>> },
+}
+
+#[derive(Deserialize)]
+enum OwnedGenericArg {
+    Type(OwnedType),
+    Binding { name: String, ty: OwnedType },
+}
+
+#[derive(Deserialize)]
+enum OwnedType {
+    UnresolvedPath {
+        name: String,
+        args: Option<Box<OwnedGenericArgs>>,
+    },
+    Generic(String),
+    Primitive(PrimitiveType),
+    Tuple(Vec<Option<OwnedType>>),
+    Slice(Option<Box<OwnedType>>),
+    BareFunction(Box<OwnedFnDecl>),
+    Never,
+    SelfType,
+    ImplTrait {
+        trait_: String,
+        args: Option<Box<OwnedGenericArgs>>,
+    },
+    QualifiedPath {
+        self_type: Box<OwnedType>,
+        name: String,
+    },
+    RawPointer {
+        mutable: bool,
+        type_: Box<OwnedType>,
+    },
+    BorrowedRef {
+        mutable: bool,
+        type_: Box<OwnedType>,
+    },
+}
+
+fn symbol<'a>(arena: &'a Arena, symbol: &str) -> Symbol<'a> {
+    arena.alloc_str(symbol)
+}
+
+impl OwnedQuery {
+    fn into_arena(self, arena: &Arena) -> Query<'_> {
+        Query {
+            name: self.name.map(|name| symbol(arena, &name)),
+            kind: self.kind.map(|kind| kind.into_arena(arena)),
+            trait_: self.trait_.map(|trait_| symbol(arena, &trait_)),
+            self_ty: self.self_ty.map(|ty| ty.into_arena(arena)),
+            attrs: arena.alloc_slice_copy(&self.attrs),
+            weights: Weights::default(),
+            kinds: ItemKinds::default(),
+        }
+    }
+}
+
+impl OwnedQueryKind {
+    fn into_arena(self, arena: &Arena) -> QueryKind<'_> {
+        match self {
+            OwnedQueryKind::FunctionQuery(function) => QueryKind::FunctionQuery(Function {
+                decl: function.decl.into_arena(arena),
+            }),
+            OwnedQueryKind::UsesQuery(ty) => QueryKind::UsesQuery(ty.into_arena(arena)),
+            OwnedQueryKind::ConstructorQuery(ty) => {
+                QueryKind::ConstructorQuery(ty.into_arena(arena))
+            }
+            OwnedQueryKind::FailsWithQuery(ty) => QueryKind::FailsWithQuery(ty.into_arena(arena)),
+        }
+    }
+}
+
+impl OwnedFnDecl {
+    fn into_arena(self, arena: &Arena) -> FnDecl<'_> {
+        FnDecl {
+            inputs: self.inputs.map(|inputs| {
+                &*arena.alloc_slice_fill_iter(inputs.into_iter().map(|arg| Argument {
+                    ty: arg.ty.map(|ty| ty.into_arena(arena)),
+                    name: arg.name.map(|name| symbol(arena, &name)),
+                }))
+            }),
+            output: self.output.map(|output| match output {
+                OwnedFnRetTy::Return(ty) => FnRetTy::Return(ty.into_arena(arena)),
+                OwnedFnRetTy::DefaultReturn => FnRetTy::DefaultReturn,
+            }),
+        }
+    }
+}
+
+impl OwnedGenericArgs {
+    fn into_arena(self, arena: &Arena) -> &GenericArgs<'_> {
+        let OwnedGenericArgs::AngleBracketed { args } = self;
+        let args = arena.alloc_slice_fill_iter(args.into_iter().map(|arg| {
+            arg.map(|arg| match arg {
+                OwnedGenericArg::Type(ty) => GenericArg::Type(ty.into_arena(arena)),
+                OwnedGenericArg::Binding { name, ty } => GenericArg::Binding {
+                    name: symbol(arena, &name),
+                    ty: ty.into_arena(arena),
+                },
+            })
+        }));
+        arena.alloc(GenericArgs::AngleBracketed { args })
+    }
+}
+
+impl OwnedType {
+    fn into_arena(self, arena: &Arena) -> Type<'_> {
+        match self {
+            OwnedType::UnresolvedPath { name, args } => Type::UnresolvedPath {
+                name: symbol(arena, &name),
+                args: args.map(|args| args.into_arena(arena)),
+            },
+            OwnedType::Generic(name) => Type::Generic(symbol(arena, &name)),
+            OwnedType::Primitive(primitive) => Type::Primitive(primitive),
+            OwnedType::Tuple(types) => Type::Tuple(
+                arena.alloc_slice_fill_iter(
+                    types
+                        .into_iter()
+                        .map(|ty| ty.map(|ty| ty.into_arena(arena))),
+                ),
+            ),
+            OwnedType::Slice(ty) => Type::Slice(ty.map(|ty| &*arena.alloc(ty.into_arena(arena)))),
+            OwnedType::BareFunction(decl) => {
+                Type::BareFunction(arena.alloc(decl.into_arena(arena)))
+            }
+            OwnedType::Never => Type::Never,
+            OwnedType::SelfType => Type::SelfType,
+            OwnedType::ImplTrait { trait_, args } => Type::ImplTrait {
+                trait_: symbol(arena, &trait_),
+                args: args.map(|args| args.into_arena(arena)),
+            },
+            OwnedType::QualifiedPath { self_type, name } => Type::QualifiedPath {
+                self_type: arena.alloc(self_type.into_arena(arena)),
+                name: symbol(arena, &name),
+            },
+            OwnedType::RawPointer { mutable, type_ } => Type::RawPointer {
+                mutable,
+                type_: arena.alloc(type_.into_arena(arena)),
+            },
+            OwnedType::BorrowedRef { mutable, type_ } => Type::BorrowedRef {
+                mutable,
+                type_: arena.alloc(type_.into_arena(arena)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::parse::parse_query_in;
+
+    #[test]
+    fn deserialize_serialized_queries() {
+        for query in [
+            "fn (&mut Vec<T>, T)",
+            "must_use fn len(&self) -> usize",
+            "in Iterator fn (impl Iterator<Item = T>, _) -> Option<(T, [u8])>",
+            "Vec<u8>::fn (..) -> *const Self::Item",
+            "uses fn(i32) -> !",
+            "fails_with io::Error",
+        ] {
+            let arena = Arena::new();
+            let parsed = parse_query_in(&arena, query).unwrap();
+            let json = serde_json::to_value(&parsed).unwrap();
+            assert_eq!(deserialize_in(&arena, &json).unwrap(), parsed, "{}", query);
+        }
+        assert!(deserialize_in(&Arena::new(), &serde_json::json!({})).is_err());
+    }
+}
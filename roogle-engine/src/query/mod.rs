@@ -1,5 +1,10 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{compare::Weights, search::ItemKinds};
+
+pub mod de;
 pub mod parse;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -9,99 +14,180 @@ pub struct Item {
     pub docs: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Query {
-    pub name: Option<Symbol>,
-    pub kind: Option<QueryKind>,
+/// Arena which trees of types in queries are allocated in by [`parse::parse_query_in`].
+///
+/// Resetting it between queries, e.g. in a batch or a REPL, reuses its memory for the next ones.
+pub type Arena = bumpalo::Bump;
+
+// The arena never drops what is allocated in it, so queries must not own anything on the heap.
+const _: () = assert!(!std::mem::needs_drop::<Query<'static>>());
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub struct Query<'a> {
+    pub name: Option<Symbol<'a>>,
+    pub kind: Option<QueryKind<'a>>,
+    /// Trait in `in Trait fn ...`, whose definition and impls are the only places searched for
+    /// methods if it is given.
+    pub trait_: Option<Symbol<'a>>,
+    /// Type in `Type::...`, whose impls, inherent or of traits, are the only places searched for
+    /// methods if it is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_ty: Option<Type<'a>>,
+    /// Attributes items must have, e.g. `must_use` in `must_use fn (&str) -> String`.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub attrs: &'a [Attribute],
+    /// How much names, inputs and outputs count in scores of items, which is given along with
+    /// queries rather than written in them.
+    #[serde(skip)]
+    pub weights: Weights,
+    /// Kinds of items to search, which are given along with queries like weights.
+    #[serde(skip)]
+    pub kinds: ItemKinds,
 }
 
-impl Query {
-    pub fn args(&self) -> Option<Vec<Argument>> {
-        self.kind
-            .as_ref()
-            .map(|kind| {
-                let QueryKind::FunctionQuery(f) = kind;
-                &f.decl
-            })
-            .and_then(|decl| decl.inputs.clone())
+impl<'a> Query<'a> {
+    /// Render the query in a canonical form, so that queries which only differ in whitespace,
+    /// trailing commas or `-> ()` are the same, e.g. to key caches and logs with.
+    ///
+    /// It's the same as [`Display`](fmt::Display), and parses back into an equal query.
+    pub fn canonicalize(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn args(&self) -> Option<&'a [Argument<'a>]> {
+        match self.kind {
+            Some(QueryKind::FunctionQuery(ref f)) => f.decl.inputs,
+            _ => None,
+        }
+    }
+
+    /// Name of the type the first argument is of if it is a path like `Vec<T>` or `&mut Vec<T>`,
+    /// to only search methods of its impls among the ones taking `self`.
+    pub fn receiver(&self) -> Option<Symbol<'a>> {
+        let mut ty = self.args()?.first()?.ty.as_ref()?;
+        loop {
+            match ty {
+                Type::BorrowedRef { type_, .. } | Type::RawPointer { type_, .. } => ty = type_,
+                Type::UnresolvedPath { name, .. } => {
+                    return Some(crate::metadata::last_segment(name))
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Name of the type in `Type::...` without its path or generic arguments, e.g. `Vec` of
+    /// `std::vec::Vec<u8>::`, which impls are searched for by as the same as receivers.
+    pub fn self_ty_name(&self) -> Option<&'a str> {
+        match self.self_ty {
+            Some(Type::UnresolvedPath { name, .. }) => Some(crate::metadata::last_segment(name)),
+            Some(Type::Primitive(ref primitive)) => Some(primitive.as_str()),
+            _ => None,
+        }
     }
 }
 
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum QueryKind {
-    FunctionQuery(Function),
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum QueryKind<'a> {
+    FunctionQuery(Function<'a>),
+    /// `uses Type`, matching functions and methods mentioning the type anywhere in their
+    /// signatures.
+    UsesQuery(Type<'a>),
+    /// `new Type` or `make Type`, matching functions and methods without `self` which return the
+    /// type, possibly in `Result` or `Option`, preferring ones named like `new`.
+    ConstructorQuery(Type<'a>),
+    /// `fails_with Type`, matching functions and methods returning `Result<_, Type>`, possibly
+    /// through a type alias like `io::Result<T>`.
+    FailsWithQuery(Type<'a>),
 }
 
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Function {
-    pub decl: FnDecl,
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub struct Function<'a> {
+    pub decl: FnDecl<'a>,
     // pub generics: Generics,
 }
 
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum GenericArgs {
-    AngleBracketed {
-        args: Vec<Option<GenericArg>>, /* bindings: Vec<TypeBinding> */
-    },
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum GenericArgs<'a> {
+    AngleBracketed { args: &'a [Option<GenericArg<'a>>] },
     // Parenthesized { inputs: Vec<Type>, output: Option<Type> },
 }
 
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum GenericArg {
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum GenericArg<'a> {
     // Lifetime(String),
-    Type(Type),
+    Type(Type<'a>),
+    /// Binding of an associated type, e.g. `Item = T` in `impl Iterator<Item = T>`.
+    Binding {
+        name: Symbol<'a>,
+        ty: Type<'a>,
+    },
     // Const(Constant),
 }
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct FnDecl {
-    pub inputs: Option<Vec<Argument>>,
-    pub output: Option<FnRetTy>,
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub struct FnDecl<'a> {
+    pub inputs: Option<&'a [Argument<'a>]>,
+    pub output: Option<FnRetTy<'a>>,
     // pub c_variadic: bool,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Argument {
-    pub ty: Option<Type>,
-    pub name: Option<Symbol>,
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub struct Argument<'a> {
+    pub ty: Option<Type<'a>>,
+    pub name: Option<Symbol<'a>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum FnRetTy {
-    Return(Type),
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum FnRetTy<'a> {
+    Return(Type<'a>),
     DefaultReturn,
 }
 
-pub type Symbol = String;
+pub type Symbol<'a> = &'a str;
 
 #[non_exhaustive]
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub enum Type {
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum Type<'a> {
     // FIXME: Give `UnresolvedPath` a better name.
     UnresolvedPath {
-        name: Symbol,
-        args: Option<Box<GenericArgs>>,
+        name: Symbol<'a>,
+        args: Option<&'a GenericArgs<'a>>,
     },
-    Generic(String),
+    Generic(&'a str),
     Primitive(PrimitiveType),
-    Tuple(Vec<Option<Type>>),
-    Slice(Option<Box<Type>>),
+    Tuple(&'a [Option<Type<'a>>]),
+    Slice(Option<&'a Type<'a>>),
+    /// Function pointer like `fn(i32) -> i32`, which returns `()` unless its output is written.
+    BareFunction(&'a FnDecl<'a>),
     Never,
+    /// `Self`, which is bound to the type of the impl block of a method compared with it.
+    SelfType,
+    /// `impl Trait`, which matches types implementing the trait, e.g. `impl Iterator<Item = T>`.
+    ImplTrait {
+        trait_: Symbol<'a>,
+        args: Option<&'a GenericArgs<'a>>,
+    },
+    /// Associated type like `Self::Item`.
+    QualifiedPath {
+        self_type: &'a Type<'a>,
+        name: Symbol<'a>,
+    },
     RawPointer {
         mutable: bool,
-        type_: Box<Type>,
+        type_: &'a Type<'a>,
     },
     BorrowedRef {
         mutable: bool,
-        type_: Box<Type>,
+        type_: &'a Type<'a>,
     },
 }
 
-impl Type {
+impl<'a> Type<'a> {
     pub fn inner_type(&self) -> &Self {
         match self {
             Type::RawPointer { type_, .. } => type_.inner_type(),
@@ -113,7 +199,7 @@ impl Type {
 
 /// N.B. this has to be different from `hir::PrimTy` because it also includes types that aren't
 /// paths, like `Unit`.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum PrimitiveType {
     Isize,
     I8,
@@ -137,7 +223,7 @@ pub enum PrimitiveType {
 }
 
 impl PrimitiveType {
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         use PrimitiveType::*;
         match self {
             Isize => "isize",
@@ -162,3 +248,185 @@ impl PrimitiveType {
         }
     }
 }
+
+/// Attribute of items which queries can require them to have.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    /// `#[must_use]`, with or without a message.
+    MustUse,
+    /// `#[inline]`, `#[inline(always)]` or `#[inline(never)]`.
+    Inline,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 2] = [Attribute::MustUse, Attribute::Inline];
+
+    /// Name of the attribute as written in queries.
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::MustUse => "must_use",
+            Attribute::Inline => "inline",
+        }
+    }
+
+    /// The attribute rustdoc renders as `attr`, e.g. `#[must_use = "..."]`, if it is one of them.
+    pub fn of(attr: &str) -> Option<Self> {
+        let attr = attr.strip_prefix("#[")?;
+        let name = attr
+            .split(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .next()?;
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Query<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(QueryKind::UsesQuery(ref ty)) => return write!(f, "uses {}", ty),
+            Some(QueryKind::ConstructorQuery(ref ty)) => return write!(f, "new {}", ty),
+            Some(QueryKind::FailsWithQuery(ref ty)) => return write!(f, "fails_with {}", ty),
+            _ => {}
+        }
+        for attr in self.attrs {
+            write!(f, "{} ", attr)?;
+        }
+        if let Some(trait_) = self.trait_ {
+            write!(f, "in {} ", trait_)?;
+        }
+        if let Some(ref ty) = self.self_ty {
+            write!(f, "{}::", ty)?;
+            return match (self.name, &self.kind) {
+                (Some(name), Some(QueryKind::FunctionQuery(function))) => {
+                    write!(f, "{}{}", name, function.decl)
+                }
+                (None, Some(QueryKind::FunctionQuery(function))) => {
+                    write!(f, "fn {}", function.decl)
+                }
+                (Some(name), _) => f.write_str(name),
+                (None, _) => Ok(()),
+            };
+        }
+        f.write_str("fn")?;
+        if let Some(name) = self.name {
+            write!(f, " {}", name)?;
+        }
+        match self.kind {
+            Some(QueryKind::FunctionQuery(ref function)) if self.name.is_some() => {
+                write!(f, "{}", function.decl)
+            }
+            Some(QueryKind::FunctionQuery(ref function)) => write!(f, " {}", function.decl),
+            Some(
+                QueryKind::UsesQuery(_)
+                | QueryKind::ConstructorQuery(_)
+                | QueryKind::FailsWithQuery(_),
+            )
+            | None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for FnDecl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.inputs {
+            Some(inputs) => {
+                f.write_str("(")?;
+                write_list(f, inputs.iter().map(Some))?;
+                f.write_str(")")?;
+            }
+            None => f.write_str("(..)")?,
+        }
+        match self.output {
+            Some(FnRetTy::Return(ref ty)) => write!(f, " -> {}", ty),
+            Some(FnRetTy::DefaultReturn) => f.write_str(" -> ()"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Argument<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name, &self.ty) {
+            (Some(name), Some(ty)) => write!(f, "{}: {}", name, ty),
+            (Some(name), None) => write!(f, "{}: _", name),
+            (None, Some(ty)) => write!(f, "{}", ty),
+            (None, None) => f.write_str("_"),
+        }
+    }
+}
+
+impl fmt::Display for Type<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::UnresolvedPath { name, args } => {
+                f.write_str(name)?;
+                args.map_or(Ok(()), |args| write!(f, "{}", args))
+            }
+            Type::ImplTrait { trait_, args } => {
+                write!(f, "impl {}", trait_)?;
+                args.map_or(Ok(()), |args| write!(f, "{}", args))
+            }
+            Type::Generic(name) => f.write_str(name),
+            Type::SelfType => f.write_str("Self"),
+            Type::QualifiedPath { self_type, name } => write!(f, "{}::{}", self_type, name),
+            Type::Primitive(PrimitiveType::Unit) => f.write_str("()"),
+            Type::Primitive(PrimitiveType::Never) | Type::Never => f.write_str("!"),
+            Type::Primitive(primitive) => f.write_str(primitive.as_str()),
+            Type::Tuple(types) => {
+                f.write_str("(")?;
+                write_list(f, types.iter().map(Option::as_ref))?;
+                f.write_str(")")
+            }
+            Type::Slice(Some(ty)) => write!(f, "[{}]", ty),
+            Type::Slice(None) => f.write_str("[_]"),
+            Type::BareFunction(decl) => write!(f, "fn{}", decl),
+            Type::RawPointer { mutable, type_ } => {
+                write!(f, "*{} {}", if *mutable { "mut" } else { "const" }, type_)
+            }
+            Type::BorrowedRef { mutable, type_ } => {
+                write!(f, "&{}{}", if *mutable { "mut " } else { "" }, type_)
+            }
+        }
+    }
+}
+
+impl fmt::Display for GenericArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let GenericArgs::AngleBracketed { args } = self;
+        f.write_str("<")?;
+        write_list(f, args.iter().map(Option::as_ref))?;
+        f.write_str(">")
+    }
+}
+
+impl fmt::Display for GenericArg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenericArg::Type(ty) => write!(f, "{}", ty),
+            GenericArg::Binding { name, ty } => write!(f, "{} = {}", name, ty),
+        }
+    }
+}
+
+/// Write `items` separated by commas, where `None` is written as `_`.
+fn write_list<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    items: impl IntoIterator<Item = Option<T>>,
+) -> fmt::Result {
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        match item {
+            Some(item) => write!(f, "{}", item)?,
+            None => f.write_str("_")?,
+        }
+    }
+    Ok(())
+}
@@ -4,91 +4,491 @@ use nom::{
     character::complete::char,
     character::complete::{alpha1, alphanumeric1, multispace0, multispace1},
     combinator::{eof, fail, map, not, opt, recognize, value},
-    error::{ContextError, ParseError},
-    multi::{many0, separated_list0},
-    sequence::{delimited, pair, preceded},
-    IResult,
+    error::{ContextError, ErrorKind, ParseError},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult, Parser,
 };
+use serde::Serialize;
+use thiserror::Error;
 
 use crate::query::*;
 
-type Symbol = String;
+pub fn parse_query<'i, 'a>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Query<'a>> {
+    alt((
+        |i| parse_uses_query(arena, i),
+        |i| parse_constructor_query(arena, i),
+        |i| parse_fails_with_query(arena, i),
+        |i| parse_impl_query(arena, i),
+        |i| parse_function_query(arena, i),
+    ))(i)
+}
+
+/// Represents a failure of parsing a whole query.
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[error("parsing query failed at offset {offset}: {message}")]
+pub struct QueryParseError {
+    /// Byte offset in the query where parsing stopped.
+    pub offset: usize,
+    pub message: String,
+}
+
+/// Parse whole `i` as a query allocated in `arena`, unlike [`parse_query`] which leaves unparsed
+/// input as is.
+pub fn parse_query_in<'a>(arena: &'a Arena, i: &str) -> Result<Query<'a>, QueryParseError> {
+    match parse_query(arena, i) {
+        Ok((rest, query)) if rest.trim().is_empty() => Ok(query),
+        Ok((rest, _)) => Err(QueryParseError {
+            offset: i.len() - rest.len(),
+            message: format!("unexpected input `{}`", rest.trim()),
+        }),
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(QueryParseError {
+            offset: i.len() - e.input.len(),
+            message: format!("expected {}", e.code.description()),
+        }),
+        Err(nom::Err::Incomplete(_)) => Err(QueryParseError {
+            offset: i.len(),
+            message: "incomplete query".to_owned(),
+        }),
+    }
+}
+
+/// Version of the query language, bumped whenever [`CAPABILITIES`] changes so that frontends can
+/// tell what a server understands without knowing its version of roogle.
+pub const QUERY_LANGUAGE_VERSION: u32 = 4;
+
+/// A feature of the query language, which frontends can enable UI affordances for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capability {
+    /// Stable name of the feature, e.g. `impl_trait`.
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Query using the feature, which parses as a whole.
+    pub example: &'static str,
+}
 
-pub fn parse_query(i: &str) -> IResult<&str, Query> {
-    parse_function_query(i)
+/// Features of the query language the combinators below parse, which must be kept in sync with
+/// them as tests check that each example parses.
+pub const CAPABILITIES: &[Capability] = &[
+    Capability {
+        name: "function_query",
+        description: "Functions and methods by their signatures",
+        example: "fn (&str) -> usize",
+    },
+    Capability {
+        name: "function_name",
+        description: "Functions and methods by their names, alone or along with their signatures",
+        example: "fn from_utf8",
+    },
+    Capability {
+        name: "self_argument",
+        description: "Receivers of methods in the shorthand form",
+        example: "fn (&mut self) -> Option<Self::Item>",
+    },
+    Capability {
+        name: "named_arguments",
+        description: "Arguments with their names",
+        example: "fn (s: &str) -> String",
+    },
+    Capability {
+        name: "wildcard_arguments",
+        description: "Arguments of any type",
+        example: "fn (_, usize) -> bool",
+    },
+    Capability {
+        name: "any_arguments",
+        description: "Any arguments at all",
+        example: "fn (..) -> bool",
+    },
+    Capability {
+        name: "trait_query",
+        description: "Methods in the definition and impls of a trait only",
+        example: "in Iterator fn (&mut self) -> Option<Self::Item>",
+    },
+    Capability {
+        name: "attributes",
+        description: "Attributes items must have, i.e. `must_use` and `inline`",
+        example: "must_use fn (&str) -> String",
+    },
+    Capability {
+        name: "uses_query",
+        description: "Items taking or returning a type",
+        example: "uses Duration",
+    },
+    Capability {
+        name: "impl_query",
+        description: "Methods of the impls of a type, inherent or of traits, optionally by their names or signatures",
+        example: "Vec<u8>::fn (&mut self, u8)",
+    },
+    Capability {
+        name: "constructor_query",
+        description: "Items returning a type from other types, by `new` or `make`",
+        example: "new Vec<T>",
+    },
+    Capability {
+        name: "fails_with_query",
+        description: "Items returning errors of a type",
+        example: "fails_with io::Error",
+    },
+    Capability {
+        name: "generic_types",
+        description: "Single upper-case letters as generic types",
+        example: "fn (Vec<T>) -> Option<T>",
+    },
+    Capability {
+        name: "paths",
+        description: "Types by their paths",
+        example: "fn () -> io::Result<String>",
+    },
+    Capability {
+        name: "associated_types",
+        description: "Associated types of `Self` and generic types",
+        example: "fn (T) -> T::Output",
+    },
+    Capability {
+        name: "associated_type_bindings",
+        description: "Bindings of associated types in generic arguments",
+        example: "fn (impl Iterator<Item = u8>) -> Vec<u8>",
+    },
+    Capability {
+        name: "impl_trait",
+        description: "`impl Trait` types",
+        example: "fn (impl AsRef<str>) -> String",
+    },
+    Capability {
+        name: "tuples",
+        description: "Tuple types",
+        example: "fn ((usize, usize)) -> usize",
+    },
+    Capability {
+        name: "slices",
+        description: "Slice types",
+        example: "fn (&[u8]) -> usize",
+    },
+    Capability {
+        name: "raw_pointers",
+        description: "Raw pointer types",
+        example: "fn (*const u8) -> *mut u8",
+    },
+    Capability {
+        name: "function_pointers",
+        description: "Function pointer types, e.g. of callbacks",
+        example: "fn (fn(i32) -> i32, i32) -> i32",
+    },
+    Capability {
+        name: "never_type",
+        description: "The never type",
+        example: "fn () -> !",
+    },
+    Capability {
+        name: "multiline",
+        description: "Queries spanning multiple lines, with trailing commas",
+        example: "fn (\n    &str,\n    usize,\n) -> String",
+    },
+];
+
+/// The query language as served to frontends, e.g. by `/capabilities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// See [`QUERY_LANGUAGE_VERSION`].
+    pub version: u32,
+    pub features: &'static [Capability],
+}
+
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: QUERY_LANGUAGE_VERSION,
+        features: CAPABILITIES,
+    }
 }
 
-fn parse_symbol<'a, E>(i: &'a str) -> IResult<&'a str, Symbol, E>
+/// `separated_list0` of nom, but collecting the list into `arena` instead of a `Vec`.
+fn separated_list0_in<'i, 'a, O, O2, E, F, G>(
+    arena: &'a Arena,
+    mut sep: G,
+    mut f: F,
+) -> impl FnMut(&'i str) -> IResult<&'i str, &'a [O], E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    F: Parser<&'i str, O, E>,
+    G: Parser<&'i str, O2, E>,
+    E: ParseError<&'i str>,
+{
+    move |mut i| {
+        let mut list = bumpalo::collections::Vec::new_in(arena);
+        loop {
+            let rest = if list.is_empty() {
+                i
+            } else {
+                match sep.parse(i) {
+                    Err(nom::Err::Error(_)) => break,
+                    Err(e) => return Err(e),
+                    // Parsing nothing forever is not a list.
+                    Ok((rest, _)) if rest.len() == i.len() => {
+                        return Err(nom::Err::Error(E::from_error_kind(
+                            i,
+                            ErrorKind::SeparatedList,
+                        )))
+                    }
+                    Ok((rest, _)) => rest,
+                }
+            };
+            match f.parse(rest) {
+                Err(nom::Err::Error(_)) => break,
+                Err(e) => return Err(e),
+                Ok((rest, o)) => {
+                    list.push(o);
+                    i = rest;
+                }
+            }
+        }
+        Ok((i, list.into_bump_slice()))
+    }
+}
+
+fn parse_symbol<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Symbol<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     map(
         recognize(pair(
             alt((tag("_"), alpha1)),
             many0(alt((tag("_"), alphanumeric1))),
         )),
-        |symbol: &str| symbol.to_string(),
+        |symbol: &str| &*arena.alloc_str(symbol),
     )(i)
 }
 
-fn parse_function_query<'a, E>(i: &'a str) -> IResult<&'a str, Query, E>
+/// Keyword `k`, which must not be followed by what would make it a longer symbol, e.g. `Self` in
+/// `SelfRef`.
+fn keyword<'i, E>(k: &'static str) -> impl FnMut(&'i str) -> IResult<&'i str, &'i str, E>
+where
+    E: ParseError<&'i str>,
+{
+    move |i| {
+        let (rest, k) = tag(k)(i)?;
+        if rest.starts_with(|c: char| c == '_' || c.is_ascii_alphanumeric()) {
+            return Err(nom::Err::Error(E::from_error_kind(i, ErrorKind::Tag)));
+        }
+        Ok((rest, k))
+    }
+}
+
+/// Closing bracket `c`, which may follow whitespace and a trailing comma as in queries spanning
+/// multiple lines.
+fn closing<'i, E>(c: char) -> impl FnMut(&'i str) -> IResult<&'i str, char, E>
+where
+    E: ParseError<&'i str>,
+{
+    preceded(
+        pair(multispace0, opt(pair(char(','), multispace0))),
+        char(c),
+    )
+}
+
+fn parse_uses_query<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Query<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, _) = pair(multispace0, keyword("uses"))(i)?;
+    let (i, ty) = parse_type(arena, i)?;
+
+    let query = Query {
+        name: None,
+        kind: Some(QueryKind::UsesQuery(ty)),
+        trait_: None,
+        self_ty: None,
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
+    };
+    Ok((i, query))
+}
+
+/// `new Type`, or `make Type` which is the same.
+fn parse_constructor_query<'i, 'a, E>(
+    arena: &'a Arena,
+    i: &'i str,
+) -> IResult<&'i str, Query<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, _) = pair(multispace0, alt((keyword("new"), keyword("make"))))(i)?;
+    let (i, ty) = parse_type(arena, i)?;
+
+    let query = Query {
+        name: None,
+        kind: Some(QueryKind::ConstructorQuery(ty)),
+        trait_: None,
+        self_ty: None,
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
+    };
+    Ok((i, query))
+}
+
+/// `fails_with Type`.
+fn parse_fails_with_query<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Query<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, _) = pair(multispace0, keyword("fails_with"))(i)?;
+    let (i, ty) = parse_type(arena, i)?;
+
+    let query = Query {
+        name: None,
+        kind: Some(QueryKind::FailsWithQuery(ty)),
+        trait_: None,
+        self_ty: None,
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
+    };
+    Ok((i, query))
+}
+
+/// `Type::` followed by an optional name and signature, e.g. `Vec<u8>::`, `Vec::push` or
+/// `str::fn (&self) -> usize`.
+fn parse_impl_query<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Query<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
-    let (i, f) = opt(tag("fn"))(i)?;
-    let (i, _) = match f {
-        Some(_) => multispace0(i)?,
-        None => multispace0(i)?,
+    let (i, ty) = preceded(multispace0, |i| parse_unresolved_path(arena, i))(i)?;
+    let (i, self_ty, name) = match ty {
+        // The last segment of a path without generic arguments is the method rather than part of
+        // the type, e.g. `push` of `Vec::push`, unless it is `fn` as in `Vec::fn (&self)`.
+        Type::UnresolvedPath { name, args: None }
+            if name.contains("::") && !i.starts_with("::") =>
+        {
+            let (path, method) = name.rsplit_once("::").unwrap_or_default();
+            let ty = Type::UnresolvedPath {
+                name: path,
+                args: None,
+            };
+            (i, ty, (method != "fn").then_some(method))
+        }
+        ty => {
+            let (i, _) = pair(tag("::"), opt(keyword("fn")))(i)?;
+            (i, ty, None)
+        }
     };
-    let (i, name) = opt(preceded(multispace1, parse_symbol))(i)?;
-    let (i, decl) = opt(parse_function)(i)?;
+    let (i, name) = match name {
+        Some(name) => (i, Some(name)),
+        None => opt(preceded(multispace0, |i| parse_symbol(arena, i)))(i)?,
+    };
+    let (i, _) = multispace0(i)?;
+    let (i, decl) = opt(|i| parse_function(arena, i))(i)?;
 
+    // Primitive types are paths of their own before `::`, e.g. `str` of `str::len`.
+    let self_ty = match self_ty {
+        Type::UnresolvedPath { name, args: None } => {
+            match parse_primitive_type::<nom::error::Error<&str>>(name) {
+                Ok(("", primitive)) => Type::Primitive(primitive),
+                _ => self_ty,
+            }
+        }
+        _ => self_ty,
+    };
     let query = Query {
         name,
         kind: decl.map(QueryKind::FunctionQuery),
+        trait_: None,
+        self_ty: Some(self_ty),
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
     };
     Ok((i, query))
 }
 
-fn parse_function<'a, E>(i: &'a str) -> IResult<&'a str, Function, E>
+fn parse_function_query<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Query<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
-    let (i, decl) = parse_function_decl(i)?;
+    let (i, attrs) = many0(terminated(parse_attribute, multispace1))(i)?;
+    let attrs = &*arena.alloc_slice_copy(&attrs);
+    let (i, trait_) = opt(delimited(
+        pair(keyword("in"), multispace1),
+        |i| parse_symbol(arena, i),
+        multispace1,
+    ))(i)?;
+    let (i, f) = opt(keyword("fn"))(i)?;
+    let (i, _) = multispace0(i)?;
+    // Names only follow `fn`, as in `fn from_utf8` or `fn len(&self) -> usize`.
+    let (i, name) = match f {
+        Some(_) => opt(terminated(|i| parse_symbol(arena, i), multispace0))(i)?,
+        None => (i, None),
+    };
+    let (i, decl) = opt(|i| parse_function(arena, i))(i)?;
+
+    let query = Query {
+        name,
+        kind: decl.map(QueryKind::FunctionQuery),
+        trait_,
+        self_ty: None,
+        attrs,
+        weights: Default::default(),
+        kinds: Default::default(),
+    };
+    Ok((i, query))
+}
+
+/// Attribute items must have, e.g. `must_use` in `must_use fn (&str) -> String`.
+fn parse_attribute<'i, E>(i: &'i str) -> IResult<&'i str, Attribute, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    alt((
+        value(Attribute::MustUse, keyword("must_use")),
+        value(Attribute::Inline, keyword("inline")),
+    ))(i)
+}
+
+fn parse_function<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Function<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, decl) = parse_function_decl(arena, i)?;
 
     let function = Function { decl };
     Ok((i, function))
 }
 
-fn parse_function_decl<'a, E>(i: &'a str) -> IResult<&'a str, FnDecl, E>
+fn parse_function_decl<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, FnDecl<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     let (i, inputs) = delimited(
         char('('),
         alt((
             value(None, tag("..")),
-            opt(parse_arguments),
-            value(Some(Vec::new()), not(eof)),
+            opt(|i| parse_arguments(arena, i)),
+            value(Some(&[][..]), not(eof)),
         )),
-        char(')'),
+        closing(')'),
     )(i)?;
-    let (i, output) = opt(parse_output)(i)?;
+    let (i, output) = opt(|i| parse_output(arena, i))(i)?;
 
     let decl = FnDecl { inputs, output };
     Ok((i, decl))
 }
 
-fn parse_arguments<'a, E>(i: &'a str) -> IResult<&'a str, Vec<Argument>, E>
+fn parse_arguments<'i, 'a, E>(
+    arena: &'a Arena,
+    i: &'i str,
+) -> IResult<&'i str, &'a [Argument<'a>], E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
-    separated_list0(
+    separated_list0_in(
+        arena,
         char(','),
         preceded(
             multispace0,
             alt((
-                parse_argument,
+                |i| parse_self_argument(arena, i),
+                |i| parse_argument(arena, i),
                 value(
                     Argument {
                         ty: None,
@@ -96,31 +496,58 @@ where
                     },
                     char('_'),
                 ),
-                map(parse_type, |ty| Argument {
-                    ty: Some(ty),
-                    name: None,
-                }),
+                map(
+                    |i| parse_type(arena, i),
+                    |ty| Argument {
+                        ty: Some(ty),
+                        name: None,
+                    },
+                ),
             )),
         ),
     )(i)
 }
 
-fn parse_argument<'a, E>(i: &'a str) -> IResult<&'a str, Argument, E>
+fn parse_argument<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Argument<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
-    let (i, name) = alt((value(None, char('_')), opt(parse_symbol)))(i)?;
+    let (i, name) = alt((value(None, char('_')), opt(|i| parse_symbol(arena, i))))(i)?;
     let (i, _) = char(':')(i)?;
     let (i, _) = multispace0(i)?;
-    let (i, ty) = alt((value(None, char('_')), opt(parse_type)))(i)?;
+    let (i, ty) = alt((value(None, char('_')), opt(|i| parse_type(arena, i))))(i)?;
 
     let arg = Argument { ty, name };
     Ok((i, arg))
 }
 
-fn parse_output<'a, E>(i: &'a str) -> IResult<&'a str, FnRetTy, E>
+/// Receiver of a method in the shorthand form, i.e. `self`, `&self` or `&mut self`.
+fn parse_self_argument<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Argument<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, borrow) = opt(pair(char('&'), opt(preceded(multispace0, keyword("mut")))))(i)?;
+    let (i, _) = preceded(multispace0, keyword("self"))(i)?;
+    // `self: Type` is an argument like any other, as canonical queries write receivers.
+    let (i, _) = not(preceded(multispace0, char(':')))(i)?;
+
+    let ty = match borrow {
+        Some((_, mutable)) => Type::BorrowedRef {
+            mutable: mutable.is_some(),
+            type_: arena.alloc(Type::SelfType),
+        },
+        None => Type::SelfType,
+    };
+    let arg = Argument {
+        ty: Some(ty),
+        name: Some(arena.alloc_str("self")),
+    };
+    Ok((i, arg))
+}
+
+fn parse_output<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, FnRetTy<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     preceded(
         multispace0,
@@ -129,154 +556,243 @@ where
                 FnRetTy::DefaultReturn,
                 preceded(preceded(tag("->"), multispace0), tag("()")),
             ),
-            map(preceded(tag("->"), parse_type), FnRetTy::Return),
+            map(
+                preceded(tag("->"), |i| parse_type(arena, i)),
+                FnRetTy::Return,
+            ),
             value(FnRetTy::DefaultReturn, eof),
         )),
     )(i)
 }
 
-fn parse_type<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+fn parse_type<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     preceded(
         multispace0,
         alt((
-            map(parse_primitive_type, Type::Primitive),
-            parse_generic_type,
-            parse_unresolved_path,
-            parse_tuple,
-            parse_slice,
+            // Primitive types also name modules of their own, e.g. `str` in `str::Utf8Error`.
+            map(
+                terminated(parse_primitive_type, not(tag("::"))),
+                Type::Primitive,
+            ),
+            |i| parse_impl_trait(arena, i),
+            |i| parse_qualified_path(arena, i),
+            value(Type::SelfType, keyword("Self")),
+            |i| parse_bare_function(arena, i),
+            |i| parse_generic_type(arena, i),
+            |i| parse_unresolved_path(arena, i),
+            |i| parse_tuple(arena, i),
+            |i| parse_slice(arena, i),
             value(Type::Never, char('!')),
-            parse_raw_pointer,
-            parse_borrowed_ref,
+            |i| parse_raw_pointer(arena, i),
+            |i| parse_borrowed_ref(arena, i),
         )),
     )(i)
 }
 
-fn parse_tuple<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+/// Function pointer like `fn(i32) -> i32`, whose qualifiers like `unsafe extern "C"` are left out
+/// as they are rarely what tells callbacks apart.
+fn parse_bare_function<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, _) = opt(pair(keyword("unsafe"), multispace1))(i)?;
+    let (i, _) = opt(tuple((
+        keyword("extern"),
+        multispace0,
+        opt(delimited(char('"'), take_while1(|c| c != '"'), char('"'))),
+        multispace0,
+    )))(i)?;
+    let (i, _) = pair(keyword("fn"), multispace0)(i)?;
+    let (i, mut decl) = parse_function_decl(arena, i)?;
+    decl.output.get_or_insert(FnRetTy::DefaultReturn);
+
+    Ok((i, Type::BareFunction(arena.alloc(decl))))
+}
+
+fn parse_tuple<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     map(
         delimited(
             char('('),
-            separated_list0(
+            separated_list0_in(
+                arena,
                 char(','),
                 preceded(
                     multispace0,
-                    alt((value(None, tag("_")), map(parse_type, Some))),
+                    alt((value(None, tag("_")), map(|i| parse_type(arena, i), Some))),
                 ),
             ),
-            char(')'),
+            closing(')'),
         ),
         Type::Tuple,
     )(i)
 }
 
-fn parse_slice<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+fn parse_slice<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     map(
         delimited(
             char('['),
-            alt((value(None, tag("_")), map(parse_type, Some))),
+            alt((value(None, tag("_")), map(|i| parse_type(arena, i), Some))),
             char(']'),
         ),
-        |ty| Type::Slice(ty.map(Box::new)),
+        |ty| Type::Slice(ty.map(|ty| &*arena.alloc(ty))),
     )(i)
 }
 
-fn parse_raw_pointer<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+fn parse_raw_pointer<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     let (i, mutable) = alt((value(true, tag("*mut")), value(false, tag("*const"))))(i)?;
-    let (i, type_) = parse_type(i)?;
+    let (i, type_) = parse_type(arena, i)?;
 
     Ok((
         i,
         Type::RawPointer {
             mutable,
-            type_: Box::new(type_),
+            type_: arena.alloc(type_),
         },
     ))
 }
 
-fn parse_borrowed_ref<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+fn parse_borrowed_ref<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     let (i, mutable) = alt((value(true, tag("&mut")), value(false, tag("&"))))(i)?;
-    let (i, type_) = parse_type(i)?;
+    let (i, type_) = parse_type(arena, i)?;
 
     Ok((
         i,
         Type::BorrowedRef {
             mutable,
-            type_: Box::new(type_),
+            type_: arena.alloc(type_),
         },
     ))
 }
 
-fn parse_unresolved_path<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+fn parse_unresolved_path<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
-    let (i, name) = parse_symbol(i)?;
-    let (i, args) = opt(parse_generic_args)(i)?;
+    // Paths are kept as written, e.g. `io::Error`, and compared by their last segments.
+    let (i, name) = map(
+        recognize(separated_list1(tag("::"), |i| parse_symbol::<E>(arena, i))),
+        |path: &str| &*arena.alloc_str(path),
+    )(i)?;
+    let (i, args) = opt(|i| parse_generic_args(arena, i))(i)?;
 
     Ok((
         i,
         Type::UnresolvedPath {
             name,
-            args: args.map(Box::new),
+            args: args.map(|args| &*arena.alloc(args)),
         },
     ))
 }
 
-fn parse_generic_args<'a, E>(i: &'a str) -> IResult<&'a str, GenericArgs, E>
+fn parse_generic_args<'i, 'a, E>(
+    arena: &'a Arena,
+    i: &'i str,
+) -> IResult<&'i str, GenericArgs<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     map(
         delimited(
             char('<'),
-            separated_list0(
+            separated_list0_in(
+                arena,
                 char(','),
                 preceded(
                     multispace0,
                     alt((
                         value(None, tag("_")),
-                        opt(map(parse_type, GenericArg::Type)),
+                        map(|i| parse_binding(arena, i), Some),
+                        map(|i| parse_type(arena, i), |ty| Some(GenericArg::Type(ty))),
                     )),
                 ),
             ),
-            char('>'),
+            closing('>'),
         ),
         |args| GenericArgs::AngleBracketed { args },
     )(i)
 }
 
-fn parse_generic_type<'a, E>(i: &'a str) -> IResult<&'a str, Type, E>
+/// Associated type of `Self` or a generic type, e.g. `Self::Item`.
+fn parse_qualified_path<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, self_type) = alt((value(Type::SelfType, keyword("Self")), |i| {
+        parse_generic_type(arena, i)
+    }))(i)?;
+    let (i, name) = preceded(tag("::"), |i| parse_symbol(arena, i))(i)?;
+
+    Ok((
+        i,
+        Type::QualifiedPath {
+            self_type: arena.alloc(self_type),
+            name,
+        },
+    ))
+}
+
+/// Binding of an associated type, e.g. `Item = T`.
+fn parse_binding<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, GenericArg<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, name) = parse_symbol(arena, i)?;
+    let (i, _) = delimited(multispace0, char('='), multispace0)(i)?;
+    let (i, ty) = parse_type(arena, i)?;
+
+    Ok((i, GenericArg::Binding { name, ty }))
+}
+
+fn parse_impl_trait<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
+{
+    let (i, _) = pair(keyword("impl"), multispace1)(i)?;
+    let (i, trait_) = parse_symbol(arena, i)?;
+    let (i, args) = opt(|i| parse_generic_args(arena, i))(i)?;
+
+    Ok((
+        i,
+        Type::ImplTrait {
+            trait_,
+            args: args.map(|args| &*arena.alloc(args)),
+        },
+    ))
+}
+
+fn parse_generic_type<'i, 'a, E>(arena: &'a Arena, i: &'i str) -> IResult<&'i str, Type<'a>, E>
+where
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     let (i, gen) = map(take_while1(|c: char| c.is_ascii_uppercase()), |s: &str| {
-        Type::Generic(s.to_owned())
+        Type::Generic(arena.alloc_str(s))
     })(i)?;
 
-    if i.chars().next().map_or(false, |c| c.is_ascii_lowercase()) {
+    if i.chars().next().is_some_and(|c| c.is_ascii_lowercase()) {
         fail(i)
     } else {
         Ok((i, gen))
     }
 }
 
-fn parse_primitive_type<'a, E>(i: &'a str) -> IResult<&'a str, PrimitiveType, E>
+fn parse_primitive_type<'i, E>(i: &'i str) -> IResult<&'i str, PrimitiveType, E>
 where
-    E: ParseError<&'a str> + ContextError<&'a str>,
+    E: ParseError<&'i str> + ContextError<&'i str>,
 {
     use PrimitiveType::*;
     alt((
@@ -299,3 +815,289 @@ where
         value(Str, tag("str")),
     ))(i)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_complete() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (&str) -> u32").unwrap();
+        assert_eq!(query.args().map(|args| args.len()), Some(1));
+    }
+
+    #[test]
+    fn parse_complete_multiline() {
+        let arena = Arena::new();
+        let query = parse_query_in(
+            &arena,
+            "fn (\n    HashMap<\n        K,\n        V,\n    >,\n    &K,\n) -> Option<&V>",
+        );
+        assert_eq!(
+            query,
+            parse_query_in(&arena, "fn (HashMap<K, V>, &K) -> Option<&V>")
+        );
+        assert!(query.is_ok());
+    }
+
+    #[test]
+    fn parse_complete_trailing_input() {
+        assert_eq!(
+            parse_query_in(&Arena::new(), "fn (&str) -> u32 }"),
+            Err(QueryParseError {
+                offset: 16,
+                message: "unexpected input `}`".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_names() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn from_utf8").unwrap();
+        assert_eq!(query.name, Some("from_utf8"));
+        assert_eq!(query.kind, None);
+
+        for q in ["fn len(&self) -> usize", "fn  len (&self) -> usize"] {
+            let query = parse_query_in(&arena, q).unwrap();
+            assert_eq!(query.name, Some("len"));
+            assert_eq!(query.args().map(|args| args.len()), Some(1));
+        }
+        assert_eq!(parse_query_in(&arena, "fn (&self)").unwrap().name, None);
+        assert!(parse_query_in(&arena, "fns").is_err());
+    }
+
+    #[test]
+    fn parse_never() {
+        let arena = Arena::new();
+        for q in ["fn (&str) -> !", "fn panic(msg: &str)->!", "(&str) -> !"] {
+            let query = parse_query_in(&arena, q).unwrap();
+            let output = match query.kind {
+                Some(QueryKind::FunctionQuery(ref f)) => &f.decl.output,
+                _ => unreachable!(),
+            };
+            assert_eq!(output, &Some(FnRetTy::Return(Type::Never)));
+        }
+        let query = parse_query_in(&arena, "fn (Option<!>) -> (!, u8)").unwrap();
+        assert_eq!(query.canonicalize(), "fn (Option<!>) -> (!, u8)");
+    }
+
+    #[test]
+    fn parse_bare_functions() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (fn(i32) -> i32, i32) -> i32").unwrap();
+        let input = match query.kind {
+            Some(QueryKind::FunctionQuery(ref f)) => f.decl.inputs.unwrap()[0].ty.as_ref(),
+            _ => unreachable!(),
+        };
+        let decl = match input {
+            Some(Type::BareFunction(decl)) => decl,
+            _ => unreachable!(),
+        };
+        assert_eq!(decl.inputs.map(<[_]>::len), Some(1));
+        assert_eq!(
+            decl.output,
+            Some(FnRetTy::Return(Type::Primitive(PrimitiveType::I32)))
+        );
+
+        for (q, canonical) in [
+            (
+                "fn (fn(i32) -> i32, i32) -> i32",
+                "fn (fn(i32) -> i32, i32) -> i32",
+            ),
+            (
+                "fn (cb: fn (u8)) -> Option<fn()>",
+                "fn (cb: fn(u8) -> ()) -> Option<fn() -> ()>",
+            ),
+            (
+                r#"fn (unsafe extern "C" fn(*mut u8)) -> bool"#,
+                "fn (fn(*mut u8) -> ()) -> bool",
+            ),
+            ("fn (fn_ptr) -> bool", "fn (fn_ptr) -> bool"),
+        ] {
+            let query = parse_query_in(&arena, q).unwrap();
+            assert_eq!(query.canonicalize(), canonical);
+            let reparsed = parse_query_in(&arena, canonical).unwrap();
+            assert_eq!(reparsed.canonicalize(), canonical);
+        }
+    }
+
+    #[test]
+    fn parse_impls() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "Vec<u8>::").unwrap();
+        assert_eq!(query.self_ty_name(), Some("Vec"));
+        assert_eq!((query.name, &query.kind), (None, &None));
+
+        for (q, canonical) in [
+            ("Vec<u8>::", "Vec<u8>::"),
+            ("Vec::push", "Vec::push"),
+            ("Vec::fn push", "Vec::push"),
+            ("io::Error::new", "io::Error::new"),
+            (
+                "Vec<T>::push(&mut self, T)",
+                "Vec<T>::push(self: &mut Self, T) -> ()",
+            ),
+            ("Vec::fn (&self) -> usize", "Vec::fn (self: &Self) -> usize"),
+        ] {
+            let query = parse_query_in(&arena, q).unwrap();
+            assert_eq!(query.canonicalize(), canonical);
+            assert_eq!(parse_query_in(&arena, canonical).unwrap(), query);
+        }
+
+        let query = parse_query_in(&arena, "str::len").unwrap();
+        assert_eq!(query.self_ty, Some(Type::Primitive(PrimitiveType::Str)));
+        assert_eq!(query.name, Some("len"));
+        assert!(parse_query_in(&arena, "Vec<u8>").is_err());
+    }
+
+    #[test]
+    fn parse_examples_of_capabilities() {
+        let arena = Arena::new();
+        for (i, capability) in CAPABILITIES.iter().enumerate() {
+            if let Err(e) = parse_query_in(&arena, capability.example) {
+                panic!("example of `{}` doesn't parse: {}", capability.name, e);
+            }
+            assert!(
+                !CAPABILITIES[..i].iter().any(|c| c.name == capability.name),
+                "`{}` is listed twice",
+                capability.name
+            );
+        }
+    }
+
+    #[test]
+    fn parse_self() {
+        let arena = Arena::new();
+        let self_ref = Type::BorrowedRef {
+            mutable: true,
+            type_: &Type::SelfType,
+        };
+        let query = parse_query_in(&arena, "fn (&mut self, SelfRef) -> Self").unwrap();
+        let args = query.args().unwrap();
+
+        assert_eq!(args[0].name, Some("self"));
+        assert_eq!(args[0].ty, Some(self_ref));
+        assert!(matches!(
+            args[1].ty,
+            Some(Type::UnresolvedPath {
+                name: "SelfRef",
+                ..
+            })
+        ));
+        assert_eq!(
+            query.canonicalize(),
+            "fn (self: &mut Self, SelfRef) -> Self"
+        );
+        assert_eq!(
+            parse_query_in(&arena, &query.canonicalize()).unwrap(),
+            query
+        );
+    }
+
+    #[test]
+    fn parse_trait() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "in Iterator fn (Self) -> Option<Self::Item>").unwrap();
+
+        assert_eq!(query.trait_, Some("Iterator"));
+        assert_eq!(
+            query.canonicalize(),
+            "in Iterator fn (Self) -> Option<Self::Item>"
+        );
+        assert!(parse_query_in(&arena, "inIterator fn ()").is_err());
+    }
+
+    #[test]
+    fn parse_uses() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "uses  Option<Duration>").unwrap();
+
+        assert!(matches!(query.kind, Some(QueryKind::UsesQuery(_))));
+        assert_eq!(query.canonicalize(), "uses Option<Duration>");
+        assert!(parse_query_in(&arena, "usesDuration").is_err());
+    }
+
+    #[test]
+    fn parse_constructor() {
+        let arena = Arena::new();
+        let new = parse_query_in(&arena, "new Duration").unwrap();
+        let make = parse_query_in(&arena, "make Duration").unwrap();
+
+        assert!(matches!(new.kind, Some(QueryKind::ConstructorQuery(_))));
+        assert_eq!(new, make);
+        assert_eq!(make.canonicalize(), "new Duration");
+    }
+
+    #[test]
+    fn parse_fails_with() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fails_with io::Error").unwrap();
+
+        assert!(matches!(
+            query.kind,
+            Some(QueryKind::FailsWithQuery(Type::UnresolvedPath {
+                name: "io::Error",
+                args: None
+            }))
+        ));
+        assert_eq!(query.canonicalize(), "fails_with io::Error");
+        assert!(parse_query_in(&arena, "fails_with io::").is_err());
+        assert!(parse_query_in(&arena, "fails_with str::Utf8Error").is_ok());
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "must_use  inline fn (usize) -> Vec<T>").unwrap();
+
+        assert_eq!(query.attrs, &[Attribute::MustUse, Attribute::Inline]);
+        assert_eq!(query.canonicalize(), "must_use inline fn (usize) -> Vec<T>");
+        assert!(parse_query_in(&arena, "in Iterator fn (&mut self)")
+            .unwrap()
+            .attrs
+            .is_empty());
+        assert!(parse_query_in(&arena, "must_usex fn ()").is_err());
+    }
+
+    #[test]
+    fn parse_impl_trait() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (impl Iterator<Item=T>) -> Vec<T>").unwrap();
+
+        assert!(matches!(
+            query.args().unwrap()[0].ty,
+            Some(Type::ImplTrait {
+                trait_: "Iterator",
+                args: Some(_),
+            })
+        ));
+        assert_eq!(
+            query.canonicalize(),
+            "fn (impl Iterator<Item = T>) -> Vec<T>"
+        );
+    }
+
+    #[test]
+    fn canonicalize() {
+        let arena = Arena::new();
+        let canonical = |query| parse_query_in(&arena, query).unwrap().canonicalize();
+
+        assert_eq!(canonical("fn(&str)->u32"), "fn (&str) -> u32");
+        assert_eq!(canonical("fn (&str) -> u32"), "fn (&str) -> u32");
+        assert_eq!(
+            canonical("fn (s: &mut str, _)"),
+            "fn (s: &mut str, _) -> ()"
+        );
+        assert_eq!(canonical("fn (..) -> *const [_]"), "fn (..) -> *const [_]");
+
+        let query = "fn (\n    HashMap<K,V,>,\n    (T,_),\n) -> Option<&V>";
+        let canonical = canonical(query);
+        assert_eq!(canonical, "fn (HashMap<K, V>, (T, _)) -> Option<&V>");
+        assert_eq!(
+            parse_query_in(&arena, &canonical),
+            parse_query_in(&arena, query)
+        );
+    }
+}
@@ -1,29 +1,90 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, fmt, ops::ControlFlow};
 
 use rustdoc_types as types;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    compare::{Compare, Similarities},
+    compare::Similarities,
+    function::{FnItem, Owner},
+    matcher::{self, DefaultMatcher, Matcher},
+    metadata::{self_ty_name, ItemMetadata, MethodOwner},
     query::Query,
+    signature,
+    summary::summary,
     Index,
 };
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Hit {
     pub name: String,
+    pub kind: ItemKind,
     pub path: Vec<String>,
     pub link: Vec<String>,
+    pub signature: String,
+    /// Docs of the item, which are serialized if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub docs: Option<String>,
+    /// First sentence of the docs as plain text, keeping code spans, e.g. ``Returns the `len`.``,
+    /// which is serialized if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
     #[serde(skip)]
+    krate: String,
+    /// Breakdown of the score, which is serialized unless empty.
+    #[serde(default, skip_serializing_if = "Similarities::is_empty")]
     similarities: Similarities,
+    /// Generic types of the item bound to concrete types of the query, e.g. `T` to `u8` for query
+    /// `Vec<u8>` and item `Vec<T>`, which is serialized unless empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub substitutions: BTreeMap<String, String>,
+    /// Cargo feature the item is only available with, e.g. `serde` for an item under
+    /// `#[cfg(feature = "serde")]`, which is serialized if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    /// Score of the item weighted as the query asks, where lower is closer, so that hits of
+    /// separate searches can be merged in order.
+    #[serde(default)]
+    pub score: f32,
+    /// Position of the hit among all hits of the search from 0, regardless of how many were
+    /// skipped, e.g. by `offset`, which is only serialized for sorted hits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    /// Scopes among the ones searched at once the item is in, e.g. `set:std` of
+    /// `set:std+crate:itertools`, which is serialized unless empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
 }
 
 impl Hit {
+    /// Name of the crate the item is in, which is empty for hits deserialized from a server.
+    pub fn krate(&self) -> &str {
+        &self.krate
+    }
+
     pub fn similarities(&self) -> &Similarities {
         &self.similarities
     }
+
+    /// `substitutions` as written after signatures, e.g. `with T = u8, U = bool`, or `None` if
+    /// there are none.
+    pub fn with_clause(&self) -> Option<String> {
+        if self.substitutions.is_empty() {
+            return None;
+        }
+        let substitutions = self
+            .substitutions
+            .iter()
+            .map(|(generic, ty)| format!("{} = {}", generic, ty))
+            .collect::<Vec<_>>();
+        Some(format!("with {}", substitutions.join(", ")))
+    }
+
+    /// Drop the breakdown of the score so that it isn't serialized, e.g. unless debugging.
+    pub fn clear_similarities(&mut self) {
+        self.similarities = Similarities::default();
+    }
 }
 
 impl PartialOrd for Hit {
@@ -32,6 +93,13 @@ impl PartialOrd for Hit {
     }
 }
 
+/// Number sorted `hits` by their positions.
+pub(crate) fn rank(hits: &mut [Hit]) {
+    for (rank, hit) in hits.iter_mut().enumerate() {
+        hit.rank = Some(rank);
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum SearchError {
     #[error("crate `{0}` is not present in the index")]
@@ -39,6 +107,15 @@ pub enum SearchError {
 
     #[error("item with id `{0}` is not present in crate `{1}`")]
     ItemNotFound(String, String),
+
+    #[error("item with id `{0}` in crate `{1}` is malformed: {2}")]
+    MalformedItem(String, String, &'static str),
+
+    #[error("`Self` appears outside of an impl")]
+    UnboundSelf,
+
+    #[error("no function or method is at path `{0}`")]
+    PathNotFound(String),
 }
 
 pub type Result<T> = std::result::Result<T, SearchError>;
@@ -66,119 +143,414 @@ impl Scope {
     }
 }
 
+/// Kind of items hits are of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    /// Free function.
+    Function,
+    /// Function associated with a type or trait, whether or not it takes `self`.
+    Method,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 2] = [ItemKind::Function, ItemKind::Method];
+
+    /// Name of the kind as given in filters like `method,function`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemKind::Function => "function",
+            ItemKind::Method => "method",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Kinds of items to search, all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemKinds(u8);
+
+impl ItemKinds {
+    pub fn contains(self, kind: ItemKind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+}
+
+impl Default for ItemKinds {
+    fn default() -> Self {
+        ItemKind::ALL.into_iter().collect()
+    }
+}
+
+impl FromIterator<ItemKind> for ItemKinds {
+    fn from_iter<I: IntoIterator<Item = ItemKind>>(iter: I) -> Self {
+        ItemKinds(iter.into_iter().fold(0, |bits, kind| bits | kind.bit()))
+    }
+}
+
+/// Represents a failure of parsing [`ItemKinds`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("unknown item kind `{0}`: expected `function` or `method`")]
+pub struct ParseItemKindsError(pub String);
+
+impl std::str::FromStr for ItemKinds {
+    type Err = ParseItemKindsError;
+
+    /// Parse comma-separated kinds like `method,function`, where none means all of them.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let kinds = s
+            .split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                ItemKind::ALL
+                    .into_iter()
+                    .find(|k| k.name() == kind)
+                    .ok_or_else(|| ParseItemKindsError(kind.to_owned()))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if kinds.is_empty() {
+            return Ok(ItemKinds::default());
+        }
+        Ok(kinds.into_iter().collect())
+    }
+}
+
+impl fmt::Display for ItemKinds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = ItemKind::ALL
+            .into_iter()
+            .filter(|&kind| self.contains(kind))
+            .map(ItemKind::name)
+            .collect::<Vec<_>>();
+        f.write_str(&names.join(","))
+    }
+}
+
+// Kinds are given as strings like `method,function` wherever they are serialized, e.g. in
+// requests to servers.
+impl Serialize for ItemKinds {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemKinds {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Index {
     /// Perform search with given query and scope.
     ///
     /// Returns [`Hit`]s whose similarity score outperforms given `threshold`.
     pub fn search(&self, query: &Query, scope: Scope, threshold: f32) -> Result<Vec<Hit>> {
+        self.search_with(&DefaultMatcher::new(threshold), query, scope, threshold)
+    }
+
+    /// Perform search like [`Index::search`], scoring items with `matcher`.
+    pub fn search_with(
+        &self,
+        matcher: &dyn Matcher,
+        query: &Query,
+        scope: Scope,
+        threshold: f32,
+    ) -> Result<Vec<Hit>> {
+        let mut hits = vec![];
+        self.search_each_with(matcher, query, scope, threshold, |hit| {
+            hits.push(hit);
+            ControlFlow::Continue(())
+        })?;
+
+        // Ties are broken by paths, so that hits are in the same order whichever crates come first.
+        hits.sort_unstable_by(|a, b| {
+            a.similarities
+                .total_cmp(&b.similarities, &query.weights)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.link.cmp(&b.link))
+        });
+        rank(&mut hits);
+        Ok(hits)
+    }
+
+    /// Names of the crates in the index out of `scope` which have hits of `query`, sorted, e.g. to
+    /// tell users finding nothing that they are searching the wrong scope.
+    pub fn other_crates_with_hits(
+        &self,
+        query: &Query,
+        scope: Scope,
+        threshold: f32,
+    ) -> Result<Vec<String>> {
+        let matcher = DefaultMatcher::new(threshold);
+        self.other_crates_with_hits_with(&matcher, query, scope, threshold)
+    }
+
+    /// Find crates like [`Index::other_crates_with_hits`], scoring items with `matcher`.
+    pub fn other_crates_with_hits_with(
+        &self,
+        matcher: &dyn Matcher,
+        query: &Query,
+        scope: Scope,
+        threshold: f32,
+    ) -> Result<Vec<String>> {
+        let scope = scope.flatten();
+        let mut krates = self
+            .crates
+            .keys()
+            .filter(|krate| !scope.contains(krate))
+            .cloned()
+            .collect::<Vec<_>>();
+        krates.sort_unstable();
+
+        let mut found = vec![];
+        for krate in krates {
+            let mut has_hits = false;
+            // One hit is enough to tell the crate has any.
+            self.search_each_with(
+                matcher,
+                query,
+                Scope::Crate(krate.clone()),
+                threshold,
+                |_| {
+                    has_hits = true;
+                    ControlFlow::Break(())
+                },
+            )?;
+            if has_hits {
+                found.push(krate);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Perform search with given query and scope, passing each [`Hit`] to `f` as soon as it is
+    /// found.
+    ///
+    /// Unlike [`Index::search`], hits are not sorted. Searching stops once `f` returns
+    /// [`ControlFlow::Break`].
+    pub fn search_each<F>(&self, query: &Query, scope: Scope, threshold: f32, f: F) -> Result<()>
+    where
+        F: FnMut(Hit) -> ControlFlow<()>,
+    {
+        let matcher = DefaultMatcher::new(threshold);
+        self.search_each_with(&matcher, query, scope, threshold, f)
+    }
+
+    /// Perform search like [`Index::search_each`], scoring items with `matcher`.
+    #[tracing::instrument(skip(self, matcher, scope, f))]
+    pub fn search_each_with<F>(
+        &self,
+        matcher: &dyn Matcher,
+        query: &Query,
+        scope: Scope,
+        threshold: f32,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Hit) -> ControlFlow<()>,
+    {
+        let names_below = matcher.scores_names_by_distance().then_some(threshold);
+        self.each_item(
+            scope,
+            Some(query),
+            names_below,
+            |krate_name, krate, item, owner, metadata| {
+                let sims =
+                    matcher.compare(query, item, krate, krate_name, owner, &self.trait_impls)?;
+                let score = sims.score_with(&query.weights);
+                if score < threshold {
+                    let mut hit = Self::hit(krate, krate_name, item, owner, metadata, sims)?;
+                    hit.score = score;
+                    hit.substitutions =
+                        matcher::substitutions(query, item, krate, owner, &self.trait_impls)?;
+                    return Ok(f(hit));
+                }
+                Ok(ControlFlow::Continue(()))
+            },
+        )
+    }
+
+    /// All functions and methods in `scope` as hits sorted by their paths, e.g. to export them.
+    ///
+    /// Their similarities are empty, as there's no query to compare them with.
+    pub fn items(&self, scope: Scope) -> Result<Vec<Hit>> {
         let mut hits = vec![];
+        self.each_item(
+            scope,
+            None,
+            None,
+            |krate_name, krate, item, owner, metadata| {
+                hits.push(Self::hit(
+                    krate,
+                    krate_name,
+                    item,
+                    owner,
+                    metadata,
+                    Similarities::default(),
+                )?);
+                Ok(ControlFlow::Continue(()))
+            },
+        )?;
+
+        hits.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(hits)
+    }
+
+    /// Pass each item search compares queries with in `scope` to `f` along with its crate, until
+    /// `f` returns [`ControlFlow::Break`].
+    ///
+    /// The items are functions and methods of inherent impls; methods of traits are only searched
+    /// for in queries naming their traits. This lets other tools analyze the same items as search
+    /// without walking impls again.
+    pub fn for_each_candidate<F>(&self, scope: Scope, mut f: F) -> Result<()>
+    where
+        F: FnMut(&types::Item, &types::Crate) -> ControlFlow<()>,
+    {
+        self.each_item(scope, None, None, |_, krate, item, _, _| Ok(f(item, krate)))
+    }
 
+    /// Pass each function and method in `scope` to `f`, along with the name of the crate, the
+    /// crate, the owner of the method if any and its metadata, until `f` returns
+    /// [`ControlFlow::Break`].
+    ///
+    /// Methods of traits are passed only if `query` names their trait, in which case nothing else
+    /// is. If `query` names a type as in `Vec<u8>::`, only methods of its impls are passed,
+    /// whether of traits or not. If the first argument of `query` is of a type like `Vec<T>`,
+    /// methods taking `self` are passed only if they are of its impls. Items lacking attributes
+    /// `query` requires aren't passed either, nor are items whose names can't score less than
+    /// `names_below` if `query` has a name alone, which are looked up in the index of names
+    /// instead of walking every item.
+    ///
+    /// They are taken from their [`ItemMetadata`], along with their owners, rather than by walking
+    /// the items and impls of crates.
+    pub(crate) fn each_item<F>(
+        &self,
+        scope: Scope,
+        query: Option<&Query<'_>>,
+        names_below: Option<f32>,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(
+            &str,
+            &types::Crate,
+            &types::Item,
+            Option<Owner<'_>>,
+            &ItemMetadata,
+        ) -> Result<ControlFlow<()>>,
+    {
+        let trait_ = query.and_then(|query| query.trait_);
+        let self_ty = query.and_then(Query::self_ty_name);
+        // Methods of impls of a type are among the ones taking it as `self` and the others.
+        let receiver = self_ty.or_else(|| query.and_then(Query::receiver));
+        let attrs = query.map(|query| query.attrs).unwrap_or_default();
+        let kinds = query.map(|query| query.kinds).unwrap_or_default();
+        let name = query
+            .filter(|query| query.kind.is_none())
+            .and_then(|query| query.name)
+            .zip(names_below);
         let krates = scope.flatten();
         for krate_name in krates {
+            let _span = tracing::debug_span!("crate", krate = %krate_name).entered();
             let krate = self
                 .crates
                 .get(&krate_name)
                 .ok_or_else(|| SearchError::CrateNotFound(krate_name.clone()))?;
-            for item in krate.index.values() {
-                match item.inner {
-                    types::ItemEnum::Function(_) => {
-                        let (path, link) = Self::path_and_link(krate, &krate_name, item, None)?;
-                        let sims = self.compare(query, item, krate, None);
-
-                        if sims.score() < threshold {
-                            hits.push(Hit {
-                                name: item.name.clone().unwrap(), // SAFETY: all functions has its name.
-                                path,
-                                link,
-                                docs: item.docs.clone(),
-                                similarities: sims,
-                            });
-                        }
-                    }
-                    types::ItemEnum::Impl(ref impl_) if impl_.trait_.is_none() => {
-                        let assoc_items = impl_
-                            .items
-                            .iter()
-                            .map(|id| {
-                                krate.index.get(id).ok_or_else(|| {
-                                    SearchError::ItemNotFound(id.0.clone(), krate_name.clone())
-                                })
-                            })
-                            .collect::<Result<Vec<_>>>()?;
-                        for assoc_item in assoc_items {
-                            if let types::ItemEnum::Method(_) = assoc_item.inner {
-                                let (path, link) = Self::path_and_link(
-                                    krate,
-                                    &krate_name,
-                                    assoc_item,
-                                    Some(impl_),
-                                )?;
-                                let sims = self.compare(query, assoc_item, krate, Some(impl_));
-
-                                if sims.score() < threshold {
-                                    hits.push(Hit {
-                                        name: assoc_item.name.clone().unwrap(), // SAFETY: all methods has its name.
-                                        path,
-                                        link,
-                                        docs: assoc_item.docs.clone(),
-                                        similarities: sims,
-                                    })
-                                }
-                            }
-                        }
-                    }
-                    // TODO(hkmatsumoto): Acknowledge trait method as well.
-                    _ => {}
+            let metadata = self.metadata(&krate_name).unwrap_or_default();
+            let positions = receiver
+                .zip(self.receivers.get(&krate_name))
+                .and_then(|(receiver, receivers)| receivers.positions(receiver))
+                .or_else(|| {
+                    let ((name, threshold), names) = name.zip(self.names.get(&krate_name))?;
+                    names.positions(name, threshold)
+                });
+            let metadata: Box<dyn Iterator<Item = &ItemMetadata>> = match positions {
+                Some(positions) => Box::new(positions.into_iter().map(|i| &metadata[i])),
+                None => Box::new(metadata.iter()),
+            };
+            for metadata in metadata {
+                let searched = match (self_ty, &metadata.owner) {
+                    (None, _) => metadata.trait_.as_deref() == trait_,
+                    (Some(self_ty), Some(owner)) => owner
+                        .impl_()
+                        .is_some_and(|impl_| self_ty_name(&impl_.for_).as_deref() == Some(self_ty)),
+                    (Some(_), None) => false,
+                };
+                if !searched
+                    || !kinds.contains(metadata.kind)
+                    || !attrs.iter().all(|attr| metadata.attrs.contains(attr))
+                {
+                    continue;
+                }
+                let owner = metadata.owner.as_ref().and_then(MethodOwner::as_owner);
+                if f(&krate_name, krate, &metadata.item, owner, metadata)?.is_break() {
+                    return Ok(());
                 }
             }
         }
 
-        hits.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-        Ok(hits)
+        Ok(())
     }
 
-    #[tracing::instrument(skip(self, krate))]
-    fn compare(
-        &self,
-        query: &Query,
-        item: &types::Item,
+    pub(crate) fn hit(
         krate: &types::Crate,
-        impl_: Option<&types::Impl>,
-    ) -> Similarities {
-        let mut generics;
-        if let Some(impl_) = impl_ {
-            generics = impl_.generics.clone();
-            generics
-                .where_predicates
-                .push(types::WherePredicate::EqPredicate {
-                    lhs: types::Type::Generic("Self".to_owned()),
-                    rhs: impl_.for_.clone(),
-                });
-        } else {
-            generics = types::Generics::default()
-        }
-        let mut substs = HashMap::default();
+        krate_name: &str,
+        item: &types::Item,
+        owner: Option<Owner<'_>>,
+        metadata: &ItemMetadata,
+        similarities: Similarities,
+    ) -> Result<Hit> {
+        let (path, link) = Self::path_and_link(krate, krate_name, item, owner)?;
+        Ok(Hit {
+            name: Self::name(item, krate_name)?,
+            kind: metadata.kind,
+            path,
+            link,
+            signature: signature::render(item).unwrap_or_default(),
+            docs: item.docs.clone(),
+            summary: item.docs.as_deref().and_then(summary),
+            krate: krate_name.to_owned(),
+            score: similarities.score(),
+            similarities,
+            substitutions: BTreeMap::new(),
+            feature: metadata.feature.clone(),
+            rank: None,
+            scopes: vec![],
+        })
+    }
 
-        let sims = query.compare(item, krate, &mut generics, &mut substs);
-        Similarities(sims)
+    /// Name of `item`, which functions and methods should always have.
+    fn name(item: &types::Item, krate_name: &str) -> Result<String> {
+        item.name.clone().ok_or_else(|| {
+            SearchError::MalformedItem(item.id.0.clone(), krate_name.to_owned(), "no name")
+        })
     }
 
-    /// Given `item` and optional `impl_`, compute its path and rustdoc link to `item`.
+    /// Given `item` and optional `owner`, compute its path and rustdoc link to `item`.
     ///
-    /// `item` must be a function or a method, otherwise assertions will fail.
-    fn path_and_link(
+    /// `item` must be a function or a method, otherwise an error is returned.
+    pub(crate) fn path_and_link(
         krate: &types::Crate,
         krate_name: &str,
         item: &types::Item,
-        impl_: Option<&types::Impl>,
+        owner: Option<Owner<'_>>,
     ) -> Result<(Vec<String>, Vec<String>)> {
-        assert!(matches!(
-            item.inner,
-            types::ItemEnum::Function(_) | types::ItemEnum::Method(_)
-        ));
-
         use types::Type;
 
+        let malformed =
+            |reason| SearchError::MalformedItem(item.id.0.clone(), krate_name.to_owned(), reason);
+
         let get_path = |id: &types::Id| -> Result<Vec<String>> {
             let path = krate
                 .paths
@@ -194,7 +566,7 @@ impl Index {
         // it is binded to.
         let mut path;
         let mut link;
-        if let Some(impl_) = impl_ {
+        if let Some(Owner::Impl(impl_)) = owner {
             let recv;
             match (&impl_.for_, &impl_.trait_) {
                 (_, Some(ref t)) => {
@@ -202,8 +574,7 @@ impl Index {
                         path = get_path(id)?;
                         recv = format!("trait.{}.html", name);
                     } else {
-                        // SAFETY: All traits are represented by `ResolvedPath`.
-                        unreachable!()
+                        return Err(malformed("trait of its impl is not a path"));
                     }
                 }
                 (
@@ -220,8 +591,7 @@ impl Index {
                         types::ItemKind::Union => recv = format!("union.{}.html", name),
                         types::ItemKind::Enum => recv = format!("enum.{}.html", name),
                         types::ItemKind::Struct => recv = format!("struct.{}.html", name),
-                        // SAFETY: ADTs are either unions or enums or structs.
-                        _ => unreachable!(),
+                        _ => return Err(malformed("impl is for neither a union, enum nor struct")),
                     }
                 }
                 (Type::Primitive(ref prim), _) => {
@@ -248,46 +618,56 @@ impl Index {
                     path = vec!["reference".to_owned()];
                     recv = "primitive.reference.html".to_owned();
                 }
-                _ => unreachable!(),
+                _ => return Err(malformed("impl is for an unsupported type")),
             }
             link = path.clone();
             if let Some(l) = link.last_mut() {
                 *l = recv;
             }
+        } else if let Some(Owner::Trait(id, _)) = owner {
+            path = get_path(id)?;
+            link = path.clone();
+            if let Some(l) = link.last_mut() {
+                *l = format!("trait.{}.html", l);
+            }
         } else {
             path = get_path(&item.id)?;
             link = path.clone();
         }
 
-        match item.inner {
-            types::ItemEnum::Function(_) => {
-                if let Some(l) = link.last_mut() {
-                    *l = format!("fn.{}.html", l);
-                }
-                Ok((path.clone(), link))
-            }
-            types::ItemEnum::Method(_) => {
-                let name = item.name.clone().unwrap(); // SAFETY: all methods has its name.
-                if let Some(l) = link.last_mut() {
-                    *l = format!("{}#method.{}", l, &name);
-                }
-                path.push(name);
-
-                Ok((path.clone(), link))
+        if FnItem::of(&item.inner).is_none() {
+            return Err(malformed("neither a function nor a method"));
+        }
+        // Methods are told apart from functions by their owners, as newer formats of rustdoc JSON
+        // have the same kind of items for both.
+        if let Some(owner) = owner {
+            let name = Self::name(item, krate_name)?;
+            // Required methods of traits are anchored apart from provided ones.
+            let anchor = match (owner, &item.inner) {
+                (Owner::Trait(..), types::ItemEnum::Method(m)) if !m.has_body => "tymethod",
+                _ => "method",
+            };
+            if let Some(l) = link.last_mut() {
+                *l = format!("{}#{}.{}", l, anchor, &name);
             }
-            // SAFETY: Already asserted at the beginning of this function.
-            _ => unreachable!(),
+            path.push(name);
+        } else if let Some(l) = link.last_mut() {
+            *l = format!("fn.{}.html", l);
         }
+        Ok((path, link))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
     use super::*;
+    use crate::compare::{Compare, GenericsScope};
     use crate::compare::{DiscreteSimilarity::*, Similarity::*};
-    use crate::query::{FnDecl, FnRetTy, Function};
+    use crate::query::{
+        parse::parse_query_in, Arena, FnDecl, FnRetTy, Function, PrimitiveType, QueryKind, Type,
+    };
 
     fn krate() -> types::Crate {
         types::Crate {
@@ -336,19 +716,26 @@ mod tests {
     #[test]
     fn compare_symbol() {
         let query = Query {
-            name: Some("foo".to_owned()),
+            name: Some("foo"),
             kind: None,
+            trait_: None,
+            self_ty: None,
+            attrs: &[],
+            weights: Default::default(),
+            kinds: Default::default(),
         };
 
         let function = foo();
         let item = item("foo".to_owned(), types::ItemEnum::Function(function));
         let krate = krate();
-        let mut generics = types::Generics::default();
+        let generics = GenericsScope::default();
         let mut substs = HashMap::default();
 
         assert_eq!(
-            query.compare(&item, &krate, &mut generics, &mut substs),
-            vec![Continuous(0.0)]
+            query
+                .compare(&item, &krate, &generics, &mut substs)
+                .unwrap(),
+            vec![Name(0.0)]
         )
     }
 
@@ -356,7 +743,7 @@ mod tests {
     fn compare_function() {
         let q = Function {
             decl: FnDecl {
-                inputs: Some(vec![]),
+                inputs: Some(&[]),
                 output: Some(FnRetTy::DefaultReturn),
             },
         };
@@ -364,12 +751,295 @@ mod tests {
         let i = foo();
 
         let krate = krate();
-        let mut generics = types::Generics::default();
+        let generics = GenericsScope::default();
         let mut substs = HashMap::default();
 
         assert_eq!(
-            q.compare(&i, &krate, &mut generics, &mut substs),
-            vec![Discrete(Equivalent), Discrete(Equivalent)]
+            q.compare(&FnItem::from(&i), &krate, &generics, &mut substs)
+                .unwrap(),
+            vec![Discrete(Equivalent), Output(0.0)]
         )
     }
+
+    #[test]
+    fn compare_never() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (&str) -> !").unwrap();
+        let q = match query.kind {
+            Some(QueryKind::FunctionQuery(ref q)) => q,
+            _ => unreachable!(),
+        };
+
+        // `fn foo(msg: &str) -> !`, which panics.
+        let mut i = foo();
+        i.decl.inputs = vec![(
+            "msg".to_owned(),
+            types::Type::BorrowedRef {
+                lifetime: None,
+                mutable: false,
+                type_: Box::new(types::Type::Primitive("str".to_owned())),
+            },
+        )];
+        i.decl.output = Some(types::Type::Primitive("never".to_owned()));
+
+        let krate = krate();
+        let generics = GenericsScope::default();
+        let sims = q
+            .compare(&FnItem::from(&i), &krate, &generics, &mut HashMap::new())
+            .unwrap();
+        assert_eq!(sims, vec![Discrete(Equivalent), Output(0.0)]);
+
+        i.decl.output = Some(types::Type::Primitive("str".to_owned()));
+        let sims = q
+            .compare(&FnItem::from(&i), &krate, &generics, &mut HashMap::new())
+            .unwrap();
+        assert_eq!(sims.last(), Some(&Output(1.0)));
+    }
+
+    #[test]
+    fn compare_bare_functions() {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (fn(i32) -> i32)").unwrap();
+        let q = match query.kind {
+            Some(QueryKind::FunctionQuery(ref q)) => q,
+            _ => unreachable!(),
+        };
+
+        // `fn foo(cb: extern "C" fn(i32) -> i32)`, which takes a callback.
+        let callback = |output| types::FunctionPointer {
+            decl: types::FnDecl {
+                inputs: vec![("x".to_owned(), types::Type::Primitive("i32".to_owned()))],
+                output,
+                c_variadic: false,
+            },
+            generic_params: vec![],
+            header: HashSet::new(),
+            abi: "C".to_owned(),
+        };
+        let mut i = foo();
+        i.decl.inputs = vec![(
+            "cb".to_owned(),
+            types::Type::FunctionPointer(Box::new(callback(Some(types::Type::Primitive(
+                "i32".to_owned(),
+            ))))),
+        )];
+
+        let krate = krate();
+        let generics = GenericsScope::default();
+        let sims = q
+            .compare(&FnItem::from(&i), &krate, &generics, &mut HashMap::new())
+            .unwrap();
+        assert!(sims.iter().all(|sim| sim.score() == 0.0), "{:?}", sims);
+
+        // Callbacks returning `()` rather than `i32`.
+        i.decl.inputs[0].1 = types::Type::FunctionPointer(Box::new(callback(None)));
+        let sims = q
+            .compare(&FnItem::from(&i), &krate, &generics, &mut HashMap::new())
+            .unwrap();
+        assert!(sims.iter().any(|sim| sim.score() > 0.0), "{:?}", sims);
+    }
+
+    #[test]
+    fn compare_newtypes() {
+        // `struct Meters(pub f64)` and `struct Wrapper<T>(pub T)`.
+        let mut krate = krate();
+        let mut newtype = |id: &str, field: types::Type, params: Vec<types::GenericParamDef>| {
+            let field_id = types::Id(format!("{}:field", id));
+            let mut field = item("0".to_owned(), types::ItemEnum::StructField(field));
+            field.id = field_id.clone();
+            krate.index.insert(field_id.clone(), field);
+            let mut newtype = item(
+                id.to_owned(),
+                types::ItemEnum::Struct(types::Struct {
+                    struct_type: types::StructType::Tuple,
+                    generics: types::Generics {
+                        params,
+                        where_predicates: vec![],
+                    },
+                    fields_stripped: false,
+                    fields: vec![field_id],
+                    impls: vec![],
+                }),
+            );
+            newtype.id = types::Id(id.to_owned());
+            krate.index.insert(newtype.id.clone(), newtype);
+        };
+        newtype("Meters", types::Type::Primitive("f64".to_owned()), vec![]);
+        newtype(
+            "Wrapper",
+            types::Type::Generic("T".to_owned()),
+            vec![types::GenericParamDef {
+                name: "T".to_owned(),
+                kind: types::GenericParamDefKind::Type {
+                    bounds: vec![],
+                    default: None,
+                },
+            }],
+        );
+        let path = |name: &str, args: Vec<types::Type>| types::Type::ResolvedPath {
+            name: name.to_owned(),
+            id: types::Id(name.to_owned()),
+            args: Some(Box::new(types::GenericArgs::AngleBracketed {
+                args: args.into_iter().map(types::GenericArg::Type).collect(),
+                bindings: vec![],
+            })),
+            param_names: vec![],
+        };
+
+        let compare = |q: &str, ty: types::Type, unwrap: bool| {
+            let arena = Arena::new();
+            let query = parse_query_in(&arena, q).unwrap();
+            let q = match query.kind {
+                Some(QueryKind::FunctionQuery(q)) => q,
+                _ => unreachable!(),
+            };
+            let mut i = foo();
+            i.decl.inputs = vec![("x".to_owned(), ty)];
+            let generics = GenericsScope::default().unwrap_newtypes(unwrap);
+            let sims = q
+                .compare(&FnItem::from(&i), &krate, &generics, &mut HashMap::new())
+                .unwrap();
+            // Similarities of the input alone, as the output is left out.
+            sims.into_iter()
+                .filter(|sim| !matches!(sim, Output(_)))
+                .collect::<Vec<_>>()
+        };
+
+        let meters = || path("Meters", vec![]);
+        assert_eq!(
+            compare("fn (f64)", meters(), false),
+            vec![Discrete(Different)]
+        );
+        assert_eq!(
+            compare("fn (f64)", meters(), true),
+            vec![Discrete(Equivalent), Discrete(Subequal)]
+        );
+        // Newtypes matching as they are aren't unwrapped.
+        assert_eq!(
+            compare("fn (Meters)", meters(), true),
+            vec![Continuous(0.0)]
+        );
+
+        let wrapper = path("Wrapper", vec![types::Type::Primitive("u8".to_owned())]);
+        assert_eq!(
+            compare("fn (u8)", wrapper, true),
+            vec![Discrete(Equivalent), Discrete(Subequal)]
+        );
+    }
+
+    #[test]
+    fn compare_unbound_self() {
+        let q = Function {
+            decl: FnDecl {
+                inputs: None,
+                output: Some(FnRetTy::Return(Type::Primitive(PrimitiveType::U32))),
+            },
+        };
+
+        // `fn foo() -> Self` outside of any impl, which only malformed JSON has.
+        let mut i = foo();
+        i.decl.output = Some(types::Type::Generic("Self".to_owned()));
+
+        let krate = krate();
+        let generics = GenericsScope::default();
+        let mut substs = HashMap::default();
+
+        assert!(matches!(
+            q.compare(&FnItem::from(&i), &krate, &generics, &mut substs),
+            Err(SearchError::UnboundSelf)
+        ))
+    }
+
+    #[test]
+    fn compare_generic_arities() {
+        let path = |name: &str, args: Vec<types::GenericArg>| types::Type::ResolvedPath {
+            name: name.to_owned(),
+            id: types::Id(format!("0:{}", name)),
+            args: Some(Box::new(types::GenericArgs::AngleBracketed {
+                args,
+                bindings: vec![],
+            })),
+            param_names: vec![],
+        };
+        let primitive =
+            |name: &str| types::GenericArg::Type(types::Type::Primitive(name.to_owned()));
+        let compare = |q: &str, i: &types::Type| {
+            let arena = Arena::new();
+            let q = match parse_query_in(&arena, &format!("uses {}", q)).unwrap().kind {
+                Some(QueryKind::UsesQuery(q)) => q,
+                _ => unreachable!(),
+            };
+            q.compare(i, &krate(), &GenericsScope::default(), &mut HashMap::new())
+                .unwrap()
+        };
+
+        // Trailing arguments left out of the same type are subequal.
+        let state = types::GenericArg::Type(path("RandomState", vec![]));
+        let map = path("HashMap", vec![primitive("u32"), primitive("bool"), state]);
+        assert_eq!(
+            compare("HashMap<u32, bool>", &map),
+            vec![
+                Continuous(0.0),
+                Discrete(Equivalent),
+                Discrete(Equivalent),
+                Discrete(Subequal)
+            ]
+        );
+        assert!(!compare("Vec<u32, bool>", &map).contains(&Discrete(Subequal)));
+
+        // Lifetimes aren't written in queries.
+        let cow = path(
+            "Cow",
+            vec![
+                types::GenericArg::Lifetime("'a".to_owned()),
+                primitive("str"),
+            ],
+        );
+        assert_eq!(
+            compare("Cow<str>", &cow),
+            vec![Continuous(0.0), Discrete(Equivalent)]
+        );
+    }
+
+    #[test]
+    fn serialize_similarities() {
+        let hit = Hit {
+            name: "foo".to_owned(),
+            kind: ItemKind::Function,
+            path: vec!["krate".to_owned(), "foo".to_owned()],
+            link: vec!["krate".to_owned(), "fn.foo.html".to_owned()],
+            signature: "fn foo()".to_owned(),
+            docs: None,
+            summary: None,
+            krate: "krate".to_owned(),
+            similarities: Similarities(vec![Discrete(Subequal), Continuous(0.5)]),
+            substitutions: BTreeMap::new(),
+            feature: None,
+            score: 0.375,
+            rank: Some(0),
+            scopes: vec![],
+        };
+
+        let json = serde_json::to_value(&hit).unwrap();
+        assert_eq!(
+            json["similarities"],
+            serde_json::json!([{ "discrete": "subequal" }, { "continuous": 0.5 }])
+        );
+        let hit: Hit = serde_json::from_value(json).unwrap();
+        assert_eq!(hit.similarities().score(), 0.375);
+    }
+
+    #[test]
+    fn parse_item_kinds() {
+        let kinds: ItemKinds = "method".parse().unwrap();
+        assert!(kinds.contains(ItemKind::Method));
+        assert!(!kinds.contains(ItemKind::Function));
+        assert_eq!(" method , function ".parse(), Ok(ItemKinds::default()));
+        assert_eq!("".parse(), Ok(ItemKinds::default()));
+        assert_eq!(ItemKinds::default().to_string(), "function,method");
+        assert_eq!(
+            "method,struct".parse::<ItemKinds>(),
+            Err(ParseItemKindsError("struct".to_owned()))
+        );
+    }
 }
@@ -0,0 +1,384 @@
+//! Rendering of function signatures from rustdoc types, e.g. `fn len(&self) -> usize`.
+
+use std::fmt::{self, Write};
+
+use rustdoc_types as types;
+
+use crate::function::FnItem;
+
+/// Render the signature of `item`, or `None` if it is not a function or a method.
+pub fn render(item: &types::Item) -> Option<String> {
+    let FnItem { decl, generics } = FnItem::of(&item.inner)?;
+
+    let mut s = String::new();
+    // Writing to `String` never fails.
+    let _ = write_fn(
+        &mut s,
+        item.name.as_deref().unwrap_or_default(),
+        decl,
+        generics,
+    );
+    Some(s)
+}
+
+fn write_fn(
+    w: &mut String,
+    name: &str,
+    decl: &types::FnDecl,
+    generics: &types::Generics,
+) -> fmt::Result {
+    write!(w, "fn {}", name)?;
+    write_generic_params(w, &generics.params)?;
+
+    w.push('(');
+    for (i, (arg, type_)) in decl.inputs.iter().enumerate() {
+        if i > 0 {
+            w.push_str(", ");
+        }
+        match (arg.as_str(), type_) {
+            ("self", types::Type::Generic(s)) if s == "Self" => w.push_str("self"),
+            (
+                "self",
+                types::Type::BorrowedRef {
+                    lifetime,
+                    mutable,
+                    type_,
+                },
+            ) if matches!(&**type_, types::Type::Generic(s) if s == "Self") => {
+                w.push('&');
+                if let Some(lifetime) = lifetime {
+                    write!(w, "{} ", lifetime)?;
+                }
+                if *mutable {
+                    w.push_str("mut ");
+                }
+                w.push_str("self");
+            }
+            _ => {
+                write!(w, "{}: ", arg)?;
+                write_type(w, type_)?;
+            }
+        }
+    }
+    if decl.c_variadic {
+        w.push_str(", ...");
+    }
+    w.push(')');
+
+    if let Some(output) = &decl.output {
+        w.push_str(" -> ");
+        write_type(w, output)?;
+    }
+
+    let predicates = generics
+        .where_predicates
+        .iter()
+        .filter(|pred| match pred {
+            // Synthesized by rustdoc for `impl Trait` in argument position.
+            types::WherePredicate::BoundPredicate {
+                ty: types::Type::Generic(g),
+                ..
+            } => !g.starts_with("impl "),
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    if !predicates.is_empty() {
+        w.push_str(" where ");
+        for (i, pred) in predicates.into_iter().enumerate() {
+            if i > 0 {
+                w.push_str(", ");
+            }
+            match pred {
+                types::WherePredicate::BoundPredicate { ty, bounds } => {
+                    write_type(w, ty)?;
+                    w.push_str(": ");
+                    write_bounds(w, bounds)?;
+                }
+                types::WherePredicate::RegionPredicate { lifetime, bounds } => {
+                    write!(w, "{}: ", lifetime)?;
+                    write_bounds(w, bounds)?;
+                }
+                types::WherePredicate::EqPredicate { lhs, rhs } => {
+                    write_type(w, lhs)?;
+                    w.push_str(" == ");
+                    write_type(w, rhs)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_generic_params(w: &mut String, params: &[types::GenericParamDef]) -> fmt::Result {
+    let params = params
+        .iter()
+        // Synthesized by rustdoc for `impl Trait` in argument position.
+        .filter(|param| !param.name.starts_with("impl "))
+        .collect::<Vec<_>>();
+    if params.is_empty() {
+        return Ok(());
+    }
+
+    w.push('<');
+    for (i, param) in params.into_iter().enumerate() {
+        if i > 0 {
+            w.push_str(", ");
+        }
+        match &param.kind {
+            types::GenericParamDefKind::Lifetime { outlives } => {
+                w.push_str(&param.name);
+                if !outlives.is_empty() {
+                    write!(w, ": {}", outlives.join(" + "))?;
+                }
+            }
+            types::GenericParamDefKind::Type { bounds, .. } => {
+                w.push_str(&param.name);
+                if !bounds.is_empty() {
+                    w.push_str(": ");
+                    write_bounds(w, bounds)?;
+                }
+            }
+            types::GenericParamDefKind::Const { ty, .. } => {
+                write!(w, "const {}: ", param.name)?;
+                write_type(w, ty)?;
+            }
+        }
+    }
+    w.push('>');
+
+    Ok(())
+}
+
+fn write_bounds(w: &mut String, bounds: &[types::GenericBound]) -> fmt::Result {
+    for (i, bound) in bounds.iter().enumerate() {
+        if i > 0 {
+            w.push_str(" + ");
+        }
+        match bound {
+            types::GenericBound::TraitBound {
+                trait_, modifier, ..
+            } => {
+                match modifier {
+                    types::TraitBoundModifier::None => {}
+                    types::TraitBoundModifier::Maybe => w.push('?'),
+                    types::TraitBoundModifier::MaybeConst => w.push_str("~const "),
+                }
+                write_type(w, trait_)?;
+            }
+            types::GenericBound::Outlives(lifetime) => w.push_str(lifetime),
+        }
+    }
+
+    Ok(())
+}
+
+fn write_type(w: &mut String, type_: &types::Type) -> fmt::Result {
+    use types::Type;
+
+    match type_ {
+        Type::ResolvedPath { name, args, .. } => {
+            w.push_str(name);
+            if let Some(args) = args {
+                write_generic_args(w, args)?;
+            }
+        }
+        Type::Generic(name) | Type::Primitive(name) => w.push_str(name),
+        Type::FunctionPointer(f) => {
+            w.push_str("fn(");
+            for (i, (_, type_)) in f.decl.inputs.iter().enumerate() {
+                if i > 0 {
+                    w.push_str(", ");
+                }
+                write_type(w, type_)?;
+            }
+            w.push(')');
+            if let Some(output) = &f.decl.output {
+                w.push_str(" -> ");
+                write_type(w, output)?;
+            }
+        }
+        Type::Tuple(types) => {
+            w.push('(');
+            for (i, type_) in types.iter().enumerate() {
+                if i > 0 {
+                    w.push_str(", ");
+                }
+                write_type(w, type_)?;
+            }
+            if types.len() == 1 {
+                w.push(',');
+            }
+            w.push(')');
+        }
+        Type::Slice(type_) => {
+            w.push('[');
+            write_type(w, type_)?;
+            w.push(']');
+        }
+        Type::Array { type_, len } => {
+            w.push('[');
+            write_type(w, type_)?;
+            write!(w, "; {}]", len)?;
+        }
+        Type::ImplTrait(bounds) => {
+            w.push_str("impl ");
+            write_bounds(w, bounds)?;
+        }
+        Type::Infer => w.push('_'),
+        Type::RawPointer { mutable, type_ } => {
+            w.push_str(if *mutable { "*mut " } else { "*const " });
+            write_type(w, type_)?;
+        }
+        Type::BorrowedRef {
+            lifetime,
+            mutable,
+            type_,
+        } => {
+            w.push('&');
+            if let Some(lifetime) = lifetime {
+                write!(w, "{} ", lifetime)?;
+            }
+            if *mutable {
+                w.push_str("mut ");
+            }
+            write_type(w, type_)?;
+        }
+        Type::QualifiedPath {
+            name,
+            self_type,
+            trait_,
+        } => {
+            w.push('<');
+            write_type(w, self_type)?;
+            w.push_str(" as ");
+            write_type(w, trait_)?;
+            write!(w, ">::{}", name)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_generic_args(w: &mut String, args: &types::GenericArgs) -> fmt::Result {
+    match args {
+        types::GenericArgs::AngleBracketed { args, bindings } => {
+            if args.is_empty() && bindings.is_empty() {
+                return Ok(());
+            }
+            w.push('<');
+            let mut first = true;
+            for arg in args {
+                if !std::mem::take(&mut first) {
+                    w.push_str(", ");
+                }
+                match arg {
+                    types::GenericArg::Lifetime(lifetime) => w.push_str(lifetime),
+                    types::GenericArg::Type(type_) => write_type(w, type_)?,
+                    types::GenericArg::Const(c) => w.push_str(&c.expr),
+                    types::GenericArg::Infer => w.push('_'),
+                }
+            }
+            for binding in bindings {
+                if !std::mem::take(&mut first) {
+                    w.push_str(", ");
+                }
+                w.push_str(&binding.name);
+                match &binding.binding {
+                    types::TypeBindingKind::Equality(type_) => {
+                        w.push_str(" = ");
+                        write_type(w, type_)?;
+                    }
+                    types::TypeBindingKind::Constraint(bounds) => {
+                        w.push_str(": ");
+                        write_bounds(w, bounds)?;
+                    }
+                }
+            }
+            w.push('>');
+        }
+        types::GenericArgs::Parenthesized { inputs, output } => {
+            w.push('(');
+            for (i, type_) in inputs.iter().enumerate() {
+                if i > 0 {
+                    w.push_str(", ");
+                }
+                write_type(w, type_)?;
+            }
+            w.push(')');
+            if let Some(output) = output {
+                w.push_str(" -> ");
+                write_type(w, output)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+
+    #[test]
+    fn render_method() {
+        let string = types::Type::ResolvedPath {
+            name: "String".to_owned(),
+            id: types::Id("0:1".to_owned()),
+            args: None,
+            param_names: vec![],
+        };
+        let option = types::Type::ResolvedPath {
+            name: "Option".to_owned(),
+            id: types::Id("0:2".to_owned()),
+            args: Some(Box::new(types::GenericArgs::AngleBracketed {
+                args: vec![types::GenericArg::Type(types::Type::BorrowedRef {
+                    lifetime: None,
+                    mutable: false,
+                    type_: Box::new(types::Type::Primitive("str".to_owned())),
+                })],
+                bindings: vec![],
+            })),
+            param_names: vec![],
+        };
+        let item = types::Item {
+            id: types::Id("0:0".to_owned()),
+            crate_id: 0,
+            name: Some("get".to_owned()),
+            span: None,
+            visibility: types::Visibility::Public,
+            docs: None,
+            links: HashMap::default(),
+            attrs: vec![],
+            deprecation: None,
+            inner: types::ItemEnum::Method(types::Method {
+                decl: types::FnDecl {
+                    inputs: vec![
+                        (
+                            "self".to_owned(),
+                            types::Type::BorrowedRef {
+                                lifetime: None,
+                                mutable: true,
+                                type_: Box::new(types::Type::Generic("Self".to_owned())),
+                            },
+                        ),
+                        ("key".to_owned(), string),
+                    ],
+                    output: Some(option),
+                    c_variadic: false,
+                },
+                generics: types::Generics::default(),
+                header: HashSet::default(),
+                abi: "\"Rust\"".to_owned(),
+                has_body: true,
+            }),
+        };
+
+        assert_eq!(
+            render(&item).as_deref(),
+            Some("fn get(&mut self, key: String) -> Option<&str>")
+        );
+    }
+}
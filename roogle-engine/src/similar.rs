@@ -0,0 +1,173 @@
+//! Reverse lookup of items with signatures similar to an existing one, e.g. to discover
+//! alternatives to `std::fs::read_to_string` across crates.
+
+use std::ops::ControlFlow;
+
+use rustdoc_types as types;
+
+use crate::{
+    function::FnItem,
+    query::{
+        Arena, Argument, FnDecl, FnRetTy, Function, GenericArg, GenericArgs, PrimitiveType, Query,
+        QueryKind, Type,
+    },
+    search::{self, Hit, Result, Scope, SearchError},
+    Index,
+};
+
+impl Index {
+    /// Search `scope` for functions and methods whose signatures are similar to the one of the
+    /// item at `path`, e.g. `std::fs::read_to_string`, leaving out the item itself.
+    ///
+    /// The item is looked up in every crate of the index, whether or not it is in `scope`.
+    pub fn find_similar_to(&self, path: &str, scope: Scope, threshold: f32) -> Result<Vec<Hit>> {
+        let arena = Arena::new();
+        let query = self
+            .query_of(&arena, path)?
+            .ok_or_else(|| SearchError::PathNotFound(path.to_owned()))?;
+
+        let mut hits = self.search(&query, scope, threshold)?;
+        hits.retain(|hit| hit.path.join("::") != path);
+        search::rank(&mut hits);
+        Ok(hits)
+    }
+
+    /// Query with the signature of the item at `path`, or `None` if no item is there.
+    fn query_of<'a>(&self, arena: &'a Arena, path: &str) -> Result<Option<Query<'a>>> {
+        let mut query = None;
+        let krates = Scope::Set(self.crates().keys().cloned().collect());
+        self.each_item(krates, None, None, |krate_name, krate, item, owner, _| {
+            // Items whose paths can't be computed can't be at `path` either.
+            match Self::path_and_link(krate, krate_name, item, owner) {
+                Ok((item_path, _)) if item_path.join("::") == path => {}
+                _ => return Ok(ControlFlow::Continue(())),
+            }
+            if let Some(f) = FnItem::of(&item.inner) {
+                query = Some(query_of_decl(arena, f.decl));
+            }
+            Ok(ControlFlow::Break(()))
+        })?;
+        Ok(query)
+    }
+}
+
+/// Query with the types of `decl`, where types which queries can't express are left as `_` and
+/// names of arguments are dropped, as they rarely match across crates.
+fn query_of_decl<'a>(arena: &'a Arena, decl: &types::FnDecl) -> Query<'a> {
+    Query {
+        name: None,
+        kind: Some(QueryKind::FunctionQuery(Function {
+            decl: decl_of(arena, decl),
+        })),
+        trait_: None,
+        self_ty: None,
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
+    }
+}
+
+/// Query declaration with the types of `decl`, leaving out names of arguments.
+fn decl_of<'a>(arena: &'a Arena, decl: &types::FnDecl) -> FnDecl<'a> {
+    let inputs = arena.alloc_slice_fill_iter(decl.inputs.iter().map(|(_, ty)| Argument {
+        ty: type_of(arena, ty),
+        name: None,
+    }));
+    let output = match decl.output {
+        Some(ref ty) => type_of(arena, ty).map(FnRetTy::Return),
+        None => Some(FnRetTy::DefaultReturn),
+    };
+
+    FnDecl {
+        inputs: Some(inputs),
+        output,
+    }
+}
+
+pub(crate) fn type_of<'a>(arena: &'a Arena, ty: &types::Type) -> Option<Type<'a>> {
+    use types::Type as T;
+
+    let ty = match ty {
+        T::ResolvedPath { name, args, .. } => {
+            let args = match args.as_deref() {
+                Some(types::GenericArgs::AngleBracketed { args, .. }) => {
+                    let args = arena.alloc_slice_fill_iter(args.iter().map(|arg| match arg {
+                        types::GenericArg::Type(ty) => type_of(arena, ty).map(GenericArg::Type),
+                        _ => None,
+                    }));
+                    Some(&*arena.alloc(GenericArgs::AngleBracketed { args }))
+                }
+                _ => None,
+            };
+            Type::UnresolvedPath {
+                // Paths are written as they are in the source, e.g. `io::Result`.
+                name: arena.alloc_str(name.rsplit("::").next().unwrap_or(name)),
+                args,
+            }
+        }
+        T::Generic(name) if name == "Self" => Type::SelfType,
+        T::Generic(name) => Type::Generic(arena.alloc_str(name)),
+        T::Primitive(name) => {
+            let primitive = PRIMITIVE_TYPES
+                .iter()
+                .find(|primitive| primitive.as_str() == name)?;
+            Type::Primitive(primitive.clone())
+        }
+        T::Tuple(types) => {
+            Type::Tuple(arena.alloc_slice_fill_iter(types.iter().map(|ty| type_of(arena, ty))))
+        }
+        // Queries have no arrays, whose closest are slices.
+        T::Slice(ty) | T::Array { type_: ty, .. } => {
+            Type::Slice(type_of(arena, ty).map(|ty| &*arena.alloc(ty)))
+        }
+        T::RawPointer { mutable, type_ } => Type::RawPointer {
+            mutable: *mutable,
+            type_: arena.alloc(type_of(arena, type_)?),
+        },
+        T::BorrowedRef { mutable, type_, .. } => Type::BorrowedRef {
+            mutable: *mutable,
+            type_: arena.alloc(type_of(arena, type_)?),
+        },
+        T::QualifiedPath {
+            name, self_type, ..
+        } => Type::QualifiedPath {
+            self_type: arena.alloc(type_of(arena, self_type)?),
+            name: arena.alloc_str(name),
+        },
+        T::FunctionPointer(f) => Type::BareFunction(arena.alloc(decl_of(arena, &f.decl))),
+        T::ImplTrait(_) | T::Infer => return None,
+    };
+    Some(ty)
+}
+
+const PRIMITIVE_TYPES: [PrimitiveType; 17] = {
+    use PrimitiveType::*;
+    [
+        Isize, I8, I16, I32, I64, I128, Usize, U8, U16, U32, U64, U128, F32, F64, Char, Bool, Str,
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::load_fixture;
+
+    #[test]
+    fn find_similar_functions() {
+        let index = load_fixture("mini_std");
+        let scope = || Scope::Crate("mini_std".to_owned());
+
+        let arena = Arena::new();
+        let query = index.query_of(&arena, "mini_std::mem::swap").unwrap();
+        assert_eq!(query.unwrap().canonicalize(), "fn (&mut T, &mut T) -> ()");
+
+        let hits = index
+            .find_similar_to("mini_std::cmp::max", scope(), 0.4)
+            .unwrap();
+        assert!(hits.iter().all(|hit| hit.name != "max"));
+        assert!(matches!(
+            index.find_similar_to("mini_std::cmp::min", scope(), 0.4),
+            Err(SearchError::PathNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+//! Snapshots of an [`Index`](crate::Index), archiving the exact crates it searches in one file so
+//! that it can be reproduced later.
+//!
+//! A snapshot is JSON of the form `{"manifest": {...}, "crates": {"<CRATE>": {...}}}`, where the
+//! manifest tells the format of the snapshot and lists each crate along with the checksum of its
+//! rustdoc JSON as written, which loading verifies.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use rustdoc_types::Crate;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use thiserror::Error;
+
+use crate::json::{from_json, on_large_stack};
+
+/// Version of the snapshot format this version of roogle writes and reads.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// What a snapshot consists of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of the snapshot format, see [`FORMAT_VERSION`].
+    pub format_version: u32,
+    /// Format version of the rustdoc JSON of the crates.
+    pub rustdoc_format_version: u32,
+    /// Version of roogle-engine which wrote the snapshot.
+    pub roogle_version: String,
+    /// Crates in the snapshot, sorted by their names.
+    pub crates: Vec<CrateEntry>,
+}
+
+/// A crate listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateEntry {
+    pub name: String,
+    pub version: Option<String>,
+    /// FNV-1a hash of the rustdoc JSON of the crate as written in the snapshot, in 16 hex digits.
+    pub checksum: String,
+}
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("failed to access the snapshot: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("snapshot is malformed: {0}")]
+    Malformed(#[from] serde_json::Error),
+
+    #[error("snapshot format version {0} is not supported (expected {FORMAT_VERSION})")]
+    UnsupportedFormat(u64),
+
+    #[error("crate `{0}` is listed in the manifest but missing")]
+    MissingCrate(String),
+
+    #[error("crate `{0}` is not listed in the manifest")]
+    UnlistedCrate(String),
+
+    #[error("checksum of crate `{0}` doesn't match the manifest")]
+    ChecksumMismatch(String),
+}
+
+#[derive(Serialize)]
+struct Written<'a> {
+    manifest: &'a Manifest,
+    crates: BTreeMap<&'a str, Box<RawValue>>,
+}
+
+#[derive(Deserialize)]
+struct Read<'a> {
+    /// Kept as a value until its format version is checked, as other versions may differ in shape.
+    manifest: serde_json::Value,
+    #[serde(borrow)]
+    crates: HashMap<String, &'a RawValue>,
+}
+
+/// Write a snapshot of `crates` into `w`, returning its manifest.
+///
+/// Crates are either owned or shared, like the ones of an [`Index`](crate::Index).
+pub fn write(
+    w: impl Write,
+    crates: &HashMap<String, impl Borrow<Crate>>,
+) -> Result<Manifest, SnapshotError> {
+    let crates = crates
+        .iter()
+        .map(|(name, krate)| {
+            let krate = krate.borrow();
+            Ok((name.as_str(), (krate, serde_json::to_string(krate)?)))
+        })
+        .collect::<Result<BTreeMap<_, _>, SnapshotError>>()?;
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        rustdoc_format_version: rustdoc_types::FORMAT_VERSION,
+        roogle_version: env!("CARGO_PKG_VERSION").to_owned(),
+        crates: crates
+            .iter()
+            .map(|(name, (krate, json))| CrateEntry {
+                name: (*name).to_owned(),
+                version: krate.crate_version.clone(),
+                checksum: checksum(json),
+            })
+            .collect(),
+    };
+    let crates = crates
+        .into_iter()
+        .map(|(name, (_, json))| Ok((name, RawValue::from_string(json)?)))
+        .collect::<Result<_, SnapshotError>>()?;
+    serde_json::to_writer(
+        w,
+        &Written {
+            manifest: &manifest,
+            crates,
+        },
+    )?;
+    Ok(manifest)
+}
+
+/// Read the snapshot `json`, failing unless its crates are exactly the ones its manifest lists.
+pub fn read(json: &str) -> Result<(Manifest, HashMap<String, Crate>), SnapshotError> {
+    let snapshot: Read<'_> = from_json(json)?;
+    let format_version = snapshot.manifest["format_version"].as_u64().unwrap_or(0);
+    if format_version != u64::from(FORMAT_VERSION) {
+        return Err(SnapshotError::UnsupportedFormat(format_version));
+    }
+    let manifest: Manifest = serde_json::from_value(snapshot.manifest)?;
+
+    if let Some(name) = snapshot
+        .crates
+        .keys()
+        .find(|name| !manifest.crates.iter().any(|entry| &entry.name == *name))
+    {
+        return Err(SnapshotError::UnlistedCrate(name.clone()));
+    }
+    let crates = manifest
+        .crates
+        .iter()
+        .map(|entry| {
+            let json = snapshot
+                .crates
+                .get(&entry.name)
+                .ok_or_else(|| SnapshotError::MissingCrate(entry.name.clone()))?
+                .get();
+            if checksum(json) != entry.checksum {
+                return Err(SnapshotError::ChecksumMismatch(entry.name.clone()));
+            }
+            Ok((entry.name.clone(), from_json(json)?))
+        })
+        .collect::<Result<_, SnapshotError>>()?;
+    Ok((manifest, crates))
+}
+
+/// Checksum of the contents of `krate`, which unlike the ones in manifests is the same however the
+/// maps in `krate` happen to be ordered.
+///
+/// Fails only if the thread serializing `krate` on a large stack can't be spawned.
+pub fn checksum_of(krate: &Crate) -> std::io::Result<String> {
+    // Objects of `serde_json::Value` are sorted by their keys. Crates always serialize, as the keys
+    // of their maps are all strings.
+    let json = on_large_stack(|| {
+        serde_json::to_value(krate)
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    })?;
+    Ok(checksum(&json))
+}
+
+/// 64-bit FNV-1a hash of `json`, which unlike `DefaultHasher` is the same across Rust versions.
+fn checksum(json: &str) -> String {
+    let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::load_fixtures;
+
+    fn snapshot() -> (Manifest, String) {
+        let index = load_fixtures(&["mini_std", "mini_ext"]);
+        let mut json = vec![];
+        let manifest = write(&mut json, index.crates()).unwrap();
+        (manifest, String::from_utf8(json).unwrap())
+    }
+
+    #[test]
+    fn read_written_snapshots() {
+        let (manifest, json) = snapshot();
+        assert_eq!(manifest.format_version, FORMAT_VERSION);
+        let names: Vec<_> = manifest.crates.iter().map(|entry| &entry.name).collect();
+        assert_eq!(names, ["mini_ext", "mini_std"]);
+
+        let (read_manifest, crates) = read(&json).unwrap();
+        assert_eq!(read_manifest, manifest);
+        assert_eq!(crates.len(), 2);
+        assert_eq!(
+            crates["mini_std"].root,
+            load_fixtures(&["mini_std"]).crates()["mini_std"].root
+        );
+    }
+
+    #[test]
+    fn reject_snapshots_not_matching_manifests() {
+        let (_, json) = snapshot();
+        // Crates are kept as written, so that only what `f` changes mismatches.
+        #[derive(Serialize, Deserialize)]
+        struct Snapshot {
+            manifest: serde_json::Value,
+            crates: BTreeMap<String, Box<RawValue>>,
+        }
+        let tampered = |f: fn(&mut Snapshot)| {
+            let mut snapshot: Snapshot = from_json(&json).unwrap();
+            f(&mut snapshot);
+            read(&serde_json::to_string(&snapshot).unwrap()).unwrap_err()
+        };
+
+        assert!(matches!(
+            tampered(|s| s.manifest["format_version"] = 2.into()),
+            SnapshotError::UnsupportedFormat(2)
+        ));
+        assert!(matches!(
+            tampered(|s| s.manifest["crates"][0]["checksum"] = "0".repeat(16).into()),
+            SnapshotError::ChecksumMismatch(name) if name == "mini_ext"
+        ));
+        assert!(matches!(
+            tampered(|s| {
+                s.crates.remove("mini_std");
+            }),
+            SnapshotError::MissingCrate(name) if name == "mini_std"
+        ));
+        assert!(matches!(
+            tampered(|s| {
+                s.manifest["crates"].as_array_mut().unwrap().pop();
+            }),
+            SnapshotError::UnlistedCrate(name) if name == "mini_std"
+        ));
+    }
+
+    #[test]
+    fn checksum_contents_of_crates() {
+        // Each load orders the maps in the crate anew.
+        let krate = || (*load_fixtures(&["mini_std"]).crates()["mini_std"]).clone();
+        let (a, mut b) = (krate(), krate());
+        assert_eq!(checksum_of(&a).unwrap(), checksum_of(&b).unwrap());
+
+        let id = b.index.keys().next().unwrap().clone();
+        b.index.remove(&id);
+        assert_ne!(checksum_of(&a).unwrap(), checksum_of(&b).unwrap());
+    }
+}
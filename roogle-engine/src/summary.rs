@@ -0,0 +1,98 @@
+//! Summaries of docs, i.e. their first sentences as plain text, so that hits can be listed
+//! without shipping whole docs along.
+
+/// Words ending with `.` which don't end sentences.
+const ABBREVIATIONS: &[&str] = &["e.g", "i.e", "etc", "vs", "cf"];
+
+/// First sentence of the first paragraph of `docs` with Markdown stripped, except for code spans
+/// which are kept as written, e.g. ``Returns the `len` of it.`` for
+/// ``Returns the [`len`](Self::len) of *it*. Panics if...``, or `None` if there is none.
+pub(crate) fn summary(docs: &str) -> Option<String> {
+    let paragraph = docs
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.trim_start_matches('#').trim_start())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut summary = String::new();
+    let mut chars = paragraph.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => {
+                // Code spans end with as many backticks as they start with.
+                let mut ticks = 1;
+                while chars.next_if_eq(&'`').is_some() {
+                    ticks += 1;
+                }
+                let fence = "`".repeat(ticks);
+                let mut span = fence.clone();
+                while let Some(c) = chars.next() {
+                    span.push(c);
+                    if span.len() > ticks && span.ends_with(&fence) {
+                        while chars.next_if_eq(&'`').is_some() {}
+                        break;
+                    }
+                }
+                summary.push_str(&span);
+            }
+            '\\' => summary.extend(chars.next()),
+            '*' | '[' => {}
+            ']' => {
+                // Targets of links, e.g. `(Self::len)` or `[len]`, which aren't part of the text.
+                let close = match chars.peek() {
+                    Some('(') => ')',
+                    Some('[') => ']',
+                    _ => continue,
+                };
+                for c in chars.by_ref() {
+                    if c == close {
+                        break;
+                    }
+                }
+            }
+            '.' | '!' | '?' if chars.peek().is_none_or(|c| c.is_whitespace()) => {
+                let word = summary.rsplit(' ').next().unwrap_or_default();
+                if c == '.' && ABBREVIATIONS.contains(&word) {
+                    summary.push(c);
+                    continue;
+                }
+                summary.push(c);
+                break;
+            }
+            _ => summary.push(c),
+        }
+    }
+
+    let summary = summary.trim();
+    (!summary.is_empty()).then(|| summary.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_docs() {
+        assert_eq!(
+            summary("Returns the [`len`](Self::len) of *it*. Panics if empty.").as_deref(),
+            Some("Returns the `len` of it.")
+        );
+        assert_eq!(
+            summary("\n# Converts\na slice of bytes to a [`str`][str], e.g. `b\"a.b\"`.\n\nMore.")
+                .as_deref(),
+            Some("Converts a slice of bytes to a `str`, e.g. `b\"a.b\"`.")
+        );
+        assert_eq!(
+            summary("Uses ``a ` b`` and [Vec] with \\*stars\\* **bold**").as_deref(),
+            Some("Uses ``a ` b`` and Vec with *stars* bold")
+        );
+        assert_eq!(
+            summary("Version 1.0 is out! Really.").as_deref(),
+            Some("Version 1.0 is out!")
+        );
+        assert_eq!(summary(" \n\n "), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! Templates of queries, i.e. the most common shapes of signatures in the index like
+//! `fn (&Self) -> T`, which frontends can offer new users as queries to start from.
+
+use std::{collections::HashMap, ops::ControlFlow};
+
+use rustdoc_types as types;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    function::FnItem,
+    query::{
+        Arena, Argument, FnDecl, FnRetTy, Function, GenericArg, GenericArgs, PrimitiveType, Query,
+        QueryKind, Type,
+    },
+    search::{Result, Scope},
+    similar::type_of,
+    Index,
+};
+
+/// Generic types which the types of shapes are named after in order, beyond which types are `_`.
+const GENERICS: [&str; 10] = ["T", "U", "V", "W", "X", "Y", "Z", "A", "B", "C"];
+
+/// A shape of signatures along with how many functions and methods are of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Template {
+    /// Query of the shape, e.g. `fn (&Self) -> T`.
+    pub query: String,
+    pub count: usize,
+}
+
+impl Index {
+    /// The `limit` most common shapes of signatures of functions and methods in `scope`, from the
+    /// most common one.
+    ///
+    /// Shapes keep `Self`, references, tuples, slices and paths with generic arguments like
+    /// `Option<T>`, while other types are generic types named in order of appearance, the same
+    /// type by the same name, e.g. `fn (T, T) -> Option<U>`.
+    pub fn templates(&self, scope: Scope, limit: usize) -> Result<Vec<Template>> {
+        let mut counts = HashMap::<String, usize>::new();
+        let mut arena = Arena::new();
+        self.each_item(scope, None, None, |_, _, item, _, _| {
+            if let Some(f) = FnItem::of(&item.inner) {
+                arena.reset();
+                *counts
+                    .entry(shape_of(&arena, f.decl).canonicalize())
+                    .or_default() += 1;
+            }
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        let mut templates = counts
+            .into_iter()
+            .map(|(query, count)| Template { query, count })
+            .collect::<Vec<_>>();
+        templates
+            .sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.query.cmp(&b.query)));
+        templates.truncate(limit);
+        Ok(templates)
+    }
+}
+
+/// Query of the shape of `decl`.
+fn shape_of<'a>(arena: &'a Arena, decl: &types::FnDecl) -> Query<'a> {
+    let mut shaper = Shaper {
+        arena,
+        types: vec![],
+    };
+    let inputs = decl
+        .inputs
+        .iter()
+        .map(|(_, ty)| Argument {
+            ty: type_of(arena, ty).and_then(|ty| shaper.shape(&ty)),
+            name: None,
+        })
+        .collect::<Vec<_>>();
+    let output = match decl.output {
+        Some(ref ty) => type_of(arena, ty)
+            .and_then(|ty| shaper.shape(&ty))
+            .map(FnRetTy::Return),
+        None => Some(FnRetTy::DefaultReturn),
+    };
+
+    Query {
+        name: None,
+        kind: Some(QueryKind::FunctionQuery(Function {
+            decl: FnDecl {
+                inputs: Some(arena.alloc_slice_fill_iter(inputs)),
+                output,
+            },
+        })),
+        trait_: None,
+        self_ty: None,
+        attrs: &[],
+        weights: Default::default(),
+        kinds: Default::default(),
+    }
+}
+
+/// Replaces types in a signature by generic types, remembering which type each one replaced.
+struct Shaper<'a> {
+    arena: &'a Arena,
+    /// Types replaced so far, as written, in the order of [`GENERICS`].
+    types: Vec<String>,
+}
+
+impl<'a> Shaper<'a> {
+    fn shape(&mut self, ty: &Type<'a>) -> Option<Type<'a>> {
+        let shape = match ty {
+            Type::UnresolvedPath {
+                name,
+                args: Some(GenericArgs::AngleBracketed { args }),
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Some(GenericArg::Type(ty)) => self.shape(ty).map(GenericArg::Type),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                Type::UnresolvedPath {
+                    name,
+                    args: Some(self.arena.alloc(GenericArgs::AngleBracketed {
+                        args: self.arena.alloc_slice_fill_iter(args),
+                    })),
+                }
+            }
+            Type::Tuple(types) => {
+                let types = types
+                    .iter()
+                    .map(|ty| ty.as_ref().and_then(|ty| self.shape(ty)))
+                    .collect::<Vec<_>>();
+                Type::Tuple(self.arena.alloc_slice_fill_iter(types))
+            }
+            Type::Slice(ty) => Type::Slice(
+                ty.and_then(|ty| self.shape(ty))
+                    .map(|ty| &*self.arena.alloc(ty)),
+            ),
+            Type::RawPointer { mutable, type_ } => Type::RawPointer {
+                mutable: *mutable,
+                type_: self.arena.alloc(self.shape(type_)?),
+            },
+            Type::BorrowedRef { mutable, type_ } => Type::BorrowedRef {
+                mutable: *mutable,
+                type_: self.arena.alloc(self.shape(type_)?),
+            },
+            Type::SelfType
+            | Type::Never
+            | Type::Primitive(PrimitiveType::Unit | PrimitiveType::Never) => ty.clone(),
+            _ => {
+                let written = ty.to_string();
+                let i = match self.types.iter().position(|ty| *ty == written) {
+                    Some(i) => i,
+                    None => {
+                        self.types.push(written);
+                        self.types.len() - 1
+                    }
+                };
+                Type::Generic(GENERICS.get(i)?)
+            }
+        };
+        Some(shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{query::parse::parse_query_in, testing::load_fixture};
+
+    #[test]
+    fn count_shapes_of_signatures() {
+        let index = load_fixture("mini_std");
+        let templates = index
+            .templates(Scope::Crate("mini_std".to_owned()), usize::MAX)
+            .unwrap();
+
+        assert!(templates.windows(2).all(|w| w[0].count >= w[1].count));
+        let len = templates
+            .iter()
+            .find(|template| template.query == "fn (&Self) -> T")
+            .unwrap();
+        assert!(len.count >= 2);
+        // Templates are queries as canonical as any other.
+        let arena = Arena::new();
+        for template in &templates {
+            let query = parse_query_in(&arena, &template.query).unwrap();
+            assert_eq!(query.canonicalize(), template.query);
+        }
+
+        let top = index
+            .templates(Scope::Crate("mini_std".to_owned()), 3)
+            .unwrap();
+        assert_eq!(top, templates[..3]);
+    }
+}
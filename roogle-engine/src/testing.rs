@@ -0,0 +1,49 @@
+//! Helpers for tests, searching the fixtures checked in under `fixtures/` instead of crates built
+//! by rustdoc.
+//!
+//! Fixtures are shaken rustdoc JSON of format version 9:
+//! - `mini_std`: functions, inherent methods of `Vec`, `Option` and `str`, `Clone for Vec`,
+//!   `IntoIter` with its `Iterator` impl, and `io::read` returning the `io::Result` alias, some
+//!   of them with `#[must_use]`, `#[inline]` or features
+//! - `mini_ext`: `Thing` implementing `Clone` of `mini_std`, getting `clone_from` from it
+//!
+//! `fixtures/ranking-tests/` holds golden rankings of queries over them, which `tests/ranking.rs`
+//! checks.
+//!
+//! It's only built for tests, or with the `testing` feature for tests of other crates, so that the
+//! published library doesn't ship it.
+
+use std::path::Path;
+
+use rustdoc_types::Crate;
+
+use crate::Index;
+
+/// Load the fixture `name` into an index, as a crate of the same name.
+///
+/// Panics if the fixture doesn't exist or is malformed, as it's only meant for tests.
+pub fn load_fixture(name: &str) -> Index {
+    load_fixtures(&[name])
+}
+
+/// Load the fixtures `names` into an index together, each as a crate of the same name.
+///
+/// Panics if a fixture doesn't exist or is malformed, as it's only meant for tests.
+pub fn load_fixtures(names: &[&str]) -> Index {
+    Index::new(
+        names
+            .iter()
+            .map(|&name| (name.to_owned(), read(name)))
+            .collect(),
+    )
+}
+
+fn read(name: &str) -> Crate {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(format!("{}.json", name));
+    let json = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read fixture `{}`: {}", path.display(), e));
+    serde_json::from_str(&json)
+        .unwrap_or_else(|e| panic!("failed to deserialize fixture `{}`: {}", name, e))
+}
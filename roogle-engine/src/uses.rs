@@ -0,0 +1,198 @@
+//! "Uses of type" search, finding every function and method mentioning a type anywhere in its
+//! signature, e.g. with `uses Duration`, to explore the API surface around the type.
+
+use std::{collections::HashMap, ops::ControlFlow};
+
+use rustdoc_types as types;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compare::{Compare, GenericsScope, Similarities},
+    function::FnItem,
+    query::Type,
+    search::{Hit, Result, Scope},
+    Index,
+};
+
+/// Where a type is mentioned in a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// `self` of a method.
+    Receiver,
+    /// Any other argument.
+    Input,
+    Output,
+}
+
+/// Functions and methods mentioning a type, grouped by where they mention it.
+///
+/// An item mentioning the type in several places, e.g. both taking and returning it, is in each of
+/// their groups. Each group is sorted by paths.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Uses {
+    pub receiver: Vec<Hit>,
+    pub input: Vec<Hit>,
+    pub output: Vec<Hit>,
+}
+
+impl Index {
+    /// Find every function and method in `scope` mentioning `ty` in its signature.
+    pub fn uses(&self, ty: &Type, scope: Scope) -> Result<Uses> {
+        let mut uses = Uses::default();
+        self.each_item(
+            scope,
+            None,
+            None,
+            |krate_name, krate, item, owner, metadata| {
+                let f = match FnItem::of(&item.inner) {
+                    Some(f) => f,
+                    None => return Ok(ControlFlow::Continue(())),
+                };
+                let generics = owner
+                    .map(GenericsScope::of_owner)
+                    .unwrap_or_default()
+                    .trait_impls(&self.trait_impls);
+                for role in roles(ty, f, krate, &generics.with(f.generics))? {
+                    let hit = Self::hit(
+                        krate,
+                        krate_name,
+                        item,
+                        owner,
+                        metadata,
+                        Similarities::default(),
+                    )?;
+                    match role {
+                        Role::Receiver => uses.receiver.push(hit),
+                        Role::Input => uses.input.push(hit),
+                        Role::Output => uses.output.push(hit),
+                    }
+                }
+                Ok(ControlFlow::Continue(()))
+            },
+        )?;
+
+        for hits in [&mut uses.receiver, &mut uses.input, &mut uses.output] {
+            hits.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        }
+        Ok(uses)
+    }
+}
+
+/// Roles in which `f` mentions `ty`, in the order of [`Role`] and without duplicates.
+pub(crate) fn roles(
+    ty: &Type,
+    f: FnItem<'_>,
+    krate: &types::Crate,
+    generics: &GenericsScope<'_>,
+) -> Result<Vec<Role>> {
+    let mut roles = vec![];
+    let mut mention = |role, i: &types::Type| -> Result<()> {
+        if !roles.contains(&role) && mentions(ty, i, krate, generics)? {
+            roles.push(role);
+        }
+        Ok(())
+    };
+
+    for (name, i) in &f.decl.inputs {
+        let role = if name == "self" {
+            Role::Receiver
+        } else {
+            Role::Input
+        };
+        mention(role, i)?;
+    }
+    if let Some(ref i) = f.decl.output {
+        mention(Role::Output, i)?;
+    }
+
+    roles.sort_unstable_by_key(|role| *role as u8);
+    Ok(roles)
+}
+
+/// Whether `ty` is equivalent to `i` or any type nested in it, e.g. `Duration` in
+/// `Option<&Duration>`.
+fn mentions(
+    ty: &Type,
+    i: &types::Type,
+    krate: &types::Crate,
+    generics: &GenericsScope<'_>,
+) -> Result<bool> {
+    use types::Type as T;
+
+    // Generic types of items bind to anything, so they don't count as mentions.
+    let sims = Similarities(ty.compare(i, krate, generics, &mut HashMap::new())?);
+    if !sims.is_empty() && sims.score() == 0.0 {
+        return Ok(true);
+    }
+
+    let nested: Vec<&types::Type> = match i {
+        T::ResolvedPath { args, .. } => match args.as_deref() {
+            Some(types::GenericArgs::AngleBracketed { args, .. }) => args
+                .iter()
+                .filter_map(|arg| match arg {
+                    types::GenericArg::Type(t) => Some(t),
+                    _ => None,
+                })
+                .collect(),
+            Some(types::GenericArgs::Parenthesized { inputs, output }) => {
+                inputs.iter().chain(output).collect()
+            }
+            None => vec![],
+        },
+        T::Tuple(types) => types.iter().collect(),
+        T::Slice(t) | T::Array { type_: t, .. } => vec![t],
+        T::RawPointer { type_, .. } | T::BorrowedRef { type_, .. } => vec![type_],
+        T::QualifiedPath { self_type, .. } => vec![self_type],
+        T::FunctionPointer(f) => f
+            .decl
+            .inputs
+            .iter()
+            .map(|(_, t)| t)
+            .chain(&f.decl.output)
+            .collect(),
+        T::Generic(_) | T::Primitive(_) | T::ImplTrait(_) | T::Infer => vec![],
+    };
+    for i in nested {
+        if mentions(ty, i, krate, generics)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        query::{parse::parse_query_in, Arena, QueryKind},
+        testing::load_fixture,
+    };
+
+    #[test]
+    fn uses_of_type() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "uses Vec").unwrap();
+        let Some(QueryKind::UsesQuery(ref ty)) = query.kind else {
+            panic!("`uses Vec` is not a uses query");
+        };
+
+        let uses = index.uses(ty, Scope::Crate("mini_std".to_owned())).unwrap();
+        let names = |hits: &[Hit]| hits.iter().map(|hit| hit.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&uses.receiver), vec!["len", "push"]);
+        assert!(uses.input.is_empty());
+        assert_eq!(
+            names(&uses.output),
+            vec!["collect_vec", "new", "with_capacity"]
+        );
+
+        // Searching with the query finds the same items, but not grouped.
+        let hits = index
+            .search(&query, Scope::Crate("mini_std".to_owned()), 0.4)
+            .unwrap();
+        assert_eq!(hits.len(), 5);
+    }
+}
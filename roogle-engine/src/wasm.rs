@@ -0,0 +1,48 @@
+//! Bindings for JavaScript, enabling search entirely on the client side, e.g. in docs sites.
+//!
+//! Build with `wasm-pack build roogle-engine --features wasm`.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    json::from_json,
+    query::{parse::parse_query_in, Arena},
+    search::Scope,
+    Index,
+};
+
+/// Upper bound of scores for hits, the same as the default of the command line.
+const THRESHOLD: f32 = 0.4;
+
+/// An index loaded once and searched many times.
+#[wasm_bindgen]
+pub struct SearchIndex(Index);
+
+#[wasm_bindgen]
+impl SearchIndex {
+    /// Load `index_bytes`, a JSON object mapping names of crates to their rustdoc JSON.
+    #[wasm_bindgen(constructor)]
+    pub fn new(index_bytes: &[u8]) -> Result<SearchIndex, JsError> {
+        let crates: HashMap<String, rustdoc_types::Crate> =
+            from_json(std::str::from_utf8(index_bytes)?)?;
+        Ok(SearchIndex(Index::new(crates)))
+    }
+
+    /// Search all crates in the index for `query`, returning an array of hits sorted by
+    /// their scores.
+    pub fn search(&self, query: &str) -> Result<JsValue, JsError> {
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, query)?;
+        let scope = Scope::Set(self.0.crates().keys().cloned().collect());
+        let hits = self.0.search(&query, scope, THRESHOLD)?;
+        Ok(serde_wasm_bindgen::to_value(&hits)?)
+    }
+}
+
+/// Search `index_bytes` for `query` at once; see [`SearchIndex`] to search an index repeatedly.
+#[wasm_bindgen]
+pub fn search(index_bytes: &[u8], query: &str) -> Result<JsValue, JsError> {
+    SearchIndex::new(index_bytes)?.search(query)
+}
@@ -0,0 +1,60 @@
+use roogle_engine::{
+    query::{parse::parse_query_in, Arena},
+    search::{Hit, Scope},
+    testing::{load_fixture, load_fixtures},
+    Index,
+};
+
+fn search(index: &Index, query: &str) -> Vec<Hit> {
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, query).unwrap();
+    index
+        .search(&query, Scope::Crate("mini_ext".to_owned()), 0.4)
+        .unwrap()
+}
+
+#[test]
+fn resolve_provided_methods_of_traits_in_other_crates() {
+    let index = load_fixtures(&["mini_std", "mini_ext"]);
+    let hits = search(&index, "in Clone fn (&mut Thing, &Thing)");
+    let hit = hits
+        .iter()
+        .find(|hit| hit.name == "clone_from")
+        .expect("`clone_from` should be resolved from `mini_std`");
+    assert_eq!(hit.path.join("::"), "mini_std::clone::Clone::clone_from");
+    assert_eq!(hit.similarities().score(), 0.0);
+    assert_eq!(hit.signature, "fn clone_from(&mut self, source: &Self)");
+    assert_eq!(
+        hit.docs.as_deref(),
+        Some("Performs copy-assignment from `source`.")
+    );
+
+    // Methods defined in the impl are still found as they were.
+    let hits = search(&index, "in Clone fn (&Thing) -> Thing");
+    assert_eq!(hits[0].name, "clone");
+}
+
+#[test]
+fn skip_provided_methods_of_traits_not_indexed() {
+    let index = load_fixture("mini_ext");
+    let hits = search(&index, "in Clone fn (&mut Thing, &Thing)");
+    assert!(hits.iter().all(|hit| hit.name != "clone_from"));
+}
+
+#[test]
+fn find_other_crates_with_hits() {
+    let index = load_fixtures(&["mini_std", "mini_ext"]);
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, "fn (&[u8]) -> Result<&str, Utf8Error>").unwrap();
+    assert!(search(&index, "fn (&[u8]) -> Result<&str, Utf8Error>").is_empty());
+    assert_eq!(
+        index
+            .other_crates_with_hits(&query, Scope::Crate("mini_ext".to_owned()), 0.4)
+            .unwrap(),
+        vec!["mini_std"]
+    );
+    assert!(index
+        .other_crates_with_hits(&query, Scope::Crate("mini_std".to_owned()), 0.4)
+        .unwrap()
+        .is_empty());
+}
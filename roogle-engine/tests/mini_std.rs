@@ -0,0 +1,136 @@
+use std::ops::ControlFlow;
+
+use roogle_engine::{
+    query::{parse::parse_query_in, Arena},
+    search::{Hit, Scope},
+    testing::load_fixture,
+};
+
+fn search(query: &str) -> Vec<Hit> {
+    let index = load_fixture("mini_std");
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, query).unwrap();
+    index
+        .search(&query, Scope::Crate("mini_std".to_owned()), 0.4)
+        .unwrap()
+}
+
+fn paths(hits: &[Hit]) -> Vec<String> {
+    hits.iter().map(|hit| hit.path.join("::")).collect()
+}
+
+#[test]
+fn rank_exact_match_first() {
+    let hits = search("fn (&[u8]) -> Result<&str, Utf8Error>");
+    assert_eq!(paths(&hits)[0], "mini_std::str::from_utf8");
+    assert_eq!(hits[0].similarities().score(), 0.0);
+}
+
+#[test]
+fn bind_self_of_methods() {
+    // Methods of primitives are documented outside of crates.
+    let hits = search("fn (&str) -> usize");
+    assert_eq!(paths(&hits)[0], "str::len");
+
+    let hits = search("fn (Option<T>, T) -> T");
+    assert_eq!(paths(&hits)[0], "mini_std::option::Option::unwrap_or");
+}
+
+#[test]
+fn link_to_documentation() {
+    let hits = search("fn (&[u8]) -> Result<&str, Utf8Error>");
+    assert_eq!(hits[0].link, vec!["mini_std", "str", "fn.from_utf8.html"]);
+
+    let hits = search("fn (&mut Vec<T>, T)");
+    let push = hits.iter().find(|hit| hit.name == "push").unwrap();
+    assert_eq!(
+        push.link,
+        vec!["mini_std", "vec", "struct.Vec.html#method.push"]
+    );
+    assert_eq!(push.signature, "fn push(&mut self, value: T)");
+}
+
+#[test]
+fn skip_trait_methods() {
+    let index = load_fixture("mini_std");
+    let mut names = vec![];
+    index
+        .for_each_candidate(Scope::Crate("mini_std".to_owned()), |item, _| {
+            names.push(item.name.clone().unwrap_or_default());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    names.sort();
+
+    assert_eq!(
+        names,
+        vec![
+            "collect_vec",
+            "from_utf8",
+            "identity",
+            "is_some",
+            "len",
+            "len",
+            "max",
+            "new",
+            "push",
+            "read",
+            "swap",
+            "trim",
+            "unwrap_or",
+            "with_capacity",
+        ]
+    );
+}
+
+#[test]
+fn search_impls_of_types() {
+    let hits = search("Vec<u8>::");
+    assert_eq!(
+        paths(&hits),
+        vec![
+            "mini_std::clone::Clone::clone",
+            "mini_std::vec::Vec::len",
+            "mini_std::vec::Vec::new",
+            "mini_std::vec::Vec::push",
+            "mini_std::vec::Vec::with_capacity",
+        ]
+    );
+
+    assert_eq!(paths(&search("Vec::psh")), vec!["mini_std::vec::Vec::push"]);
+    let hits = search("str::fn (&self) -> usize");
+    assert_eq!(paths(&hits)[0], "str::len");
+    assert!(paths(&hits).iter().all(|path| path.starts_with("str::")));
+}
+
+#[test]
+fn rank_hits_by_scores() {
+    let hits = search("fn (&self) -> usize");
+    assert!(hits.len() > 1);
+    for (rank, pair) in hits.windows(2).enumerate() {
+        assert!(pair[0].score <= pair[1].score);
+        assert_eq!(pair[0].rank, Some(rank));
+    }
+    assert_eq!(hits[0].score, hits[0].similarities().score());
+
+    // Scores and ranks are serialized so that hits of separate requests can be merged.
+    let json = serde_json::to_value(&hits[1]).unwrap();
+    assert_eq!(json["rank"], 1);
+    assert!(json["score"].is_number());
+}
+
+#[test]
+fn keep_generics_of_typedefs_out_of_substitutions() {
+    // `io::Result<T>` is expanded into `Result<T, io::Error>`, whose `T` isn't a generic of `read`.
+    let hits = search("fn (&mut [u8]) -> Result<&str, Error>");
+    let read = hits.iter().find(|hit| hit.name == "read").unwrap();
+    assert!(read.substitutions.is_empty(), "{:?}", read.substitutions);
+}
+
+#[test]
+fn match_concrete_types_with_impl_trait() {
+    // `collect_vec` takes `IntoIter<T>` itself, which implements `Iterator<Item = T>`.
+    let hits = search("fn (impl Iterator<Item = u8>) -> Vec<u8>");
+    assert_eq!(paths(&hits)[0], "mini_std::vec::IntoIter::collect_vec");
+    assert_eq!(hits[0].substitutions["T"], "u8");
+}
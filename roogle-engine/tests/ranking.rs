@@ -0,0 +1,455 @@
+//! Invariants of scores which should hold however scoring is tuned, and golden rankings which
+//! show how tuning it changes hits.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use proptest::prelude::*;
+use rustdoc_types as types;
+
+use roogle_engine::{
+    compare::{Similarities, Weights},
+    matcher::{DefaultMatcher, Matcher},
+    metadata::TraitImpls,
+    query::{parse::parse_query_in, Arena, Query},
+    search::{Hit, Scope},
+    testing::{load_fixture, load_fixtures},
+};
+
+const PRIMITIVES: &[&str] = &["u8", "u32", "usize", "i64", "f64", "bool", "char", "str"];
+
+/// Types without generics, which are the same however they are bound.
+fn ty() -> impl Strategy<Value = types::Type> {
+    let leaf = prop::sample::select(PRIMITIVES).prop_map(|p| types::Type::Primitive(p.to_owned()));
+    leaf.prop_recursive(3, 8, 1, |inner| {
+        prop_oneof![
+            (any::<bool>(), inner.clone()).prop_map(|(mutable, ty)| types::Type::BorrowedRef {
+                lifetime: None,
+                mutable,
+                type_: Box::new(ty),
+            }),
+            inner
+                .clone()
+                .prop_map(|ty| types::Type::Slice(Box::new(ty))),
+            inner.prop_map(|ty| types::Type::ResolvedPath {
+                name: "Option".to_owned(),
+                id: types::Id("0:1".to_owned()),
+                args: Some(Box::new(types::GenericArgs::AngleBracketed {
+                    args: vec![types::GenericArg::Type(ty)],
+                    bindings: vec![],
+                })),
+                param_names: vec![],
+            }),
+        ]
+    })
+}
+
+fn decl() -> impl Strategy<Value = types::FnDecl> {
+    (prop::collection::vec(ty(), 0..4), prop::option::of(ty())).prop_map(|(inputs, output)| {
+        types::FnDecl {
+            inputs: inputs
+                .into_iter()
+                .enumerate()
+                .map(|(i, ty)| (format!("x{}", i), ty))
+                .collect(),
+            output,
+            c_variadic: false,
+        }
+    })
+}
+
+/// Render `ty` in the syntax of queries.
+fn render(ty: &types::Type) -> String {
+    match ty {
+        types::Type::Primitive(p) => p.clone(),
+        types::Type::BorrowedRef {
+            mutable: true,
+            type_,
+            ..
+        } => format!("&mut {}", render(type_)),
+        types::Type::BorrowedRef { type_, .. } => format!("&{}", render(type_)),
+        types::Type::Slice(ty) => format!("[{}]", render(ty)),
+        types::Type::ResolvedPath {
+            name,
+            args: Some(args),
+            ..
+        } => match &**args {
+            types::GenericArgs::AngleBracketed { args, .. } => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        types::GenericArg::Type(ty) => render(ty),
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                format!("{}<{}>", name, args.join(", "))
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Query of functions whose inputs are `inputs` and output is `output`.
+fn query(inputs: &[types::Type], output: &Option<types::Type>) -> String {
+    let inputs = inputs.iter().map(render).collect::<Vec<_>>().join(", ");
+    with_output(inputs, output)
+}
+
+/// [`query`] with inputs named `{prefix}0`, `{prefix}1` and so on.
+fn named_query(inputs: &[types::Type], output: &Option<types::Type>, prefix: &str) -> String {
+    let inputs = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("{}{}: {}", prefix, i, render(ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    with_output(inputs, output)
+}
+
+fn with_output(inputs: String, output: &Option<types::Type>) -> String {
+    match output {
+        Some(output) => format!("fn ({}) -> {}", inputs, render(output)),
+        None => format!("fn ({}) -> ()", inputs),
+    }
+}
+
+/// Score of `query` against a function `f` declared as `decl`.
+fn score(query: &str, decl: &types::FnDecl) -> f32 {
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, query).unwrap();
+    similarities(&query, "f", decl).score()
+}
+
+/// Similarities of `query` to a function `name` declared as `decl`.
+fn similarities(query: &Query, name: &str, decl: &types::FnDecl) -> Similarities {
+    let item = types::Item {
+        id: types::Id("0:0".to_owned()),
+        crate_id: 0,
+        name: Some(name.to_owned()),
+        span: None,
+        visibility: types::Visibility::Public,
+        docs: None,
+        links: HashMap::new(),
+        attrs: vec![],
+        deprecation: None,
+        inner: types::ItemEnum::Function(types::Function {
+            decl: decl.clone(),
+            generics: types::Generics::default(),
+            header: HashSet::new(),
+            abi: "Rust".to_owned(),
+        }),
+    };
+    let krate = types::Crate {
+        root: types::Id("0:0".to_owned()),
+        crate_version: None,
+        includes_private: false,
+        index: HashMap::new(),
+        paths: HashMap::new(),
+        external_crates: HashMap::new(),
+        format_version: types::FORMAT_VERSION,
+    };
+    DefaultMatcher::default()
+        .compare(query, &item, &krate, "krate", None, &TraitImpls::default())
+        .unwrap()
+}
+
+fn inputs(decl: &types::FnDecl) -> Vec<types::Type> {
+    decl.inputs.iter().map(|(_, ty)| ty.clone()).collect()
+}
+
+/// Top hits of representative queries, optionally named, in `mini_std`.
+///
+/// Naming a query or its arguments must not move items of the closest signatures down. The
+/// threshold is loose so that runners-up are ranked too.
+#[test]
+fn ranking_contract() {
+    let cases: &[(&str, Option<&str>, &[&str])] = &[
+        (
+            "fn (&[u8]) -> Result<&str, Utf8Error>",
+            None,
+            &["mini_std::str::from_utf8", "mini_std::io::read"],
+        ),
+        (
+            "fn (&[u8]) -> Result<&str, Utf8Error>",
+            Some("read"),
+            &["mini_std::str::from_utf8", "mini_std::io::read"],
+        ),
+        ("fn (&mut T, &mut T)", None, &["mini_std::mem::swap"]),
+        ("fn (&mut T, &mut T)", Some("max"), &["mini_std::mem::swap"]),
+        ("fn (T, T) -> T", None, &["mini_std::cmp::max"]),
+        ("fn (T, T) -> T", Some("swap"), &["mini_std::cmp::max"]),
+        ("fn (&str) -> usize", None, &["str::len", "str::trim"]),
+        (
+            "fn (s: &str) -> usize",
+            Some("trim"),
+            &["str::len", "str::trim"],
+        ),
+        (
+            "fn (&mut Vec<T>, value: T)",
+            None,
+            &["mini_std::vec::Vec::push"],
+        ),
+        (
+            "fn (Option<T>, default: T) -> T",
+            Some("is_some"),
+            &["mini_std::option::Option::unwrap_or"],
+        ),
+        (
+            "fn (T) -> T",
+            None,
+            &["mini_std::convert::identity", "mini_std::cmp::max"],
+        ),
+        (
+            "new Vec",
+            None,
+            &[
+                "mini_std::vec::Vec::new",
+                "mini_std::vec::Vec::with_capacity",
+            ],
+        ),
+    ];
+
+    let index = load_fixture("mini_std");
+    for (query, name, top) in cases {
+        let arena = Arena::new();
+        let mut q = parse_query_in(&arena, query).unwrap();
+        q.name = *name;
+        let hits = index
+            .search(&q, Scope::Crate("mini_std".to_owned()), 0.6)
+            .unwrap();
+        let paths = hits
+            .iter()
+            .take(top.len())
+            .map(|hit| hit.path.join("::"))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, *top, "query `{}` named {:?}", query, name);
+    }
+}
+
+/// Number of hits golden rankings keep when they are blessed.
+const GOLDEN_TOP: usize = 5;
+
+/// A block of a file of golden rankings, separated from others by blank lines.
+enum Block {
+    /// Query and the paths of its expected top hits, preceded by comments.
+    Case {
+        comments: Vec<String>,
+        query: String,
+        expected: Vec<String>,
+        line: usize,
+    },
+    /// Comments or `crates: ...`, kept as they are.
+    Verbatim(Vec<String>),
+}
+
+fn parse_golden(text: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut lines = text.lines().enumerate().peekable();
+    while lines.peek().is_some() {
+        let mut block = vec![];
+        while let Some((i, line)) = lines.next_if(|(_, line)| !line.trim().is_empty()) {
+            block.push((i + 1, line.trim().to_owned()));
+        }
+        lines.next();
+        if block.is_empty() {
+            continue;
+        }
+
+        let comments = block.iter().take_while(|(_, line)| line.starts_with('#'));
+        let comments: Vec<_> = comments.map(|(_, line)| line.clone()).collect();
+        match block.get(comments.len()) {
+            Some((line, query)) if !query.starts_with("crates:") => blocks.push(Block::Case {
+                query: query.clone(),
+                expected: block[comments.len() + 1..]
+                    .iter()
+                    .map(|(i, rank)| match rank.split_once(". ") {
+                        Some((_, path)) => path.to_owned(),
+                        None => panic!("line {}: expected `<RANK>. <PATH>`", i),
+                    })
+                    .collect(),
+                comments,
+                line: *line,
+            }),
+            _ => blocks.push(Block::Verbatim(block.into_iter().map(|(_, l)| l).collect())),
+        }
+    }
+    blocks
+}
+
+/// Fixtures listed by `crates: ...` among `blocks`, or `default` if none are.
+fn golden_crates(blocks: &[Block], default: &str) -> Vec<String> {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Verbatim(lines) => lines.iter().find_map(|line| line.strip_prefix("crates:")),
+            Block::Case { .. } => None,
+        })
+        .map(|krates| {
+            krates
+                .split(',')
+                .map(|krate| krate.trim().to_owned())
+                .collect()
+        })
+        .next()
+        .unwrap_or_else(|| vec![default.to_owned()])
+}
+
+fn write_golden(blocks: &[Block], actual: &HashMap<usize, Vec<Hit>>) -> String {
+    let blocks = blocks.iter().map(|block| match block {
+        Block::Verbatim(lines) => lines.join("\n"),
+        Block::Case {
+            comments,
+            query,
+            line,
+            ..
+        } => {
+            let ranks = actual[line]
+                .iter()
+                .take(GOLDEN_TOP)
+                .enumerate()
+                .map(|(rank, hit)| format!("{}. {}", rank + 1, hit.path.join("::")));
+            comments
+                .iter()
+                .cloned()
+                .chain([query.clone()])
+                .chain(ranks)
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    });
+    blocks.collect::<Vec<_>>().join("\n\n") + "\n"
+}
+
+/// Rankings of the queries in `fixtures/ranking-tests/*.txt`, so that changes to scoring are
+/// reviewed as changes to the hits of concrete queries.
+///
+/// Every case whose top hits differ is reported before failing. With `ROOGLE_BLESS=1`, the files
+/// are rewritten with the current rankings instead.
+#[test]
+fn golden_rankings() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/ranking-tests");
+    let bless = std::env::var_os("ROOGLE_BLESS").is_some();
+    let mut diffs = String::new();
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let file = path.file_name().unwrap().to_string_lossy().into_owned();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let blocks = parse_golden(&text);
+        let krates = golden_crates(&blocks, path.file_stem().unwrap().to_str().unwrap());
+        let index = load_fixtures(&krates.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let mut actual = HashMap::new();
+        for block in &blocks {
+            let Block::Case {
+                query,
+                expected,
+                line,
+                ..
+            } = block
+            else {
+                continue;
+            };
+            let arena = Arena::new();
+            let q = parse_query_in(&arena, query)
+                .unwrap_or_else(|e| panic!("{}:{}: {}", file, line, e));
+            let hits = index.search(&q, Scope::Set(krates.clone()), 0.6).unwrap();
+
+            let top = hits.iter().take(expected.len());
+            let paths = top.map(|hit| hit.path.join("::")).collect::<Vec<_>>();
+            if !bless && (paths != *expected || expected.is_empty()) {
+                diffs += &format!("{}:{}: {}\n", file, line, query);
+                for rank in 0..expected.len().max(paths.len()).max(1) {
+                    match (expected.get(rank), hits.get(rank)) {
+                        (Some(expected), Some(hit)) if hit.path.join("::") == *expected => {
+                            diffs += &format!("    {}. {}\n", rank + 1, expected);
+                        }
+                        (expected, hit) => {
+                            if let Some(expected) = expected {
+                                diffs += &format!("  - {}. {}\n", rank + 1, expected);
+                            }
+                            if let Some(hit) = hit {
+                                let path = hit.path.join("::");
+                                diffs +=
+                                    &format!("  + {}. {} ({:.3})\n", rank + 1, path, hit.score);
+                            }
+                        }
+                    }
+                }
+            }
+            actual.insert(*line, hits);
+        }
+
+        if bless {
+            std::fs::write(&path, write_golden(&blocks, &actual)).unwrap();
+        }
+    }
+    assert!(
+        diffs.is_empty(),
+        "rankings differ from the golden ones; rerun with `ROOGLE_BLESS=1` to accept them\n{}",
+        diffs
+    );
+}
+
+proptest! {
+    #[test]
+    fn exact_signature_scores_best(decl in decl(), other in ty(), at in any::<prop::sample::Index>()) {
+        let inputs = inputs(&decl);
+        let exact = score(&query(&inputs, &decl.output), &decl);
+        prop_assert_eq!(exact, 0.0);
+
+        // Replace either an input or the output with another type.
+        let mut mutated_inputs = inputs.clone();
+        let mut mutated_output = decl.output.clone();
+        let at = at.index(inputs.len() + 1);
+        match mutated_inputs.get_mut(at) {
+            Some(input) => *input = other,
+            None => mutated_output = Some(other),
+        }
+        let mutated = score(&query(&mutated_inputs, &mutated_output), &decl);
+        prop_assert!(exact <= mutated, "{} > {}", exact, mutated);
+    }
+
+    #[test]
+    fn extra_argument_never_improves(
+        decl in decl(),
+        mut query_inputs in prop::collection::vec(ty(), 0..4),
+        query_output in prop::option::of(ty()),
+        extra in ty(),
+    ) {
+        // An extra argument could fill in one the query lacks otherwise.
+        prop_assume!(query_inputs.len() >= decl.inputs.len());
+
+        let before = score(&query(&query_inputs, &query_output), &decl);
+        query_inputs.push(extra);
+        let after = score(&query(&query_inputs, &query_output), &decl);
+        prop_assert!(before <= after, "{} > {}", before, after);
+    }
+
+    #[test]
+    fn names_never_outrank_signatures(decl in decl(), other in decl()) {
+        // The query has the signature of `decl`, but the names of `other` and its inputs.
+        let arena = Arena::new();
+        let mut query =
+            parse_query_in(&arena, &named_query(&inputs(&decl), &decl.output, "y")).unwrap();
+        query.name = Some("g");
+        let mut other = other;
+        for (i, (name, _)) in other.inputs.iter_mut().enumerate() {
+            *name = format!("y{}", i);
+        }
+
+        let exact = similarities(&query, "f", &decl);
+        let named = similarities(&query, "g", &other);
+        prop_assert_eq!(exact.score(), 0.0);
+        if named.score() > 0.0 {
+            prop_assert!(
+                exact.total_cmp(&named, &Weights::default()).is_lt(),
+                "{:?} >= {:?}",
+                exact,
+                named
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#ifndef ROOGLE_H
+#define ROOGLE_H
+
+/* Generated by cbindgen from roogle-ffi/src/lib.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An index loaded by [`roogle_index_load`], opaque to C.
+typedef struct RoogleIndex RoogleIndex;
+
+// Load the index in directory `dir`, laid out the same way as the one `roogle serve` loads.
+//
+// Crates which fail to load are left out of every scope, and searching them by `crate:<crate>`
+// returns an error saying why. Returns `NULL` on failure, storing a message into `*error` unless
+// `error` is `NULL`. The index must be freed with [`roogle_index_free`].
+//
+// # Safety
+//
+// `dir` must be a valid C string, and `error` either `NULL` or valid for writes.
+struct RoogleIndex *roogle_index_load(const char *dir, char **error);
+
+// Free `index` returned by [`roogle_index_load`]. Does nothing if `index` is `NULL`.
+//
+// # Safety
+//
+// `index` must be either `NULL` or returned by [`roogle_index_load`], and not freed yet.
+void roogle_index_free(struct RoogleIndex *index);
+
+// Search `index` for `query` within `scope` and return a JSON object, either
+// `{"hits": [...]}` sorted from the closest match or `{"error": "..."}`.
+//
+// `scope` is `all`, `set:<set>` or `crate:<crate>`, or several of them joined with `+`, or
+// `NULL` for `all`. Hits score below `threshold`, where lower scores mean closer matches, and
+// at most `limit` hits are returned unless `limit` is 0.
+//
+// # Safety
+//
+// `index` must be returned by [`roogle_index_load`] and not freed yet, and `query` and `scope`
+// valid C strings, except that `scope` may be `NULL`.
+char *roogle_search(const struct RoogleIndex *index,
+                    const char *query,
+                    const char *scope,
+                    float threshold,
+                    size_t limit);
+
+// Free `s` returned by any function here. Does nothing if `s` is `NULL`.
+//
+// # Safety
+//
+// `s` must be either `NULL` or returned by a function here, and not freed yet.
+void roogle_string_free(char *s);
+
+#endif  /* ROOGLE_H */
@@ -0,0 +1,233 @@
+//! C bindings for the engine, so that editors and tools not written in Rust can embed search.
+//!
+//! Strings passed in are NUL-terminated UTF-8. Strings passed out are owned by the caller and must
+//! be freed with [`roogle_string_free`]. `include/roogle.h` declares these functions for C and is
+//! generated with `cbindgen --config cbindgen.toml --output include/roogle.h`.
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use roogle_engine::{
+    query::{parse::parse_query_in, Arena},
+    search::Hit,
+    Index,
+};
+use roogle_util::store::{self, Scopes, ALL_SCOPE};
+
+/// An index loaded by [`roogle_index_load`], opaque to C.
+pub struct RoogleIndex {
+    index: Index,
+    scopes: Scopes,
+}
+
+/// Load the index in directory `dir`, laid out the same way as the one `roogle serve` loads.
+///
+/// Crates which fail to load are left out of every scope, and searching them by `crate:<crate>`
+/// returns an error saying why. Returns `NULL` on failure, storing a message into `*error` unless
+/// `error` is `NULL`. The index must be freed with [`roogle_index_free`].
+///
+/// # Safety
+///
+/// `dir` must be a valid C string, and `error` either `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn roogle_index_load(
+    dir: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut RoogleIndex {
+    let result = guard(|| {
+        let dir = unsafe { CStr::from_ptr(dir) }
+            .to_str()
+            .context("index directory is not UTF-8")?;
+        let (index, scopes) = store::load(Path::new(dir), ALL_SCOPE)?;
+        Ok(RoogleIndex { index, scopes })
+    });
+    match result {
+        Ok(index) => Box::into_raw(Box::new(index)),
+        Err(e) => {
+            if !error.is_null() {
+                unsafe { *error = into_c_string(format!("{:#}", e)) };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free `index` returned by [`roogle_index_load`]. Does nothing if `index` is `NULL`.
+///
+/// # Safety
+///
+/// `index` must be either `NULL` or returned by [`roogle_index_load`], and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn roogle_index_free(index: *mut RoogleIndex) {
+    if !index.is_null() {
+        drop(unsafe { Box::from_raw(index) });
+    }
+}
+
+/// Search `index` for `query` within `scope` and return a JSON object, either
+/// `{"hits": [...]}` sorted from the closest match or `{"error": "..."}`.
+///
+/// `scope` is `all`, `set:<set>` or `crate:<crate>`, or several of them joined with `+`, or
+/// `NULL` for `all`. Hits score below `threshold`, where lower scores mean closer matches, and
+/// at most `limit` hits are returned unless `limit` is 0.
+///
+/// # Safety
+///
+/// `index` must be returned by [`roogle_index_load`] and not freed yet, and `query` and `scope`
+/// valid C strings, except that `scope` may be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn roogle_search(
+    index: *const RoogleIndex,
+    query: *const c_char,
+    scope: *const c_char,
+    threshold: f32,
+    limit: usize,
+) -> *mut c_char {
+    let result = guard(|| {
+        let index = unsafe { index.as_ref() }.context("index is NULL")?;
+        let query = unsafe { CStr::from_ptr(query) }
+            .to_str()
+            .context("query is not UTF-8")?;
+        let scope = if scope.is_null() {
+            ALL_SCOPE
+        } else {
+            unsafe { CStr::from_ptr(scope) }
+                .to_str()
+                .context("scope is not UTF-8")?
+        };
+        search(index, query, scope, threshold, limit)
+    });
+    let response = match result {
+        Ok(hits) => Response {
+            hits: Some(hits),
+            error: None,
+        },
+        Err(e) => Response {
+            hits: None,
+            error: Some(format!("{:#}", e)),
+        },
+    };
+    let json = serde_json::to_string(&response).unwrap_or_else(|e| {
+        serde_json::json!({ "error": format!("failed to serialize hits: {}", e) }).to_string()
+    });
+    into_c_string(json)
+}
+
+/// Free `s` returned by any function here. Does nothing if `s` is `NULL`.
+///
+/// # Safety
+///
+/// `s` must be either `NULL` or returned by a function here, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn roogle_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+#[derive(Serialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hits: Option<Vec<Hit>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run `f`, turning a panic into an error rather than unwinding into C.
+fn guard<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err(anyhow!("roogle panicked")))
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // Neither messages nor JSON contain NUL except in escaped form.
+    CString::new(s).unwrap_or_default().into_raw()
+}
+
+fn search(
+    index: &RoogleIndex,
+    query: &str,
+    scope: &str,
+    threshold: f32,
+    limit: usize,
+) -> Result<Vec<Hit>> {
+    let scope = index.scopes.resolve(scope)?;
+
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, query)?;
+    let mut hits = index.index.search(&query, scope, threshold)?;
+    if limit > 0 {
+        hits.truncate(limit);
+    }
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Call `roogle_search` and return the response as a Rust string.
+    fn search_json(index: *const RoogleIndex, query: &str, scope: &str) -> String {
+        let query = CString::new(query).unwrap();
+        let scope = CString::new(scope).unwrap();
+        unsafe {
+            let response = roogle_search(index, query.as_ptr(), scope.as_ptr(), 0.4, 0);
+            let json = CStr::from_ptr(response).to_str().unwrap().to_owned();
+            roogle_string_free(response);
+            json
+        }
+    }
+
+    #[test]
+    fn load_and_search() {
+        let dir = std::env::temp_dir().join(format!("roogle-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("crate")).unwrap();
+        let krate = rustdoc_types::Crate {
+            root: rustdoc_types::Id("0:0".to_owned()),
+            crate_version: None,
+            includes_private: false,
+            index: Default::default(),
+            paths: Default::default(),
+            external_crates: Default::default(),
+            format_version: rustdoc_types::FORMAT_VERSION,
+        };
+        std::fs::write(
+            dir.join("crate").join("empty.json"),
+            serde_json::to_string(&krate).unwrap(),
+        )
+        .unwrap();
+
+        let c_dir = CString::new(dir.to_str().unwrap()).unwrap();
+        let mut error = ptr::null_mut();
+        let index = unsafe { roogle_index_load(c_dir.as_ptr(), &mut error) };
+        assert!(!index.is_null());
+        assert!(error.is_null());
+
+        assert_eq!(search_json(index, "fn ()", "crate:empty"), r#"{"hits":[]}"#);
+        assert_eq!(
+            search_json(index, "fn ()", "crate:missing"),
+            r#"{"error":"crate `missing` not found"}"#
+        );
+        assert!(search_json(index, "fn (", "all").starts_with(r#"{"error":"parsing query"#));
+
+        unsafe { roogle_index_free(index) };
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_missing_index() {
+        let c_dir = CString::new("/nonexistent/roogle-index").unwrap();
+        let mut error = ptr::null_mut();
+        let index = unsafe { roogle_index_load(c_dir.as_ptr(), &mut error) };
+        assert!(index.is_null());
+        assert!(!error.is_null());
+        unsafe { roogle_string_free(error) };
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use rustdoc_types::{Crate, ExternalCrate, Id, Item, ItemSummary};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use roogle_engine::snapshot::checksum_of;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("delta applies to a crate with checksum {expected}, not {actual}")]
+    BaseMismatch { expected: String, actual: String },
+
+    #[error("failed to checksum the crate: {0}")]
+    Checksum(#[from] std::io::Error),
+}
+
+/// Represents changes between two versions of a crate.
+///
+/// Deltas let a hosted index be updated incrementally instead of re-shipping every crate JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    /// Checksum of the old version, which is the only one this delta applies to; see
+    /// [`checksum_of`].
+    pub base_checksum: String,
+
+    /// The version of the crate after applying this delta.
+    pub crate_version: Option<String>,
+
+    /// The root item of the new version.
+    pub root: Id,
+
+    /// External crates of the new version, which are few enough to ship as a whole.
+    pub external_crates: HashMap<u32, ExternalCrate>,
+
+    /// Items which are only present in the new version.
+    pub added: HashMap<Id, Item>,
+
+    /// Items which are present in both versions but differ.
+    pub changed: HashMap<Id, Item>,
+
+    /// Items which are only present in the old version.
+    pub removed: Vec<Id>,
+
+    /// Paths which are either added or changed in the new version.
+    pub paths: HashMap<Id, ItemSummary>,
+
+    /// Paths which are only present in the old version.
+    pub removed_paths: Vec<Id>,
+}
+
+impl Delta {
+    /// Whether the delta changes nothing of the items and paths of the crate.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+            && self.paths.is_empty()
+            && self.removed_paths.is_empty()
+    }
+}
+
+/// Compute a [`Delta`] which turns `old` into `new`.
+pub fn diff(old: &Crate, new: &Crate) -> Result<Delta, Error> {
+    let mut added = HashMap::default();
+    let mut changed = HashMap::default();
+    for (id, item) in &new.index {
+        match old.index.get(id) {
+            None => {
+                added.insert(id.clone(), item.clone());
+            }
+            Some(old_item) if old_item != item => {
+                changed.insert(id.clone(), item.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    let removed = old
+        .index
+        .keys()
+        .filter(|id| !new.index.contains_key(id))
+        .cloned()
+        .collect();
+
+    let paths = new
+        .paths
+        .iter()
+        .filter(|(id, summary)| old.paths.get(id) != Some(summary))
+        .map(|(id, summary)| (id.clone(), summary.clone()))
+        .collect();
+    let removed_paths = old
+        .paths
+        .keys()
+        .filter(|id| !new.paths.contains_key(id))
+        .cloned()
+        .collect();
+
+    Ok(Delta {
+        base_checksum: checksum_of(old)?,
+        crate_version: new.crate_version.clone(),
+        root: new.root.clone(),
+        external_crates: new.external_crates.clone(),
+        added,
+        changed,
+        removed,
+        paths,
+        removed_paths,
+    })
+}
+
+/// Apply `delta` to `krate`, producing the new version of it.
+///
+/// Fails unless `krate` is the old version the delta was computed from.
+pub fn apply(krate: Crate, delta: Delta) -> Result<Crate, Error> {
+    let actual = checksum_of(&krate)?;
+    if actual != delta.base_checksum {
+        return Err(Error::BaseMismatch {
+            expected: delta.base_checksum,
+            actual,
+        });
+    }
+
+    let Crate {
+        crate_version,
+        includes_private,
+        mut index,
+        mut paths,
+        format_version,
+        ..
+    } = krate;
+
+    for id in &delta.removed {
+        index.remove(id);
+    }
+    index.extend(delta.added);
+    index.extend(delta.changed);
+
+    for id in &delta.removed_paths {
+        paths.remove(id);
+    }
+    paths.extend(delta.paths);
+
+    Ok(Crate {
+        root: delta.root,
+        crate_version: delta.crate_version.or(crate_version),
+        includes_private,
+        index,
+        paths,
+        external_crates: delta.external_crates,
+        format_version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn krate(items: &[(&str, &str)]) -> Crate {
+        let index = items
+            .iter()
+            .map(|(id, name)| {
+                let item = Item {
+                    id: Id(id.to_string()),
+                    crate_id: 0,
+                    name: Some(name.to_string()),
+                    span: None,
+                    visibility: rustdoc_types::Visibility::Public,
+                    docs: None,
+                    links: HashMap::default(),
+                    attrs: vec![],
+                    deprecation: None,
+                    inner: rustdoc_types::ItemEnum::Macro(String::new()),
+                };
+                (Id(id.to_string()), item)
+            })
+            .collect();
+
+        Crate {
+            root: Id("0:0".to_owned()),
+            crate_version: Some("0.0.0".to_owned()),
+            includes_private: false,
+            index,
+            paths: HashMap::default(),
+            external_crates: HashMap::default(),
+            format_version: 0,
+        }
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let old = krate(&[("0:1", "foo"), ("0:2", "bar"), ("0:3", "baz")]);
+        let mut new = krate(&[("0:1", "foo"), ("0:2", "bar2"), ("0:4", "qux")]);
+        new.crate_version = Some("0.1.0".to_owned());
+        new.root = Id("0:4".to_owned());
+        new.external_crates.insert(
+            1,
+            ExternalCrate {
+                name: "core".to_owned(),
+                html_root_url: None,
+            },
+        );
+
+        let delta = diff(&old, &new).unwrap();
+        assert_eq!(delta.added.len(), 1);
+        assert_eq!(delta.changed.len(), 1);
+        assert_eq!(delta.removed, vec![Id("0:3".to_owned())]);
+
+        assert_eq!(apply(old, delta).unwrap(), new);
+    }
+
+    #[test]
+    fn refuse_to_apply_to_other_crates() {
+        let old = krate(&[("0:1", "foo")]);
+        let new = krate(&[("0:1", "foo"), ("0:2", "bar")]);
+        let delta = diff(&old, &new).unwrap();
+
+        let other = krate(&[("0:1", "baz")]);
+        assert!(matches!(
+            apply(other, delta.clone()),
+            Err(Error::BaseMismatch { .. })
+        ));
+        assert!(apply(new, delta).is_err());
+    }
+
+    #[test]
+    fn diff_identical() {
+        let krate = krate(&[("0:1", "foo")]);
+        assert!(diff(&krate, &krate).unwrap().is_empty());
+    }
+}
@@ -1,3 +1,9 @@
+pub mod delta;
+pub mod search_index;
+pub mod store;
+
+pub use roogle_engine::json::{from_json, MAX_DEPTH, STACK_SIZE};
+
 use std::collections::HashMap;
 
 use rustdoc_types::{Crate, Id, Item, ItemSummary};
@@ -29,9 +35,10 @@ pub fn shake(krate: Crate) -> Crate {
     }
 }
 
-fn shake_index(index: HashMap<Id, Item>) -> HashMap<Id, Item> {
+fn shake_index(mut index: HashMap<Id, Item>) -> HashMap<Id, Item> {
     use rustdoc_types::ItemEnum::*;
 
+    inherit_feature_gates(&mut index);
     index
         .into_iter()
         .filter(|(_, item)| {
@@ -43,6 +50,46 @@ fn shake_index(index: HashMap<Id, Item>) -> HashMap<Id, Item> {
         .collect()
 }
 
+/// Copy attributes gating modules and types behind cargo features, like `#[cfg(feature = "...")]`
+/// or `#[doc(cfg(feature = "..."))]`, onto the items in the modules and the impls of the types,
+/// which unlike them are kept by shaking, so that hits of them tell which features they need.
+///
+/// Items gated by features of their own keep theirs.
+fn inherit_feature_gates(index: &mut HashMap<Id, Item>) {
+    use rustdoc_types::ItemEnum::*;
+
+    let mut gated = index
+        .values()
+        .filter_map(|item| Some((item.id.clone(), feature_gate(&item.attrs)?.clone())))
+        .collect::<Vec<_>>();
+    while let Some((id, gate)) = gated.pop() {
+        let children = match index.get(&id).map(|item| &item.inner) {
+            Some(Module(module)) => module.items.clone(),
+            Some(Struct(struct_)) => struct_.impls.clone(),
+            Some(Enum(enum_)) => enum_.impls.clone(),
+            Some(Union(union_)) => union_.impls.clone(),
+            _ => continue,
+        };
+        for child in children {
+            let item = match index.get_mut(&child) {
+                Some(item) if feature_gate(&item.attrs).is_none() => item,
+                _ => continue,
+            };
+            item.attrs.push(gate.clone());
+            gated.push((child, gate.clone()));
+        }
+    }
+}
+
+/// The attribute among `attrs` which makes the item available only with a cargo feature.
+fn feature_gate(attrs: &[String]) -> Option<&String> {
+    attrs.iter().find(|attr| {
+        (attr.starts_with("#[cfg(") || attr.starts_with("#[doc(cfg("))
+            && attr.contains("feature")
+            && !attr.contains("not(")
+    })
+}
+
 fn shake_paths(paths: HashMap<Id, ItemSummary>) -> HashMap<Id, ItemSummary> {
     use rustdoc_types::ItemKind::*;
 
@@ -51,3 +98,95 @@ fn shake_paths(paths: HashMap<Id, ItemSummary>) -> HashMap<Id, ItemSummary> {
         .filter(|(_, item)| matches!(item.kind, Struct | Union | Enum | Function | Trait | Method))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use rustdoc_types::{
+        FnDecl, Function, Generics, Impl, ItemEnum, Module, Struct, StructType, Type, Visibility,
+    };
+
+    use super::*;
+
+    fn item(id: &str, attrs: &[&str], inner: ItemEnum) -> (Id, Item) {
+        let item = Item {
+            id: Id(id.to_owned()),
+            crate_id: 0,
+            name: Some(id.to_owned()),
+            span: None,
+            visibility: Visibility::Public,
+            docs: None,
+            links: HashMap::default(),
+            attrs: attrs.iter().map(|attr| attr.to_string()).collect(),
+            deprecation: None,
+            inner,
+        };
+        (item.id.clone(), item)
+    }
+
+    fn function() -> ItemEnum {
+        ItemEnum::Function(Function {
+            decl: FnDecl {
+                inputs: vec![],
+                output: None,
+                c_variadic: false,
+            },
+            generics: Generics::default(),
+            header: Default::default(),
+            abi: "Rust".to_owned(),
+        })
+    }
+
+    #[test]
+    fn inherit_feature_gates_of_modules_and_types() {
+        let net = r#"#[cfg(feature = "net")]"#;
+        let rt = r#"#[doc(cfg(feature = "rt"))]"#;
+        let ids = |ids: &[&str]| ids.iter().map(|id| Id(id.to_string())).collect();
+        let index = [
+            item(
+                "net",
+                &[net],
+                ItemEnum::Module(Module {
+                    is_crate: false,
+                    items: ids(&["connect", "spawn", "Socket"]),
+                }),
+            ),
+            item("connect", &["#[inline]"], function()),
+            item("spawn", &[rt], function()),
+            item(
+                "Socket",
+                &[],
+                ItemEnum::Struct(Struct {
+                    struct_type: StructType::Unit,
+                    generics: Generics::default(),
+                    fields_stripped: false,
+                    fields: vec![],
+                    impls: ids(&["impl"]),
+                }),
+            ),
+            item(
+                "impl",
+                &[],
+                ItemEnum::Impl(Impl {
+                    is_unsafe: false,
+                    generics: Generics::default(),
+                    provided_trait_methods: vec![],
+                    trait_: None,
+                    for_: Type::Primitive("u8".to_owned()),
+                    items: vec![],
+                    negative: false,
+                    synthetic: false,
+                    blanket_impl: None,
+                }),
+            ),
+            item("free", &[], function()),
+        ];
+
+        let index = shake_index(index.into_iter().collect());
+        let attrs = |id: &str| index[&Id(id.to_owned())].attrs.clone();
+        assert_eq!(attrs("connect"), vec!["#[inline]", net]);
+        assert_eq!(attrs("spawn"), vec![rt]);
+        assert_eq!(attrs("impl"), vec![net]);
+        assert!(attrs("free").is_empty());
+        assert!(!index.contains_key(&Id("Socket".to_owned())));
+    }
+}
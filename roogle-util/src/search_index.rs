@@ -0,0 +1,310 @@
+//! Reading `search-index.js` which rustdoc emits for its HTML output.
+//!
+//! Crates published only as HTML (e.g. on docs.rs before JSON output was stable) still ship this
+//! file. It lacks most of what rustdoc JSON has, so crates built from it are degraded: functions
+//! and methods have their names, paths, summaries and rough signatures, but no generics.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rustdoc_types::{
+    Crate, FnDecl, Function, Generics, Id, Impl, Item, ItemEnum, ItemKind, ItemSummary, Method,
+    Module, Type, Visibility,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("`JSON.parse('...')` is not found")]
+    NotFound,
+
+    #[error("malformed search index: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Types of items in `search-index.js`, numbered the same as `ItemType` in rustdoc.
+mod item_type {
+    pub const STRUCT: u64 = 3;
+    pub const ENUM: u64 = 4;
+    pub const FUNCTION: u64 = 5;
+    pub const METHOD: u64 = 11;
+    pub const PRIMITIVE: u64 = 15;
+    pub const UNION: u64 = 19;
+}
+
+/// Items of a crate in `search-index.js`, where the `n`th item is described by the `n`th element
+/// of each array.
+#[derive(Debug, Deserialize)]
+struct CrateIndex {
+    #[serde(default)]
+    doc: String,
+    t: Vec<u64>,
+    n: Vec<String>,
+    q: Vec<String>,
+    d: Vec<String>,
+    i: Vec<usize>,
+    #[serde(default)]
+    f: Vec<Value>,
+    p: Vec<(u64, String)>,
+}
+
+/// Parse `search-index.js` into crates keyed by their names.
+pub fn parse(js: &str) -> Result<HashMap<String, Crate>, Error> {
+    let start = js.find("JSON.parse('").ok_or(Error::NotFound)? + "JSON.parse('".len();
+    let end = js
+        .rfind("')")
+        .filter(|&end| end >= start)
+        .ok_or(Error::NotFound)?;
+    let json = unescape(&js[start..end]);
+
+    let krates: BTreeMap<String, CrateIndex> = serde_json::from_str(&json)?;
+    Ok(krates
+        .into_iter()
+        .map(|(name, index)| {
+            let krate = to_crate(&name, index);
+            (name, krate)
+        })
+        .collect())
+}
+
+/// Undo escaping of a single-quoted JavaScript string, including line continuations.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\n') | None => {}
+            Some(c) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn to_crate(name: &str, index: CrateIndex) -> Crate {
+    let mut items = HashMap::new();
+    let mut paths = HashMap::new();
+    let mut functions = vec![];
+    let mut methods = BTreeMap::<usize, (Vec<Id>, Vec<String>)>::new();
+
+    let mut module = name.to_owned();
+    for (n, (ty, item_name)) in index.t.iter().zip(&index.n).enumerate() {
+        if let Some(q) = index.q.get(n).filter(|q| !q.is_empty()) {
+            module = q.clone();
+        }
+        let id = Id(format!("0:{}", n + 1));
+        let docs = index.d.get(n).filter(|d| !d.is_empty()).cloned();
+        let decl = decl(index.f.get(n).unwrap_or(&Value::Null));
+        let parent = index.i.get(n).copied().unwrap_or_default();
+
+        let inner = match *ty {
+            item_type::FUNCTION => {
+                let mut path: Vec<String> = module.split("::").map(str::to_owned).collect();
+                path.push(item_name.clone());
+                paths.insert(
+                    id.clone(),
+                    ItemSummary {
+                        crate_id: 0,
+                        path,
+                        kind: ItemKind::Function,
+                    },
+                );
+                functions.push(id.clone());
+                ItemEnum::Function(Function {
+                    decl,
+                    generics: Generics::default(),
+                    header: HashSet::new(),
+                    abi: "Rust".to_owned(),
+                })
+            }
+            item_type::METHOD if parent > 0 => {
+                let (ids, modules) = methods.entry(parent).or_default();
+                ids.push(id.clone());
+                modules.push(module.clone());
+                ItemEnum::Method(Method {
+                    decl,
+                    generics: Generics::default(),
+                    header: HashSet::new(),
+                    abi: "Rust".to_owned(),
+                    has_body: true,
+                })
+            }
+            _ => continue,
+        };
+        items.insert(id.clone(), item(id, Some(item_name.clone()), docs, inner));
+    }
+
+    for (parent, (ids, modules)) in methods {
+        let (ty, parent_name) = match index.p.get(parent - 1) {
+            Some(parent) => parent,
+            None => continue,
+        };
+        let kind = match *ty {
+            item_type::STRUCT => ItemKind::Struct,
+            item_type::ENUM => ItemKind::Enum,
+            item_type::UNION => ItemKind::Union,
+            item_type::PRIMITIVE => ItemKind::Primitive,
+            // Methods of traits can't be searched for yet.
+            _ => continue,
+        };
+        let for_ = if kind == ItemKind::Primitive {
+            Type::Primitive(parent_name.clone())
+        } else {
+            let id = Id(format!("1:{}", parent));
+            let mut path: Vec<String> = modules[0].split("::").map(str::to_owned).collect();
+            path.push(parent_name.clone());
+            paths.insert(
+                id.clone(),
+                ItemSummary {
+                    crate_id: 0,
+                    path,
+                    kind,
+                },
+            );
+            Type::ResolvedPath {
+                name: parent_name.clone(),
+                id,
+                args: None,
+                param_names: vec![],
+            }
+        };
+        let id = Id(format!("2:{}", parent));
+        let impl_ = ItemEnum::Impl(Impl {
+            is_unsafe: false,
+            generics: Generics::default(),
+            provided_trait_methods: vec![],
+            trait_: None,
+            for_,
+            items: ids,
+            negative: false,
+            synthetic: false,
+            blanket_impl: None,
+        });
+        items.insert(id.clone(), item(id, None, None, impl_));
+    }
+
+    let root = Id("0:0".to_owned());
+    let docs = Some(index.doc).filter(|doc| !doc.is_empty());
+    let module = ItemEnum::Module(Module {
+        is_crate: true,
+        items: functions,
+    });
+    items.insert(
+        root.clone(),
+        item(root.clone(), Some(name.to_owned()), docs, module),
+    );
+
+    Crate {
+        root,
+        crate_version: None,
+        includes_private: false,
+        index: items,
+        paths,
+        external_crates: HashMap::new(),
+        format_version: rustdoc_types::FORMAT_VERSION,
+    }
+}
+
+fn item(id: Id, name: Option<String>, docs: Option<String>, inner: ItemEnum) -> Item {
+    Item {
+        id,
+        crate_id: 0,
+        name,
+        span: None,
+        visibility: Visibility::Public,
+        docs,
+        links: HashMap::new(),
+        attrs: vec![],
+        deprecation: None,
+        inner,
+    }
+}
+
+/// Build a rough declaration out of a signature in `search-index.js`, which is
+/// `[[input, ...], output]` with each type being `[name, item type]`.
+fn decl(f: &Value) -> FnDecl {
+    let inputs = f
+        .get(0)
+        .and_then(Value::as_array)
+        .map(|inputs| inputs.iter().filter_map(ty).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let output = f.get(1).and_then(|output| {
+        // A single type is written as is, and multiple ones as an array of them.
+        if output.get(1).is_some_and(Value::is_u64) {
+            ty(output)
+        } else {
+            let types = output.as_array()?.iter().filter_map(ty).collect();
+            Some(Type::Tuple(types))
+        }
+    });
+
+    FnDecl {
+        inputs: inputs.into_iter().map(|ty| ("_".to_owned(), ty)).collect(),
+        output,
+        c_variadic: false,
+    }
+}
+
+fn ty(ty: &Value) -> Option<Type> {
+    let name = match ty.get(0)? {
+        // Types with generics are written as `[name, [generic, ...]]`.
+        Value::Array(ty) => ty.first()?.as_str()?,
+        name => name.as_str()?,
+    }
+    .to_owned();
+    if ty.get(1).and_then(Value::as_u64) == Some(item_type::PRIMITIVE) {
+        Some(Type::Primitive(name))
+    } else {
+        Some(Type::ResolvedPath {
+            name,
+            id: Id(String::new()),
+            args: None,
+            param_names: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_functions_and_methods() {
+        let js = r#"var searchIndex = JSON.parse('{\
+"mini":{"doc":"","t":[5,11],"n":["parse_u32","len"],"q":["mini",""],"d":["Parses a `u32`.","It\'s the length."],"i":[0,1],"f":[[[["str",15]],["u32",15]],null],"p":[[3,"Foo"]]}\
+}');
+if (window.initSearch) {window.initSearch(searchIndex)};
+"#;
+        let krates = parse(js).unwrap();
+        let krate = &krates["mini"];
+
+        let parse_u32 = &krate.index[&Id("0:1".to_owned())];
+        assert_eq!(parse_u32.name.as_deref(), Some("parse_u32"));
+        match &parse_u32.inner {
+            ItemEnum::Function(f) => {
+                assert_eq!(
+                    f.decl.inputs,
+                    vec![("_".to_owned(), Type::Primitive("str".to_owned()))]
+                );
+                assert_eq!(f.decl.output, Some(Type::Primitive("u32".to_owned())));
+            }
+            _ => panic!("`parse_u32` is not a function"),
+        }
+        assert_eq!(
+            krate.paths[&Id("0:1".to_owned())].path,
+            vec!["mini", "parse_u32"]
+        );
+
+        let len = &krate.index[&Id("0:2".to_owned())];
+        assert_eq!(len.docs.as_deref(), Some("It's the length."));
+        assert_eq!(krate.paths[&Id("1:1".to_owned())].path, vec!["mini", "Foo"]);
+        match &krate.index[&Id("2:1".to_owned())].inner {
+            ItemEnum::Impl(impl_) => assert_eq!(impl_.items, vec![Id("0:2".to_owned())]),
+            _ => panic!("no impl for `Foo`"),
+        }
+    }
+}
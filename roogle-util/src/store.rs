@@ -0,0 +1,516 @@
+//! Loading the index directory into an [`Index`] along with its scopes, shared by the command
+//! line and the C bindings.
+//!
+//! The directory consists of `crate/<CRATE>.json` and `set/<SET>.json`, with `delta/<CRATE>.json`
+//! applied to the crate of the same name if any.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Instant,
+};
+
+use rustdoc_types::Crate;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use roogle_engine::{
+    search::{Hit, Scope},
+    Index,
+};
+
+use crate::{
+    delta::{self, Delta},
+    from_json, shake,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to read `{0:?}`: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("failed to deserialize `{0:?}`: {1}")]
+    Deserialize(PathBuf, serde_json::Error),
+
+    #[error("failed to apply `{0:?}`: {1}")]
+    Delta(PathBuf, delta::Error),
+
+    #[error("failed to get crate name from `{0:?}`")]
+    CrateName(PathBuf),
+
+    #[error(
+        "unsupported format version {0} (expected {})",
+        rustdoc_types::FORMAT_VERSION
+    )]
+    FormatVersion(u32),
+
+    #[error("set `{0}` not found")]
+    SetNotFound(String),
+
+    #[error("crate `{0}` not found")]
+    CrateNotFound(String),
+
+    #[error("crate `{0}` is degraded: {1}")]
+    Degraded(String, String),
+
+    #[error("parsing scope `{0}` failed")]
+    MalformedScope(String),
+
+    #[error("failed to resolve the default scope: {0}")]
+    DefaultScope(Box<Error>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Load the index and scopes in `dir`, with `default_scope` to search in by default.
+///
+/// Crates failing to load are left out of the scopes and marked as degraded with why, which is
+/// logged as well.
+pub fn load(dir: &Path, default_scope: &str) -> Result<(Index, Scopes)> {
+    let (index, degraded) = make_index(dir)?;
+    let mut scopes = make_scopes(dir, default_scope)?;
+    for krate in degraded.keys() {
+        scopes.krates.remove(krate);
+    }
+    scopes.degraded = degraded;
+    Ok((index, scopes))
+}
+
+/// Load all crates in `<INDEX>/crate` on as many threads as available, applying their deltas if
+/// any, along with the causes of the ones failing to load keyed by their names.
+fn make_index(dir: &Path) -> Result<(Index, BTreeMap<String, String>)> {
+    let start = Instant::now();
+    let read_dir = |e| Error::Read(dir.join("crate"), e);
+    let paths = std::fs::read_dir(dir.join("crate"))
+        .map_err(read_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(read_dir)?;
+
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(paths.len());
+    let queue = Mutex::new(paths.into_iter());
+    let crates = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut crates = vec![];
+                    loop {
+                        let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                        let Some(path) = next else { break };
+                        let result = load_crate(dir, &path).map_err(|e| {
+                            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                            warn!(krate = %name, "loading a crate failed: {}", e);
+                            (name.into_owned(), e.to_string())
+                        });
+                        crates.push(result);
+                    }
+                    crates
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect::<Vec<_>>()
+    });
+    let (crates, degraded): (Vec<_>, Vec<_>) =
+        crates.into_iter().partition(std::result::Result::is_ok);
+    let crates = crates.into_iter().flatten().collect::<HashMap<_, _>>();
+    let degraded = degraded
+        .into_iter()
+        .filter_map(std::result::Result::err)
+        .collect::<BTreeMap<_, _>>();
+
+    info!(
+        crates = crates.len(),
+        degraded = degraded.len(),
+        workers,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "index loaded"
+    );
+    Ok((Index::new(crates), degraded))
+}
+
+/// Load the crate at `path` in `<INDEX>/crate`, applying its delta if any.
+fn load_crate(dir: &Path, path: &Path) -> Result<(String, Crate)> {
+    let start = Instant::now();
+    let json = std::fs::read_to_string(path).map_err(|e| Error::Read(path.to_owned(), e))?;
+    let mut krate: Crate = match from_json(&json) {
+        Ok(krate) => krate,
+        Err(e) => {
+            // Tell crates built by other versions of rustdoc from corrupt ones, as they are fixed
+            // by building them again rather than by recovering the files.
+            #[derive(Deserialize)]
+            struct Version {
+                format_version: u32,
+            }
+            if let Ok(Version { format_version }) = from_json(&json) {
+                check_format_version(format_version)?;
+            }
+            return Err(Error::Deserialize(path.to_owned(), e));
+        }
+    };
+    check_format_version(krate.format_version)?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| Error::CrateName(path.to_owned()))?
+        .to_owned();
+    if let Some(delta) = read_delta(dir, &name)? {
+        krate = delta::apply(krate, delta).map_err(|e| Error::Delta(delta_path(dir, &name), e))?;
+    }
+
+    info!(
+        krate = %name,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "crate loaded"
+    );
+    Ok((name, shake(krate)))
+}
+
+/// Read a delta for `krate` from `<INDEX>/delta/<krate>.json`, if any.
+fn read_delta(dir: &Path, krate: &str) -> Result<Option<Delta>> {
+    let path = delta_path(dir, krate);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&path).map_err(|e| Error::Read(path.clone(), e))?;
+    let delta: Delta = from_json(&json).map_err(|e| Error::Deserialize(path, e))?;
+    debug!(
+        krate,
+        added = delta.added.len(),
+        changed = delta.changed.len(),
+        removed = delta.removed.len(),
+        "applying delta"
+    );
+    Ok(Some(delta))
+}
+
+fn delta_path(dir: &Path, krate: &str) -> PathBuf {
+    dir.join("delta").join(format!("{}.json", krate))
+}
+
+/// Name of the scope which spans all crates in the index.
+pub const ALL_SCOPE: &str = "all";
+
+pub struct Scopes {
+    pub sets: HashMap<String, Scope>,
+    pub krates: HashMap<String, Scope>,
+    /// Crates in `<INDEX>/crate` which failed to load, along with why, keyed by their names.
+    ///
+    /// They are in no scope, not even in the sets listing them.
+    pub degraded: BTreeMap<String, String>,
+
+    /// Scope to search in when a request doesn't specify one.
+    pub default: String,
+}
+
+impl Scopes {
+    /// Resolve `scope` which is either `all`, `set:<set>` or `crate:<crate>`, or several of them
+    /// joined with `+` like `set:std+crate:itertools` to search them all at once.
+    pub fn resolve(&self, scope: &str) -> Result<Scope> {
+        let members = members(scope);
+        if members.len() <= 1 {
+            return self.resolve_one(scope);
+        }
+        let mut krates: Vec<String> = vec![];
+        for member in members {
+            for krate in self.resolve_one(member)?.flatten() {
+                if !krates.contains(&krate) {
+                    krates.push(krate);
+                }
+            }
+        }
+        Ok(Scope::Set(krates))
+    }
+
+    fn resolve_one(&self, scope: &str) -> Result<Scope> {
+        let scope = match scope.split(':').collect::<Vec<_>>().as_slice() {
+            [ALL_SCOPE] => {
+                let mut krates = self.krates.keys().cloned().collect::<Vec<_>>();
+                krates.sort_unstable();
+                Scope::Set(krates)
+            }
+            ["set", set] => match self
+                .sets
+                .get(*set)
+                .ok_or_else(|| Error::SetNotFound(set.to_string()))?
+            {
+                Scope::Set(krates) => Scope::Set(
+                    krates
+                        .iter()
+                        .filter(|krate| !self.degraded.contains_key(*krate))
+                        .cloned()
+                        .collect(),
+                ),
+                scope => scope.clone(),
+            },
+            ["crate", krate] => match self.degraded.get(*krate) {
+                Some(cause) => return Err(Error::Degraded(krate.to_string(), cause.clone())),
+                None => self
+                    .krates
+                    .get(*krate)
+                    .ok_or_else(|| Error::CrateNotFound(krate.to_string()))?
+                    .clone(),
+            },
+            _ => return Err(Error::MalformedScope(scope.to_owned())),
+        };
+        Ok(scope)
+    }
+
+    /// Crates of each scope joined in `scope` like `set:std+crate:itertools`, to tell hits which
+    /// of them they are in, or none if `scope` is a single scope.
+    pub fn attribution(&self, scope: &str) -> Result<Attribution> {
+        let members = members(scope);
+        if members.len() <= 1 {
+            return Ok(Attribution::default());
+        }
+        let members = members
+            .into_iter()
+            .map(|member| Ok((member.to_owned(), self.resolve_one(member)?.flatten())))
+            .collect::<Result<_>>()?;
+        Ok(Attribution(members))
+    }
+
+    /// Names of all scopes, in the form accepted by [`Scopes::resolve`].
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![ALL_SCOPE.to_owned()];
+        for set in self.sets.keys() {
+            names.push(format!("set:{}", set));
+        }
+        for krate in self.krates.keys() {
+            names.push(format!("crate:{}", krate));
+        }
+        names
+    }
+}
+
+/// Scopes joined with `+` in `scope`, or with spaces as `+` in query strings decodes to.
+fn members(scope: &str) -> Vec<&str> {
+    scope
+        .split(|c: char| c == '+' || c.is_whitespace())
+        .filter(|member| !member.is_empty())
+        .collect()
+}
+
+/// Scopes joined in a federated search, e.g. `set:std` and `crate:itertools` of
+/// `set:std+crate:itertools`, along with their crates.
+#[derive(Debug, Default)]
+pub struct Attribution(Vec<(String, Vec<String>)>);
+
+impl Attribution {
+    /// Set [`Hit::scopes`] to the joined scopes the crate of `hit` is in.
+    pub fn attribute(&self, hit: &mut Hit) {
+        hit.scopes = self
+            .0
+            .iter()
+            .filter(|(_, krates)| krates.iter().any(|krate| krate == hit.krate()))
+            .map(|(scope, _)| scope.clone())
+            .collect();
+    }
+}
+
+/// Register a scope for each crate in `<INDEX>/crate` and each set in `<INDEX>/set`.
+pub fn make_scopes(dir: &Path, default_scope: &str) -> Result<Scopes> {
+    let krates: HashMap<String, Scope> = std::fs::read_dir(dir.join("crate"))
+        .map_err(|e| Error::Read(dir.join("crate"), e))?
+        .map(|entry| {
+            let path = entry.map_err(|e| Error::Read(dir.join("crate"), e))?.path();
+            let krate = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| Error::CrateName(path.clone()))?;
+
+            Ok((krate.to_owned(), Scope::Crate(krate.to_owned())))
+        })
+        .filter_map(|res: Result<_>| {
+            if let Err(ref e) = res {
+                warn!("registering a scope skipped: {}", e)
+            }
+            res.ok()
+        })
+        .collect();
+    make_scopes_of(krates, dir, default_scope)
+}
+
+/// Register `krates` and a scope for each set in `<INDEX>/set`.
+pub fn make_scopes_of(
+    krates: HashMap<String, Scope>,
+    dir: &Path,
+    default_scope: &str,
+) -> Result<Scopes> {
+    let sets: HashMap<String, Scope> = match std::fs::read_dir(dir.join("set")) {
+        Err(e) => {
+            warn!("registering sets skipped: {}", e);
+            HashMap::default()
+        }
+        Ok(entry) => entry
+            .map(|entry| {
+                let path = entry.map_err(|e| Error::Read(dir.join("set"), e))?.path();
+                let json =
+                    std::fs::read_to_string(&path).map_err(|e| Error::Read(path.clone(), e))?;
+                let set = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| Error::CrateName(path.clone()))?
+                    .to_owned();
+                let krates = serde_json::from_str::<Vec<String>>(&json)
+                    .map_err(|e| Error::Deserialize(path.clone(), e))?;
+
+                Ok((set, Scope::Set(krates)))
+            })
+            .filter_map(|res: Result<_>| {
+                if let Err(ref e) = res {
+                    warn!("registering a scope skipped: {}", e)
+                }
+                res.ok()
+            })
+            .collect(),
+    };
+    let scopes = Scopes {
+        sets,
+        krates,
+        degraded: BTreeMap::new(),
+        default: default_scope.to_owned(),
+    };
+    scopes
+        .resolve(&scopes.default)
+        .map_err(|e| Error::DefaultScope(Box::new(e)))?;
+    Ok(scopes)
+}
+
+/// Check that `krate` is something the engine can search in.
+///
+/// Its root item isn't required, as shaking drops modules, the root one included.
+pub fn validate_crate(krate: &Crate) -> Result<()> {
+    check_format_version(krate.format_version)
+}
+
+/// Check that rustdoc JSON of `format_version` is something the engine can read.
+fn check_format_version(format_version: u32) -> Result<()> {
+    if format_version != rustdoc_types::FORMAT_VERSION {
+        return Err(Error::FormatVersion(format_version));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes() -> Scopes {
+        let krates = ["std", "core", "itertools"]
+            .into_iter()
+            .map(|krate| (krate.to_owned(), Scope::Crate(krate.to_owned())))
+            .collect();
+        let sets = [(
+            "libstd".to_owned(),
+            Scope::Set(vec!["std".to_owned(), "core".to_owned()]),
+        )]
+        .into_iter()
+        .collect();
+        Scopes {
+            sets,
+            krates,
+            degraded: BTreeMap::new(),
+            default: ALL_SCOPE.to_owned(),
+        }
+    }
+
+    #[test]
+    fn leave_degraded_crates_out_of_scopes() {
+        let mut scopes = scopes();
+        scopes.krates.remove("std");
+        scopes
+            .degraded
+            .insert("std".to_owned(), "unsupported format version".to_owned());
+
+        assert_eq!(
+            scopes.resolve("set:libstd").unwrap().flatten(),
+            vec!["core"]
+        );
+        assert_eq!(
+            scopes.resolve(ALL_SCOPE).unwrap().flatten(),
+            vec!["core", "itertools"]
+        );
+        let e = scopes.resolve("crate:std").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "crate `std` is degraded: unsupported format version"
+        );
+    }
+
+    #[test]
+    fn load_deeply_nested_crates_on_workers() {
+        let dir = std::env::temp_dir().join(format!("roogle-store-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("crate")).unwrap();
+        // `fn f(x: [[..[u8]..]])`, nested deeper than the default stacks of the workers hold.
+        let depth = 2000;
+        let ty = format!(
+            r#"{}{{"kind":"primitive","inner":"u8"}}{}"#,
+            r#"{"kind":"slice","inner":"#.repeat(depth),
+            "}".repeat(depth)
+        );
+        let function = format!(
+            r#"{{"id":"0:1","crate_id":0,"name":"f","span":null,"visibility":"public","docs":null,
+            "links":{{}},"attrs":[],"deprecation":null,"kind":"function","inner":{{"decl":{{
+            "inputs":[["x",{}]],"output":null,"c_variadic":false}},"generics":{{"params":[],
+            "where_predicates":[]}},"header":[],"abi":"Rust"}}}}"#,
+            ty
+        );
+        let krate = format!(
+            r#"{{"root":"0:0","crate_version":null,"includes_private":false,"index":{{"0:1":{}}},
+            "paths":{{}},"external_crates":{{}},"format_version":{}}}"#,
+            function,
+            rustdoc_types::FORMAT_VERSION
+        );
+        std::fs::write(dir.join("crate").join("deep.json"), krate).unwrap();
+
+        let (index, degraded) = make_index(&dir).unwrap();
+        assert!(degraded.is_empty(), "{:?}", degraded);
+        assert!(index.crates().contains_key("deep"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_joined_scopes() {
+        let scopes = scopes();
+        let krates = |scope| scopes.resolve(scope).unwrap().flatten();
+        assert_eq!(
+            krates("set:libstd+crate:itertools+crate:core"),
+            vec!["std", "core", "itertools"]
+        );
+        // Query strings decode `+` to a space.
+        assert_eq!(
+            krates("set:libstd crate:itertools"),
+            vec!["std", "core", "itertools"]
+        );
+        assert!(scopes.resolve("set:libstd+crate:nope").is_err());
+
+        let attribution = scopes
+            .attribution("set:libstd+crate:core+crate:itertools")
+            .unwrap();
+        let of = |krate: &str| {
+            attribution
+                .0
+                .iter()
+                .filter(|(_, krates)| krates.iter().any(|k| k == krate))
+                .map(|(scope, _)| scope.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(of("core"), vec!["set:libstd", "crate:core"]);
+        assert_eq!(of("itertools"), vec!["crate:itertools"]);
+        assert!(scopes.attribution("set:libstd").unwrap().0.is_empty());
+    }
+}
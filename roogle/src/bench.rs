@@ -0,0 +1,148 @@
+//! The `bench` subcommand, measuring search over a corpus of queries.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use roogle_engine::query::{parse::parse_query_in, Arena};
+
+use crate::{
+    config::Config,
+    store::{load, warn_degraded},
+    DEFAULT_THRESHOLD,
+};
+
+/// Upper bounds of buckets to report the distribution of hit counts with.
+const HIT_BUCKETS: &[usize] = &[0, 1, 10, 100, 1000];
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    /// Scope to search in: `all`, `set:<SET>` or `crate:<CRATE>` [default: all].
+    #[structopt(long)]
+    scope: Option<String>,
+
+    /// Upper bound of scores for hits, where lower scores mean closer matches [default: 0.4].
+    #[structopt(long)]
+    threshold: Option<f32>,
+
+    /// File containing a query per line.
+    #[structopt(long, name = "FILE")]
+    queries: PathBuf,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    let corpus = std::fs::read_to_string(&opt.queries)
+        .with_context(|| format!("failed to read `{:?}`", opt.queries))?;
+    let queries = corpus
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let (index, scopes) = load(&config.index(opt.index), &config.scope(opt.scope))?;
+    warn_degraded(&scopes);
+    println!(
+        "loaded {} crates in {}",
+        index.crates().len(),
+        format_duration(start.elapsed())
+    );
+
+    let threshold = opt
+        .threshold
+        .or(config.threshold)
+        .unwrap_or(DEFAULT_THRESHOLD);
+    let mut latencies = vec![];
+    let mut hit_counts = vec![];
+    let mut failures = 0;
+    let mut arena = Arena::new();
+    for query in &queries {
+        let start = Instant::now();
+        arena.reset();
+        let result = parse_query_in(&arena, query)
+            .map_err(anyhow::Error::from)
+            .and_then(|parsed| {
+                let scope = scopes.resolve(&scopes.default)?;
+                Ok(index.search(&parsed, scope, threshold)?)
+            });
+        let elapsed = start.elapsed();
+        match result {
+            Ok(hits) => {
+                latencies.push(elapsed);
+                hit_counts.push(hits.len());
+            }
+            Err(e) => {
+                eprintln!("`{}` failed: {:#}", query, e);
+                failures += 1;
+            }
+        }
+    }
+    latencies.sort_unstable();
+    hit_counts.sort_unstable();
+
+    println!("queries: {} ({} failed)", queries.len(), failures);
+    if latencies.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "latency: p50 {}, p95 {}, max {}",
+        format_duration(percentile(&latencies, 50.0)),
+        format_duration(percentile(&latencies, 95.0)),
+        format_duration(latencies[latencies.len() - 1]),
+    );
+    println!(
+        "hits: p50 {}, p95 {}, max {}",
+        percentile(&hit_counts, 50.0),
+        percentile(&hit_counts, 95.0),
+        hit_counts[hit_counts.len() - 1],
+    );
+    let mut lower = 0;
+    for &upper in HIT_BUCKETS {
+        let count = hit_counts
+            .iter()
+            .filter(|&&n| lower <= n && n <= upper)
+            .count();
+        if lower == upper {
+            println!("  {:>9} hits: {}", upper, count);
+        } else {
+            println!("  {:>9} hits: {}", format!("{}-{}", lower, upper), count);
+        }
+        lower = upper + 1;
+    }
+    let count = hit_counts.iter().filter(|&&n| lower <= n).count();
+    println!("  {:>9} hits: {}", format!("{}-", lower), count);
+
+    Ok(())
+}
+
+/// The `p`th percentile of `sorted` by the nearest-rank method.
+fn percentile<T: Copy>(sorted: &[T], p: f64) -> T {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank() {
+        let sorted = (1..=20).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 50.0), 10);
+        assert_eq!(percentile(&sorted, 95.0), 19);
+        assert_eq!(percentile(&sorted, 100.0), 20);
+        assert_eq!(percentile(&[7], 50.0), 7);
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    io::Cursor,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use fnv::FnvHasher;
+use rocket::{
+    http::{ContentType, Header},
+    response::Responder,
+};
+use rustdoc_types::{Crate, Id, Item};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use roogle_engine::{
+    compare::{Similarities, Similarity},
+    function::Owner,
+    matcher::{DefaultMatcher, Matcher},
+    metadata::TraitImpls,
+    query::Query,
+    search,
+};
+
+use crate::store::write_atomically;
+
+/// A serialized response body along with its content type.
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub content_type: ContentType,
+    pub body: Arc<[u8]>,
+    /// Whether this response is served from [`ResponseCache`], exposed as `X-Cache`.
+    pub cached: bool,
+    /// Other headers of the response, cached along with it.
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl<'r> Responder<'r, 'static> for Rendered {
+    fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        let mut res = rocket::Response::build();
+        res.header(self.content_type).header(Header::new(
+            "X-Cache",
+            if self.cached { "HIT" } else { "MISS" },
+        ));
+        for (name, value) in self.headers {
+            res.header(Header::new(name, value));
+        }
+        res.sized_body(self.body.len(), Cursor::new(self.body)).ok()
+    }
+}
+
+struct Entry {
+    created: Instant,
+    etag: String,
+    rendered: Rendered,
+}
+
+/// Cache of serialized search responses, so that hot queries skip both search and serialization.
+///
+/// Entries expire after `ttl`, and once the index or scopes change, i.e. the `ETag` differs from
+/// the one an entry was rendered with.
+pub struct ResponseCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResponseCache {
+    /// Create a cache holding up to `capacity` responses. Caching is disabled if either `ttl` or
+    /// `capacity` is zero.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        ResponseCache {
+            ttl,
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&self, key: &str, etag: &str) -> Option<Rendered> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get(key) {
+            Some(entry) if entry.etag == etag && entry.created.elapsed() < self.ttl => {
+                Some(Rendered {
+                    cached: true,
+                    ..entry.rendered.clone()
+                })
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: String, etag: &str, rendered: Rendered) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.etag == etag && entry.created.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                created: Instant::now(),
+                etag: etag.to_owned(),
+                rendered,
+            },
+        );
+    }
+}
+
+/// Similarities of items scoring under the threshold of a query, keyed by the names of their
+/// crates and their ids.
+///
+/// All items of the crates here have been compared, so items missing from a crate scored the
+/// threshold or more.
+type Scores = HashMap<String, HashMap<Id, Similarities>>;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Comparisons {
+    /// Version of the index the scores were computed with.
+    version: String,
+    /// Scores keyed by FNV hashes of queries along with thresholds, which unlike `DefaultHasher`
+    /// are the same across Rust versions.
+    queries: HashMap<u64, QueryScores>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueryScores {
+    /// How many times the query has been searched for, to keep popular ones on eviction.
+    uses: u64,
+    scores: Arc<Scores>,
+}
+
+/// Cache of scores of items compared with queries, persisted across restarts so that popular
+/// queries stay warm after redeploys.
+///
+/// Scores are dropped once the version of the index differs from the one they were computed with.
+pub struct ComparisonCache {
+    path: PathBuf,
+    capacity: usize,
+    comparisons: Mutex<Comparisons>,
+}
+
+impl ComparisonCache {
+    /// Load the cache persisted at `path`, holding scores of up to `capacity` queries. It starts
+    /// empty if `path` doesn't exist or the scores are for another `version` of the index.
+    pub fn load(path: PathBuf, capacity: usize, version: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: String,
+        }
+
+        // Scores for other versions, which may have been written in older formats, are dropped
+        // without reading them.
+        let comparisons = match std::fs::read_to_string(&path) {
+            Ok(json) => match serde_json::from_str::<Version>(&json) {
+                Ok(Version { version: ref read }) if read == version => serde_json::from_str(&json)
+                    .with_context(|| format!("failed to deserialize `{:?}`", path))?,
+                _ => Comparisons::default(),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Comparisons::default(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read `{:?}`", path)),
+        };
+        let comparisons = if comparisons.version == version {
+            info!(
+                queries = comparisons.queries.len(),
+                "comparison cache loaded"
+            );
+            comparisons
+        } else {
+            Comparisons {
+                version: version.to_owned(),
+                queries: HashMap::new(),
+            }
+        };
+
+        Ok(ComparisonCache {
+            path,
+            capacity,
+            comparisons: Mutex::new(comparisons),
+        })
+    }
+
+    /// Persist the cache to the path it was loaded from.
+    pub fn save(&self) -> Result<()> {
+        let comparisons = self
+            .comparisons
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let json = serde_json::to_string(&*comparisons).context("serializing cache failed")?;
+        write_atomically(&self.path, &json)
+    }
+
+    /// Matcher for searching for `query` with `threshold` in `version` of the index, which serves
+    /// cached scores and records the others for [`ComparisonCache::insert`].
+    pub fn matcher(&self, version: &str, query: &Query<'_>, threshold: f32) -> CachedMatcher {
+        let mut hasher = FnvHasher::default();
+        query.hash(&mut hasher);
+        threshold.to_bits().hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut comparisons = self
+            .comparisons
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if comparisons.version != version {
+            comparisons.version = version.to_owned();
+            comparisons.queries.clear();
+        }
+        let cached = match comparisons.queries.get_mut(&key) {
+            Some(query) => {
+                query.uses += 1;
+                query.scores.clone()
+            }
+            None => Arc::default(),
+        };
+
+        CachedMatcher {
+            matcher: DefaultMatcher::new(threshold),
+            threshold,
+            version: version.to_owned(),
+            key,
+            cached,
+            computed: Mutex::default(),
+        }
+    }
+
+    /// Cache the scores `matcher` has computed, which must be of a search that compared all items
+    /// in its scope.
+    pub fn insert(&self, matcher: CachedMatcher) {
+        let computed = matcher
+            .computed
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        if computed.is_empty() || self.capacity == 0 {
+            return;
+        }
+
+        let mut comparisons = self
+            .comparisons
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if comparisons.version != matcher.version {
+            // The index has changed during the search.
+            return;
+        }
+        if comparisons.queries.len() >= self.capacity
+            && !comparisons.queries.contains_key(&matcher.key)
+        {
+            let least_used = comparisons
+                .queries
+                .iter()
+                .min_by_key(|(_, query)| query.uses)
+                .map(|(key, _)| *key);
+            if let Some(least_used) = least_used {
+                comparisons.queries.remove(&least_used);
+            }
+        }
+        let query = comparisons
+            .queries
+            .entry(matcher.key)
+            .or_insert_with(|| QueryScores {
+                uses: 1,
+                scores: Arc::default(),
+            });
+        Arc::make_mut(&mut query.scores).extend(computed);
+    }
+}
+
+/// [`Matcher`] serving scores from [`ComparisonCache`] for crates it has them for, and comparing
+/// items of the others with [`DefaultMatcher`].
+pub struct CachedMatcher {
+    matcher: DefaultMatcher,
+    threshold: f32,
+    version: String,
+    key: u64,
+    cached: Arc<Scores>,
+    computed: Mutex<Scores>,
+}
+
+impl Matcher for CachedMatcher {
+    fn compare(
+        &self,
+        query: &Query<'_>,
+        item: &Item,
+        krate: &Crate,
+        krate_name: &str,
+        owner: Option<Owner<'_>>,
+        impls: &TraitImpls,
+    ) -> search::Result<Similarities> {
+        if let Some(scores) = self.cached.get(krate_name) {
+            return Ok(scores
+                .get(&item.id)
+                .cloned()
+                .unwrap_or_else(|| Similarities(vec![Similarity::Continuous(f32::INFINITY)])));
+        }
+
+        let sims = self
+            .matcher
+            .compare(query, item, krate, krate_name, owner, impls)?;
+        let mut computed = self.computed.lock().unwrap_or_else(PoisonError::into_inner);
+        if !computed.contains_key(krate_name) {
+            computed.insert(krate_name.to_owned(), HashMap::new());
+        }
+        if sims.score_with(&query.weights) < self.threshold {
+            if let Some(scores) = computed.get_mut(krate_name) {
+                scores.insert(item.id.clone(), sims.clone());
+            }
+        }
+        Ok(sims)
+    }
+
+    // Scores are cached only for items `DefaultMatcher` scores below the threshold.
+    fn scores_names_by_distance(&self) -> bool {
+        self.matcher.scores_names_by_distance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use roogle_engine::{
+        query::{parse::parse_query_in, Arena},
+        search::Scope,
+        testing::load_fixture,
+    };
+
+    use super::*;
+
+    fn rendered(body: &str) -> Rendered {
+        Rendered {
+            content_type: ContentType::JSON,
+            body: body.as_bytes().into(),
+            cached: false,
+            headers: vec![],
+        }
+    }
+
+    #[test]
+    fn invalidate_on_etag_change() {
+        let cache = ResponseCache::new(Duration::from_secs(60), 2);
+        cache.insert("a".to_owned(), "v1", rendered("[]"));
+        assert!(cache.get("a", "v1").unwrap().cached);
+        assert!(cache.get("a", "v2").is_none());
+        assert!(cache.get("a", "v1").is_none());
+    }
+
+    #[test]
+    fn evict_oldest() {
+        let cache = ResponseCache::new(Duration::from_secs(60), 2);
+        cache.insert("a".to_owned(), "v1", rendered("1"));
+        cache.insert("b".to_owned(), "v1", rendered("2"));
+        cache.insert("c".to_owned(), "v1", rendered("3"));
+        assert!(cache.get("a", "v1").is_none());
+        assert!(cache.get("b", "v1").is_some());
+        assert!(cache.get("c", "v1").is_some());
+    }
+
+    #[test]
+    fn persist_comparisons() {
+        let index = load_fixture("mini_std");
+        let arena = Arena::new();
+        let query = parse_query_in(&arena, "fn (&[u8]) -> Result<&str, Utf8Error>").unwrap();
+        let scope = || Scope::Crate("mini_std".to_owned());
+        let path =
+            std::env::temp_dir().join(format!("roogle-comparisons-{}.json", std::process::id()));
+
+        let cache = ComparisonCache::load(path.clone(), 1, "v1").unwrap();
+        let matcher = cache.matcher("v1", &query, 0.4);
+        let hits = index.search_with(&matcher, &query, scope(), 0.4).unwrap();
+        cache.insert(matcher);
+        cache.save().unwrap();
+
+        let cache = ComparisonCache::load(path.clone(), 1, "v1").unwrap();
+        let matcher = cache.matcher("v1", &query, 0.4);
+        assert!(matcher.cached.contains_key("mini_std"));
+        let cached = index.search_with(&matcher, &query, scope(), 0.4).unwrap();
+        assert_eq!(
+            cached.iter().map(|hit| &hit.path).collect::<Vec<_>>(),
+            hits.iter().map(|hit| &hit.path).collect::<Vec<_>>()
+        );
+        // Similarities are cached as a whole, so that ties are broken the same way.
+        assert_eq!(
+            cached
+                .iter()
+                .map(|hit| hit.similarities())
+                .collect::<Vec<_>>(),
+            hits.iter()
+                .map(|hit| hit.similarities())
+                .collect::<Vec<_>>()
+        );
+
+        let cache = ComparisonCache::load(path.clone(), 1, "v2").unwrap();
+        assert!(cache.matcher("v2", &query, 0.4).cached.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+//! The `completions` subcommand, generating shell completion scripts.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Result;
+use structopt::{clap::Shell, StructOpt};
+
+use crate::{
+    config::Config,
+    store::{make_scopes, ALL_SCOPE},
+    Cli,
+};
+
+/// Flags taking a scope, whose values are completed with scopes in the index.
+const SCOPE_FLAGS: &[&str] = &["scope", "default-scope"];
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Index to complete scope names from [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    /// Shell to generate a completion script for.
+    #[structopt(possible_values = &Shell::variants())]
+    shell: Shell,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    let mut script = vec![];
+    Cli::clap().gen_completions_to("roogle", opt.shell, &mut script);
+    let script = String::from_utf8_lossy(&script);
+
+    // Scope names are just nice to have, so go without them if the index isn't there.
+    let mut scopes = make_scopes(&config.index(opt.index), ALL_SCOPE)
+        .map(|scopes| scopes.names())
+        .unwrap_or_default();
+    scopes.sort_unstable();
+
+    let script = complete_scopes(&script, opt.shell, &scopes);
+    std::io::stdout().lock().write_all(script.as_bytes())?;
+    Ok(())
+}
+
+/// Make `script` complete values of [`SCOPE_FLAGS`] with `scopes`, which clap can't do by itself
+/// as they aren't restricted to fixed values.
+fn complete_scopes(script: &str, shell: Shell, scopes: &[String]) -> String {
+    let mut completed = String::with_capacity(script.len());
+    // Whether the last line was the `case` branch for a scope flag in bash.
+    let mut in_scope_branch = false;
+    for line in script.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        match shell {
+            Shell::Bash if in_scope_branch && trimmed.starts_with("COMPREPLY=") => {
+                completed.push_str(&format!(
+                    "{}COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+                    indent,
+                    scopes.join(" ")
+                ));
+            }
+            Shell::Zsh
+                if SCOPE_FLAGS
+                    .iter()
+                    .any(|flag| trimmed.starts_with(&format!("'--{}=[", flag)))
+                    && trimmed.ends_with("]' \\") =>
+            {
+                // Colons separate fields of `_arguments` specs unless escaped.
+                let scopes = scopes
+                    .iter()
+                    .map(|scope| scope.replace(':', "\\:"))
+                    .collect::<Vec<_>>();
+                completed.push_str(line.trim_end_matches("' \\"));
+                completed.push_str(&format!(": :({})' \\", scopes.join(" ")));
+            }
+            Shell::Fish
+                if SCOPE_FLAGS
+                    .iter()
+                    .any(|flag| trimmed.contains(&format!(" -l {} ", flag))) =>
+            {
+                completed.push_str(line);
+                completed.push_str(&format!(" -r -f -a \"{}\"", scopes.join(" ")));
+            }
+            _ => completed.push_str(line),
+        }
+        completed.push('\n');
+
+        in_scope_branch = SCOPE_FLAGS
+            .iter()
+            .any(|flag| trimmed == format!("--{})", flag));
+    }
+    completed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_scope_names() {
+        let scopes = vec!["all".to_owned(), "crate:mini".to_owned()];
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut script = vec![];
+            Cli::clap().gen_completions_to("roogle", shell, &mut script);
+            let script = complete_scopes(&String::from_utf8(script).unwrap(), shell, &scopes);
+
+            let expected = match shell {
+                Shell::Zsh => "(all crate\\:mini)",
+                _ => "\"all crate:mini\"",
+            };
+            // `search`, `repl`, `tui`, `suggest`, `serve`, `bench`, `export` and `links` take a
+            // scope.
+            let count = if cfg!(feature = "server") { 8 } else { 7 };
+            assert_eq!(script.matches(expected).count(), count, "{}", script);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! The user configuration file, giving defaults to flags of subcommands.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{output::Format, store::ALL_SCOPE};
+
+/// Index to use when neither `--index` nor the configuration file gives one.
+pub const DEFAULT_INDEX: &str = "roogle-index";
+
+/// Defaults read from `~/.config/roogle/config.toml`, which flags given on the command line
+/// override.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub index: Option<PathBuf>,
+    pub scope: Option<String>,
+    pub threshold: Option<f32>,
+    pub limit: Option<usize>,
+    pub format: Option<Format>,
+    pub remote: Option<String>,
+}
+
+impl Config {
+    /// `flag` if given, or else the index in the configuration file or `roogle-index`.
+    pub fn index(&self, flag: Option<PathBuf>) -> PathBuf {
+        flag.or_else(|| self.index.clone())
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_INDEX))
+    }
+
+    /// `flag` if given, or else the scope in the configuration file or `all`.
+    pub fn scope(&self, flag: Option<String>) -> String {
+        flag.or_else(|| self.scope.clone())
+            .unwrap_or_else(|| ALL_SCOPE.to_owned())
+    }
+}
+
+/// Read the configuration file, or the defaults if there's none.
+pub fn load() -> Result<Config> {
+    let path = match dirs::config_dir() {
+        Some(dir) => dir.join("roogle").join("config.toml"),
+        None => return Ok(Config::default()),
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read `{:?}`", path)),
+    };
+    toml::from_str(&contents).with_context(|| format!("failed to parse `{:?}`", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_override_config() {
+        let config: Config = toml::from_str(
+            r#"
+            index = "/var/lib/roogle"
+            format = "table"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.index(None), PathBuf::from("/var/lib/roogle"));
+        assert_eq!(config.index(Some("idx".into())), PathBuf::from("idx"));
+        assert_eq!(config.scope(None), ALL_SCOPE);
+        assert_eq!(config.format, Some(Format::Table));
+    }
+}
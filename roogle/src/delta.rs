@@ -0,0 +1,75 @@
+//! The `delta` subcommand, emitting a delta which turns a crate in the index into a newer build of
+//! it, so that hosted indexes can be updated without shipping whole crates.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rustdoc_types::Crate;
+use structopt::StructOpt;
+
+use roogle_util::{delta, from_json, shake};
+
+use crate::{
+    config::Config,
+    store::{is_valid_crate_name, validate_crate, write_atomically},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    /// Write the delta to `PATH` instead of `<INDEX>/delta/<CRATE>.json`, e.g. to ship it to
+    /// another index with the same crate.
+    #[structopt(short, long, name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Name of the crate in the index.
+    #[structopt(name = "CRATE")]
+    krate: String,
+
+    /// Path to rustdoc JSON of the new version of the crate.
+    new: PathBuf,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    let dir = config.index(opt.index);
+    if !is_valid_crate_name(&opt.krate) {
+        bail!("invalid crate name `{}`", opt.krate);
+    }
+
+    let old = read(&dir.join("crate").join(format!("{}.json", opt.krate)))?;
+    let new = read(&opt.new)?;
+    validate_crate(&new)?;
+    // Crates are shaken when they are loaded, so leave out what would be shaken off anyway.
+    let delta = delta::diff(&old, &shake(new)).context("failed to compute delta")?;
+
+    let path = opt
+        .output
+        .unwrap_or_else(|| dir.join("delta").join(format!("{}.json", opt.krate)));
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{:?}`", parent))?;
+    }
+    let json = serde_json::to_string(&delta).context("failed to serialize delta")?;
+    write_atomically(&path, &json)?;
+    println!(
+        "delta of `{}` written to `{}`: {} added, {} changed, {} removed",
+        opt.krate,
+        path.display(),
+        delta.added.len(),
+        delta.changed.len(),
+        delta.removed.len()
+    );
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Crate> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read `{:?}`", path))?;
+    from_json(&json).with_context(|| format!("failed to deserialize `{:?}`", path))
+}
@@ -0,0 +1,198 @@
+//! The `export` subcommand, writing items into the `search-index.js` format of rustdoc so that
+//! static documentation sites can merge them into their own search.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use roogle_engine::search::Hit;
+
+use crate::{
+    config::Config,
+    store::{load, warn_degraded},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    /// Crates to export: `all`, `set:<SET>` or `crate:<CRATE>` [default: all].
+    #[structopt(long)]
+    scope: Option<String>,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    let (index, scopes) = load(&config.index(opt.index), &config.scope(opt.scope))?;
+    warn_degraded(&scopes);
+    let hits = index.items(scopes.resolve(&scopes.default)?)?;
+    write_search_index(&mut std::io::stdout().lock(), &hits)
+}
+
+/// Types of items in `search-index.js`, numbered the same as `ItemType` in rustdoc.
+mod item_type {
+    pub const STRUCT: u8 = 3;
+    pub const ENUM: u8 = 4;
+    pub const FUNCTION: u8 = 5;
+    pub const TRAIT: u8 = 8;
+    pub const METHOD: u8 = 11;
+    pub const PRIMITIVE: u8 = 15;
+    pub const UNION: u8 = 19;
+}
+
+/// Items of a crate in `search-index.js`, where the `n`th item is described by the `n`th element
+/// of each array.
+#[derive(Debug, Default, Serialize)]
+struct CrateIndex {
+    /// Documentation of the crate itself.
+    doc: String,
+    /// Types of items.
+    t: Vec<u8>,
+    /// Names.
+    n: Vec<String>,
+    /// Paths of the modules, or empty if the same as the previous item's.
+    q: Vec<String>,
+    /// Descriptions.
+    d: Vec<String>,
+    /// 1-based indices into `p` of the types methods belong to, or 0 for functions.
+    i: Vec<usize>,
+    /// Signatures for type-based search, which are omitted as rustdoc allows.
+    f: Vec<Option<()>>,
+    /// Types methods belong to, as pairs of their item types and names.
+    p: Vec<(u8, String)>,
+}
+
+/// Write `hits` into `w` as `search-index.js` of rustdoc, grouped by crate.
+pub fn write_search_index(w: &mut impl Write, hits: &[Hit]) -> Result<()> {
+    let mut krates = BTreeMap::<&str, (CrateIndex, HashMap<(u8, String), usize>)>::new();
+    for hit in hits {
+        // Hits from a server don't know their crates, so fall back on the first path segment.
+        let krate = match hit.krate() {
+            "" => hit.path.first().map_or("", String::as_str),
+            krate => krate,
+        };
+        let (index, parents) = krates.entry(krate).or_default();
+
+        let page = hit.link.last().map_or("", String::as_str);
+        let (ty, module, parent) = match page.split_once(".html#method.") {
+            Some((parent, _)) => {
+                let parent = parent.split_once('.').map(|(kind, name)| {
+                    let ty = match kind {
+                        "enum" => item_type::ENUM,
+                        "union" => item_type::UNION,
+                        "trait" => item_type::TRAIT,
+                        "primitive" => item_type::PRIMITIVE,
+                        _ => item_type::STRUCT,
+                    };
+                    (ty, name.to_owned())
+                });
+                // Drop the names of the type and the method.
+                (item_type::METHOD, hit.path.len().saturating_sub(2), parent)
+            }
+            None => (item_type::FUNCTION, hit.path.len().saturating_sub(1), None),
+        };
+        let module = match &hit.path[..module] {
+            // Methods of primitives, which have no module.
+            [] => krate.to_owned(),
+            module => module.join("::"),
+        };
+
+        let parent = match parent {
+            Some(parent) => {
+                let next = parents.len() + 1;
+                let i = *parents.entry(parent.clone()).or_insert(next);
+                if i == next {
+                    index.p.push(parent);
+                }
+                i
+            }
+            None => 0,
+        };
+
+        let same_module = index
+            .q
+            .iter()
+            .rev()
+            .find(|q| !q.is_empty())
+            .is_some_and(|q| *q == module);
+        index.t.push(ty);
+        index.n.push(hit.name.clone());
+        index
+            .q
+            .push(if same_module { String::new() } else { module });
+        index.d.push(hit.summary.clone().unwrap_or_default());
+        index.i.push(parent);
+        index.f.push(None);
+    }
+
+    // The JSON is embedded in a single-quoted JavaScript string, one crate per line.
+    writeln!(w, "var searchIndex = JSON.parse('{{\\")?;
+    for (i, (krate, (index, _))) in krates.iter().enumerate() {
+        let json = serde_json::to_string(index).context("serializing search index failed")?;
+        let separator = if i + 1 < krates.len() { "," } else { "" };
+        writeln!(
+            w,
+            "{}:{}{}\\",
+            escape(&serde_json::to_string(krate)?),
+            escape(&json),
+            separator
+        )?;
+    }
+    writeln!(w, "}}');")?;
+    writeln!(
+        w,
+        "if (window.initSearch) {{window.initSearch(searchIndex)}};"
+    )?;
+    Ok(())
+}
+
+/// Escape `json` to embed it in a single-quoted JavaScript string.
+fn escape(json: &str) -> String {
+    json.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_functions_and_methods() {
+        let hits: Vec<Hit> = serde_json::from_value(serde_json::json!([
+            {
+                "name": "parse_u32",
+                "kind": "function",
+                "path": ["mini", "parse_u32"],
+                "link": ["mini", "fn.parse_u32.html"],
+                "signature": "fn parse_u32(s: &str) -> u32",
+                "summary": "Parses a `u32`.",
+            },
+            {
+                "name": "len",
+                "kind": "method",
+                "path": ["mini", "Foo", "len"],
+                "link": ["mini", "struct.Foo.html#method.len"],
+                "signature": "fn len(&self) -> usize",
+                "summary": "It's the length.",
+            },
+        ]))
+        .unwrap();
+
+        let mut js = vec![];
+        write_search_index(&mut js, &hits).unwrap();
+        assert_eq!(
+            String::from_utf8(js).unwrap(),
+            r#"var searchIndex = JSON.parse('{\
+"mini":{"doc":"","t":[5,11],"n":["parse_u32","len"],"q":["mini",""],"d":["Parses a `u32`.","It\'s the length."],"i":[0,1],"f":[null,null],"p":[[3,"Foo"]]}\
+}');
+if (window.initSearch) {window.initSearch(searchIndex)};
+"#
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::info;
+
+use roogle_engine::{compare::Weights, query::parse::QueryParseError, search::ItemKinds, Index};
+
+use crate::{
+    cache::ComparisonCache,
+    server::{perform_search, RequestId, SearchRequest, Shared},
+    store::Scopes,
+    DEFAULT_LIMIT, DEFAULT_THRESHOLD, MAX_LIMIT,
+};
+
+pub mod proto {
+    tonic::include_proto!("roogle");
+}
+
+use proto::roogle_server::{Roogle, RoogleServer};
+
+/// gRPC counterpart of the HTTP routes, sharing the same index and scopes.
+pub struct RoogleService {
+    index: Shared<Index>,
+    scopes: Shared<Scopes>,
+    comparisons: Option<Arc<ComparisonCache>>,
+}
+
+/// Serve [`RoogleService`] on `addr` until `shutdown` completes, letting in-flight calls finish.
+pub async fn serve(
+    addr: SocketAddr,
+    index: Shared<Index>,
+    scopes: Shared<Scopes>,
+    comparisons: Option<Arc<ComparisonCache>>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    info!(%addr, "serving gRPC");
+    Server::builder()
+        .add_service(RoogleServer::new(RoogleService {
+            index,
+            scopes,
+            comparisons,
+        }))
+        .serve_with_shutdown(addr, shutdown)
+        .await?;
+    info!("gRPC shut down gracefully");
+    Ok(())
+}
+
+fn request_id<T>(request: &Request<T>) -> RequestId {
+    let id = request
+        .metadata()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or("grpc");
+    RequestId(id.to_owned())
+}
+
+/// Status of a failed search, which is `INVALID_ARGUMENT` if it's the request at fault, i.e. its
+/// query or scope, and `INTERNAL` otherwise.
+fn search_status(e: anyhow::Error) -> Status {
+    if e.is::<QueryParseError>() || e.is::<roogle_util::store::Error>() {
+        Status::invalid_argument(format!("{:#}", e))
+    } else {
+        Status::internal(format!("{:#}", e))
+    }
+}
+
+#[tonic::async_trait]
+impl Roogle for RoogleService {
+    async fn search(
+        &self,
+        request: Request<proto::SearchRequest>,
+    ) -> Result<Response<proto::SearchResponse>, Status> {
+        let request_id = request_id(&request);
+        let proto::SearchRequest {
+            query,
+            scope,
+            threshold,
+            limit,
+            offset,
+            exact,
+            weights,
+            kinds,
+        } = request.into_inner();
+        let weights = weights
+            .parse::<Weights>()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let kinds = kinds
+            .parse::<ItemKinds>()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let request = SearchRequest {
+            query,
+            scope: Some(scope).filter(|scope| !scope.is_empty()),
+            threshold: if threshold > 0.0 {
+                threshold
+            } else {
+                DEFAULT_THRESHOLD
+            },
+            limit: if limit > 0 {
+                limit as usize
+            } else {
+                DEFAULT_LIMIT
+            },
+            offset: offset as usize,
+            debug: false,
+            exact,
+            weights,
+            kinds,
+            newtypes: false,
+        };
+        // Searching takes long enough to stall other calls on the runtime's threads.
+        let (index, scopes, comparisons) = (
+            self.index.clone(),
+            self.scopes.clone(),
+            self.comparisons.clone(),
+        );
+        let found = rocket::tokio::task::spawn_blocking(move || {
+            perform_search(
+                &request,
+                &request_id,
+                &index.read(),
+                &scopes.read(),
+                comparisons.as_deref(),
+            )
+        })
+        .await
+        .map_err(|e| Status::internal(format!("failed to join search: {}", e)))?
+        .map_err(search_status)?;
+
+        let hits = found
+            .hits
+            .into_iter()
+            .map(|hit| proto::Hit {
+                score: hit.score,
+                rank: hit.rank.unwrap_or_default() as u32,
+                name: hit.name,
+                kind: hit.kind.name().to_owned(),
+                path: hit.path,
+                link: hit.link,
+                docs: hit.docs.unwrap_or_default(),
+                summary: hit.summary.unwrap_or_default(),
+                feature: hit.feature.unwrap_or_default(),
+                scopes: hit.scopes,
+            })
+            .collect();
+        Ok(Response::new(proto::SearchResponse {
+            hits,
+            found_in_other_crates: found.found_in_other_crates,
+        }))
+    }
+
+    async fn suggest(
+        &self,
+        request: Request<proto::SuggestRequest>,
+    ) -> Result<Response<proto::SuggestResponse>, Status> {
+        let proto::SuggestRequest {
+            prefix,
+            scope,
+            limit,
+        } = request.into_inner();
+        let limit = if limit > 0 {
+            (limit as usize).min(MAX_LIMIT)
+        } else {
+            DEFAULT_LIMIT
+        };
+
+        let krates = {
+            let scopes = self.scopes.read();
+            let scope = if scope.is_empty() {
+                &scopes.default
+            } else {
+                &scope
+            };
+            scopes
+                .resolve(scope)
+                .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?
+                .flatten()
+        };
+
+        let prefix = prefix.to_lowercase();
+        let index = self.index.read();
+        let mut paths = krates
+            .iter()
+            .filter_map(|krate| index.crates().get(krate))
+            .flat_map(|krate| krate.paths.values())
+            .filter(|summary| {
+                summary
+                    .path
+                    .last()
+                    .is_some_and(|name| name.to_lowercase().starts_with(&prefix))
+            })
+            .map(|summary| summary.path.join("::"))
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths.dedup();
+        paths.truncate(limit);
+
+        Ok(Response::new(proto::SuggestResponse { paths }))
+    }
+
+    async fn list_scopes(
+        &self,
+        _: Request<proto::ListScopesRequest>,
+    ) -> Result<Response<proto::ListScopesResponse>, Status> {
+        let scopes = self.scopes.read().names();
+
+        Ok(Response::new(proto::ListScopesResponse { scopes }))
+    }
+}
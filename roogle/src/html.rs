@@ -0,0 +1,104 @@
+use std::fmt::Write;
+
+use roogle_engine::search::Hit;
+
+/// Crates documented at doc.rust-lang.org rather than docs.rs.
+const STD_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Render a minimal results page for browsers, so the server is usable without the frontend.
+///
+/// If there are no hits, it points to `found_in_other_crates` which have some.
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub fn results(query: &str, hits: &[Hit], found_in_other_crates: &[String]) -> String {
+    let mut html = String::new();
+    // Writing to `String` never fails.
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{query} - Roogle</title>
+<style>
+body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }}
+input[name=query] {{ width: 40em; max-width: 100%; font-family: monospace; }}
+li {{ margin-bottom: 1em; }}
+code {{ display: block; }}
+.path {{ color: #666; font-size: small; }}
+.with {{ display: block; color: #666; font-family: monospace; }}
+.feature {{ color: #a60; font-size: small; margin-left: 1em; }}
+</style>
+</head>
+<body>
+<form action="/v1/search" method="get">
+<input name="query" value="{query}" autofocus>
+<button>Search</button>
+</form>
+"#,
+        query = escape(query)
+    );
+
+    if hits.is_empty() && !found_in_other_crates.is_empty() {
+        let _ = writeln!(
+            html,
+            "<p>No results. Found in other crates: {}.</p>",
+            escape(&found_in_other_crates.join(", "))
+        );
+    } else if hits.is_empty() {
+        html.push_str("<p>No results.</p>\n");
+    } else {
+        html.push_str("<ol>\n");
+        for hit in hits {
+            let _ = writeln!(
+                html,
+                r#"<li><a href="{link}"><code>{signature}</code></a>{with}<span class="path">{path}</span>{feature}</li>"#,
+                link = escape(&link(hit)),
+                signature = escape(&hit.signature),
+                with = hit
+                    .with_clause()
+                    .map(|with| format!(r#"<span class="with">{}</span>"#, escape(&with)))
+                    .unwrap_or_default(),
+                path = escape(&hit.path.join("::")),
+                feature = hit
+                    .feature
+                    .as_ref()
+                    .map(|feature| format!(
+                        r#"<span class="feature">feature {}</span>"#,
+                        escape(feature)
+                    ))
+                    .unwrap_or_default(),
+            );
+        }
+        html.push_str("</ol>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+/// Absolute URL of the documentation of `hit`.
+pub fn link(hit: &Hit) -> String {
+    let krate = hit.link.first().map(String::as_str).unwrap_or_default();
+    let link = hit.link.join("/");
+    if STD_CRATES.contains(&krate) {
+        format!("https://doc.rust-lang.org/{}", link)
+    } else {
+        format!("https://docs.rs/{}/latest/{}", krate, link)
+    }
+}
+
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
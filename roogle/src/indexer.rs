@@ -0,0 +1,302 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use rustdoc_types::Crate;
+use semver::VersionReq;
+use structopt::StructOpt;
+use tracing::{debug, warn};
+
+use roogle_util::{from_json, search_index, shake};
+
+use crate::{
+    config::Config,
+    store::{is_valid_crate_name, persist_crate, remove_delta, validate_crate, write_atomically},
+};
+
+#[derive(Debug, StructOpt)]
+pub enum Opt {
+    /// Build a crate on crates.io and add it to the index.
+    Crate {
+        #[structopt(flatten)]
+        target: Target,
+
+        /// Version requirement of the crate, defaulting to the latest one.
+        #[structopt(long)]
+        version: Option<String>,
+
+        /// Name of the crate on crates.io.
+        name: String,
+    },
+    /// Build crates listed in a manifest and add them to the index.
+    ///
+    /// Each line of the manifest is a crate name optionally followed by a version requirement,
+    /// e.g. `serde 1.0`. Empty lines and ones starting with `#` are ignored.
+    List {
+        #[structopt(flatten)]
+        target: Target,
+
+        /// Register the listed crates as a set named `SET` as well.
+        #[structopt(long, name = "SET")]
+        set: Option<String>,
+
+        /// Path to the manifest.
+        manifest: PathBuf,
+    },
+    /// Import crates from `search-index.js` of rustdoc where rustdoc JSON isn't available.
+    ///
+    /// Crates imported this way only have names, paths, summaries and rough signatures of their
+    /// functions and methods, so type-based queries match them loosely.
+    SearchIndex {
+        #[structopt(flatten)]
+        target: Target,
+
+        /// Path to `search-index.js`.
+        file: PathBuf,
+    },
+}
+
+// Where and how to write crates into. Not a doc comment, as structopt would take it for the
+// description of subcommands flattening this.
+#[derive(Debug, StructOpt)]
+pub struct Target {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long = "index", name = "INDEX")]
+    index_flag: Option<PathBuf>,
+
+    /// `--index` or its default from the configuration file.
+    #[structopt(skip)]
+    index: PathBuf,
+
+    /// Toolchain to build rustdoc JSON with.
+    #[structopt(long, default_value = "nightly")]
+    toolchain: String,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    match opt {
+        Opt::Crate {
+            mut target,
+            version,
+            name,
+        } => {
+            target.index = config.index(target.index_flag.take());
+            add_crate(&target, &name, version.as_deref())
+        }
+        Opt::List {
+            mut target,
+            set,
+            manifest,
+        } => {
+            target.index = config.index(target.index_flag.take());
+            add_crates(&target, &manifest, set.as_deref())
+        }
+        Opt::SearchIndex { mut target, file } => {
+            target.index = config.index(target.index_flag.take());
+            import_search_index(&target, &file)
+        }
+    }
+}
+
+/// Build crates listed in `manifest`, carrying on even if some of them fail.
+fn add_crates(target: &Target, manifest: &Path, set: Option<&str>) -> Result<()> {
+    if let Some(set) = set {
+        if !is_valid_crate_name(set) {
+            bail!("invalid set name `{}`", set);
+        }
+    }
+    let manifest = std::fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read `{:?}`", manifest))?;
+    let krates = manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut words = line.split_whitespace();
+            // SAFETY: `line` isn't empty.
+            let name = words.next().unwrap();
+            (name, words.next())
+        })
+        .collect::<Vec<_>>();
+
+    let mut failed = vec![];
+    for (i, (name, version)) in krates.iter().enumerate() {
+        eprintln!("[{}/{}] indexing `{}`", i + 1, krates.len(), name);
+        if let Err(e) = add_crate(target, name, *version) {
+            eprintln!("error: {:#}", e);
+            failed.push(*name);
+        }
+    }
+
+    if let Some(set) = set {
+        let krates = krates
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !failed.contains(name))
+            .collect::<Vec<_>>();
+        let dir = target.index.join("set");
+        std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{:?}`", dir))?;
+        let json = serde_json::to_string(&krates).context("failed to serialize set")?;
+        write_atomically(&dir.join(format!("{}.json", set)), &json)?;
+        println!("set `{}` registered", set);
+    }
+
+    if !failed.is_empty() {
+        bail!("failed to index {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Build crate `name` and write it into the index, replacing the existing one.
+fn add_crate(target: &Target, name: &str, version: Option<&str>) -> Result<()> {
+    if !is_valid_crate_name(name) {
+        bail!("invalid crate name `{}`", name);
+    }
+
+    let krate = index_crate(name, version, &target.toolchain)?;
+    validate_crate(&krate)?;
+
+    let dir = target.index.join("crate");
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{:?}`", dir))?;
+    persist_crate(&target.index, name, &shake(krate))?;
+    remove_delta(&target.index, name)?;
+    println!("crate `{}` indexed", name);
+    Ok(())
+}
+
+/// Write crates described by `search-index.js` at `file` into the index.
+fn import_search_index(target: &Target, file: &Path) -> Result<()> {
+    let js =
+        std::fs::read_to_string(file).with_context(|| format!("failed to read `{:?}`", file))?;
+    let krates =
+        search_index::parse(&js).with_context(|| format!("failed to parse `{:?}`", file))?;
+
+    let dir = target.index.join("crate");
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{:?}`", dir))?;
+    let mut krates = krates.into_iter().collect::<Vec<_>>();
+    krates.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, krate) in krates {
+        if !is_valid_crate_name(&name) {
+            bail!("invalid crate name `{}`", name);
+        }
+        validate_crate(&krate)?;
+        persist_crate(&target.index, &name, &shake(krate))?;
+        remove_delta(&target.index, &name)?;
+        println!("crate `{}` indexed", name);
+    }
+    Ok(())
+}
+
+/// Build rustdoc JSON of crate `name` published on crates.io.
+///
+/// This generates a throwaway package depending on the crate and documents it with `toolchain`,
+/// which has to be a nightly one as JSON output is unstable yet.
+pub fn index_crate(name: &str, version: Option<&str>, toolchain: &str) -> Result<Crate> {
+    if let Some(version) = version {
+        validate_version(version)?;
+    }
+    let dir = workdir(name)?;
+    let result = build(&dir, name, version, toolchain);
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        warn!("failed to remove `{:?}`: {}", dir, e);
+    }
+    result
+}
+
+/// Check that `version` is a version requirement like `1.0` or `>=0.3, <0.5`.
+pub fn validate_version(version: &str) -> Result<()> {
+    VersionReq::parse(version)
+        .map(|_| ())
+        .with_context(|| format!("invalid version requirement `{}`", version))
+}
+
+fn workdir(name: &str) -> Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let dir = std::env::temp_dir().join(format!("roogle-{}-{}", name, nanos));
+    std::fs::create_dir_all(dir.join("src"))
+        .with_context(|| format!("failed to create `{:?}`", dir))?;
+    Ok(dir)
+}
+
+/// Manifest of the package depending on crate `name` of `version`.
+fn manifest(name: &str, version: Option<&str>) -> Result<String> {
+    let mut manifest = toml::toml! {
+        [package]
+        name = "roogle-indexer"
+        version = "0.0.0"
+        edition = "2021"
+
+        [workspace]
+    };
+    let dependencies = [(
+        name.to_owned(),
+        toml::Value::String(version.unwrap_or("*").to_owned()),
+    )];
+    if let Some(manifest) = manifest.as_table_mut() {
+        manifest.insert(
+            "dependencies".to_owned(),
+            toml::Value::Table(dependencies.into_iter().collect()),
+        );
+    }
+    toml::to_string(&manifest).context("failed to serialize manifest")
+}
+
+fn build(dir: &Path, name: &str, version: Option<&str>, toolchain: &str) -> Result<Crate> {
+    let manifest = manifest(name, version)?;
+    std::fs::write(dir.join("Cargo.toml"), manifest).context("failed to write manifest")?;
+    std::fs::write(dir.join("src").join("lib.rs"), "").context("failed to write `lib.rs`")?;
+
+    debug!(name, ?version, toolchain, "building rustdoc JSON");
+    let output = Command::new("cargo")
+        .arg(format!("+{}", toolchain))
+        .args(["doc", "--no-deps", "--package", name])
+        .env("RUSTDOCFLAGS", "-Z unstable-options --output-format json")
+        .current_dir(dir)
+        .output()
+        .context("failed to run cargo")?;
+    if !output.status.success() {
+        bail!(
+            "documenting `{}` failed:\n{}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let path = dir
+        .join("target")
+        .join("doc")
+        .join(format!("{}.json", name.replace('-', "_")));
+    let json =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read `{:?}`", path))?;
+    from_json(&json).with_context(|| format!("failed to deserialize `{:?}`", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_versions() {
+        assert!(validate_version("1.0").is_ok());
+        assert!(validate_version(">=0.3, <0.5").is_ok());
+        let e = validate_version("1.0\"\nevil = { path = \"/\" }").unwrap_err();
+        assert!(e.to_string().starts_with("invalid version requirement"));
+    }
+
+    #[test]
+    fn depend_on_crates_in_manifests() {
+        let manifest = manifest("serde", Some("1.0")).unwrap();
+        assert!(
+            manifest.contains("[dependencies]\nserde = \"1.0\"\n"),
+            "{}",
+            manifest
+        );
+        assert!(manifest.contains("[workspace]"), "{}", manifest);
+    }
+}
@@ -0,0 +1,240 @@
+//! The `links` subcommand, checking that hits link to documentation which exists, as broken links,
+//! e.g. of methods of primitive types, otherwise only come to light when users follow them.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+
+use roogle_engine::search::Hit;
+
+use crate::{
+    config::Config,
+    html,
+    store::{load, warn_degraded},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    /// Crates to check: `all`, `set:<SET>` or `crate:<CRATE>` [default: all].
+    #[structopt(long)]
+    scope: Option<String>,
+
+    /// Documentation to check links in, e.g. `target/doc`, instead of docs.rs and
+    /// doc.rust-lang.org.
+    #[structopt(long, name = "DIR")]
+    docs: Option<PathBuf>,
+
+    /// Print every broken link as well as their patterns.
+    #[structopt(short, long)]
+    verbose: bool,
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    let (index, scopes) = load(&config.index(opt.index), &config.scope(opt.scope))?;
+    warn_degraded(&scopes);
+    let hits = index.items(scopes.resolve(&scopes.default)?)?;
+
+    let mut pages = Pages {
+        docs: opt.docs,
+        ids: HashMap::new(),
+    };
+    let mut patterns = BTreeMap::<String, usize>::new();
+    let mut stdout = std::io::stdout().lock();
+    let mut broken = 0;
+    for hit in &hits {
+        let Some(problem) = pages.check(hit)? else {
+            continue;
+        };
+        if opt.verbose {
+            writeln!(stdout, "{}: {}", pages.url(hit), problem)?;
+        }
+        *patterns
+            .entry(format!("{} ({})", pattern(hit), problem.reason()))
+            .or_default() += 1;
+        broken += 1;
+    }
+
+    let mut patterns = patterns.into_iter().collect::<Vec<_>>();
+    patterns.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    for (pattern, count) in patterns {
+        writeln!(stdout, "{:>6}  {}", count, pattern)?;
+    }
+    if broken > 0 {
+        bail!("{} of {} links are broken", broken, hits.len());
+    }
+    writeln!(stdout, "all {} links resolve", hits.len())?;
+    Ok(())
+}
+
+/// Why a link doesn't resolve.
+#[derive(Debug, PartialEq, Eq)]
+enum Problem {
+    NoPage,
+    NoAnchor(String),
+}
+
+impl Problem {
+    fn reason(&self) -> &'static str {
+        match self {
+            Problem::NoPage => "no page",
+            Problem::NoAnchor(_) => "no anchor",
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::NoPage => write!(f, "no page"),
+            Problem::NoAnchor(anchor) => write!(f, "no anchor `{}`", anchor),
+        }
+    }
+}
+
+/// Pages of documentation, either in a directory or on the web, along with the ids of the elements
+/// of the ones fetched so far, or `None` for missing ones.
+struct Pages {
+    docs: Option<PathBuf>,
+    ids: HashMap<String, Option<HashSet<String>>>,
+}
+
+impl Pages {
+    /// URL of the documentation of `hit`, which is a path in the directory if any.
+    fn url(&self, hit: &Hit) -> String {
+        match self.docs {
+            Some(ref docs) => docs.join(hit.link.join("/")).display().to_string(),
+            None => html::link(hit),
+        }
+    }
+
+    /// Why the link of `hit` doesn't resolve, if it doesn't.
+    fn check(&mut self, hit: &Hit) -> Result<Option<Problem>> {
+        let url = self.url(hit);
+        let (page, anchor) = match url.split_once('#') {
+            Some((page, anchor)) => (page.to_owned(), Some(anchor)),
+            None => (url.clone(), None),
+        };
+        if !self.ids.contains_key(&page) {
+            let html = match self.docs {
+                Some(_) => read(&page)?,
+                None => fetch(&page)?,
+            };
+            self.ids.insert(page.clone(), html.as_deref().map(ids));
+        }
+        let problem = match (&self.ids[&page], anchor) {
+            (None, _) => Some(Problem::NoPage),
+            (Some(ids), Some(anchor)) if !ids.contains(anchor) => {
+                Some(Problem::NoAnchor(anchor.to_owned()))
+            }
+            _ => None,
+        };
+        Ok(problem)
+    }
+}
+
+fn read(path: &str) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(html) => Ok(Some(html)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read `{}`", path)),
+    }
+}
+
+#[cfg(feature = "remote")]
+fn fetch(url: &str) -> Result<Option<String>> {
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+    match ureq::get(url).timeout(TIMEOUT).call() {
+        Ok(response) => Ok(Some(
+            response
+                .into_string()
+                .with_context(|| format!("failed to read `{}`", url))?,
+        )),
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to fetch `{}`", url)),
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+fn fetch(_: &str) -> Result<Option<String>> {
+    bail!("checking links on the web requires the `remote` feature; give `--docs` instead")
+}
+
+/// Ids of the elements of `html`, which anchors of links refer to.
+fn ids(html: &str) -> HashSet<String> {
+    html.split(" id=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"'))
+        .map(|(id, _)| id.to_owned())
+        .collect()
+}
+
+/// Shape of the link of `hit` with names left out, e.g. `primitive.*.html#method.*` for
+/// `primitive.str.html#method.len`, so that links broken the same way are counted together.
+fn pattern(hit: &Hit) -> String {
+    let file = hit.link.last().map(String::as_str).unwrap_or_default();
+    let (page, anchor) = match file.split_once('#') {
+        Some((page, anchor)) => (page, Some(anchor)),
+        None => (file, None),
+    };
+    let kind = |s: &str| s.split_once('.').map_or(s, |(kind, _)| kind).to_owned();
+    let mut pattern = format!("{}.*.html", kind(page));
+    if let Some(anchor) = anchor {
+        pattern.push_str(&format!("#{}.*", kind(anchor)));
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(link: &[&str]) -> Hit {
+        serde_json::from_value(serde_json::json!({
+            "name": "len",
+            "kind": "method",
+            "path": ["mini", "len"],
+            "link": link,
+            "signature": "fn len(&self) -> usize",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn check_links_in_directory() {
+        let docs = std::env::temp_dir().join(format!("roogle-links-{}", std::process::id()));
+        std::fs::create_dir_all(docs.join("mini")).unwrap();
+        std::fs::write(
+            docs.join("mini").join("struct.Foo.html"),
+            r#"<section id="method.len" class="method"><h4 class="code-header">"#,
+        )
+        .unwrap();
+
+        let mut pages = Pages {
+            docs: Some(docs.clone()),
+            ids: HashMap::new(),
+        };
+        let found = hit(&["mini", "struct.Foo.html#method.len"]);
+        let no_anchor = hit(&["mini", "struct.Foo.html#tymethod.len"]);
+        let no_page = hit(&["mini", "primitive.str.html#method.len"]);
+        assert_eq!(pages.check(&found).unwrap(), None);
+        assert_eq!(
+            pages.check(&no_anchor).unwrap(),
+            Some(Problem::NoAnchor("tymethod.len".to_owned()))
+        );
+        assert_eq!(pages.check(&no_page).unwrap(), Some(Problem::NoPage));
+        assert_eq!(pattern(&no_page), "primitive.*.html#method.*");
+        assert_eq!(pattern(&hit(&["mini", "fn.parse.html"])), "fn.*.html");
+
+        std::fs::remove_dir_all(docs).unwrap();
+    }
+}
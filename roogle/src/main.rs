@@ -1,115 +1,154 @@
+#[cfg(feature = "server")]
 #[macro_use]
 extern crate rocket;
 
-use std::{collections::HashMap, path::PathBuf};
-
-use anyhow::{anyhow, Context, Result};
-use rocket::{
-    fairing::{Fairing, Info, Kind},
-    http::Header,
-    response::content,
-    State,
-};
-use rustdoc_types::Crate;
-use serde::Deserialize;
-use structopt::StructOpt;
-use tracing::{debug, warn};
-
-use roogle_engine::{query::parse::parse_query, search::Scope, Index};
-use roogle_util::shake;
-
-#[get("/search?<scope>", data = "<query>", rank = 2)]
-fn search_with_data(
-    query: &str,
-    scope: &str,
-    index: &State<Index>,
-    scopes: &State<Scopes>,
-) -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
-    search(query, scope, index, scopes)
+mod bench;
+#[cfg(feature = "server")]
+mod cache;
+mod completions;
+mod config;
+mod delta;
+mod export;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod html;
+mod indexer;
+mod links;
+#[cfg(feature = "server")]
+mod openapi;
+mod output;
+mod repl;
+mod search;
+#[cfg(feature = "server")]
+mod share;
+// Public so that the `uri!` macros Rocket generates for routes aren't reported as unused.
+#[cfg(feature = "server")]
+pub mod server;
+mod snapshot;
+mod store;
+mod suggest;
+mod tui;
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use structopt::{clap::AppSettings, StructOpt};
+
+use roogle_engine::query::parse::{capabilities, QueryParseError};
+
+/// Default threshold of similarity scores for hits.
+///
+/// NOTE(hkmatsumoto): Just a temporal value; maybe needs discussion in the future.
+const DEFAULT_THRESHOLD: f32 = 0.4;
+
+/// Default number of hits to return.
+const DEFAULT_LIMIT: usize = 30;
+
+/// Upper bound of the number of hits to return at once.
+#[cfg(feature = "server")]
+const MAX_LIMIT: usize = 100;
+
+/// A Rust API search engine.
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ArgRequiredElseHelp)]
+struct Cli {
+    /// Print features of the query language this version understands as JSON, e.g. for frontends
+    /// to check against, and exit.
+    #[structopt(long)]
+    capabilities: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-#[get("/search?<scope>&<query>")]
-fn search(
-    query: &str,
-    scope: &str,
-    index: &State<Index>,
-    scopes: &State<Scopes>,
-) -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
-    let scope = match scope.split(':').collect::<Vec<_>>().as_slice() {
-        ["set", set] => scopes
-            .inner()
-            .sets
-            .get(*set)
-            .context(format!("set `{}` not found", set))?,
-        ["crate", krate] => scopes
-            .inner()
-            .krates
-            .get(*krate)
-            .context(format!("krate `{}` not found", krate))?,
-        _ => Err(anyhow!("parsing scope `{}` failed", scope))?,
-    };
-    debug!(?scope);
-
-    let query = parse_query(query)
-        .ok()
-        .context(format!("parsing query `{}` failed", query))?
-        .1;
-    debug!(?query);
-
-    let hits = index
-        .search(
-            &query,
-            scope.clone(),
-            0.4, // NOTE(hkmatsumoto): Just a temporal value; maybe needs discussion in the future.
-        )
-        .with_context(|| format!("search with query `{:?}` failed", query))?;
-    let hits = hits
-        .into_iter()
-        .inspect(|hit| debug!(?hit.name, ?hit.link, similarities = ?hit.similarities(), score = ?hit.similarities().score()))
-        .take(30)
-        .collect::<Vec<_>>();
-
-    Ok(content::Json(
-        serde_json::to_string(&hits).context("serializing search result failed")?,
-    ))
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Search the index for a query and print hits.
+    Search(search::Opt),
+    /// Search the index interactively.
+    Repl(repl::Opt),
+    /// Browse hits in a terminal UI as queries are typed.
+    Tui(tui::Opt),
+    /// Find functions converting given types into another, printing them as JSON for editors.
+    Suggest(suggest::Opt),
+    /// Serve search over HTTP.
+    #[cfg(feature = "server")]
+    Serve(server::Opt),
+    /// Build rustdoc JSON of crates on crates.io and add them to the index.
+    Index(indexer::Opt),
+    /// Measure search over a corpus of queries.
+    Bench(bench::Opt),
+    /// Write functions and methods in the index as `search-index.js` of rustdoc.
+    Export(export::Opt),
+    /// Check that hits link to documentation which exists, reporting patterns of broken links.
+    Links(links::Opt),
+    /// Archive the crates in the index into a snapshot, or restore them from one.
+    Snapshot(snapshot::Opt),
+    /// Write a delta turning a crate in the index into a newer build of it.
+    Delta(delta::Opt),
+    /// Print a completion script for a shell.
+    Completions(completions::Opt),
 }
 
-#[get("/scopes")]
-fn scopes(
-    scopes: &State<Scopes>,
-) -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
-    let mut result = vec![];
-    for set in scopes.inner().sets.keys() {
-        result.push(format!("set:{}", set));
-    }
-    for krate in scopes.inner().krates.keys() {
-        result.push(format!("crate:{}", krate));
-    }
+fn main() {
+    init_logger();
 
-    Ok(content::Json(
-        serde_json::to_string(&result).context("serializing scopes failed")?,
-    ))
+    let cli = Cli::from_args();
+    let result: Result<()> = match cli.command {
+        // Clap shows help unless either is given.
+        _ if cli.capabilities => print_capabilities(),
+        None => Ok(()),
+        Some(command) => config::load().and_then(|config| match command {
+            Command::Search(opt) => search::run(opt, &config),
+            Command::Repl(opt) => repl::run(opt, &config),
+            Command::Tui(opt) => tui::run(opt, &config),
+            Command::Suggest(opt) => suggest::run(opt, &config),
+            #[cfg(feature = "server")]
+            Command::Serve(opt) => server::run(opt, &config),
+            Command::Index(opt) => indexer::run(opt, &config),
+            Command::Bench(opt) => bench::run(opt, &config),
+            Command::Export(opt) => export::run(opt, &config),
+            Command::Links(opt) => links::run(opt, &config),
+            Command::Snapshot(opt) => snapshot::run(opt, &config),
+            Command::Delta(opt) => delta::run(opt, &config),
+            Command::Completions(opt) => completions::run(opt, &config),
+        }),
+    };
+    if let Err(e) = result {
+        if is_broken_pipe(&e) {
+            // The reader, e.g. `head`, has got enough output.
+            return;
+        }
+        eprintln!("error: {:#}", e);
+        std::process::exit(exit_code(&e));
+    }
 }
 
-#[derive(Debug, StructOpt)]
-struct Opt {
-    #[structopt(short, long, name = "INDEX", default_value = "roogle-index")]
-    index: PathBuf,
+fn print_capabilities() -> Result<()> {
+    let json =
+        serde_json::to_string_pretty(&capabilities()).context("serializing capabilities failed")?;
+    writeln!(std::io::stdout().lock(), "{}", json)?;
+    Ok(())
 }
 
-#[launch]
-fn rocket() -> _ {
-    init_logger();
-
-    let opt = Opt::from_args();
+/// Exit status for `e`: 2 for malformed queries, 3 for failures in loading the index, and 1 for
+/// the others.
+fn exit_code(e: &anyhow::Error) -> i32 {
+    if e.downcast_ref::<QueryParseError>().is_some() {
+        2
+    } else if e.downcast_ref::<store::IndexError>().is_some() {
+        3
+    } else {
+        1
+    }
+}
 
-    let index = make_index(&opt).unwrap();
-    let scopes = make_scopes(&opt).unwrap();
-    rocket::build()
-        .attach(Cors)
-        .manage(index)
-        .manage(scopes)
-        .mount("/", routes![search, search_with_data, scopes])
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+    })
 }
 
 fn init_logger() {
@@ -129,107 +168,3 @@ fn init_logger() {
         .with(layer)
         .init();
 }
-
-fn make_index(opt: &Opt) -> Result<Index> {
-    let crates = std::fs::read_dir(format!("{}/crate", opt.index.display()))
-        .context("failed to read index files")?
-        .map(|entry| {
-            let entry = entry?;
-            let json = std::fs::read_to_string(entry.path())
-                .with_context(|| format!("failed to read `{:?}`", entry.file_name()))?;
-            let mut deserializer = serde_json::Deserializer::from_str(&json);
-            deserializer.disable_recursion_limit();
-            let krate = Crate::deserialize(&mut deserializer)
-                .with_context(|| format!("failed to deserialize `{:?}`", entry.file_name()))?;
-            let file_name = entry
-                .path()
-                .with_extension("")
-                .file_name()
-                .with_context(|| format!("failed to get file name from `{:?}`", entry.path()))?
-                .to_str()
-                .context("failed to get `&str` from `&OsStr`")?
-                .to_owned();
-            Ok((file_name, shake(krate)))
-        })
-        .filter_map(|res: Result<_, anyhow::Error>| {
-            if let Err(ref e) = res {
-                warn!("parsing a JSON file skipped: {}", e);
-            }
-            res.ok()
-        })
-        .collect::<HashMap<_, _>>();
-    Ok(Index { crates })
-}
-
-struct Scopes {
-    sets: HashMap<String, Scope>,
-    krates: HashMap<String, Scope>,
-}
-
-fn make_scopes(opt: &Opt) -> Result<Scopes> {
-    let krates: HashMap<String, Scope> =
-        std::fs::read_dir(format!("{}/crate", opt.index.display()))
-            .context("failed to read crate files")?
-            .map(|entry| {
-                let entry = entry?;
-                let path = entry.path();
-                let krate = path.file_stem().unwrap().to_str().unwrap(); // SAFETY: files in `roogle-index` has a name.
-
-                Ok((krate.to_owned(), Scope::Crate(krate.to_owned())))
-            })
-            .filter_map(|res: Result<_, anyhow::Error>| {
-                if let Err(ref e) = res {
-                    warn!("registering a scope skipped: {}", e)
-                }
-                res.ok()
-            })
-            .collect();
-    let sets: HashMap<String, Scope> =
-        match std::fs::read_dir(format!("{}/set", opt.index.display())) {
-            Err(e) => {
-                warn!("registering sets skipped: {}", e);
-                HashMap::default()
-            }
-            Ok(entry) => {
-                entry
-                    .map(|entry| {
-                        let entry = entry?;
-                        let path = entry.path();
-                        let json = std::fs::read_to_string(&path)
-                            .context(format!("failed to read `{:?}`", path))?;
-                        let set = path.file_stem().unwrap().to_str().unwrap().to_owned(); // SAFETY: files in `roogle-index` has a name.
-                        let krates = serde_json::from_str::<Vec<String>>(&json)
-                            .context(format!("failed to deserialize set `{}`", &set))?;
-
-                        Ok((set, Scope::Set(krates)))
-                    })
-                    .filter_map(|res: Result<_, anyhow::Error>| {
-                        if let Err(ref e) = res {
-                            warn!("registering a scope skipped: {}", e)
-                        }
-                        res.ok()
-                    })
-                    .collect()
-            }
-        };
-    Ok(Scopes { sets, krates })
-}
-
-struct Cors;
-
-#[rocket::async_trait]
-impl Fairing for Cors {
-    fn info(&self) -> Info {
-        Info {
-            name: "CORS",
-            kind: Kind::Response,
-        }
-    }
-
-    async fn on_response<'r>(&self, _: &'r rocket::Request<'_>, res: &mut rocket::Response<'r>) {
-        res.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        res.set_header(Header::new("Access-Control-Allow-Methods", "GET"));
-        res.set_header(Header::new("Access-Control-Allow-Headers", "Content-Type"));
-        res.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-    }
-}
@@ -0,0 +1,874 @@
+use roogle_engine::{query::parse::QueryParseError, search::Hit};
+use schemars::gen::SchemaGenerator;
+use serde_json::{json, Value};
+
+use crate::{server::API_BASE, store::ALL_SCOPE, DEFAULT_LIMIT, DEFAULT_THRESHOLD, MAX_LIMIT};
+
+/// OpenAPI document describing the HTTP routes.
+///
+/// Keep this in sync with routes mounted in `server.rs`.
+pub fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Roogle",
+            "description": "A Rust API search engine",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": API_BASE }],
+        "paths": {
+            "/search": {
+                "get": {
+                    "summary": "Search functions and methods",
+                    "description": "The query may also be given as the request body.",
+                    "parameters": [
+                        query_param("query", "Query to search with", false),
+                        scope_param(),
+                        limit_param(),
+                        offset_param(),
+                        fields_param(),
+                        debug_param(),
+                        exact_param(),
+                        weights_param(),
+                        kinds_param(),
+                    ],
+                    "responses": {
+                        "200": hits_response(),
+                        "400": rejected_response(),
+                    },
+                },
+                "post": {
+                    "summary": "Search functions and methods",
+                    "parameters": [fields_param()],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/SearchRequest" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": hits_response(),
+                        "400": rejected_response(),
+                    },
+                },
+            },
+            "/search/stream": {
+                "get": {
+                    "summary": "Search functions and methods, streaming unsorted hits",
+                    "parameters": [
+                        query_param("query", "Query to search with", true),
+                        scope_param(),
+                        limit_param(),
+                        fields_param(),
+                        debug_param(),
+                        exact_param(),
+                        weights_param(),
+                        kinds_param(),
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Hits as server-sent events, followed by an `end` or `error` event",
+                            "content": { "text/event-stream": {} },
+                        },
+                        "400": rejected_response(),
+                    },
+                },
+            },
+            "/suggest": {
+                "get": {
+                    "summary": "Find functions converting given types into another",
+                    "description": "Meant for editors suggesting conversions from the expected type at the cursor.",
+                    "parameters": [
+                        {
+                            "name": "from",
+                            "in": "query",
+                            "description": "Type of an argument, given once per argument in order; `_` for unknown ones",
+                            "schema": { "type": "array", "items": { "type": "string" } },
+                            "style": "form",
+                            "explode": true,
+                        },
+                        query_param("to", "Type to return, or any type if absent", false),
+                        scope_param(),
+                        limit_param(),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "Suggestions sorted from the closest match",
+                            json!({
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/Suggestion" },
+                            }),
+                        ),
+                    },
+                },
+            },
+            "/similar": {
+                "get": {
+                    "summary": "Find functions and methods with signatures similar to an item",
+                    "parameters": [
+                        query_param("item", "Path of a function or method, e.g. `std::fs::read_to_string`", true),
+                        scope_param(),
+                        limit_param(),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "Hits sorted from the closest match, leaving out the item itself",
+                            json!({
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/Hit" },
+                            }),
+                        ),
+                        "404": { "description": "No function or method is at the path" },
+                    },
+                },
+            },
+            "/templates": {
+                "get": {
+                    "summary": "List the most common shapes of signatures as queries to start from",
+                    "parameters": [scope_param(), limit_param()],
+                    "responses": {
+                        "200": json_response(
+                            "Templates from the most common one",
+                            json!({
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/Template" },
+                            }),
+                        ),
+                    },
+                },
+            },
+            "/uses": {
+                "get": {
+                    "summary": "Find functions and methods mentioning a type in their signatures",
+                    "description": "The same as searching with `uses <type>`, but grouped by where the type is mentioned.",
+                    "parameters": [
+                        query_param("of", "Type to find uses of, e.g. `Duration`", true),
+                        scope_param(),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "Hits grouped by role, each sorted by paths",
+                            json!({ "$ref": "#/components/schemas/Uses" }),
+                        ),
+                        "400": json_response(
+                            "Parse error of the type",
+                            json!({ "$ref": "#/components/schemas/QueryParseError" }),
+                        ),
+                    },
+                },
+            },
+            "/parse": {
+                "get": {
+                    "summary": "Parse a query without performing search",
+                    "parameters": [query_param("query", "Query to parse", false)],
+                    "responses": {
+                        "200": json_response("Parsed query", json!({ "type": "object" })),
+                        "400": json_response(
+                            "Parse error",
+                            json!({ "$ref": "#/components/schemas/QueryParseError" }),
+                        ),
+                    },
+                },
+            },
+            "/capabilities": {
+                "get": {
+                    "summary": "List features of the query language the server understands",
+                    "responses": {
+                        "200": json_response(
+                            "Capabilities",
+                            json!({ "$ref": "#/components/schemas/Capabilities" }),
+                        ),
+                    },
+                },
+            },
+            "/share": {
+                "post": {
+                    "summary": "Share a search by a short token",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/ShareRequest" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "201": json_response(
+                            "Shared",
+                            json!({
+                                "type": "object",
+                                "required": ["token", "path"],
+                                "properties": {
+                                    "token": { "type": "string" },
+                                    "path": { "description": "`/q/<token>`", "type": "string" },
+                                },
+                            }),
+                        ),
+                        "400": json_response(
+                            "Parse error",
+                            json!({ "$ref": "#/components/schemas/QueryParseError" }),
+                        ),
+                        "413": { "description": "Request exceeds `limits.share`" },
+                        "422": { "description": "Malformed request" },
+                        "507": { "description": "As many searches as `--max-shared-searches` are shared already" },
+                    },
+                },
+            },
+            "/share/{token}": {
+                "get": {
+                    "summary": "Get a shared search",
+                    "parameters": [token_param()],
+                    "responses": {
+                        "200": json_response(
+                            "Shared search",
+                            json!({ "$ref": "#/components/schemas/SharedSearch" }),
+                        ),
+                        "404": { "description": "No search is shared as `token`" },
+                    },
+                },
+            },
+            "/scopes": {
+                "get": {
+                    "summary": "List available scopes",
+                    "responses": {
+                        "200": json_response(
+                            "Scopes",
+                            json!({ "type": "array", "items": { "type": "string" } }),
+                        ),
+                        "304": { "description": "Not modified since `If-None-Match`" },
+                    },
+                },
+            },
+            "/crates": {
+                "get": {
+                    "summary": "List loaded crates",
+                    "responses": {
+                        "200": json_response(
+                            "Crates",
+                            json!({
+                                "type": "array",
+                                "items": { "$ref": "#/components/schemas/CrateSummary" },
+                            }),
+                        ),
+                        "304": { "description": "Not modified since `If-None-Match`" },
+                    },
+                },
+            },
+            "/stats": {
+                "get": {
+                    "summary": "Count crates and sets, and list crates which failed to load",
+                    "responses": {
+                        "200": json_response(
+                            "Stats",
+                            json!({
+                                "type": "object",
+                                "required": ["crates", "sets", "degraded"],
+                                "properties": {
+                                    "crates": { "type": "integer" },
+                                    "sets": { "type": "integer" },
+                                    "degraded": {
+                                        "description": "Crates in the index directory which failed to load and aren't searched",
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "required": ["name", "cause"],
+                                            "properties": {
+                                                "name": { "type": "string" },
+                                                "cause": { "type": "string" },
+                                            },
+                                        },
+                                    },
+                                },
+                            }),
+                        ),
+                    },
+                },
+            },
+            "/crates/{name}": {
+                "put": {
+                    "summary": "Upload rustdoc JSON of a crate",
+                    "security": [{ "admin": [] }],
+                    "parameters": [name_param()],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": { "type": "object" } } },
+                    },
+                    "responses": admin_responses("Crate replaced", Some("Crate added")),
+                },
+            },
+            "/crates/{name}/reindex": {
+                "post": {
+                    "summary": "Rebuild a crate from crates.io",
+                    "security": [{ "admin": [] }],
+                    "parameters": [
+                        name_param(),
+                        query_param("version", "Version requirement of the crate", false),
+                    ],
+                    "responses": {
+                        "200": { "description": "Crate reindexed" },
+                        "400": { "description": "Invalid version requirement" },
+                        "401": { "description": "Invalid admin token" },
+                        "403": { "description": "Admin routes are disabled" },
+                        "422": { "description": "Invalid crate or set" },
+                    },
+                },
+            },
+            "/snapshot": {
+                "get": {
+                    "summary": "Download a snapshot of the crates in the index",
+                    "security": [{ "admin": [] }],
+                    "responses": {
+                        "200": json_response(
+                            "Snapshot",
+                            json!({ "$ref": "#/components/schemas/Snapshot" }),
+                        ),
+                        "401": { "description": "Invalid admin token" },
+                        "403": { "description": "Admin routes are disabled" },
+                    },
+                },
+            },
+            "/sets/{name}": {
+                "put": {
+                    "summary": "Create or replace a set of crates",
+                    "security": [{ "admin": [] }],
+                    "parameters": [name_param()],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": { "type": "string" } },
+                            },
+                        },
+                    },
+                    "responses": admin_responses("Set replaced", Some("Set added")),
+                },
+                "delete": {
+                    "summary": "Remove a set of crates",
+                    "security": [{ "admin": [] }],
+                    "parameters": [name_param()],
+                    "responses": {
+                        "200": { "description": "Set removed" },
+                        "401": { "description": "Invalid admin token" },
+                        "403": { "description": "Admin routes are disabled" },
+                        "404": { "description": "Set is not present" },
+                        "409": { "description": "Set is the default scope" },
+                    },
+                },
+            },
+            "/schema.json": {
+                "get": {
+                    "summary": "JSON Schema of hits, search responses and parse errors",
+                    "responses": {
+                        "200": { "description": "JSON Schema", "content": { "application/schema+json": {} } },
+                    },
+                },
+            },
+            "/q/{token}": {
+                "servers": [{ "url": "/" }],
+                "get": {
+                    "summary": "Search as a shared search does",
+                    "parameters": [
+                        token_param(),
+                        limit_param(),
+                        offset_param(),
+                        fields_param(),
+                        debug_param(),
+                    ],
+                    "responses": {
+                        "200": hits_response(),
+                        "400": json_response(
+                            "What is wrong with a parameter",
+                            json!({ "type": "string" }),
+                        ),
+                        "404": { "description": "No search is shared as `token`" },
+                        "422": json_response(
+                            "The query no longer parses",
+                            json!({ "$ref": "#/components/schemas/SharedSearch" }),
+                        ),
+                    },
+                },
+            },
+            "/healthz": {
+                "servers": [{ "url": "/" }],
+                "get": {
+                    "summary": "Check that the process is up",
+                    "responses": { "200": { "description": "Up" } },
+                },
+            },
+            "/readyz": {
+                "servers": [{ "url": "/" }],
+                "get": {
+                    "summary": "Check that the index is loaded",
+                    "responses": {
+                        "200": json_response("Ready", readiness()),
+                        "503": json_response("Not ready", readiness()),
+                    },
+                },
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "admin": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+/// Schemas of the bodies in [`document`], kept apart as the document would exceed the recursion
+/// limit of `json!` otherwise.
+fn schemas() -> Value {
+    json!({
+        "Hit": {
+            "type": "object",
+            "required": ["name", "kind", "path", "link", "signature"],
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "type": "string", "enum": ["function", "method"] },
+                "path": { "type": "array", "items": { "type": "string" } },
+                "link": { "type": "array", "items": { "type": "string" } },
+                "signature": { "type": "string" },
+                "docs": {
+                    "description": "Docs of the item",
+                    "type": "string",
+                    "nullable": true,
+                },
+                "summary": {
+                    "description": "First sentence of the docs as plain text, keeping code spans",
+                    "type": "string",
+                },
+                "similarities": {
+                    "description": "Breakdown of the score, only with `debug`",
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/Similarity" },
+                },
+                "substitutions": {
+                    "description": "Generic types of the item bound to concrete types of the query, e.g. `{ \"T\": \"u8\" }` for query `Vec<u8>` and item `Vec<T>`",
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+                "feature": {
+                    "description": "Cargo feature the item is only available with, e.g. `serde` for an item under `#[cfg(feature = \"serde\")]`",
+                    "type": "string",
+                },
+                "score": {
+                    "description": "Score of the item from 0 to 1 weighted by `weights`, where lower is closer",
+                    "type": "number",
+                },
+                "rank": {
+                    "description": "Position of the hit among all hits of the search from 0, counting ones skipped by `offset`",
+                    "type": "integer",
+                },
+                "scopes": {
+                    "description": "Scopes joined in `scope` like `set:std+crate:itertools` the item is in",
+                    "type": "array",
+                    "items": { "type": "string" },
+                },
+            },
+        },
+        "Similarity": {
+            "description": "Either how a part of the query matched, or a score from 0 to 1 where lower is closer",
+            "oneOf": [
+                {
+                    "type": "object",
+                    "required": ["discrete"],
+                    "properties": {
+                        "discrete": { "type": "string", "enum": ["equivalent", "subequal", "different"] },
+                    },
+                },
+                {
+                    "type": "object",
+                    "required": ["continuous"],
+                    "properties": { "continuous": { "type": "number" } },
+                },
+                {
+                    "description": "How similar a name is from 0 to 1, which only tells apart hits of the same score unless names are weighted",
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": { "name": { "type": "number" } },
+                },
+                {
+                    "description": "How similar a part of the output is from 0 to 1",
+                    "type": "object",
+                    "required": ["output"],
+                    "properties": { "output": { "type": "number" } },
+                },
+            ],
+        },
+        "Suggestion": {
+            "type": "object",
+            "required": ["path", "signature", "score"],
+            "properties": {
+                "path": { "type": "string" },
+                "signature": { "type": "string" },
+                "score": { "type": "number" },
+            },
+        },
+        "Uses": {
+            "type": "object",
+            "required": ["receiver", "input", "output"],
+            "properties": {
+                "receiver": { "type": "array", "items": { "$ref": "#/components/schemas/Hit" } },
+                "input": { "type": "array", "items": { "$ref": "#/components/schemas/Hit" } },
+                "output": { "type": "array", "items": { "$ref": "#/components/schemas/Hit" } },
+            },
+        },
+        "SearchRequest": {
+            "type": "object",
+            "required": ["query"],
+            "properties": {
+                "query": { "type": "string" },
+                "scope": { "type": "string", "default": ALL_SCOPE },
+                "threshold": { "type": "number", "default": DEFAULT_THRESHOLD },
+                "limit": { "type": "integer", "default": DEFAULT_LIMIT, "maximum": MAX_LIMIT },
+                "offset": { "type": "integer", "default": 0 },
+                "debug": { "type": "boolean", "default": false },
+                "exact": { "type": "boolean", "default": false },
+                "weights": { "$ref": "#/components/schemas/Weights" },
+                "kinds": {
+                    "description": "Kinds of items to respond with, e.g. `method,function`",
+                    "type": "string",
+                    "default": "function,method",
+                },
+                "newtypes": {
+                    "description": "Also respond with items taking or returning newtypes of the types in the query, e.g. `struct Meters(pub f64)` for `f64`",
+                    "type": "boolean",
+                    "default": false,
+                },
+            },
+        },
+        "ShareRequest": {
+            "type": "object",
+            "required": ["query"],
+            "properties": {
+                "query": { "type": "string" },
+                "scope": { "type": "string", "default": ALL_SCOPE },
+                "exact": { "type": "boolean", "default": false },
+                "weights": { "$ref": "#/components/schemas/Weights" },
+                "kinds": { "type": "string", "default": "function,method" },
+                "newtypes": { "type": "boolean", "default": false },
+            },
+        },
+        "SharedSearch": {
+            "type": "object",
+            "required": ["query", "canonical", "parsed"],
+            "properties": {
+                "query": { "type": "string" },
+                "canonical": { "type": "string" },
+                "parsed": {
+                    "description": "The query as parsed when it was shared",
+                    "type": "object",
+                },
+                "scope": { "type": "string", "nullable": true },
+                "exact": { "type": "boolean" },
+                "weights": { "$ref": "#/components/schemas/Weights" },
+                "kinds": { "type": "string" },
+                "newtypes": { "type": "boolean" },
+            },
+        },
+        "Weights": {
+            "description": "How much names, inputs and outputs count in scores of hits",
+            "type": "object",
+            "properties": {
+                "name": { "type": "number", "minimum": 0, "default": 0 },
+                "inputs": { "type": "number", "minimum": 0, "default": 1 },
+                "output": { "type": "number", "minimum": 0, "default": 1 },
+            },
+        },
+        "QueryParseError": {
+            "type": "object",
+            "required": ["offset", "message"],
+            "properties": {
+                "offset": { "type": "integer" },
+                "message": { "type": "string" },
+            },
+        },
+        "Template": {
+            "type": "object",
+            "required": ["query", "count"],
+            "properties": {
+                "query": {
+                    "description": "Query of a shape of signatures, e.g. `fn (&Self) -> T`",
+                    "type": "string",
+                },
+                "count": {
+                    "description": "Number of functions and methods of the shape",
+                    "type": "integer",
+                },
+            },
+        },
+        "Capabilities": {
+            "type": "object",
+            "required": ["version", "features"],
+            "properties": {
+                "version": {
+                    "description": "Version of the query language, bumped whenever its features change",
+                    "type": "integer",
+                },
+                "features": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["name", "description", "example"],
+                        "properties": {
+                            "name": { "type": "string" },
+                            "description": { "type": "string" },
+                            "example": { "type": "string" },
+                        },
+                    },
+                },
+            },
+        },
+        "Snapshot": {
+            "type": "object",
+            "required": ["manifest", "crates"],
+            "properties": {
+                "manifest": {
+                    "type": "object",
+                    "required": ["format_version", "rustdoc_format_version", "roogle_version", "crates"],
+                    "properties": {
+                        "format_version": { "type": "integer" },
+                        "rustdoc_format_version": { "type": "integer" },
+                        "roogle_version": { "type": "string" },
+                        "crates": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["name", "checksum"],
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "version": { "type": "string", "nullable": true },
+                                    "checksum": {
+                                        "description": "FNV-1a hash of the rustdoc JSON of the crate as written, in 16 hex digits",
+                                        "type": "string",
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+                "crates": {
+                    "description": "Rustdoc JSON of the crates keyed by their names",
+                    "type": "object",
+                },
+            },
+        },
+        "CrateSummary": {
+            "type": "object",
+            "required": ["name", "items", "functions", "methods", "traits", "sets"],
+            "properties": {
+                "name": { "type": "string" },
+                "version": { "type": "string", "nullable": true },
+                "items": { "type": "integer" },
+                "functions": { "type": "integer" },
+                "methods": { "type": "integer" },
+                "traits": { "type": "integer" },
+                "sets": { "type": "array", "items": { "type": "string" } },
+            },
+        },
+    })
+}
+
+/// JSON Schema of what the search routes respond with, derived from the types themselves so that
+/// clients can validate responses against it.
+///
+/// `SearchResponse` is the body of `/search`, and `QueryParseError` the one of `/parse` failing.
+pub fn json_schema() -> Value {
+    let mut gen = SchemaGenerator::default();
+    let search_response = gen.subschema_for::<Vec<Hit>>();
+    gen.subschema_for::<QueryParseError>();
+
+    let mut definitions = gen.take_definitions();
+    definitions.insert("SearchResponse".to_owned(), search_response);
+    json!({
+        "$schema": gen.settings().meta_schema,
+        "title": "Roogle",
+        "definitions": definitions,
+    })
+}
+
+fn query_param(name: &str, description: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "description": description,
+        "required": required,
+        "schema": { "type": "string" },
+    })
+}
+
+fn scope_param() -> Value {
+    json!({
+        "name": "scope",
+        "in": "query",
+        "description": "`all`, `set:<set>` or `crate:<crate>`, or several of them joined with `+` like `set:std+crate:itertools`; defaults to the server's default scope",
+        "schema": { "type": "string" },
+    })
+}
+
+fn limit_param() -> Value {
+    json!({
+        "name": "limit",
+        "in": "query",
+        "schema": { "type": "integer", "default": DEFAULT_LIMIT, "maximum": MAX_LIMIT },
+    })
+}
+
+fn offset_param() -> Value {
+    json!({
+        "name": "offset",
+        "in": "query",
+        "schema": { "type": "integer", "default": 0 },
+    })
+}
+
+fn fields_param() -> Value {
+    json!({
+        "name": "fields",
+        "in": "query",
+        "description": "Comma-separated names of hit fields to respond with, e.g. `name,link,signature`, or all of them if absent",
+        "schema": { "type": "string" },
+    })
+}
+
+fn debug_param() -> Value {
+    json!({
+        "name": "debug",
+        "in": "query",
+        "description": "Respond with the breakdown of scores of hits as `similarities`",
+        "schema": { "type": "boolean", "default": false },
+    })
+}
+
+fn exact_param() -> Value {
+    json!({
+        "name": "exact",
+        "in": "query",
+        "description": "Only respond with items matching the query exactly, without generic types binding to anything or names being just alike",
+        "schema": { "type": "boolean", "default": false },
+    })
+}
+
+fn weights_param() -> Value {
+    json!({
+        "name": "weights",
+        "in": "query",
+        "description": "How much names, inputs and outputs count in scores of hits, e.g. `output:3` to rank by outputs first",
+        "schema": { "type": "string", "default": "name:0,inputs:1,output:1" },
+    })
+}
+
+fn kinds_param() -> Value {
+    json!({
+        "name": "kinds",
+        "in": "query",
+        "description": "Kinds of items to respond with, e.g. `method` or `method,function`",
+        "schema": { "type": "string", "default": "function,method" },
+    })
+}
+
+fn name_param() -> Value {
+    json!({
+        "name": "name",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" },
+    })
+}
+
+fn token_param() -> Value {
+    json!({
+        "name": "token",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string", "pattern": "^[0-9a-f]{12}$" },
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+fn admin_responses(ok: &str, created: Option<&str>) -> Value {
+    let mut responses = json!({
+        "200": { "description": ok },
+        "401": { "description": "Invalid admin token" },
+        "403": { "description": "Admin routes are disabled" },
+        "422": { "description": "Invalid crate or set" },
+    });
+    if let Some(created) = created {
+        responses["201"] = json!({ "description": created });
+    }
+    responses
+}
+
+/// Response to searches with a query which doesn't parse, or a parameter like `fields` which
+/// doesn't either.
+fn rejected_response() -> Value {
+    json_response(
+        "Parse error of the query, or what is wrong with a parameter",
+        json!({
+            "oneOf": [
+                { "$ref": "#/components/schemas/QueryParseError" },
+                { "type": "string" },
+            ],
+        }),
+    )
+}
+
+fn hits_response() -> Value {
+    let hits = json!({
+        "type": "array",
+        "items": { "$ref": "#/components/schemas/Hit" },
+    });
+    json!({
+        "description": "Hits sorted by relevance, as MessagePack or an HTML page if preferred by `Accept`",
+        "headers": {
+            "X-Found-In-Other-Crates": {
+                "description": "Crates out of the scope which have hits, separated by commas, if there are none in the scope",
+                "schema": { "type": "string" },
+            },
+        },
+        "content": {
+            "application/json": { "schema": hits },
+            "application/msgpack": { "schema": hits },
+            "text/html": {},
+        },
+    })
+}
+
+fn readiness() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "ready": { "type": "boolean" },
+            "crates": { "type": "integer" },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_schema_of_hits() {
+        let schema = json_schema();
+        let definitions = &schema["definitions"];
+        assert_eq!(
+            definitions["SearchResponse"]["items"]["$ref"],
+            "#/definitions/Hit"
+        );
+        assert_eq!(
+            definitions["Hit"]["required"],
+            json!(["kind", "link", "name", "path", "signature"])
+        );
+        assert!(definitions["Hit"]["properties"]["krate"].is_null());
+        assert!(definitions["QueryParseError"].is_object());
+    }
+}
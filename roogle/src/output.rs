@@ -0,0 +1,139 @@
+//! Printing hits on the command line.
+
+use std::{io::Write, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use serde::Deserialize;
+
+use roogle_engine::{compare::Weights, search::Hit};
+
+use crate::{export::write_search_index, html};
+
+/// How to print hits, given by `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Signatures with substitutions of generic types, followed by paths, scores and summaries
+    /// of docs, colored on terminals.
+    Plain,
+    /// Aligned columns with a header.
+    Table,
+    /// A Markdown table linking to the documentation.
+    Markdown,
+    /// The same as the JSON the server responds with.
+    Json,
+    /// `search-index.js` of rustdoc, to merge hits into the search of documentation.
+    #[serde(rename = "search-index")]
+    SearchIndex,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] =
+        &["plain", "table", "markdown", "json", "search-index"];
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "table" => Ok(Format::Table),
+            "markdown" => Ok(Format::Markdown),
+            "json" => Ok(Format::Json),
+            "search-index" => Ok(Format::SearchIndex),
+            _ => Err(anyhow!("unknown format `{}`", s)),
+        }
+    }
+}
+
+/// Write `hits` into `w` in `format`, scoring them with `weights`.
+pub fn write_hits(
+    w: &mut impl Write,
+    hits: &[Hit],
+    format: Format,
+    weights: &Weights,
+) -> Result<()> {
+    match format {
+        Format::Plain => {
+            for (i, hit) in hits.iter().enumerate() {
+                if i > 0 {
+                    writeln!(w)?;
+                }
+                write!(w, "{}", hit.signature.bold())?;
+                if let Some(with) = hit.with_clause() {
+                    write!(w, " {}", with.dimmed())?;
+                }
+                writeln!(w)?;
+                write!(w, "    {}", hit.path.join("::").green())?;
+                if let Some(ref feature) = hit.feature {
+                    write!(w, " {}", format!("(feature: {})", feature).yellow())?;
+                }
+                if !hit.scopes.is_empty() {
+                    write!(w, " {}", format!("(in {})", hit.scopes.join(", ")).cyan())?;
+                }
+                // Hits from servers come without similarities, but scored with the same weights.
+                let score = if hit.similarities().0.is_empty() {
+                    hit.score
+                } else {
+                    hit.similarities().score_with(weights)
+                };
+                write!(w, " {}", format!("(score: {:.3})", score).dimmed())?;
+                writeln!(w)?;
+                if let Some(ref summary) = hit.summary {
+                    writeln!(w, "    {}", summary)?;
+                }
+            }
+        }
+        Format::Table => {
+            let rows = hits
+                .iter()
+                .map(|hit| (hit.path.join("::"), hit.signature.as_str()))
+                .collect::<Vec<_>>();
+            let width = rows
+                .iter()
+                .map(|(path, _)| path.chars().count())
+                .chain(Some("PATH".len()))
+                .max()
+                .unwrap_or_default();
+            writeln!(w, "{:width$}  SIGNATURE", "PATH", width = width)?;
+            for (path, signature) in rows {
+                writeln!(w, "{:width$}  {}", path, signature, width = width)?;
+            }
+        }
+        Format::Markdown => {
+            writeln!(w, "| Path | Signature |")?;
+            writeln!(w, "| --- | --- |")?;
+            for hit in hits {
+                writeln!(
+                    w,
+                    "| [`{}`]({}) | `{}` |",
+                    hit.path.join("::"),
+                    html::link(hit),
+                    hit.signature.replace('|', "\\|")
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer(&mut *w, hits).context("serializing hits failed")?;
+            writeln!(w)?;
+        }
+        Format::SearchIndex => write_search_index(w, hits)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_all_names() {
+        for name in Format::NAMES {
+            assert!(name.parse::<Format>().is_ok());
+        }
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
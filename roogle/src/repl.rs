@@ -0,0 +1,393 @@
+//! The `repl` subcommand, searching the index interactively.
+//!
+//! Line editing follows Emacs key bindings, e.g. Ctrl-R searches the history backward.
+//! `:open [N]` opens the documentation of the `N`th hit of the last search, the first by default.
+
+use std::{borrow::Cow, fmt::Write, path::PathBuf};
+
+use anyhow::{Context, Result};
+use rustdoc_types::ItemKind;
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Editor, Helper,
+};
+use structopt::StructOpt;
+
+use roogle_engine::{
+    query::{parse::parse_query_in, Arena},
+    Index,
+};
+
+use crate::{
+    config::Config,
+    output::{write_hits, Format},
+    search::{hint_other_crates, open_docs, other_crates, search, Params},
+    store::{load, warn_degraded},
+};
+
+/// Maximum number of queries kept in the history.
+const HISTORY_SIZE: usize = 1000;
+
+/// Keywords of the query syntax.
+const KEYWORDS: &[&str] = &["fn", "mut", "const"];
+
+/// Primitive types the query syntax recognizes.
+const PRIMITIVES: &[&str] = &[
+    "isize", "i8", "i16", "i32", "i64", "i128", "usize", "u8", "u16", "u32", "u64", "u128", "f32",
+    "f64", "char", "bool", "str",
+];
+
+/// ANSI escape sequences to highlight queries with.
+mod style {
+    pub const KEYWORD: &str = "\x1b[1;35m";
+    pub const PRIMITIVE: &str = "\x1b[36m";
+    pub const GENERIC: &str = "\x1b[33m";
+    pub const PUNCTUATION: &str = "\x1b[2m";
+    pub const INVALID: &str = "\x1b[4;31m";
+    pub const RESET: &str = "\x1b[0m";
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    params: Params,
+}
+
+pub fn run(mut opt: Opt, config: &Config) -> Result<()> {
+    opt.params.configure(config);
+    let (index, scopes) = load(&config.index(opt.index), opt.params.scope())?;
+    warn_degraded(&scopes);
+
+    let editor_config = rustyline::Config::builder()
+        .max_history_size(HISTORY_SIZE)
+        .history_ignore_dups(true)
+        .history_ignore_space(true)
+        .build();
+    let mut editor = Editor::with_config(editor_config);
+    editor.set_helper(Some(ReplHelper::new(&index)));
+    let history = history_path();
+    if let Some(ref history) = history {
+        // The history doesn't exist on the first run.
+        let _ = editor.load_history(history);
+    }
+
+    let mut arena = Arena::new();
+    let mut last_hits = vec![];
+    loop {
+        let line = match editor.readline("roogle> ") {
+            Ok(line) => line,
+            // Ctrl-C discards the line being edited, as in shells.
+            Err(ReadlineError::Interrupted) => continue,
+            // Ctrl-D quits.
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e).context("failed to read a line"),
+        };
+        let query = line.trim();
+        if query.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str());
+
+        if let Some(n) = query.strip_prefix(":open") {
+            let hit = match n.trim() {
+                "" => Some(1),
+                n => n.parse::<usize>().ok(),
+            }
+            .and_then(|n| last_hits.get(n.checked_sub(1)?));
+            match hit {
+                Some(hit) => {
+                    if let Err(e) = open_docs(hit) {
+                        eprintln!("error: {:#}", e);
+                    }
+                }
+                None => eprintln!("error: no such hit"),
+            }
+            continue;
+        }
+
+        match search(&index, &scopes, &mut arena, query, &opt.params) {
+            Ok(hits) => {
+                write_hits(
+                    &mut std::io::stdout().lock(),
+                    &hits,
+                    Format::Plain,
+                    &opt.params.weights(),
+                )?;
+                if hits.is_empty() {
+                    let krates = parse_query_in(&arena, query)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|query| other_crates(&index, &scopes, &query, &opt.params));
+                    match krates {
+                        Ok(krates) => {
+                            if let Some(hint) = hint_other_crates(opt.params.scope(), &krates) {
+                                eprintln!("{}", hint);
+                            }
+                        }
+                        Err(e) => eprintln!("error: {:#}", e),
+                    }
+                }
+                last_hits = hits;
+            }
+            Err(e) => eprintln!("error: {:#}", e),
+        }
+    }
+
+    if let Some(ref history) = history {
+        if let Err(e) = save_history(&mut editor, history) {
+            eprintln!("warning: saving history failed: {:#}", e);
+        }
+    }
+    Ok(())
+}
+
+/// `~/.local/share/roogle/history` on Linux, or its counterpart on other platforms.
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("roogle").join("history"))
+}
+
+fn save_history(editor: &mut Editor<ReplHelper>, path: &std::path::Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create `{:?}`", dir))?;
+    }
+    editor
+        .save_history(path)
+        .with_context(|| format!("failed to write `{:?}`", path))
+}
+
+/// Completes names in queries with keywords, primitives and items in the index.
+struct ReplHelper {
+    /// Names to complete with, sorted and deduplicated.
+    names: Vec<String>,
+}
+
+impl ReplHelper {
+    fn new(index: &Index) -> Self {
+        let items = index
+            .crates()
+            .values()
+            .flat_map(|krate| krate.paths.values())
+            .filter(|summary| {
+                matches!(
+                    summary.kind,
+                    ItemKind::Struct
+                        | ItemKind::Union
+                        | ItemKind::Enum
+                        | ItemKind::Function
+                        | ItemKind::Typedef
+                        | ItemKind::Trait
+                        | ItemKind::TraitAlias
+                        | ItemKind::ForeignType
+                )
+            })
+            .filter_map(|summary| summary.path.last().cloned());
+        let mut names = KEYWORDS
+            .iter()
+            .chain(PRIMITIVES)
+            .map(|name| name.to_string())
+            .chain(items)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        ReplHelper { names }
+    }
+
+    /// Start of the word before `pos` in `line`, and the names it may be completed to.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let word = &line[start..pos];
+        if word.is_empty() {
+            return (pos, vec![]);
+        }
+
+        // `names` is sorted, so the ones starting with `word` are contiguous.
+        let first = self.names.partition_point(|name| name.as_str() < word);
+        let candidates = self.names[first..]
+            .iter()
+            .take_while(|name| name.starts_with(word))
+            .cloned()
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize) -> bool {
+        // Any edit may turn the query valid or invalid.
+        true
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Colorize tokens of `line`, underlining the part the query parser fails at, if any.
+fn highlight(line: &str) -> String {
+    if line.starts_with(':') {
+        // A command rather than a query.
+        return line.to_owned();
+    }
+
+    let valid_len = match parse_query_in(&Arena::new(), line) {
+        // Don't underline whitespace before the offending token.
+        Err(e) if !line.trim().is_empty() => line.len() - line[e.offset..].trim_start().len(),
+        _ => line.len(),
+    };
+    let (mut rest, invalid) = line.split_at(valid_len);
+
+    let mut highlighted = String::with_capacity(line.len());
+    while let Some(c) = rest.chars().next() {
+        let len = if is_ident_char(c) {
+            rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len())
+        } else {
+            c.len_utf8()
+        };
+        let (token, tail) = rest.split_at(len);
+        rest = tail;
+
+        let style = if KEYWORDS.contains(&token) {
+            Some(style::KEYWORD)
+        } else if PRIMITIVES.contains(&token) {
+            Some(style::PRIMITIVE)
+        } else if token.chars().all(|c| c.is_ascii_uppercase()) {
+            // Same as the query parser, which takes all-uppercase names as generics.
+            Some(style::GENERIC)
+        } else if !is_ident_char(c) && !c.is_whitespace() {
+            Some(style::PUNCTUATION)
+        } else {
+            None
+        };
+        match style {
+            // Writing to `String` never fails.
+            Some(style) => {
+                let _ = write!(highlighted, "{}{}{}", style, token, style::RESET);
+            }
+            None => highlighted.push_str(token),
+        }
+    }
+    if !invalid.is_empty() {
+        let _ = write!(highlighted, "{}{}{}", style::INVALID, invalid, style::RESET);
+    }
+
+    highlighted
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            // Keep reading lines, so that long queries can be broken up.
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+/// Whether `input` has brackets left open, i.e. `(`, `[` or `<` without their counterparts.
+fn is_incomplete(input: &str) -> bool {
+    if input.starts_with(':') {
+        return false;
+    }
+
+    let mut depth = 0usize;
+    let mut prev = None;
+    for c in input.chars() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            // `->` isn't a closing bracket.
+            '>' if prev == Some('-') => {}
+            ')' | ']' | '>' => match depth.checked_sub(1) {
+                Some(d) => depth = d,
+                // Unbalanced the other way, which the query parser will report.
+                None => return false,
+            },
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    depth > 0
+}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continue_on_open_brackets() {
+        assert!(is_incomplete("fn (Option<"));
+        assert!(is_incomplete("fn (&[u8],\n    HashMap<K, V>"));
+        assert!(!is_incomplete("fn (Option<T>) -> T"));
+        assert!(!is_incomplete("fn () -> ()"));
+        assert!(!is_incomplete("fn (T)) -> ("));
+    }
+
+    #[test]
+    fn complete_word_before_cursor() {
+        let helper = ReplHelper {
+            names: vec!["Option".to_owned(), "Ordering".to_owned(), "u8".to_owned()],
+        };
+        assert_eq!(
+            helper.candidates("fn (O) -> u", 5),
+            (4, vec!["Option".to_owned(), "Ordering".to_owned()])
+        );
+        assert_eq!(
+            helper.candidates("fn (O) -> u", 11),
+            (10, vec!["u8".to_owned()])
+        );
+        assert_eq!(helper.candidates("fn (", 4), (4, vec![]));
+    }
+
+    #[test]
+    fn highlight_invalid_part() {
+        use style::*;
+
+        assert_eq!(
+            highlight("fn (T) -> u8 }"),
+            format!(
+                "{k}fn{r} {p}({r}{g}T{r}{p}){r} {p}-{r}{p}>{r} {t}u8{r} {i}}}{r}",
+                k = KEYWORD,
+                p = PUNCTUATION,
+                g = GENERIC,
+                t = PRIMITIVE,
+                i = INVALID,
+                r = RESET
+            )
+        );
+    }
+}
@@ -0,0 +1,491 @@
+//! The `search` subcommand, searching the index once from the command line.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use roogle_engine::{
+    compare::Weights,
+    matcher::{DefaultMatcher, ExactMatcher},
+    query::{parse::parse_query_in, Arena, Query},
+    search::{Hit, ItemKinds},
+    templates::Template,
+    Index,
+};
+
+use crate::{
+    config::Config,
+    html,
+    output::{write_hits, Format},
+    store::{load, warn_degraded, Scopes, ALL_SCOPE},
+    DEFAULT_LIMIT, DEFAULT_THRESHOLD,
+};
+
+/// How long to wait for a server given by `--remote`.
+#[cfg(feature = "remote")]
+const REMOTE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Header of search responses telling crates out of the scope which have hits if it has none,
+/// separated by commas.
+#[cfg_attr(not(any(feature = "server", feature = "remote")), allow(dead_code))]
+pub const FOUND_IN_OTHER_CRATES: &str = "X-Found-In-Other-Crates";
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    params: Params,
+
+    /// How to print hits: `plain`, `table`, `markdown`, `json` or `search-index` [default: plain].
+    #[structopt(long, possible_values = Format::NAMES)]
+    format: Option<Format>,
+
+    /// URL of a server to search with instead of the local index, e.g.
+    /// `https://roogle.hkmatsumoto.com`.
+    #[structopt(long, name = "URL")]
+    remote: Option<String>,
+
+    /// Search the local index even if the configuration file gives a remote server.
+    #[structopt(long, conflicts_with = "URL")]
+    local: bool,
+
+    /// Open the documentation of the top hit in the browser.
+    #[structopt(long, conflicts_with = "FILE")]
+    open: bool,
+
+    /// Search for each line of `FILE` and print results as newline-delimited JSON.
+    #[structopt(long, name = "FILE", conflicts_with = "query")]
+    queries: Option<PathBuf>,
+
+    /// Print the most common shapes of signatures in the scope instead, e.g. `fn (&Self) -> T`, as
+    /// queries to start from.
+    #[structopt(long, conflicts_with_all = &["query", "FILE", "open"])]
+    templates: bool,
+
+    /// Query to search for, e.g. `fn (&str) -> usize`, or each line of stdin if omitted.
+    query: Option<String>,
+}
+
+/// Options on how to search, shared by `search` and `repl`.
+#[derive(Debug, StructOpt)]
+pub struct Params {
+    /// Scope to search in: `all`, `set:<SET>` or `crate:<CRATE>`, or several of them joined with
+    /// `+` [default: all].
+    #[structopt(long)]
+    scope: Option<String>,
+
+    /// Maximum number of hits to print [default: 30].
+    #[structopt(long)]
+    limit: Option<usize>,
+
+    /// Upper bound of scores for hits, where lower scores mean closer matches [default: 0.4].
+    #[structopt(long)]
+    threshold: Option<f32>,
+
+    /// Only find items matching the query exactly, without generic types binding to anything or
+    /// names being just alike.
+    #[structopt(long)]
+    exact: bool,
+
+    /// Also find items taking or returning newtypes of the types in the query, e.g. `Meters` of
+    /// `struct Meters(pub f64)` for `f64`, ranking them after items with the types themselves.
+    #[structopt(long)]
+    newtypes: bool,
+
+    /// How much names, inputs and outputs count in scores, e.g. `output:3` to rank by outputs
+    /// first [default: name:0,inputs:1,output:1].
+    #[structopt(long)]
+    weights: Option<Weights>,
+
+    /// Kinds of items to find, e.g. `method` or `method,function` [default: function,method].
+    #[structopt(long)]
+    kinds: Option<ItemKinds>,
+}
+
+impl Params {
+    /// Fill in parameters not given by the command line from `config`.
+    pub fn configure(&mut self, config: &Config) {
+        self.scope = self.scope.take().or_else(|| config.scope.clone());
+        self.limit = self.limit.or(config.limit);
+        self.threshold = self.threshold.or(config.threshold);
+    }
+
+    pub fn scope(&self) -> &str {
+        self.scope.as_deref().unwrap_or(ALL_SCOPE)
+    }
+
+    pub fn weights(&self) -> Weights {
+        self.weights.unwrap_or_default()
+    }
+
+    pub fn kinds(&self) -> ItemKinds {
+        self.kinds.unwrap_or_default()
+    }
+
+    /// `query` with the weights and kinds of items given as parameters.
+    fn apply<'a>(&self, query: &Query<'a>) -> Query<'a> {
+        Query {
+            weights: self.weights(),
+            kinds: self.kinds(),
+            ..query.clone()
+        }
+    }
+}
+
+pub fn run(mut opt: Opt, config: &Config) -> Result<()> {
+    opt.params.configure(config);
+    let format = opt.format.or(config.format).unwrap_or(Format::Plain);
+    let remote = match opt.remote {
+        Some(ref url) => Some(url),
+        None if !opt.local => config.remote.as_ref(),
+        None => None,
+    };
+
+    let source = match remote {
+        Some(url) => Source::Remote(url.trim_end_matches('/').to_owned()),
+        None => {
+            let (index, scopes) = load(&config.index(opt.index), opt.params.scope())?;
+            warn_degraded(&scopes);
+            Source::Local(Box::new(index), scopes)
+        }
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    if opt.templates {
+        let templates = source.templates(&opt.params)?;
+        return write_templates(&mut stdout, &templates, format);
+    }
+
+    let mut arena = Arena::new();
+    if let Some(ref path) = opt.queries {
+        let file = File::open(path).with_context(|| format!("failed to open `{:?}`", path))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.with_context(|| format!("failed to read `{:?}`", path))?;
+            let query = line.trim();
+            if query.is_empty() {
+                continue;
+            }
+
+            let result = match source.search(&mut arena, query, &opt.params) {
+                Ok(found) => BatchResult {
+                    query,
+                    hits: Some(found.hits),
+                    found_in_other_crates: found.found_in_other_crates,
+                    error: None,
+                },
+                Err(e) => BatchResult {
+                    query,
+                    hits: None,
+                    found_in_other_crates: vec![],
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            serde_json::to_writer(&mut stdout, &result).context("serializing result failed")?;
+            writeln!(stdout)?;
+        }
+        return Ok(());
+    }
+
+    match opt.query {
+        Some(ref query) => {
+            let found = source.search(&mut arena, query, &opt.params)?;
+            write_hits(&mut stdout, &found.hits, format, &opt.params.weights())?;
+            if let Some(hint) = hint_other_crates(opt.params.scope(), &found.found_in_other_crates)
+            {
+                eprintln!("{}", hint);
+            }
+            match found.hits.first() {
+                Some(hit) if opt.open => open_docs(hit),
+                _ => Ok(()),
+            }
+        }
+        None => {
+            let stdin = std::io::stdin();
+            if stdin.is_terminal() {
+                bail!("no query given; pass one as an argument or via stdin");
+            }
+            for line in stdin.lock().lines() {
+                let line = line.context("failed to read stdin")?;
+                let query = line.trim();
+                if query.is_empty() {
+                    continue;
+                }
+                let found = source.search(&mut arena, query, &opt.params)?;
+                write_hits(&mut stdout, &found.hits, format, &opt.params.weights())?;
+                if let Some(hint) =
+                    hint_other_crates(opt.params.scope(), &found.found_in_other_crates)
+                {
+                    eprintln!("{}", hint);
+                }
+                if let Some(hit) = found.hits.first().filter(|_| opt.open) {
+                    open_docs(hit)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Where to search in, either the local index or a server.
+enum Source {
+    Local(Box<Index>, Scopes),
+    /// Base URL of a server.
+    Remote(String),
+}
+
+impl Source {
+    fn search(&self, arena: &mut Arena, query: &str, params: &Params) -> Result<Found> {
+        match self {
+            Source::Local(index, scopes) => {
+                let hits = search(index, scopes, arena, query, params)?;
+                let found_in_other_crates = if hits.is_empty() {
+                    other_crates(index, scopes, &parse_query_in(arena, query)?, params)?
+                } else {
+                    vec![]
+                };
+                Ok(Found {
+                    hits,
+                    found_in_other_crates,
+                })
+            }
+            Source::Remote(url) => remote_search(url, query, params),
+        }
+    }
+
+    fn templates(&self, params: &Params) -> Result<Vec<Template>> {
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+        match self {
+            Source::Local(index, scopes) => {
+                let scope = scopes.resolve(&scopes.default)?;
+                Ok(index.templates(scope, limit)?)
+            }
+            Source::Remote(url) => remote_templates(url, params.scope(), limit),
+        }
+    }
+}
+
+/// Hits of a query, along with the crates out of its scope which have hits if it has none.
+#[derive(Debug, Default)]
+pub struct Found {
+    pub hits: Vec<Hit>,
+    pub found_in_other_crates: Vec<String>,
+}
+
+/// Search with the server at `url` through `POST /v1/search`.
+#[cfg(feature = "remote")]
+fn remote_search(url: &str, query: &str, params: &Params) -> Result<Found> {
+    // Fail the same way as searching locally, without a round trip.
+    parse_query_in(&Arena::new(), query)?;
+
+    let endpoint = format!("{}/v1/search", url);
+    let mut request = serde_json::json!({
+        "query": query,
+        "limit": params.limit.unwrap_or(DEFAULT_LIMIT),
+        "threshold": params.threshold.unwrap_or(DEFAULT_THRESHOLD),
+        "exact": params.exact,
+        "weights": params.weights(),
+        "kinds": params.kinds(),
+        "newtypes": params.newtypes,
+    });
+    // Servers search in their own default scope unless one is given.
+    if let Some(ref scope) = params.scope {
+        request["scope"] = scope.as_str().into();
+    }
+    let response = ureq::post(&endpoint)
+        .timeout(REMOTE_TIMEOUT)
+        .set("Accept", "application/json")
+        .send_json(request);
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => bail!(
+            "`{}` responded with {}: {}",
+            endpoint,
+            status,
+            response.into_string().unwrap_or_default()
+        ),
+        Err(e) => return Err(e).with_context(|| format!("failed to request `{}`", endpoint)),
+    };
+    let found_in_other_crates = response
+        .header(FOUND_IN_OTHER_CRATES)
+        .map(|krates| {
+            krates
+                .split(',')
+                .map(|krate| krate.trim().to_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let hits = response
+        .into_json()
+        .with_context(|| format!("failed to deserialize hits from `{}`", endpoint))?;
+    Ok(Found {
+        hits,
+        found_in_other_crates,
+    })
+}
+
+#[cfg(not(feature = "remote"))]
+fn remote_search(url: &str, _: &str, _: &Params) -> Result<Found> {
+    bail!(
+        "searching with `{}` requires roogle built with the `remote` feature",
+        url
+    )
+}
+
+/// Templates of the server at `url` through `GET /v1/templates`.
+#[cfg(feature = "remote")]
+fn remote_templates(url: &str, scope: &str, limit: usize) -> Result<Vec<Template>> {
+    let endpoint = format!("{}/v1/templates", url);
+    let response = ureq::get(&endpoint)
+        .timeout(REMOTE_TIMEOUT)
+        .query("scope", scope)
+        .query("limit", &limit.to_string())
+        .call();
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => bail!(
+            "`{}` responded with {}: {}",
+            endpoint,
+            status,
+            response.into_string().unwrap_or_default()
+        ),
+        Err(e) => return Err(e).with_context(|| format!("failed to request `{}`", endpoint)),
+    };
+    response
+        .into_json()
+        .with_context(|| format!("failed to deserialize templates from `{}`", endpoint))
+}
+
+#[cfg(not(feature = "remote"))]
+fn remote_templates(url: &str, _: &str, _: usize) -> Result<Vec<Template>> {
+    bail!(
+        "listing templates of `{}` requires roogle built with the `remote` feature",
+        url
+    )
+}
+
+/// Write `templates` as JSON in the `json` format, and as lines of their counts and queries in
+/// the others.
+fn write_templates(w: &mut impl Write, templates: &[Template], format: Format) -> Result<()> {
+    if format == Format::Json {
+        serde_json::to_writer(&mut *w, templates).context("serializing templates failed")?;
+        writeln!(w)?;
+        return Ok(());
+    }
+    for template in templates {
+        writeln!(w, "{:>6}  {}", template.count, template.query)?;
+    }
+    Ok(())
+}
+
+/// Open the documentation of `hit` in the browser, preferring the local one built by
+/// `cargo doc` in `target/doc` to the one on the web.
+pub fn open_docs(hit: &Hit) -> Result<()> {
+    let local = hit
+        .link
+        .iter()
+        .fold(PathBuf::from("target").join("doc"), |path, segment| {
+            path.join(segment)
+        });
+    let result = if local.is_file() {
+        // Browsers don't resolve relative paths against our working directory.
+        open::that(local.canonicalize().unwrap_or(local))
+    } else {
+        open::that(html::link(hit))
+    };
+    result.with_context(|| format!("failed to open the documentation of `{}`", hit.name))
+}
+
+/// A line of `--queries` output, either with hits or an error.
+#[derive(Debug, Serialize)]
+struct BatchResult<'a> {
+    query: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hits: Option<Vec<Hit>>,
+    /// Crates out of the scope which have hits if there are none in it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    found_in_other_crates: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Search `index` for `query` within the default scope of `scopes`, i.e. `--scope`.
+///
+/// `query` is parsed in `arena` after resetting it, so that searching for many queries in turn
+/// reuses its memory.
+pub fn search(
+    index: &Index,
+    scopes: &Scopes,
+    arena: &mut Arena,
+    query: &str,
+    params: &Params,
+) -> Result<Vec<Hit>> {
+    arena.reset();
+    search_parsed(index, scopes, &parse_query_in(arena, query)?, params)
+}
+
+/// [`search`] with a query already parsed.
+pub fn search_parsed(
+    index: &Index,
+    scopes: &Scopes,
+    query: &Query,
+    params: &Params,
+) -> Result<Vec<Hit>> {
+    let scope = scopes.resolve(&scopes.default)?;
+    let attribution = scopes.attribution(&scopes.default)?;
+    let threshold = params.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    let query = &params.apply(query);
+    let matcher = DefaultMatcher::new(threshold).unwrap_newtypes(params.newtypes);
+    let hits = if params.exact {
+        index.search_with(&ExactMatcher(&matcher), query, scope, threshold)?
+    } else {
+        index.search_with(&matcher, query, scope, threshold)?
+    };
+    Ok(hits
+        .into_iter()
+        .take(params.limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|mut hit| {
+            attribution.attribute(&mut hit);
+            hit
+        })
+        .collect())
+}
+
+/// Crates out of `--scope` which have hits of `query`, to hint at them when it has none.
+pub fn other_crates(
+    index: &Index,
+    scopes: &Scopes,
+    query: &Query,
+    params: &Params,
+) -> Result<Vec<String>> {
+    let scope = scopes.resolve(&scopes.default)?;
+    let threshold = params.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    let query = &params.apply(query);
+    let matcher = DefaultMatcher::new(threshold).unwrap_newtypes(params.newtypes);
+    let krates = if params.exact {
+        index.other_crates_with_hits_with(&ExactMatcher(&matcher), query, scope, threshold)?
+    } else {
+        index.other_crates_with_hits_with(&matcher, query, scope, threshold)?
+    };
+    Ok(krates)
+}
+
+/// Hint that `krates` have hits of a query which has none in `scope`, if there are any.
+pub fn hint_other_crates(scope: &str, krates: &[String]) -> Option<String> {
+    if krates.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "no hits in `{}`, but found in other crates: {}",
+        scope,
+        krates.join(", ")
+    ))
+}
@@ -0,0 +1,2173 @@
+//! The `serve` subcommand, serving search over HTTP and optionally gRPC.
+
+use std::{
+    net::IpAddr,
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use rocket::{
+    data::{ByteUnit, Data, Limits},
+    fairing::{Fairing, Info, Kind},
+    figment::Figment,
+    form::{self, FromFormField, ValueField},
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome},
+    response::{
+        content, status,
+        stream::{Event, EventStream},
+        Responder,
+    },
+    serde::json::Json,
+    Build, Rocket, Shutdown, State,
+};
+use rustdoc_types::Crate;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+use tracing::{debug, info};
+
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::{
+    cache::{ComparisonCache, Rendered, ResponseCache},
+    config::Config,
+    html, indexer, openapi,
+    search::{Found, FOUND_IN_OTHER_CRATES},
+    share::{self, SharedSearch, SharesFull},
+    store::{
+        is_valid_crate_name, load, load_snapshot, persist_crate, remove_delta, validate_crate,
+        write_atomically, Scopes,
+    },
+    suggest::Suggestion,
+    DEFAULT_LIMIT, DEFAULT_THRESHOLD, MAX_LIMIT,
+};
+use roogle_engine::{
+    compare::Weights,
+    matcher::{DefaultMatcher, ExactMatcher, Matcher},
+    query::{
+        parse::{capabilities, parse_query_in, QueryParseError},
+        Arena, Query, QueryKind,
+    },
+    search::{Hit, ItemKinds, Scope, SearchError},
+    snapshot, Index,
+};
+use roogle_util::{from_json, shake};
+
+#[allow(clippy::too_many_arguments)]
+#[get(
+    "/search?<scope>&<limit>&<offset>&<fields>&<debug>&<exact>&<weights>&<kinds>",
+    data = "<query>",
+    rank = 2
+)]
+fn search_with_data(
+    query: &str,
+    scope: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    fields: form::Result<'_, Fields>,
+    debug: bool,
+    exact: bool,
+    weights: form::Result<'_, WeightsParam>,
+    kinds: form::Result<'_, KindsParam>,
+    cache: SearchCache<'_>,
+    request_id: &RequestId,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    comparisons: &State<Option<Arc<ComparisonCache>>>,
+) -> Result<Searched, rocket::response::Debug<anyhow::Error>> {
+    search(
+        query,
+        scope,
+        limit,
+        offset,
+        fields,
+        debug,
+        exact,
+        weights,
+        kinds,
+        cache,
+        request_id,
+        index,
+        scopes,
+        comparisons,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/search?<scope>&<query>&<limit>&<offset>&<fields>&<debug>&<exact>&<weights>&<kinds>")]
+fn search(
+    query: &str,
+    scope: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    fields: form::Result<'_, Fields>,
+    debug: bool,
+    exact: bool,
+    weights: form::Result<'_, WeightsParam>,
+    kinds: form::Result<'_, KindsParam>,
+    cache: SearchCache<'_>,
+    request_id: &RequestId,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    comparisons: &State<Option<Arc<ComparisonCache>>>,
+) -> Result<Searched, rocket::response::Debug<anyhow::Error>> {
+    let (fields, weights, kinds) = match (param(fields), param(weights), param(kinds)) {
+        (Ok(fields), Ok(weights), Ok(kinds)) => (fields, weights, kinds),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            return rejected(Status::BadRequest, &e).map(Err)
+        }
+    };
+    let request = SearchRequest {
+        query: query.to_owned(),
+        scope: scope.map(ToOwned::to_owned),
+        threshold: DEFAULT_THRESHOLD,
+        limit: limit.unwrap_or(DEFAULT_LIMIT),
+        offset: offset.unwrap_or_default(),
+        debug,
+        exact,
+        weights: weights.0,
+        kinds: kinds.0,
+        newtypes: false,
+    };
+
+    searched(cache.respond(&request, fields, request_id, || {
+        perform_search(
+            &request,
+            request_id,
+            &index.read(),
+            &scopes.read(),
+            comparisons.as_deref(),
+        )
+    }))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    /// Respond with the breakdown of scores as well.
+    #[serde(default)]
+    pub debug: bool,
+    /// Only respond with items matching the query exactly.
+    #[serde(default)]
+    pub exact: bool,
+    /// How much names, inputs and outputs of items count in their scores.
+    #[serde(default)]
+    pub weights: Weights,
+    /// Kinds of items to respond with, like `method,function`.
+    #[serde(default)]
+    pub kinds: ItemKinds,
+    /// Also respond with items taking or returning newtypes of the types in the query.
+    #[serde(default)]
+    pub newtypes: bool,
+}
+
+fn default_threshold() -> f32 {
+    DEFAULT_THRESHOLD
+}
+
+fn default_limit() -> usize {
+    DEFAULT_LIMIT
+}
+
+#[post("/search?<fields>", format = "json", data = "<request>")]
+fn search_with_json(
+    request: Json<SearchRequest>,
+    fields: form::Result<'_, Fields>,
+    cache: SearchCache<'_>,
+    request_id: &RequestId,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    comparisons: &State<Option<Arc<ComparisonCache>>>,
+) -> Result<Searched, rocket::response::Debug<anyhow::Error>> {
+    let fields = match param(fields) {
+        Ok(fields) => fields,
+        Err(e) => return rejected(Status::BadRequest, &e).map(Err),
+    };
+    searched(cache.respond(&request, fields, request_id, || {
+        perform_search(
+            &request,
+            request_id,
+            &index.read(),
+            &scopes.read(),
+            comparisons.as_deref(),
+        )
+    }))
+}
+
+/// Hits of a search, or why it was rejected.
+type Searched = Result<Rendered, Rejected>;
+
+/// Search rejected with a status like 400, and a JSON body saying why.
+type Rejected = status::Custom<content::Json<String>>;
+
+/// Respond with the outcome of a search, telling queries failing to parse from other failures.
+fn searched(result: Result<Rendered>) -> Result<Searched, rocket::response::Debug<anyhow::Error>> {
+    match result {
+        Ok(rendered) => Ok(Ok(rendered)),
+        Err(e) => match e.downcast::<QueryParseError>() {
+            Ok(e) => rejected(Status::BadRequest, &e).map(Err),
+            Err(e) => Err(e.into()),
+        },
+    }
+}
+
+/// Reject a search with `status` and `reason` serialized as its body.
+fn rejected(
+    status: Status,
+    reason: &impl Serialize,
+) -> Result<Rejected, rocket::response::Debug<anyhow::Error>> {
+    let json = serde_json::to_string(reason).context("serializing error failed")?;
+    Ok(status::Custom(status, content::Json(json)))
+}
+
+/// Take a parameter of a search, or what is wrong with it like "`fields`: unknown field `bogus`",
+/// for searches to be rejected with 400 rather than forwarded to 404.
+fn param<T>(param: form::Result<'_, T>) -> Result<T, String> {
+    param.map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| match &e.name {
+                Some(name) => format!("`{}`: {}", name, e),
+                None => e.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    })
+}
+
+/// Names of [`Hit`] fields which can be selected with `?fields=`.
+const HIT_FIELDS: &[&str] = &[
+    "name",
+    "kind",
+    "path",
+    "link",
+    "signature",
+    "docs",
+    "summary",
+    "substitutions",
+    "feature",
+    "score",
+    "rank",
+    "scopes",
+];
+
+/// Subset of [`Hit`] fields to respond with, given as comma-separated names like
+/// `?fields=name,link`.
+///
+/// Whole hits, `docs` included, are responded with if `?fields=` is absent, so that responses to
+/// clients predating it keep their shape.
+#[derive(Debug)]
+struct Fields(Option<Vec<String>>);
+
+impl<'v> FromFormField<'v> for Fields {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        let mut fields = vec![];
+        for name in field.value.split(',').map(str::trim) {
+            if !HIT_FIELDS.contains(&name) {
+                Err(form::Error::validation(format!("unknown field `{}`", name)))?;
+            }
+            fields.push(name.to_owned());
+        }
+        Ok(Fields(Some(fields)))
+    }
+
+    fn default() -> Option<Self> {
+        Some(Fields(None))
+    }
+}
+
+/// [`Weights`] given like `?weights=output:3`, or the default ones if `?weights=` is absent.
+#[derive(Debug)]
+struct WeightsParam(Weights);
+
+impl<'v> FromFormField<'v> for WeightsParam {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value.parse() {
+            Ok(weights) => Ok(WeightsParam(weights)),
+            Err(e) => Err(form::Error::validation(e.to_string()))?,
+        }
+    }
+
+    fn default() -> Option<Self> {
+        Some(WeightsParam(Weights::default()))
+    }
+}
+
+/// [`ItemKinds`] given like `?kinds=method,function`, or all of them if `?kinds=` is absent.
+#[derive(Debug)]
+struct KindsParam(ItemKinds);
+
+impl<'v> FromFormField<'v> for KindsParam {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value.parse() {
+            Ok(kinds) => Ok(KindsParam(kinds)),
+            Err(e) => Err(form::Error::validation(e.to_string()))?,
+        }
+    }
+
+    fn default() -> Option<Self> {
+        Some(KindsParam(ItemKinds::default()))
+    }
+}
+
+impl Fields {
+    /// Serialize `hit`, keeping selected fields only, as well as `similarities` if it has them.
+    fn select(&self, hit: &Hit) -> serde_json::Result<serde_json::Value> {
+        let value = serde_json::to_value(hit)?;
+        let fields = match (&self.0, value) {
+            (Some(fields), serde_json::Value::Object(map)) => map
+                .into_iter()
+                .filter(|(key, _)| key == "similarities" || fields.contains(key))
+                .collect(),
+            (_, value) => value,
+        };
+        Ok(fields)
+    }
+}
+
+/// Representation of search results the client prefers, according to `Accept`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Representation {
+    Json,
+    MsgPack,
+    Html,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Representation {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let representation = match req.accept().map(|accept| accept.preferred().media_type()) {
+            Some(media_type) if media_type.is_msgpack() => Representation::MsgPack,
+            Some(media_type) if media_type.is_html() => Representation::Html,
+            _ => Representation::Json,
+        };
+        Outcome::Success(representation)
+    }
+}
+
+/// Search result, serialized as MessagePack or JSON, or rendered as a results page.
+struct Hits {
+    query: String,
+    hits: Vec<Hit>,
+    /// Crates out of the scope which have hits if there are none in it, responded with as
+    /// [`FOUND_IN_OTHER_CRATES`].
+    found_in_other_crates: Vec<String>,
+    fields: Fields,
+}
+
+impl Hits {
+    fn render(&self, representation: Representation) -> Result<Rendered> {
+        let (content_type, body) = match representation {
+            Representation::Json => (ContentType::JSON, self.to_json()?.into_bytes()),
+            Representation::MsgPack => (ContentType::MsgPack, self.to_msgpack()?),
+            Representation::Html => (
+                ContentType::HTML,
+                html::results(&self.query, &self.hits, &self.found_in_other_crates).into_bytes(),
+            ),
+        };
+        Ok(Rendered {
+            content_type,
+            body: body.into(),
+            cached: false,
+            headers: match self.found_in_other_crates.as_slice() {
+                [] => vec![],
+                krates => vec![(FOUND_IN_OTHER_CRATES, krates.join(", "))],
+            },
+        })
+    }
+
+    fn to_json(&self) -> Result<String> {
+        let json = match self.fields.0 {
+            Some(_) => serde_json::to_string(&self.selected()?),
+            None => serde_json::to_string(&self.hits),
+        };
+        json.context("serializing search result failed")
+    }
+
+    fn to_msgpack(&self) -> Result<Vec<u8>> {
+        let msgpack = match self.fields.0 {
+            Some(_) => rmp_serde::to_vec_named(&self.selected()?),
+            None => rmp_serde::to_vec_named(&self.hits),
+        };
+        msgpack.context("serializing search result failed")
+    }
+
+    fn selected(&self) -> Result<Vec<serde_json::Value>> {
+        self.hits
+            .iter()
+            .map(|hit| self.fields.select(hit))
+            .collect::<serde_json::Result<_>>()
+            .context("selecting fields of search result failed")
+    }
+}
+
+/// Request guard serving search routes from [`ResponseCache`], or filling it on misses.
+struct SearchCache<'r> {
+    cache: &'r ResponseCache,
+    etag: String,
+    representation: Representation,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SearchCache<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let representation = rocket::outcome::try_outcome!(req.guard::<Representation>().await);
+        let cache = req.rocket().state::<ResponseCache>();
+        let etag = req.rocket().state::<Shared<ETag>>();
+        match (cache, etag) {
+            (Some(cache), Some(etag)) => Outcome::Success(SearchCache {
+                cache,
+                etag: etag.read().0.clone(),
+                representation,
+            }),
+            _ => Outcome::Forward(()),
+        }
+    }
+}
+
+impl SearchCache<'_> {
+    /// Respond to `request` with a cached response if any, or with hits returned by `search`.
+    fn respond(
+        &self,
+        request: &SearchRequest,
+        fields: Fields,
+        request_id: &RequestId,
+        search: impl FnOnce() -> Result<Found>,
+    ) -> Result<Rendered> {
+        // Queries differing only in e.g. whitespace share responses.
+        let canonical = SearchRequest {
+            query: canonical_query(&request.query),
+            ..request.clone()
+        };
+        let key = format!("{:?} {:?} {:?}", self.representation, fields, canonical);
+        if let Some(rendered) = self.cache.get(&key, &self.etag) {
+            debug!(request_id = %request_id.0, query = %request.query, "search response cached");
+            return Ok(rendered);
+        }
+
+        let Found {
+            mut hits,
+            found_in_other_crates,
+        } = search()?;
+        if !request.debug {
+            hits.iter_mut().for_each(Hit::clear_similarities);
+        }
+        let hits = Hits {
+            // Pages are cached for every query with the same canonical form, so they show that.
+            query: canonical.query,
+            hits,
+            found_in_other_crates,
+            fields,
+        };
+        let rendered = hits.render(self.representation)?;
+        self.cache.insert(key, &self.etag, rendered.clone());
+        Ok(rendered)
+    }
+}
+
+/// `query` in its canonical form, or as is if it fails to parse.
+fn canonical_query(query: &str) -> String {
+    match parse_query_in(&Arena::new(), query) {
+        Ok(query) => query.canonicalize(),
+        Err(_) => query.to_owned(),
+    }
+}
+
+/// Search `index` as `request` asks, serving scores from `comparisons` if given unless the request
+/// asks for their breakdown.
+///
+/// Fails with [`QueryParseError`] if the query doesn't parse as a whole.
+pub fn perform_search(
+    request: &SearchRequest,
+    request_id: &RequestId,
+    index: &Index,
+    scopes: &Scopes,
+    comparisons: Option<&ComparisonCache>,
+) -> Result<Found> {
+    let arena = Arena::new();
+    let query = parse_query_in(&arena, &request.query)?;
+    perform_search_with(request, &query, request_id, index, scopes, comparisons)
+}
+
+/// Search `index` like [`perform_search`], but with `query` parsed already instead of the one of
+/// `request`.
+fn perform_search_with(
+    request: &SearchRequest,
+    query: &Query<'_>,
+    request_id: &RequestId,
+    index: &Index,
+    scopes: &Scopes,
+    comparisons: Option<&ComparisonCache>,
+) -> Result<Found> {
+    let start = Instant::now();
+
+    let scope_name = request.scope.as_deref().unwrap_or(&scopes.default);
+    let scope = scopes.resolve(scope_name)?;
+    let attribution = scopes.attribution(scope_name)?;
+    debug!(?scope);
+
+    let query = Query {
+        weights: request.weights,
+        kinds: request.kinds,
+        ..query.clone()
+    };
+    debug!(?query);
+
+    let search = |matcher: &dyn Matcher| {
+        if request.exact {
+            index.search_with(
+                &ExactMatcher(matcher),
+                &query,
+                scope.clone(),
+                request.threshold,
+            )
+        } else {
+            index.search_with(matcher, &query, scope.clone(), request.threshold)
+        }
+    };
+    // Scores are cached as `DefaultMatcher` computes them without unwrapping newtypes.
+    let hits = match comparisons.filter(|_| !request.debug && !request.newtypes) {
+        Some(comparisons) => {
+            let version = format!(
+                "{:016x}",
+                index_version(index).context("failed to identify the index")?
+            );
+            let matcher = comparisons.matcher(&version, &query, request.threshold);
+            let hits = search(&matcher);
+            if hits.is_ok() {
+                comparisons.insert(matcher);
+            }
+            hits
+        }
+        None => search(&DefaultMatcher::new(request.threshold).unwrap_newtypes(request.newtypes)),
+    }
+    .with_context(|| format!("search with query `{:?}` failed", query))?;
+    let total = hits.len();
+    let hits = hits
+        .into_iter()
+        .inspect(|hit| debug!(?hit.name, ?hit.link, similarities = ?hit.similarities(), score = ?hit.score))
+        .skip(request.offset)
+        .take(request.limit.min(MAX_LIMIT))
+        .map(|mut hit| {
+            attribution.attribute(&mut hit);
+            hit
+        })
+        .collect::<Vec<_>>();
+    let found_in_other_crates = if total == 0 {
+        let matcher = DefaultMatcher::new(request.threshold).unwrap_newtypes(request.newtypes);
+        let matcher: &dyn Matcher = if request.exact {
+            &ExactMatcher(&matcher)
+        } else {
+            &matcher
+        };
+        index
+            .other_crates_with_hits_with(matcher, &query, scope, request.threshold)
+            .context("searching other crates failed")?
+    } else {
+        vec![]
+    };
+
+    info!(
+        target: "roogle::search",
+        request_id = %request_id.0,
+        query = %request.query,
+        canonical = %query.canonicalize(),
+        scope = %scope_name,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        hits = total,
+        "search performed"
+    );
+
+    Ok(Found {
+        hits,
+        found_in_other_crates,
+    })
+}
+
+/// Perform search like `/search`, but emit hits as server-sent events as soon as they are found.
+///
+/// Hits are not sorted. The stream ends with an `end` event, or an `error` event if search failed.
+/// It also ends without either of them once the server starts shutting down. Responds with 400 and
+/// the parse error instead if the query doesn't parse.
+#[allow(clippy::too_many_arguments)]
+#[get("/search/stream?<scope>&<query>&<limit>&<fields>&<debug>&<exact>&<weights>&<kinds>")]
+fn search_stream(
+    query: &str,
+    scope: Option<&str>,
+    limit: Option<usize>,
+    fields: form::Result<'_, Fields>,
+    debug: bool,
+    exact: bool,
+    weights: form::Result<'_, WeightsParam>,
+    kinds: form::Result<'_, KindsParam>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    mut shutdown: Shutdown,
+) -> Result<Result<EventStream![], Rejected>, rocket::response::Debug<anyhow::Error>> {
+    let (fields, weights, kinds) = match (param(fields), param(weights), param(kinds)) {
+        (Ok(fields), Ok(weights), Ok(kinds)) => (fields, weights, kinds),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            return rejected(Status::BadRequest, &e).map(Err)
+        }
+    };
+    let (scope, attribution) = {
+        let scopes = scopes.read();
+        let scope = scope.unwrap_or(&scopes.default);
+        (
+            scopes.resolve(scope).context("resolving scope failed")?,
+            scopes
+                .attribution(scope)
+                .context("resolving scope failed")?,
+        )
+    };
+    // Fail before streaming, although the query is parsed again in the arena of the search task.
+    if let Err(e) = parse_query_in(&Arena::new(), query) {
+        return rejected(Status::BadRequest, &e).map(Err);
+    }
+    let query = query.to_owned();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    let (tx, mut rx) = rocket::tokio::sync::mpsc::channel(64);
+    let index = index.inner().clone();
+    rocket::tokio::task::spawn_blocking(move || {
+        let arena = Arena::new();
+        let query = match parse_query_in(&arena, &query) {
+            Ok(query) => Query {
+                weights: weights.0,
+                kinds: kinds.0,
+                ..query
+            },
+            Err(_) => unreachable!("query `{}` has already been parsed", query),
+        };
+        let mut count = 0;
+        let default = DefaultMatcher::new(DEFAULT_THRESHOLD);
+        let exact_matcher = ExactMatcher(&default);
+        let matcher: &dyn Matcher = if exact { &exact_matcher } else { &default };
+        let result =
+            index
+                .read()
+                .search_each_with(matcher, &query, scope, DEFAULT_THRESHOLD, |mut hit| {
+                    count += 1;
+                    attribution.attribute(&mut hit);
+                    if !debug {
+                        hit.clear_similarities();
+                    }
+                    let event = match fields.select(&hit) {
+                        Ok(hit) => Event::json(&hit),
+                        Err(_) => return ControlFlow::Break(()),
+                    };
+                    if tx.blocking_send(event).is_err() || count >= limit {
+                        // Either the client has gone or it has got enough hits.
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                });
+        let last = match result {
+            Ok(()) => Event::data("").event("end"),
+            Err(e) => Event::data(e.to_string()).event("error"),
+        };
+        let _ = tx.blocking_send(last);
+    });
+
+    Ok(Ok(EventStream! {
+        loop {
+            let event = rocket::tokio::select! {
+                event = rx.recv() => event,
+                // Don't hold off graceful shutdown for the client.
+                _ = &mut shutdown => None,
+            };
+            match event {
+                Some(event) => yield event,
+                None => break,
+            }
+        }
+    }))
+}
+
+/// Find functions taking arguments of types `from`, given once per argument, and returning `to`,
+/// for editors to suggest conversions; see the `suggest` subcommand.
+#[get("/suggest?<from>&<to>&<scope>&<limit>")]
+fn suggest(
+    from: Vec<&str>,
+    to: Option<&str>,
+    scope: Option<&str>,
+    limit: Option<usize>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+) -> Result<Json<Vec<Suggestion>>, rocket::response::Debug<anyhow::Error>> {
+    let arena = Arena::new();
+    let query = crate::suggest::query(&arena, &from, to)?;
+    let scopes = scopes.read();
+    let scope = scopes
+        .resolve(scope.unwrap_or(&scopes.default))
+        .context("resolving scope failed")?;
+    let suggestions = index
+        .read()
+        .search(&query, scope, DEFAULT_THRESHOLD)
+        .context("search failed")?
+        .iter()
+        .take(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .map(Suggestion::from)
+        .collect();
+    Ok(Json(suggestions))
+}
+
+/// Find functions and methods with signatures similar to the one of the item at path `item`, e.g.
+/// `std::fs::read_to_string`, to discover alternatives to it across crates.
+///
+/// Responds with 404 and the error as a JSON string if no function or method is at `item`.
+#[get("/similar?<item>&<scope>&<limit>")]
+fn similar(
+    item: &str,
+    scope: Option<&str>,
+    limit: Option<usize>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+) -> Result<status::Custom<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    let scopes = scopes.read();
+    let scope = scopes
+        .resolve(scope.unwrap_or(&scopes.default))
+        .context("resolving scope failed")?;
+    let (status, json) = match index.read().find_similar_to(item, scope, DEFAULT_THRESHOLD) {
+        Ok(mut hits) => {
+            hits.truncate(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT));
+            hits.iter_mut().for_each(Hit::clear_similarities);
+            (
+                Status::Ok,
+                serde_json::to_string(&hits).context("serializing hits failed")?,
+            )
+        }
+        Err(e @ SearchError::PathNotFound(_)) => (
+            Status::NotFound,
+            serde_json::to_string(&e.to_string()).context("serializing error failed")?,
+        ),
+        Err(e) => return Err(anyhow::Error::new(e).context("search failed").into()),
+    };
+
+    Ok(status::Custom(status, content::Json(json)))
+}
+
+/// List the most common shapes of signatures in `scope`, e.g. `fn (&Self) -> T`, for frontends to
+/// offer as queries to start from.
+#[get("/templates?<scope>&<limit>")]
+fn templates(
+    scope: Option<&str>,
+    limit: Option<usize>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+) -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
+    let scopes = scopes.read();
+    let scope = scopes
+        .resolve(scope.unwrap_or(&scopes.default))
+        .context("resolving scope failed")?;
+    let templates = index
+        .read()
+        .templates(scope, limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .context("listing templates failed")?;
+    Ok(content::Json(
+        serde_json::to_string(&templates).context("serializing templates failed")?,
+    ))
+}
+
+/// Find every function and method mentioning type `of`, e.g. `Duration`, in its signature,
+/// grouped by whether they mention it as the receiver, another argument or the output.
+///
+/// Responds with 400 and the parse error if `of` isn't a type.
+#[get("/uses?<of>&<scope>")]
+fn uses(
+    of: &str,
+    scope: Option<&str>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+) -> Result<status::Custom<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    const PREFIX: &str = "uses ";
+
+    let arena = Arena::new();
+    let ty = match parse_query_in(&arena, &format!("{}{}", PREFIX, of)) {
+        Ok(Query {
+            kind: Some(QueryKind::UsesQuery(ty)),
+            ..
+        }) => ty,
+        Ok(_) => unreachable!("`uses` queries are parsed as such"),
+        Err(mut e) => {
+            // Point at `of` rather than the query made of it.
+            e.offset = e.offset.saturating_sub(PREFIX.len());
+            let json = serde_json::to_string(&e).context("serializing parse error failed")?;
+            return Ok(status::Custom(Status::BadRequest, content::Json(json)));
+        }
+    };
+
+    let scopes = scopes.read();
+    let scope = scopes
+        .resolve(scope.unwrap_or(&scopes.default))
+        .context("resolving scope failed")?;
+    let uses = index.read().uses(&ty, scope).context("search failed")?;
+    let json = serde_json::to_string(&uses).context("serializing uses failed")?;
+    Ok(status::Custom(Status::Ok, content::Json(json)))
+}
+
+#[get("/parse", data = "<query>", rank = 2)]
+fn parse_with_data(
+    query: &str,
+) -> Result<status::Custom<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    parse(query)
+}
+
+/// Parse given `query` without performing search.
+///
+/// Responds with the parsed query, or with a parse error and its offset.
+#[get("/parse?<query>")]
+fn parse(
+    query: &str,
+) -> Result<status::Custom<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    let arena = Arena::new();
+    let (status, json) = match parse_query_in(&arena, query) {
+        Ok(query) => (
+            Status::Ok,
+            serde_json::to_string(&query).context("serializing query failed")?,
+        ),
+        Err(e) => (
+            Status::BadRequest,
+            serde_json::to_string(&e).context("serializing parse error failed")?,
+        ),
+    };
+
+    Ok(status::Custom(status, content::Json(json)))
+}
+
+/// Lists features of the query language the server understands, so that frontends can offer
+/// only what it parses.
+#[get("/capabilities")]
+fn capabilities_json() -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
+    Ok(content::Json(
+        serde_json::to_string(&capabilities()).context("serializing capabilities failed")?,
+    ))
+}
+
+/// Request to share a search, which is given like [`SearchRequest`] without paging.
+#[derive(Debug, Deserialize)]
+pub struct ShareRequest {
+    pub query: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub exact: bool,
+    #[serde(default)]
+    pub weights: Weights,
+    #[serde(default)]
+    pub kinds: ItemKinds,
+    #[serde(default)]
+    pub newtypes: bool,
+}
+
+/// Default upper bound of the size of a request to share a search, configurable via
+/// `limits.share`.
+const DEFAULT_SHARE_LIMIT: ByteUnit = ByteUnit::Kibibyte(16);
+
+/// Share a search, responding with its token and the path resolving it, `/q/<token>`.
+///
+/// Responds with 400 and the parse error if the query doesn't parse, and with 507 if as many
+/// searches as `--max-shared-searches` are shared already.
+#[post("/share", format = "json", data = "<data>")]
+async fn share_search(
+    data: Data<'_>,
+    limits: &Limits,
+    opt: &State<Opt>,
+) -> Result<status::Custom<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    let limit = limits.get("share").unwrap_or(DEFAULT_SHARE_LIMIT);
+    let json = data
+        .open(limit)
+        .into_string()
+        .await
+        .context("failed to read the request")?;
+    if !json.is_complete() {
+        let json = serde_json::to_string(&format!("request exceeds the limit of {}", limit))
+            .context("serializing error failed")?;
+        return Ok(status::Custom(Status::PayloadTooLarge, content::Json(json)));
+    }
+    let request = match serde_json::from_str::<ShareRequest>(&json) {
+        Ok(request) => request,
+        Err(e) => {
+            let json = serde_json::to_string(&e.to_string()).context("serializing error failed")?;
+            return Ok(status::Custom(
+                Status::UnprocessableEntity,
+                content::Json(json),
+            ));
+        }
+    };
+
+    let arena = Arena::new();
+    let query = match parse_query_in(&arena, &request.query) {
+        Ok(query) => query,
+        Err(e) => {
+            let json = serde_json::to_string(&e).context("serializing parse error failed")?;
+            return Ok(status::Custom(Status::BadRequest, content::Json(json)));
+        }
+    };
+    let search = SharedSearch {
+        canonical: query.canonicalize(),
+        parsed: serde_json::to_value(&query).context("serializing query failed")?,
+        query: request.query,
+        scope: request.scope,
+        exact: request.exact,
+        weights: request.weights,
+        kinds: request.kinds,
+        newtypes: request.newtypes,
+    };
+    let token = match share::save(&opt.index, &search, opt.max_shared_searches) {
+        Ok(token) => token,
+        Err(e) if e.is::<SharesFull>() => {
+            let json = serde_json::to_string(&e.to_string()).context("serializing error failed")?;
+            return Ok(status::Custom(
+                Status::InsufficientStorage,
+                content::Json(json),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    info!(%token, query = %search.query, "search shared");
+
+    let json = serde_json::json!({ "token": token, "path": format!("/q/{}", token) });
+    Ok(status::Custom(
+        Status::Created,
+        content::Json(json.to_string()),
+    ))
+}
+
+/// The search shared as `token`, along with its query as parsed when it was shared.
+#[get("/share/<token>")]
+fn shared_search(
+    token: &str,
+    opt: &State<Opt>,
+) -> Result<Option<Json<SharedSearch>>, rocket::response::Debug<anyhow::Error>> {
+    Ok(share::load(&opt.index, token)?.map(Json))
+}
+
+/// Perform the search shared as `token` like `/search`, responding with 404 if there is none.
+///
+/// The query is searched with as it was parsed when it was shared; see [`SharedSearch::query_in`].
+/// If it can't be restored, it responds with 422 and the shared search.
+#[allow(clippy::too_many_arguments)]
+#[get("/q/<token>?<limit>&<offset>&<fields>&<debug>")]
+fn resolve_shared_search(
+    token: &str,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    fields: form::Result<'_, Fields>,
+    debug: bool,
+    cache: SearchCache<'_>,
+    request_id: &RequestId,
+    opt: &State<Opt>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    comparisons: &State<Option<Arc<ComparisonCache>>>,
+) -> Result<Option<Searched>, rocket::response::Debug<anyhow::Error>> {
+    let fields = match param(fields) {
+        Ok(fields) => fields,
+        Err(e) => return rejected(Status::BadRequest, &e).map(|rejected| Some(Err(rejected))),
+    };
+    let search = match share::load(&opt.index, token)? {
+        Some(search) => search,
+        None => return Ok(None),
+    };
+    let arena = Arena::new();
+    let query = match search.query_in(&arena) {
+        Some(query) => query,
+        None => {
+            return rejected(Status::UnprocessableEntity, &search)
+                .map(|rejected| Some(Err(rejected)))
+        }
+    };
+    let request = SearchRequest {
+        query: query.canonicalize(),
+        scope: search.scope,
+        threshold: DEFAULT_THRESHOLD,
+        limit: limit.unwrap_or(DEFAULT_LIMIT),
+        offset: offset.unwrap_or_default(),
+        debug,
+        exact: search.exact,
+        weights: search.weights,
+        kinds: search.kinds,
+        newtypes: search.newtypes,
+    };
+
+    let rendered = cache.respond(&request, fields, request_id, || {
+        perform_search_with(
+            &request,
+            &query,
+            request_id,
+            &index.read(),
+            &scopes.read(),
+            comparisons.as_deref(),
+        )
+    })?;
+    Ok(Some(Ok(rendered)))
+}
+
+#[get("/scopes")]
+fn scopes(
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+    if_none_match: IfNoneMatch,
+) -> Result<Cached<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    let scopes = scopes.read();
+    let etag = etag.read();
+    if if_none_match.matches(&etag) {
+        return Ok(Cached::not_modified(&etag));
+    }
+
+    let result = scopes.names();
+
+    Ok(Cached::fresh(
+        &etag,
+        content::Json(serde_json::to_string(&result).context("serializing scopes failed")?),
+    ))
+}
+
+#[derive(Debug, Serialize)]
+struct CrateSummary<'a> {
+    name: &'a str,
+    version: Option<&'a str>,
+    items: usize,
+    functions: usize,
+    methods: usize,
+    traits: usize,
+    sets: Vec<&'a str>,
+}
+
+#[get("/crates")]
+fn crates(
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+    if_none_match: IfNoneMatch,
+) -> Result<Cached<content::Json<String>>, rocket::response::Debug<anyhow::Error>> {
+    use rustdoc_types::ItemEnum;
+
+    let index = index.read();
+    let scopes = scopes.read();
+    let etag = etag.read();
+    if if_none_match.matches(&etag) {
+        return Ok(Cached::not_modified(&etag));
+    }
+
+    let mut result = index
+        .crates()
+        .iter()
+        .map(|(name, krate)| {
+            let count = |f: fn(&ItemEnum) -> bool| {
+                krate.index.values().filter(|item| f(&item.inner)).count()
+            };
+            let mut sets = scopes
+                .sets
+                .iter()
+                .filter(|(_, scope)| matches!(scope, Scope::Set(krates) if krates.contains(name)))
+                .map(|(set, _)| set.as_str())
+                .collect::<Vec<_>>();
+            sets.sort_unstable();
+
+            CrateSummary {
+                name,
+                version: krate.crate_version.as_deref(),
+                items: krate.index.len(),
+                functions: count(|inner| matches!(inner, ItemEnum::Function(_))),
+                methods: count(|inner| matches!(inner, ItemEnum::Method(_))),
+                traits: count(|inner| matches!(inner, ItemEnum::Trait(_))),
+                sets,
+            }
+        })
+        .collect::<Vec<_>>();
+    result.sort_unstable_by_key(|summary| summary.name);
+
+    Ok(Cached::fresh(
+        &etag,
+        content::Json(serde_json::to_string(&result).context("serializing crates failed")?),
+    ))
+}
+
+#[get("/openapi.json")]
+fn openapi_json() -> content::Json<String> {
+    content::Json(openapi::document().to_string())
+}
+
+#[get("/schema.json")]
+fn schema_json() -> content::Json<String> {
+    content::Json(openapi::json_schema().to_string())
+}
+
+#[derive(Serialize)]
+struct Stats<'a> {
+    crates: usize,
+    sets: usize,
+    degraded: Vec<DegradedCrate<'a>>,
+}
+
+#[derive(Serialize)]
+struct DegradedCrate<'a> {
+    name: &'a str,
+    cause: &'a str,
+}
+
+/// Reports how many crates and sets are searched, and which crates in the index directory failed
+/// to load and why.
+#[get("/stats")]
+fn stats(
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+) -> Result<content::Json<String>, rocket::response::Debug<anyhow::Error>> {
+    let scopes = scopes.read();
+    let stats = Stats {
+        crates: index.read().crates().len(),
+        sets: scopes.sets.len(),
+        degraded: scopes
+            .degraded
+            .iter()
+            .map(|(name, cause)| DegradedCrate { name, cause })
+            .collect(),
+    };
+    Ok(content::Json(
+        serde_json::to_string(&stats).context("serializing stats failed")?,
+    ))
+}
+
+/// Reports that the process is up.
+#[get("/healthz")]
+fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Reports whether the index is loaded and how many crates are available.
+///
+/// An index with no crates is ready once loaded, as crates may be uploaded into it.
+#[get("/readyz")]
+fn readyz(
+    loaded: &State<Loaded>,
+    index: &State<Shared<Index>>,
+) -> status::Custom<content::Json<String>> {
+    let ready = loaded.0.load(Ordering::Acquire);
+    let krates = index.read().crates().len();
+    let status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+
+    status::Custom(
+        status,
+        content::Json(format!(r#"{{"ready":{},"crates":{}}}"#, ready, krates)),
+    )
+}
+
+/// Whether the index has been loaded and the server lifted off with it, which `/readyz` reports.
+#[derive(Default)]
+struct Loaded(AtomicBool);
+
+/// Default upper bound of the size of an uploaded crate, configurable via `limits.crate`.
+const DEFAULT_CRATE_LIMIT: ByteUnit = ByteUnit::Mebibyte(256);
+
+/// Upload rustdoc JSON of crate `name`, which will be shaken and merged into the index.
+///
+/// The crate is persisted in the index directory as well so that it survives restarts.
+#[put("/crates/<name>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+async fn upload_crate(
+    name: &str,
+    data: Data<'_>,
+    _admin: Admin,
+    limits: &Limits,
+    opt: &State<Opt>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+) -> Result<status::Custom<String>, rocket::response::Debug<anyhow::Error>> {
+    if !is_valid_crate_name(name) {
+        return Ok(status::Custom(
+            Status::UnprocessableEntity,
+            format!("invalid crate name `{}`", name),
+        ));
+    }
+
+    let limit = limits.get("crate").unwrap_or(DEFAULT_CRATE_LIMIT);
+    let json = data
+        .open(limit)
+        .into_string()
+        .await
+        .context("failed to read uploaded crate")?;
+    if !json.is_complete() {
+        return Ok(status::Custom(
+            Status::PayloadTooLarge,
+            format!("crate exceeds the limit of {}", limit),
+        ));
+    }
+
+    let krate = rocket::tokio::task::spawn_blocking(move || from_json::<Crate>(&json))
+        .await
+        .context("failed to join deserialization")?;
+    let krate = match krate {
+        Ok(krate) => krate,
+        Err(e) => {
+            return Ok(status::Custom(
+                Status::UnprocessableEntity,
+                format!("failed to deserialize crate: {}", e),
+            ))
+        }
+    };
+    if let Err(e) = validate_crate(&krate) {
+        return Ok(status::Custom(Status::UnprocessableEntity, e.to_string()));
+    }
+    let replaced = store_crate(name, krate, opt, index, scopes, etag).await?;
+    info!(krate = name, replaced, "crate uploaded");
+
+    let status = if replaced {
+        Status::Ok
+    } else {
+        Status::Created
+    };
+    Ok(status::Custom(status, format!("crate `{}` indexed", name)))
+}
+
+/// Build crate `name` from crates.io and swap it into the index once the build succeeds.
+#[post("/crates/<name>/reindex?<version>")]
+async fn reindex_crate(
+    name: &str,
+    version: Option<&str>,
+    _admin: Admin,
+    opt: &State<Opt>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+) -> Result<status::Custom<String>, rocket::response::Debug<anyhow::Error>> {
+    if !is_valid_crate_name(name) {
+        return Ok(status::Custom(
+            Status::UnprocessableEntity,
+            format!("invalid crate name `{}`", name),
+        ));
+    }
+    if let Some(Err(e)) = version.map(indexer::validate_version) {
+        return Ok(status::Custom(Status::BadRequest, format!("{:#}", e)));
+    }
+
+    let start = Instant::now();
+    let (krate_name, version_req, toolchain) = (
+        name.to_owned(),
+        version.map(ToOwned::to_owned),
+        opt.toolchain.clone(),
+    );
+    let krate = rocket::tokio::task::spawn_blocking(move || {
+        indexer::index_crate(&krate_name, version_req.as_deref(), &toolchain)
+    })
+    .await
+    .context("failed to join indexing")??;
+    if let Err(e) = validate_crate(&krate) {
+        return Ok(status::Custom(Status::UnprocessableEntity, e.to_string()));
+    }
+    let replaced = store_crate(name, krate, opt, index, scopes, etag).await?;
+    info!(
+        krate = name,
+        replaced,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "crate reindexed"
+    );
+
+    Ok(status::Custom(
+        Status::Ok,
+        format!("crate `{}` reindexed", name),
+    ))
+}
+
+/// Download a snapshot of the crates in the index, which `roogle snapshot load` or
+/// `roogle serve --snapshot` reproduces the index from.
+#[get("/snapshot")]
+async fn download_snapshot(
+    _admin: Admin,
+    index: &State<Shared<Index>>,
+) -> Result<content::Json<Vec<u8>>, rocket::response::Debug<anyhow::Error>> {
+    let index = index.inner().clone();
+    let json = rocket::tokio::task::spawn_blocking(move || {
+        let mut json = vec![];
+        snapshot::write(&mut json, index.read().crates()).map(|_| json)
+    })
+    .await
+    .context("failed to join writing the snapshot")?
+    .context("failed to write the snapshot")?;
+    Ok(content::Json(json))
+}
+
+/// Create or replace set `name` consisting of given crates.
+///
+/// The set is persisted in the index directory as well so that it survives restarts. The file is
+/// written first under the lock of the scopes, so that a set failing to be registered is neither
+/// served nor left to be served after a restart, and crates can't go away in between.
+#[put("/sets/<name>", format = "json", data = "<krates>")]
+fn put_set(
+    name: &str,
+    krates: Json<Vec<String>>,
+    _admin: Admin,
+    opt: &State<Opt>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+) -> Result<status::Custom<String>, rocket::response::Debug<anyhow::Error>> {
+    if !is_valid_crate_name(name) {
+        return Ok(status::Custom(
+            Status::UnprocessableEntity,
+            format!("invalid set name `{}`", name),
+        ));
+    }
+    let krates = krates.into_inner();
+    let replaced = {
+        let mut scopes = scopes.write();
+        if let Some(krate) = krates
+            .iter()
+            .find(|krate| !scopes.krates.contains_key(*krate))
+        {
+            return Ok(status::Custom(
+                Status::UnprocessableEntity,
+                format!("crate `{}` is not present in the index", krate),
+            ));
+        }
+
+        let dir = opt.index.join("set");
+        std::fs::create_dir_all(&dir).with_context(|| format!("failed to create `{:?}`", dir))?;
+        let json = serde_json::to_string(&krates).context("failed to serialize set")?;
+        write_atomically(&dir.join(format!("{}.json", name)), &json)?;
+
+        scopes
+            .sets
+            .insert(name.to_owned(), Scope::Set(krates))
+            .is_some()
+    };
+    refresh_etag(index, scopes, etag)?;
+    info!(set = name, replaced, "set registered");
+
+    let status = if replaced {
+        Status::Ok
+    } else {
+        Status::Created
+    };
+    Ok(status::Custom(status, format!("set `{}` registered", name)))
+}
+
+/// Remove set `name` from both the live scopes and the index directory.
+///
+/// The file is removed first, so that a set failing to be removed is still served as it would be
+/// after a restart.
+#[delete("/sets/<name>")]
+fn delete_set(
+    name: &str,
+    _admin: Admin,
+    opt: &State<Opt>,
+    index: &State<Shared<Index>>,
+    scopes: &State<Shared<Scopes>>,
+    etag: &State<Shared<ETag>>,
+) -> Result<status::Custom<String>, rocket::response::Debug<anyhow::Error>> {
+    {
+        let mut scopes = scopes.write();
+        if scopes.default == format!("set:{}", name) {
+            return Ok(status::Custom(
+                Status::Conflict,
+                format!("set `{}` is the default scope", name),
+            ));
+        }
+        if !scopes.sets.contains_key(name) {
+            return Ok(status::Custom(
+                Status::NotFound,
+                format!("set `{}` is not present", name),
+            ));
+        }
+
+        let path = opt.index.join("set").join(format!("{}.json", name));
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            // Nothing is left to be served after a restart, so removing it from memory is enough.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("failed to remove `{:?}`", path))
+                    .into())
+            }
+        }
+        scopes.sets.remove(name);
+    }
+    refresh_etag(index, scopes, etag)?;
+    info!(set = name, "set removed");
+
+    Ok(status::Custom(
+        Status::Ok,
+        format!("set `{}` removed", name),
+    ))
+}
+
+/// Shake `krate`, persist it in the index directory and insert it into the live index, returning
+/// whether it replaced an existing crate.
+///
+/// This runs on a blocking thread, as each step takes long for large crates, inserting all the
+/// more as it recomputes what the index derives from every crate.
+async fn store_crate(
+    name: &str,
+    krate: Crate,
+    opt: &Opt,
+    index: &Shared<Index>,
+    scopes: &Shared<Scopes>,
+    etag: &Shared<ETag>,
+) -> Result<bool> {
+    let (name, dir) = (name.to_owned(), opt.index.clone());
+    let (index, scopes, etag) = (index.clone(), scopes.clone(), etag.clone());
+    rocket::tokio::task::spawn_blocking(move || {
+        let krate = shake(krate);
+        persist_crate(&dir, &name, &krate)?;
+        remove_delta(&dir, &name)?;
+        register_crate(&name, krate, &index, &scopes, &etag)
+    })
+    .await
+    .context("failed to join storing the crate")?
+}
+
+/// Insert `krate` into the live index, returning whether it replaced an existing crate.
+fn register_crate(
+    name: &str,
+    krate: Crate,
+    index: &Shared<Index>,
+    scopes: &Shared<Scopes>,
+    etag: &Shared<ETag>,
+) -> Result<bool> {
+    let replaced = index.write().insert(name.to_owned(), krate).is_some();
+    {
+        let mut scopes = scopes.write();
+        scopes
+            .krates
+            .insert(name.to_owned(), Scope::Crate(name.to_owned()));
+        scopes.degraded.remove(name);
+    }
+    refresh_etag(index, scopes, etag)?;
+    Ok(replaced)
+}
+
+/// Recompute the [`ETag`] after `index` or `scopes` changed.
+fn refresh_etag(index: &Shared<Index>, scopes: &Shared<Scopes>, etag: &Shared<ETag>) -> Result<()> {
+    let new_etag =
+        ETag::new(&index.read(), &scopes.read()).context("failed to identify the index")?;
+    *etag.write() = new_etag;
+    Ok(())
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long = "index", name = "INDEX")]
+    index_flag: Option<PathBuf>,
+
+    /// `--index` or its default from the configuration file.
+    #[structopt(skip)]
+    index: PathBuf,
+
+    /// Snapshot to load crates from instead of `<INDEX>/crate`, still taking sets from the index.
+    #[structopt(long, name = "SNAPSHOT")]
+    snapshot: Option<PathBuf>,
+
+    /// Toolchain to build rustdoc JSON with when reindexing crates.
+    #[structopt(long, default_value = "nightly")]
+    toolchain: String,
+
+    /// Token required to use admin routes, falling back to `admin_token` in `Rocket.toml`.
+    ///
+    /// Admin routes are disabled if no token is given.
+    #[structopt(long, env = "ROOGLE_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+
+    /// Scope to search in when a request doesn't specify one [default: all].
+    #[structopt(long)]
+    default_scope: Option<String>,
+
+    /// IP address to serve on, overriding `ROCKET_ADDRESS` and `Rocket.toml`.
+    #[structopt(long)]
+    address: Option<IpAddr>,
+
+    /// Port to serve on, overriding `ROCKET_PORT` and `Rocket.toml`.
+    #[structopt(short, long)]
+    port: Option<u16>,
+
+    /// Number of worker threads, overriding `ROCKET_WORKERS` and `Rocket.toml`.
+    #[structopt(long)]
+    workers: Option<usize>,
+
+    /// Seconds to cache search responses for, or `0` to disable caching.
+    #[structopt(long, default_value = "60")]
+    cache_ttl: u64,
+
+    /// Maximum number of search responses to cache.
+    #[structopt(long, default_value = "1024")]
+    cache_capacity: usize,
+
+    /// File to persist scores of items compared with queries in across restarts, which enables
+    /// caching them.
+    #[structopt(long, name = "PATH")]
+    comparison_cache: Option<PathBuf>,
+
+    /// Maximum number of queries to cache scores of items for, dropping the least searched ones.
+    #[structopt(long, default_value = "1024")]
+    comparison_cache_capacity: usize,
+
+    /// Seconds to wait for in-flight requests on shutdown, overriding `shutdown.grace` in
+    /// `Rocket.toml`.
+    #[structopt(long)]
+    shutdown_grace: Option<u32>,
+
+    /// Maximum number of searches to keep shared, refusing to share more.
+    #[structopt(long, default_value = "10000")]
+    max_shared_searches: usize,
+
+    /// Port to serve gRPC on, along with HTTP on the same address.
+    #[cfg(feature = "grpc")]
+    #[structopt(long)]
+    grpc_port: Option<u16>,
+}
+
+impl Opt {
+    /// Rocket's configuration with the server options given by the command line merged.
+    fn figment(&self) -> Figment {
+        let mut figment = rocket::Config::figment();
+        if let Some(address) = self.address {
+            figment = figment.merge(("address", address));
+        }
+        if let Some(port) = self.port {
+            figment = figment.merge(("port", port));
+        }
+        if let Some(workers) = self.workers {
+            figment = figment.merge(("workers", workers));
+        }
+        if let Some(grace) = self.shutdown_grace {
+            figment = figment.merge(("shutdown.grace", grace));
+        }
+        figment
+    }
+}
+
+/// Serve until SIGTERM or Ctrl-C.
+pub fn run(mut opt: Opt, config: &Config) -> Result<()> {
+    opt.index = config.index(opt.index_flag.take());
+    let rocket = rocket(opt, config)?;
+    let workers = rocket::Config::from(rocket.figment()).workers;
+    let comparisons = rocket
+        .state::<Option<Arc<ComparisonCache>>>()
+        .cloned()
+        .flatten();
+
+    // Rocket only waits for in-flight requests on shutdown if its workers are named this way.
+    let runtime = rocket::tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .thread_name("rocket-worker-thread")
+        .enable_all()
+        .build()
+        .context("failed to start the runtime")?;
+
+    // Rocket stops accepting connections on SIGTERM or Ctrl-C, then waits `shutdown.grace`
+    // seconds for in-flight requests before returning here.
+    runtime
+        .block_on(rocket.launch())
+        .map_err(|e| anyhow!("serving failed: {}", e))?;
+    info!("shut down gracefully");
+    if let Some(comparisons) = comparisons {
+        comparisons.save()?;
+    }
+    Ok(())
+}
+
+fn rocket(opt: Opt, config: &Config) -> Result<Rocket<Build>> {
+    let default_scope = config.scope(opt.default_scope.clone());
+    let (index, scopes) = match opt.snapshot {
+        Some(ref snapshot) => load_snapshot(snapshot, &opt.index, &default_scope)?,
+        None => load(&opt.index, &default_scope)?,
+    };
+    let etag = ETag::new(&index, &scopes).context("failed to identify the index")?;
+    let comparisons = match opt.comparison_cache {
+        Some(ref path) => Some(Arc::new(ComparisonCache::load(
+            path.clone(),
+            opt.comparison_cache_capacity,
+            &format!(
+                "{:016x}",
+                index_version(&index).context("failed to identify the index")?
+            ),
+        )?)),
+        None => None,
+    };
+    let index = Shared::new(index);
+    let scopes = Shared::new(scopes);
+    let etag = Shared::new(etag);
+
+    #[cfg(feature = "grpc")]
+    let grpc_port = opt.grpc_port;
+
+    let figment = opt.figment();
+    let admin_token = AdminToken(
+        opt.admin_token
+            .clone()
+            .or_else(|| figment.extract_inner("admin_token").ok()),
+    );
+
+    let rocket = rocket::custom(figment)
+        .attach(Cors)
+        .attach(RequestIds::default())
+        .manage(index.clone())
+        .manage(scopes.clone())
+        .manage(etag)
+        .manage(comparisons.clone())
+        .manage(ResponseCache::new(
+            Duration::from_secs(opt.cache_ttl),
+            opt.cache_capacity,
+        ))
+        .manage(opt)
+        .manage(admin_token)
+        .manage(Loaded::default())
+        .attach(rocket::fairing::AdHoc::on_liftoff("Readiness", |rocket| {
+            Box::pin(async move {
+                if let Some(loaded) = rocket.state::<Loaded>() {
+                    loaded.0.store(true, Ordering::Release);
+                }
+            })
+        }))
+        .attach(Unversioned)
+        .mount(API_BASE, api_routes())
+        // Compatibility shim for clients predating `/v1`.
+        .mount("/", api_routes())
+        .mount("/", routes![healthz, readyz, resolve_shared_search]);
+
+    #[cfg(feature = "grpc")]
+    let rocket = match grpc_port {
+        Some(port) => rocket.attach(rocket::fairing::AdHoc::on_liftoff("gRPC", move |rocket| {
+            let addr = std::net::SocketAddr::new(rocket.config().address, port);
+            let shutdown = rocket.shutdown();
+            Box::pin(async move {
+                rocket::tokio::spawn(async move {
+                    if let Err(e) = grpc::serve(addr, index, scopes, comparisons, shutdown).await {
+                        warn!("serving gRPC failed: {}", e);
+                    }
+                });
+            })
+        })),
+        None => rocket,
+    };
+
+    Ok(rocket)
+}
+
+/// Base path of the current version of the API.
+pub const API_BASE: &str = "/v1";
+
+fn api_routes() -> Vec<rocket::Route> {
+    routes![
+        search,
+        search_with_data,
+        search_with_json,
+        search_stream,
+        suggest,
+        similar,
+        uses,
+        templates,
+        parse,
+        parse_with_data,
+        capabilities_json,
+        scopes,
+        crates,
+        stats,
+        upload_crate,
+        reindex_crate,
+        download_snapshot,
+        put_set,
+        delete_set,
+        share_search,
+        shared_search,
+        openapi_json,
+        schema_json
+    ]
+}
+
+/// Token required by [`Admin`], given via `--admin-token`, `ROOGLE_ADMIN_TOKEN` or
+/// `admin_token` in `Rocket.toml`.
+struct AdminToken(Option<String>);
+
+/// Request guard for admin routes, i.e. the ones mutating the index or sets.
+///
+/// Requires `Authorization: Bearer <TOKEN>` where `<TOKEN>` is the [`AdminToken`].
+struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match req
+            .rocket()
+            .state::<AdminToken>()
+            .and_then(|token| token.0.as_deref())
+        {
+            Some(token) => token,
+            None => return Outcome::Failure((Status::Forbidden, "admin routes are disabled")),
+        };
+        let given = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|auth| auth.strip_prefix("Bearer "));
+        match given {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+                Outcome::Success(Admin)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, "invalid admin token")),
+        }
+    }
+}
+
+/// Compare `a` and `b` in time independent of where they differ, not to leak the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Identifies a version of the loaded index and scopes.
+///
+/// Served as an `ETag` so that clients can cache responses derived from them.
+struct ETag(String);
+
+impl ETag {
+    fn new(index: &Index, scopes: &Scopes) -> std::io::Result<Self> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        index_version(index)?.hash(&mut hasher);
+
+        let mut sets = scopes.sets.iter().collect::<Vec<_>>();
+        sets.sort_unstable_by_key(|(name, _)| *name);
+        for (name, set) in sets {
+            name.hash(&mut hasher);
+            set.clone().flatten().hash(&mut hasher);
+        }
+
+        Ok(ETag(format!("\"{:016x}\"", hasher.finish())))
+    }
+}
+
+/// Identify a version of the crates in `index` by their contents, regardless of scopes.
+///
+/// It's the same across restarts serving the same crates, so that the comparison cache persisted
+/// with it stays valid.
+fn index_version(index: &Index) -> std::io::Result<u64> {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = fnv::FnvHasher::default();
+
+    let mut krates = index.crates().keys().collect::<Vec<_>>();
+    krates.sort_unstable();
+    for name in krates {
+        name.hash(&mut hasher);
+        index.checksum(name)?.hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+/// Represents the `If-None-Match` header of a request.
+struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    fn matches(&self, etag: &ETag) -> bool {
+        match self.0 {
+            Some(ref tags) => tags
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag.0),
+            None => false,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            req.headers()
+                .get_one("If-None-Match")
+                .map(ToOwned::to_owned),
+        ))
+    }
+}
+
+/// A response tagged with an `ETag`, or `304 Not Modified` if the client already has it.
+struct Cached<R> {
+    etag: String,
+    body: Option<R>,
+}
+
+impl<R> Cached<R> {
+    fn fresh(etag: &ETag, body: R) -> Self {
+        Cached {
+            etag: etag.0.clone(),
+            body: Some(body),
+        }
+    }
+
+    fn not_modified(etag: &ETag) -> Self {
+        Cached {
+            etag: etag.0.clone(),
+            body: None,
+        }
+    }
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Cached<R> {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        let mut res = match self.body {
+            Some(body) => body.respond_to(req)?,
+            None => rocket::Response::build()
+                .status(Status::NotModified)
+                .finalize(),
+        };
+        res.set_header(Header::new("ETag", self.etag));
+        Ok(res)
+    }
+}
+
+struct Cors;
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _: &'r rocket::Request<'_>, res: &mut rocket::Response<'r>) {
+        res.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        res.set_header(Header::new("Access-Control-Allow-Methods", "GET, POST"));
+        res.set_header(Header::new(
+            "Access-Control-Allow-Headers",
+            "Content-Type, If-None-Match",
+        ));
+        res.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        res.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            "X-Request-Id, ETag, Deprecation, Link, X-Cache, X-Found-In-Other-Crates",
+        ));
+    }
+}
+
+/// Marks responses to unversioned API paths as deprecated in favor of [`API_BASE`].
+struct Unversioned;
+
+#[rocket::async_trait]
+impl Fairing for Unversioned {
+    fn info(&self) -> Info {
+        Info {
+            name: "Unversioned API deprecation",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r rocket::Request<'_>, res: &mut rocket::Response<'r>) {
+        let path = req.uri().path();
+        // Probes and links to shared searches are only served unversioned.
+        let is_unversioned = req.route().is_some_and(|route| route.uri.base() == "/")
+            && !matches!(path.as_str(), "/healthz" | "/readyz")
+            && !path.as_str().starts_with("/q/");
+        if is_unversioned {
+            res.set_header(Header::new("Deprecation", "true"));
+            res.set_header(Header::new(
+                "Link",
+                format!("<{}{}>; rel=\"successor-version\"", API_BASE, path),
+            ));
+        }
+    }
+}
+
+/// Identifies a request, either given by the client via `X-Request-Id` or generated by
+/// [`RequestIds`].
+#[derive(Debug)]
+pub struct RequestId(pub String);
+
+/// Most bytes of an `X-Request-Id` given by a client, past which one is generated instead.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+impl RequestId {
+    /// Whether `id` given by a client is short enough and made up of `[A-Za-z0-9._-]` only, so
+    /// that it can't bloat or forge lines of logs it's written into.
+    fn is_valid(id: &str) -> bool {
+        !id.is_empty()
+            && id.len() <= MAX_REQUEST_ID_LEN
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(req.local_cache(|| RequestId(String::new())))
+    }
+}
+
+/// Attaches a [`RequestId`] to every request and echoes it back in the response.
+struct RequestIds {
+    next: AtomicU64,
+}
+
+impl Default for RequestIds {
+    fn default() -> Self {
+        // Seed with the startup time and the process ID so IDs don't collide across restarts, nor
+        // across servers started at once.
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            ^ u64::from(std::process::id()).rotate_right(16);
+        RequestIds {
+            next: AtomicU64::new(seed),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestIds {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut rocket::Request<'_>, _: &mut rocket::Data<'_>) {
+        let id = match req.headers().get_one("X-Request-Id") {
+            Some(id) if RequestId::is_valid(id) => id.to_owned(),
+            _ => format!("{:016x}", self.next.fetch_add(1, Ordering::Relaxed)),
+        };
+        req.local_cache(|| RequestId(id));
+    }
+
+    async fn on_response<'r>(&self, req: &'r rocket::Request<'_>, res: &mut rocket::Response<'r>) {
+        let id = req.local_cache(|| RequestId(String::new()));
+        res.set_header(Header::new("X-Request-Id", id.0.clone()));
+    }
+}
+
+/// State shared among requests, which admin routes may update.
+pub struct Shared<T>(Arc<RwLock<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(inner: T) -> Self {
+        Shared(Arc::new(RwLock::new(inner)))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        // Writers never leave the state half-updated, so it's fine to ignore poisoning.
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared(Arc::clone(&self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rocket::local::blocking::Client;
+    use roogle_engine::testing::load_fixtures;
+
+    use super::*;
+
+    /// Client of a server over the fixtures with `args` given to `roogle serve`, keeping its index
+    /// in `dir`.
+    fn client(dir: &Path, args: &[&str]) -> Client {
+        client_of(&["mini_std", "mini_ext"], dir, args)
+    }
+
+    /// Client of a server like [`client`] over the fixtures `names` alone.
+    fn client_of(names: &[&str], dir: &Path, args: &[&str]) -> Client {
+        std::fs::create_dir_all(dir).unwrap();
+        let snapshot = dir.join("snapshot.json");
+        load_fixtures(names).save(&snapshot).unwrap();
+        let mut opt = Opt::from_iter(
+            ["serve", "--snapshot", snapshot.to_str().unwrap()]
+                .iter()
+                .chain(args),
+        );
+        opt.index = dir.join("index");
+        Client::tracked(rocket(opt, &Config::default()).unwrap()).unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roogle-server-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn guard_admin_routes_with_token() {
+        let put_set = |client: &Client, auth: Option<&str>| {
+            let mut req = client
+                .put(format!("{}/sets/mini", API_BASE))
+                .header(ContentType::JSON)
+                .body(r#"["mini_std"]"#);
+            if let Some(auth) = auth {
+                req = req.header(Header::new("Authorization", auth.to_owned()));
+            }
+            req.dispatch().status()
+        };
+
+        let dir = temp_dir("admin-disabled");
+        let disabled = client(&dir, &[]);
+        assert_eq!(put_set(&disabled, Some("Bearer secret")), Status::Forbidden);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("admin");
+        let admin = client(&dir, &["--admin-token", "secret"]);
+        assert_eq!(put_set(&admin, None), Status::Unauthorized);
+        assert_eq!(put_set(&admin, Some("Bearer secre")), Status::Unauthorized);
+        assert_eq!(put_set(&admin, Some("secret")), Status::Unauthorized);
+        assert_eq!(put_set(&admin, Some("Bearer secret")), Status::Created);
+        assert_eq!(put_set(&admin, Some("Bearer secret")), Status::Ok);
+        assert!(dir.join("index").join("set").join("mini.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn select_fields_of_hits() {
+        let dir = temp_dir("fields");
+        let client = client(&dir, &[]);
+        let search = |fields: &str| {
+            client
+                .get(format!(
+                    "{}/search?query=fn%20(%26%5Bu8%5D)%20-%3E%20Result{}",
+                    API_BASE, fields
+                ))
+                .dispatch()
+        };
+
+        // Whole hits are responded with by default, docs included.
+        let hits: serde_json::Value = search("").into_json().unwrap();
+        let hit = hits[0].as_object().unwrap();
+        for field in ["name", "path", "link", "signature", "docs", "summary"] {
+            assert!(
+                hit.contains_key(field),
+                "{} is missing from {:?}",
+                field,
+                hit
+            );
+        }
+
+        let hits: serde_json::Value = search("&fields=name,link").into_json().unwrap();
+        let mut fields = hits[0].as_object().unwrap().keys().collect::<Vec<_>>();
+        fields.sort_unstable();
+        assert_eq!(fields, ["link", "name"]);
+
+        let response = search("&fields=name,bogus");
+        assert_eq!(response.status(), Status::BadRequest);
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains("`fields`: unknown field `bogus`"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_health_and_readiness() {
+        let dir = temp_dir("readyz");
+        let client = client(&dir, &[]);
+        let response = client.get("/healthz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().unwrap(), "ok");
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_string().unwrap(),
+            r#"{"ready":true,"crates":2}"#
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Crates may be uploaded into an empty index, which is ready once loaded as well.
+        let dir = temp_dir("readyz-empty");
+        let client = client_of(&[], &dir, &[]);
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_string().unwrap(),
+            r#"{"ready":true,"crates":0}"#
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn echo_valid_request_ids_only() {
+        let dir = temp_dir("request-id");
+        let client = client(&dir, &[]);
+        let request_id = |id: Option<&str>| {
+            let mut req = client.get("/healthz");
+            if let Some(id) = id {
+                req = req.header(Header::new("X-Request-Id", id.to_owned()));
+            }
+            let response = req.dispatch();
+            response
+                .headers()
+                .get_one("X-Request-Id")
+                .unwrap()
+                .to_owned()
+        };
+
+        assert_eq!(request_id(Some("req-1.a_B")), "req-1.a_B");
+        let generated = request_id(None);
+        assert_eq!(generated.len(), 16);
+        assert_ne!(request_id(None), generated);
+        for invalid in [
+            "",
+            "has space",
+            "quote\"",
+            &"a".repeat(MAX_REQUEST_ID_LEN + 1),
+        ] {
+            let id = request_id(Some(invalid));
+            assert_eq!(id.len(), 16, "{:?} is echoed as {:?}", invalid, id);
+            assert!(id.bytes().all(|b| b.is_ascii_hexdigit()));
+        }
+        assert_eq!(
+            request_id(Some(&"a".repeat(MAX_REQUEST_ID_LEN))),
+            "a".repeat(MAX_REQUEST_ID_LEN)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compare_tokens_in_constant_time() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret!"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
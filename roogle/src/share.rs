@@ -0,0 +1,219 @@
+//! Searches shared by short tokens, persisted as `share/<TOKEN>.json` in the index directory so
+//! that links to them survive restarts.
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+
+use roogle_engine::{
+    compare::Weights,
+    query::{de::deserialize_in, parse::parse_query_in, Arena, Query},
+    search::ItemKinds,
+};
+
+use crate::store::write_atomically;
+
+/// A shared search, along with its query as parsed when it was shared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedSearch {
+    /// Query as it was given.
+    pub query: String,
+    /// `query` in its canonical form.
+    pub canonical: String,
+    /// `query` as parsed when it was shared, which is searched with whenever the search is
+    /// resolved so that what it meant is kept even if the grammar changes and it no longer parses
+    /// the same.
+    pub parsed: serde_json::Value,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub exact: bool,
+    #[serde(default)]
+    pub weights: Weights,
+    #[serde(default)]
+    pub kinds: ItemKinds,
+    #[serde(default)]
+    pub newtypes: bool,
+}
+
+impl SharedSearch {
+    /// Token of the search, which is the same whenever the same search is shared.
+    ///
+    /// It's an FNV hash, which unlike `DefaultHasher` is the same across Rust versions.
+    pub fn token(&self) -> String {
+        let mut hasher = FnvHasher::default();
+        self.canonical.hash(&mut hasher);
+        self.scope.hash(&mut hasher);
+        self.exact.hash(&mut hasher);
+        self.weights.hash(&mut hasher);
+        self.kinds.hash(&mut hasher);
+        // Hashed only if set, so that searches shared before it existed keep their tokens.
+        if self.newtypes {
+            self.newtypes.hash(&mut hasher);
+        }
+        // 48 bits keep links short while collisions stay unlikely; they are refused anyway.
+        format!("{:012x}", hasher.finish() & 0xffff_ffff_ffff)
+    }
+
+    /// The query to search with, allocated in `arena`.
+    ///
+    /// It's the one parsed when the search was shared, or if that no longer deserializes, the
+    /// canonical form parsed anew, or else the query as it was given. `None` if none of them does.
+    pub fn query_in<'a>(&self, arena: &'a Arena) -> Option<Query<'a>> {
+        deserialize_in(arena, &self.parsed)
+            .ok()
+            .or_else(|| parse_query_in(arena, &self.canonical).ok())
+            .or_else(|| parse_query_in(arena, &self.query).ok())
+    }
+
+    /// Whether `self` and `other` are the same search, even if their queries were given
+    /// differently.
+    fn is_same_as(&self, other: &SharedSearch) -> bool {
+        let other = SharedSearch {
+            query: self.query.clone(),
+            ..other.clone()
+        };
+        *self == other
+    }
+}
+
+/// Failure of sharing a search as there are as many shared searches as allowed already.
+#[derive(Debug)]
+pub struct SharesFull(pub usize);
+
+impl fmt::Display for SharesFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} searches are shared already, which is the maximum",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for SharesFull {}
+
+/// Persist `search` in the index directory `dir`, returning its token.
+///
+/// Fails if another search has the same token, or with [`SharesFull`] if `capacity` searches are
+/// shared already and `search` isn't one of them.
+pub fn save(dir: &Path, search: &SharedSearch, capacity: usize) -> Result<String> {
+    let token = search.token();
+    if let Some(existing) = load(dir, &token)? {
+        if !existing.is_same_as(search) {
+            bail!("token `{}` is taken by another search", token);
+        }
+        return Ok(token);
+    }
+
+    let path = path(dir, &token);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{:?}`", parent))?;
+        let shared = std::fs::read_dir(parent)
+            .with_context(|| format!("failed to read `{:?}`", parent))?
+            .count();
+        if shared >= capacity {
+            return Err(SharesFull(capacity).into());
+        }
+    }
+    let json = serde_json::to_string(search).context("failed to serialize shared search")?;
+    write_atomically(&path, &json)?;
+    Ok(token)
+}
+
+/// The search shared as `token` in the index directory `dir`, or `None` if there is none.
+pub fn load(dir: &Path, token: &str) -> Result<Option<SharedSearch>> {
+    if !is_valid_token(token) {
+        return Ok(None);
+    }
+    let path = path(dir, token);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read `{:?}`", path))?;
+    let search = serde_json::from_str(&json)
+        .with_context(|| format!("failed to deserialize shared search `{:?}`", path))?;
+    Ok(Some(search))
+}
+
+fn path(dir: &Path, token: &str) -> PathBuf {
+    dir.join("share").join(format!("{}.json", token))
+}
+
+/// Whether `token` looks like one [`SharedSearch::token`] makes, so that it is safe in paths.
+fn is_valid_token(token: &str) -> bool {
+    token.len() == 12 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared(query: &str) -> SharedSearch {
+        SharedSearch {
+            query: query.to_owned(),
+            canonical: query.to_owned(),
+            parsed: serde_json::json!({}),
+            scope: Some("set:libstd".to_owned()),
+            exact: false,
+            weights: Weights::default(),
+            kinds: ItemKinds::default(),
+            newtypes: false,
+        }
+    }
+
+    #[test]
+    fn save_and_load_shared_searches() {
+        let dir = std::env::temp_dir().join(format!("roogle-share-{}", std::process::id()));
+        let search = shared("fn (&str) -> usize");
+
+        let token = save(&dir, &search, 2).unwrap();
+        assert_eq!(token, search.token());
+        assert_eq!(save(&dir, &search, 2).unwrap(), token);
+        let respelled = SharedSearch {
+            query: "fn(&str)->usize".to_owned(),
+            ..search.clone()
+        };
+        assert_eq!(save(&dir, &respelled, 2).unwrap(), token);
+        let rescoped = SharedSearch {
+            scope: None,
+            ..search.clone()
+        };
+        assert!(save(&dir, &rescoped, 1)
+            .unwrap_err()
+            .downcast_ref::<SharesFull>()
+            .is_some());
+        assert_ne!(shared("fn (&str) -> String").token(), token);
+        let with_newtypes = SharedSearch {
+            newtypes: true,
+            ..search.clone()
+        };
+        assert_ne!(with_newtypes.token(), token);
+        assert_eq!(load(&dir, &token).unwrap(), Some(search));
+        assert_eq!(load(&dir, "000000000000").unwrap(), None);
+        assert_eq!(load(&dir, "../crate/std").unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn search_with_queries_as_parsed() {
+        let arena = Arena::new();
+        let parsed = parse_query_in(&arena, "fn (&str) -> usize").unwrap();
+        // As if the grammar had changed since the search was shared.
+        let search = SharedSearch {
+            parsed: serde_json::to_value(&parsed).unwrap(),
+            ..shared("fn {&str} -> usize")
+        };
+        assert_eq!(search.query_in(&arena), Some(parsed));
+        assert_eq!(shared("fn {&str} -> usize").query_in(&arena), None);
+    }
+}
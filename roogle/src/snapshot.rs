@@ -0,0 +1,215 @@
+//! The `snapshot` subcommand, archiving the crates in the index into one file and restoring them,
+//! so that the exact index backing a deployment can be reproduced.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+
+use roogle_engine::snapshot;
+
+use crate::{
+    config::Config,
+    store::{
+        is_valid_crate_name, load, persist_crate, remove_delta, validate_crate, warn_degraded,
+        ALL_SCOPE,
+    },
+};
+
+#[derive(Debug, StructOpt)]
+pub enum Opt {
+    /// Write the crates in the index, with their deltas applied, into a snapshot.
+    Save {
+        /// Directory of the index [default: roogle-index].
+        #[structopt(short, long, name = "INDEX")]
+        index: Option<PathBuf>,
+
+        /// Write the snapshot even if some crates fail to load, leaving them out of it.
+        #[structopt(long)]
+        allow_degraded: bool,
+
+        /// Path to write the snapshot to.
+        snapshot: PathBuf,
+    },
+    /// Write the crates in a snapshot into the index, replacing the ones of the same names.
+    ///
+    /// Deltas of the restored crates are removed, as the snapshot has them applied already.
+    Load {
+        /// Directory of the index [default: roogle-index].
+        #[structopt(short, long, name = "INDEX")]
+        index: Option<PathBuf>,
+
+        /// Remove crates not in the snapshot from the index as well.
+        #[structopt(long)]
+        prune: bool,
+
+        /// Path to the snapshot.
+        snapshot: PathBuf,
+    },
+}
+
+pub fn run(opt: Opt, config: &Config) -> Result<()> {
+    match opt {
+        Opt::Save {
+            index,
+            allow_degraded,
+            snapshot,
+        } => {
+            let (index, scopes) = load(&config.index(index), ALL_SCOPE)?;
+            if !scopes.degraded.is_empty() {
+                let degraded = scopes
+                    .degraded
+                    .iter()
+                    .map(|(krate, cause)| format!("crate `{}`: {}", krate, cause))
+                    .collect::<Vec<_>>();
+                if !allow_degraded {
+                    bail!(
+                        "{} crates failed to load, pass `--allow-degraded` to leave them out:\n{}",
+                        degraded.len(),
+                        degraded.join("\n")
+                    );
+                }
+                warn_degraded(&scopes);
+            }
+            let manifest = index
+                .save(&snapshot)
+                .with_context(|| format!("failed to write snapshot `{:?}`", snapshot))?;
+            for krate in &manifest.crates {
+                println!(
+                    "{} {} {}",
+                    krate.name,
+                    krate.version.as_deref().unwrap_or("-"),
+                    krate.checksum
+                );
+            }
+            Ok(())
+        }
+        Opt::Load {
+            index,
+            prune,
+            snapshot,
+        } => restore(&config.index(index), &snapshot, prune),
+    }
+}
+
+/// Write the crates in `snapshot` into the index directory `dir`, removing the others if `prune`.
+///
+/// Nothing is written unless every crate in `snapshot` can be written and searched.
+fn restore(dir: &Path, snapshot: &Path, prune: bool) -> Result<()> {
+    let json = std::fs::read_to_string(snapshot)
+        .with_context(|| format!("failed to read `{:?}`", snapshot))?;
+    let (manifest, crates) = snapshot::read(&json)
+        .with_context(|| format!("failed to read snapshot `{:?}`", snapshot))?;
+    for entry in &manifest.crates {
+        // Names are file names in the index, which must not escape it.
+        if !is_valid_crate_name(&entry.name) {
+            bail!("invalid crate name `{}` in the snapshot", entry.name);
+        }
+        validate_crate(&crates[&entry.name])
+            .with_context(|| format!("invalid crate `{}` in the snapshot", entry.name))?;
+    }
+
+    let crate_dir = dir.join("crate");
+    std::fs::create_dir_all(&crate_dir)
+        .with_context(|| format!("failed to create `{:?}`", crate_dir))?;
+    for entry in &manifest.crates {
+        persist_crate(dir, &entry.name, &crates[&entry.name])?;
+        remove_delta(dir, &entry.name)?;
+        println!("crate `{}` restored", entry.name);
+    }
+
+    if prune {
+        for entry in std::fs::read_dir(&crate_dir)
+            .with_context(|| format!("failed to read `{:?}`", crate_dir))?
+        {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if crates.contains_key(name) {
+                continue;
+            }
+            std::fs::remove_file(&path)
+                .with_context(|| format!("failed to remove `{:?}`", path))?;
+            remove_delta(dir, name)?;
+            println!("crate `{}` removed", name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use roogle_engine::testing::load_fixtures;
+    use rustdoc_types::{Crate, Id};
+
+    use super::*;
+
+    #[test]
+    fn restore_shaken_crates() {
+        let dir = std::env::temp_dir().join(format!("roogle-restore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot.json");
+        let index = load_fixtures(&["mini_std", "mini_ext"]);
+        index.save(&path).unwrap();
+
+        restore(&dir.join("index"), &path, false).unwrap();
+        let (restored, scopes) = load(&dir.join("index"), ALL_SCOPE).unwrap();
+        assert!(scopes.degraded.is_empty());
+        let mut names = restored.crates().keys().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["mini_ext", "mini_std"]);
+        // Loading shakes crates again, which drops the associated types the fixture has.
+        assert_eq!(
+            restored.crates()["mini_std"].index.len(),
+            roogle_util::shake((*index.crates()["mini_std"]).clone())
+                .index
+                .len()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_nothing_from_invalid_snapshots() {
+        let dir = std::env::temp_dir().join(format!("roogle-snapshot-{}", std::process::id()));
+        let krate = |format_version| Crate {
+            root: Id("0:0".to_owned()),
+            crate_version: None,
+            includes_private: false,
+            index: Default::default(),
+            paths: Default::default(),
+            external_crates: Default::default(),
+            format_version,
+        };
+        let restore_crates = |crates: HashMap<String, Crate>| {
+            let path = dir.join("snapshot.json");
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut json = vec![];
+            snapshot::write(&mut json, &crates).unwrap();
+            std::fs::write(&path, json).unwrap();
+            restore(&dir.join("index"), &path, false)
+        };
+
+        let e = restore_crates(HashMap::from([(
+            "../escape".to_owned(),
+            krate(rustdoc_types::FORMAT_VERSION),
+        )]))
+        .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "invalid crate name `../escape` in the snapshot"
+        );
+        let e = restore_crates(HashMap::from([("old".to_owned(), krate(0))])).unwrap_err();
+        assert!(
+            format!("{:#}", e).contains("unsupported format version 0"),
+            "{:#}",
+            e
+        );
+        assert!(!dir.join("index").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+//! Loading and persisting the index directory, shared by all subcommands.
+//!
+//! The directory consists of `crate/<CRATE>.json`, `set/<SET>.json` and `delta/<CRATE>.json`, and
+//! `share/<TOKEN>.json` once searches are shared through the server. Loading it is shared with the
+//! C bindings through [`roogle_util::store`].
+
+#[cfg(feature = "server")]
+use std::time::Instant;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rustdoc_types::Crate;
+#[cfg(feature = "server")]
+use tracing::info;
+
+#[cfg(feature = "server")]
+use roogle_engine::search::Scope;
+use roogle_engine::Index;
+#[cfg(feature = "server")]
+use roogle_util::store::make_scopes_of;
+pub use roogle_util::store::{make_scopes, validate_crate, Scopes, ALL_SCOPE};
+
+/// Context of errors in loading the index directory, so that they can be told from others.
+#[derive(Debug)]
+pub struct IndexError(PathBuf);
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load the index in `{}`", self.0.display())
+    }
+}
+
+/// Load the index and scopes in `dir`, with `default_scope` to search in by default.
+///
+/// Crates failing to load are left out of the scopes and marked as degraded with why, which is
+/// logged as well; subcommands run in terminals print them with [`warn_degraded`].
+pub fn load(dir: &Path, default_scope: &str) -> Result<(Index, Scopes)> {
+    roogle_util::store::load(dir, default_scope).with_context(|| IndexError(dir.to_owned()))
+}
+
+/// Print a warning for each degraded crate in `scopes` to stderr, which the log of the server has
+/// already.
+pub fn warn_degraded(scopes: &Scopes) {
+    for (krate, cause) in &scopes.degraded {
+        eprintln!(
+            "warning: crate `{}` is degraded and not searched: {}",
+            krate, cause
+        );
+    }
+}
+
+/// Load the index from the snapshot at `snapshot` instead of `<INDEX>/crate`, with the sets in
+/// `dir` and `default_scope` to search in by default.
+#[cfg(feature = "server")]
+pub fn load_snapshot(snapshot: &Path, dir: &Path, default_scope: &str) -> Result<(Index, Scopes)> {
+    let start = Instant::now();
+    let index = Index::load(snapshot).with_context(|| IndexError(snapshot.to_owned()))?;
+    info!(
+        crates = index.crates().len(),
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "snapshot loaded"
+    );
+    let krates = index
+        .crates()
+        .keys()
+        .map(|krate| (krate.clone(), Scope::Crate(krate.clone())))
+        .collect();
+    let scopes =
+        make_scopes_of(krates, dir, default_scope).with_context(|| IndexError(dir.to_owned()))?;
+    Ok((index, scopes))
+}
+
+/// Write shaken `krate` into the index directory, replacing the existing one atomically.
+pub fn persist_crate(dir: &Path, name: &str, krate: &Crate) -> Result<()> {
+    let path = dir.join("crate").join(format!("{}.json", name));
+    write_atomically(
+        &path,
+        &serde_json::to_string(krate).context("failed to serialize crate")?,
+    )
+}
+
+/// Remove the delta of `krate` from the index directory if any, as it applies to the crate which
+/// was there before rather than to one written anew.
+pub fn remove_delta(dir: &Path, krate: &str) -> Result<()> {
+    let path = dir.join("delta").join(format!("{}.json", krate));
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("failed to remove `{:?}`", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write `contents` to `path` via a temporary file so that readers never see a partial file.
+pub fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents).with_context(|| format!("failed to write `{:?}`", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to write `{:?}`", path))?;
+    Ok(())
+}
+
+pub fn is_valid_crate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
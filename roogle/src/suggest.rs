@@ -0,0 +1,118 @@
+//! The `suggest` subcommand, finding functions which convert given types into another.
+//!
+//! Unlike `search`, this takes types rather than a query and prints results in a format meant to
+//! be stable, so that editors can suggest e.g. "`std::str::from_utf8` converts `&[u8]` into
+//! `Result<&str, Utf8Error>`" from the expected type at the cursor. `/suggest` serves the same
+//! over HTTP.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use roogle_engine::{
+    query::{parse::parse_query_in, Arena, Query, QueryKind},
+    search::Hit,
+};
+
+use crate::{
+    config::Config,
+    search::{search_parsed, Params},
+    store::{load, warn_degraded},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    params: Params,
+
+    /// Type of an argument, e.g. `&[u8]`, given once per argument in order; `_` for unknown ones.
+    #[structopt(long = "from", name = "TYPE")]
+    from: Vec<String>,
+
+    /// Type to return, e.g. `Option<u32>`, or any type if omitted.
+    #[structopt(long = "to", name = "RETURN")]
+    to: Option<String>,
+}
+
+pub fn run(mut opt: Opt, config: &Config) -> Result<()> {
+    opt.params.configure(config);
+    let (index, scopes) = load(&config.index(opt.index), opt.params.scope())?;
+    warn_degraded(&scopes);
+
+    let arena = Arena::new();
+    let query = query(&arena, &opt.from, opt.to.as_deref())?;
+    let hits = search_parsed(&index, &scopes, &query, &opt.params)?;
+    let suggestions = hits.iter().map(Suggestion::from).collect::<Vec<_>>();
+
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &suggestions).context("serializing suggestions failed")?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+/// A function converting the given types, whose fields are kept stable for tools.
+#[derive(Debug, Serialize)]
+pub struct Suggestion {
+    /// Fully qualified path, e.g. `std::str::from_utf8`.
+    pub path: String,
+    pub signature: String,
+    /// Lower scores mean closer matches.
+    pub score: f32,
+}
+
+impl From<&Hit> for Suggestion {
+    fn from(hit: &Hit) -> Self {
+        Suggestion {
+            path: hit.path.join("::"),
+            signature: hit.signature.clone(),
+            score: hit.similarities().score(),
+        }
+    }
+}
+
+/// Query for functions taking arguments of types `from` and returning `to`, or any type if `to`
+/// is `None`, allocated in `arena`.
+pub fn query<'a>(
+    arena: &'a Arena,
+    from: &[impl AsRef<str>],
+    to: Option<&str>,
+) -> Result<Query<'a>> {
+    let args = from
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut query = match to {
+        Some(to) => parse_query_in(arena, &format!("fn ({}) -> {}", args, to))?,
+        None => parse_query_in(arena, &format!("fn ({})", args))?,
+    };
+    if to.is_none() {
+        // `fn (..)` means returning `()`, whereas any return type will do here.
+        if let Some(QueryKind::FunctionQuery(ref mut f)) = query.kind {
+            f.decl.output = None;
+        }
+    }
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_without_return_type() {
+        let arena = Arena::new();
+        let query = query(&arena, &["&[u8]", "_"], None).unwrap();
+        assert_eq!(query.args().map(|args| args.len()), Some(2));
+        let Some(QueryKind::FunctionQuery(f)) = query.kind else {
+            panic!("not a function query");
+        };
+        assert_eq!(f.decl.output, None);
+    }
+}
@@ -0,0 +1,208 @@
+//! The `tui` subcommand, browsing hits in a terminal UI as queries are typed.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+use structopt::StructOpt;
+
+use roogle_engine::{query::Arena, search::Hit, Index};
+
+use crate::{
+    config::Config,
+    search::{open_docs, search, Params},
+    store::{load, warn_degraded, Scopes},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Opt {
+    /// Directory of the index [default: roogle-index].
+    #[structopt(short, long, name = "INDEX")]
+    index: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    params: Params,
+}
+
+pub fn run(mut opt: Opt, config: &Config) -> Result<()> {
+    opt.params.configure(config);
+    let (index, scopes) = load(&config.index(opt.index), opt.params.scope())?;
+    warn_degraded(&scopes);
+
+    let mut app = App {
+        index: &index,
+        scopes: &scopes,
+        params: &opt.params,
+        input: String::new(),
+        arena: Arena::new(),
+        hits: vec![],
+        list: ListState::default(),
+        message: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct App<'a> {
+    index: &'a Index,
+    scopes: &'a Scopes,
+    params: &'a Params,
+
+    input: String,
+    /// Arena which queries are parsed in while being typed.
+    arena: Arena,
+    /// Hits of the last query which parsed successfully.
+    hits: Vec<Hit>,
+    list: ListState,
+    /// Error to show in place of the key bindings, if any.
+    message: Option<String>,
+}
+
+impl App<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("failed to draw")?;
+            if let Event::Key(key) = event::read().context("failed to read an event")? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Handle `key`, returning whether to keep running.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.search();
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.search();
+            }
+            KeyCode::Down => self.list.select_next(),
+            KeyCode::Up => self.list.select_previous(),
+            KeyCode::Enter => {
+                if let Some(hit) = self.list.selected().and_then(|i| self.hits.get(i)) {
+                    self.message = open_docs(hit).err().map(|e| format!("{:#}", e));
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn search(&mut self) {
+        if self.input.trim().is_empty() {
+            self.hits.clear();
+            self.message = None;
+            return;
+        }
+
+        // Keep showing the last hits while the query is being typed and fails to parse.
+        match search(
+            self.index,
+            self.scopes,
+            &mut self.arena,
+            &self.input,
+            self.params,
+        ) {
+            Ok(hits) => {
+                self.list
+                    .select(if hits.is_empty() { None } else { Some(0) });
+                self.hits = hits;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("{:#}", e)),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let [input, body, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [results, preview] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        frame.render_widget(
+            Paragraph::new(self.input.as_str())
+                .block(Block::default().borders(Borders::ALL).title("Query")),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.input.chars().count() as u16, input.y + 1));
+
+        let items = self
+            .hits
+            .iter()
+            .map(|hit| ListItem::new(hit.signature.as_str()))
+            .collect::<Vec<_>>();
+        let title = format!("Hits ({})", self.hits.len());
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            results,
+            &mut self.list,
+        );
+
+        let text = match self.list.selected().and_then(|i| self.hits.get(i)) {
+            Some(hit) => {
+                let mut text = vec![
+                    Line::from(Span::styled(
+                        hit.signature.as_str(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Line::from(Span::styled(
+                        hit.path.join("::"),
+                        Style::default().fg(Color::Green),
+                    )),
+                    Line::default(),
+                ];
+                text.extend(
+                    // Servers respond with summaries rather than whole docs.
+                    hit.docs
+                        .as_ref()
+                        .or(hit.summary.as_ref())
+                        .into_iter()
+                        .flat_map(|docs| docs.lines())
+                        .map(Line::from),
+                );
+                text
+            }
+            None => vec![],
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Docs")),
+            preview,
+        );
+
+        let status_line = match self.message {
+            Some(ref message) => Line::from(Span::styled(
+                message.as_str(),
+                Style::default().fg(Color::Red),
+            )),
+            None => Line::from("↑/↓: select  Enter: open docs  Esc: quit"),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}